@@ -0,0 +1,439 @@
+use crate::file_organizer::patch::{self, PatchOperation};
+use crate::file_organizer::plan::PlanOperationKind;
+use crate::file_organizer::settings::{FileOperation, Recipe};
+use crate::file_organizer::{FileOutcome, RecipeContext, match_file};
+use anyhow::Result;
+use colored::*;
+use flate2::Compression;
+use flate2::read::GzDecoder;
+use flate2::write::GzEncoder;
+use std::collections::BTreeMap;
+use std::fs;
+use std::fs::DirEntry;
+use std::path::{Path, PathBuf};
+use tracing::{error, info};
+
+/// The default format of the date buckets of a compact recipe, naming their archives.
+const DEFAULT_COMPACT_FORMAT: &str = "%Y-%m-%d";
+
+/// The extension of the archives of a compact recipe.
+const ARCHIVE_EXTENSION: &str = "tar.gz";
+
+/// The extension of an archive being written, renamed once complete.
+const PARTIAL_ARCHIVE_EXTENSION: &str = "partial";
+
+/// Runs a compact recipe.
+/// The matched files are grouped by date bucket and each bucket is bundled into a single `<bucket>.tar.gz` archive of its destination folder, the originals being removed if the recipe moves files.
+/// Only the buckets with matched files get an archive.
+///
+/// ### Parameters
+/// - `entries`: The entries of the source folder.
+/// - `recipe`: The recipe to run.
+/// - `context`: The context of the recipe run.
+///
+/// ### Returns
+/// - `Vec<Result<FileOutcome>>`: The results of the recipe run, one per entry.
+pub fn run_compact(
+    entries: Vec<DirEntry>,
+    recipe: &Recipe,
+    context: &RecipeContext,
+) -> Vec<Result<FileOutcome>> {
+    let mut results = Vec::new();
+    let mut buckets: BTreeMap<(PathBuf, String), Vec<PathBuf>> = BTreeMap::new();
+    let compact_format = recipe
+        .compact_format
+        .as_deref()
+        .unwrap_or(DEFAULT_COMPACT_FORMAT);
+    for entry in entries {
+        let path = entry.path();
+        match match_file(&path, recipe, context) {
+            Ok(Some(match_result)) => buckets
+                .entry((
+                    match_result.dest_folder,
+                    match_result.file_date.format(compact_format).to_string(),
+                ))
+                .or_default()
+                .push(path),
+            Ok(None) => results.push(Ok(FileOutcome::NotMatched)),
+            Err(e) => results.push(Err(e.context(path.display().to_string()))),
+        }
+    }
+    for ((dest_folder, bucket), mut files) in buckets {
+        files.sort();
+        let archive = get_archive(&dest_folder, &bucket, recipe);
+        let created_folder = context
+            .patch
+            .as_ref()
+            .and_then(|_| patch::get_created_folder(&dest_folder));
+        let mut backup = None;
+        if let Some(patch) = &context.patch
+            && archive.exists()
+        {
+            match patch.backup_copy(&archive) {
+                Ok(archive_backup) => backup = Some(archive_backup),
+                Err(e) => {
+                    results.extend(files.iter().map(|_| {
+                        Err(anyhow::Error::msg(format!(
+                            "{} - Error backing up archive {}: {}",
+                            recipe.name,
+                            archive.display(),
+                            e
+                        )))
+                    }));
+                    continue;
+                }
+            }
+        }
+        if !context.dry_run
+            && let Err(e) = write_archive(&archive, &files, recipe)
+        {
+            error!(
+                "{} {} {} - {}: {}",
+                "❌".red(),
+                recipe.name.blue(),
+                "Archive failed".red(),
+                archive.display(),
+                e
+            );
+            results.extend(files.iter().map(|_| {
+                Err(anyhow::Error::msg(format!(
+                    "{} - Error writing archive {}: {}",
+                    recipe.name,
+                    archive.display(),
+                    e
+                )))
+            }));
+            continue;
+        }
+        if !context.quiet {
+            info!(
+                operation = "compact",
+                destination = %archive.display(),
+                "{} {} {} - {} ({} file(s))",
+                "✅".green(),
+                recipe.name.blue(),
+                "Files archived".green(),
+                archive.display(),
+                files.len()
+            );
+        }
+        if let Some(folders_counts) = &context.folders_counts {
+            *folders_counts
+                .lock()
+                .unwrap()
+                .entry(dest_folder.clone())
+                .or_default() += files.len() as u32;
+        }
+        for file in files {
+            if !context.dry_run
+                && recipe.operation == FileOperation::Move
+                && let Err(e) = fs::remove_file(&file)
+            {
+                results.push(Err(anyhow::Error::msg(format!(
+                    "{} - Error removing archived file {}: {}",
+                    recipe.name,
+                    file.display(),
+                    e
+                ))));
+                continue;
+            }
+            if let Some(patch) = &context.patch {
+                patch.record(PatchOperation {
+                    recipe: recipe.name.clone(),
+                    operation: PlanOperationKind::from(&recipe.operation),
+                    archive_member: Some(get_member_name(&file, recipe).to_path_buf()),
+                    source: file,
+                    destination: archive.clone(),
+                    backup: backup.take(),
+                    created_folder: created_folder.clone(),
+                    size: None,
+                    modified: None,
+                });
+            }
+            results.push(Ok(FileOutcome::Processed));
+        }
+    }
+    results
+}
+
+/// Gets the archive of a date bucket.
+/// When the archive of the bucket already exists, it is appended to if the recipe appends, else a new archive is numbered after it.
+///
+/// ### Parameters
+/// - `dest_folder`: The destination folder of the bucket.
+/// - `bucket`: The name of the bucket.
+/// - `recipe`: The recipe.
+///
+/// ### Returns
+/// - `PathBuf`: The archive to write the files of the bucket into.
+fn get_archive(dest_folder: &Path, bucket: &str, recipe: &Recipe) -> PathBuf {
+    let archive = dest_folder.join(format!("{}.{}", bucket, ARCHIVE_EXTENSION));
+    if !archive.exists() || recipe.compact_append.unwrap_or(false) {
+        return archive;
+    }
+    let mut index = 1;
+    loop {
+        let archive = dest_folder.join(format!("{} ({}).{}", bucket, index, ARCHIVE_EXTENSION));
+        if !archive.exists() {
+            return archive;
+        }
+        index += 1;
+    }
+}
+
+/// Writes files into an archive, after the members of the archive if it already exists.
+/// The archive is written next to its final path and renamed once complete, so that an interrupted run never leaves a truncated archive.
+///
+/// ### Parameters
+/// - `archive`: The archive to write.
+/// - `files`: The files to archive, named in the archive after their path from the source folder.
+/// - `recipe`: The recipe.
+///
+/// ### Returns
+/// - `Result<(), anyhow::Error>`: The result of the write.
+fn write_archive(archive: &Path, files: &[PathBuf], recipe: &Recipe) -> Result<()> {
+    if let Some(dest_folder) = archive.parent() {
+        fs::create_dir_all(dest_folder)?;
+    }
+    let mut partial_archive = archive.as_os_str().to_os_string();
+    partial_archive.push(".");
+    partial_archive.push(PARTIAL_ARCHIVE_EXTENSION);
+    let partial_archive = PathBuf::from(partial_archive);
+    let write = || -> Result<()> {
+        let mut builder = tar::Builder::new(GzEncoder::new(
+            fs::File::create(&partial_archive)?,
+            Compression::default(),
+        ));
+        if archive.exists() {
+            let mut existing_archive = tar::Archive::new(GzDecoder::new(fs::File::open(archive)?));
+            // The members are appended with their full path, which a long name only holds in the extension entry before their header.
+            for member in existing_archive.entries()? {
+                let member = member?;
+                let mut header = member.header().clone();
+                let path = member.path()?.into_owned();
+                builder.append_data(&mut header, path, member)?;
+            }
+        }
+        for file in files {
+            builder.append_path_with_name(file, get_member_name(file, recipe))?;
+        }
+        builder.into_inner()?.finish()?;
+        Ok(())
+    };
+    if let Err(e) = write() {
+        let _ = fs::remove_file(&partial_archive);
+        return Err(e);
+    }
+    fs::rename(&partial_archive, archive)?;
+    Ok(())
+}
+
+/// Gets the name of a file in the archive of a compact recipe.
+///
+/// ### Parameters
+/// - `file`: The archived file.
+/// - `recipe`: The recipe.
+///
+/// ### Returns
+/// - `&Path`: The path of the file from its source folder, its file name if it is in none of them.
+fn get_member_name<'a>(file: &'a Path, recipe: &Recipe) -> &'a Path {
+    recipe
+        .get_source_folders()
+        .into_iter()
+        .find_map(|v| file.strip_prefix(v).ok())
+        .or(file.file_name().map(Path::new))
+        .unwrap_or(file)
+}
+
+/// Extracts a file from an archive written by a compact recipe.
+///
+/// ### Parameters
+/// - `archive`: The archive.
+/// - `member_name`: The name of the file in the archive.
+/// - `to_file`: The path to extract the file to, its folder being created if needed.
+///
+/// ### Returns
+/// - `Result<(), anyhow::Error>`: The result of the extraction, an error if the archive has no such file.
+pub fn extract_member(archive: &Path, member_name: &Path, to_file: &Path) -> Result<()> {
+    let mut archive_reader = tar::Archive::new(GzDecoder::new(fs::File::open(archive)?));
+    for member in archive_reader.entries()? {
+        let mut member = member?;
+        if member.path()? == member_name {
+            if let Some(folder) = to_file.parent() {
+                fs::create_dir_all(folder)?;
+            }
+            member.unpack(to_file)?;
+            return Ok(());
+        }
+    }
+    Err(anyhow::Error::msg(format!(
+        "No {} in the archive {}",
+        member_name.display(),
+        archive.display()
+    )))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::file_organizer::{FileOrganizer, RunOptions};
+    use std::io::Read;
+    use tempfile::TempDir;
+
+    /// Reads the members of an archive with their content.
+    ///
+    /// ### Parameters
+    /// - `archive`: The archive to read.
+    ///
+    /// ### Returns
+    /// - `Vec<(String, String)>`: The name and content of each member, in the archive order.
+    fn read_members(archive: &Path) -> Vec<(String, String)> {
+        let mut archive_reader =
+            tar::Archive::new(GzDecoder::new(fs::File::open(archive).unwrap()));
+        archive_reader
+            .entries()
+            .unwrap()
+            .map(|member| {
+                let mut member = member.unwrap();
+                let name = member.path().unwrap().to_string_lossy().to_string();
+                let mut content = String::new();
+                member.read_to_string(&mut content).unwrap();
+                (name, content)
+            })
+            .collect()
+    }
+
+    /// Writes sensor logs dated by their day into a folder.
+    ///
+    /// ### Parameters
+    /// - `folder`: The folder to write the logs into.
+    /// - `logs`: The name, content and modification day, as `YYYY-MM-DD`, of each log.
+    fn write_logs(folder: &Path, logs: &[(&str, &str, &str)]) {
+        fs::create_dir_all(folder).unwrap();
+        for (name, content, day) in logs {
+            let file = folder.join(name);
+            fs::write(&file, content).unwrap();
+            let modified = chrono::NaiveDate::parse_from_str(day, "%Y-%m-%d")
+                .unwrap()
+                .and_hms_opt(12, 0, 0)
+                .unwrap()
+                .and_utc();
+            fs::File::options()
+                .write(true)
+                .open(&file)
+                .unwrap()
+                .set_modified(modified.into())
+                .unwrap();
+        }
+    }
+
+    /// Runs a compact recipe, ignoring the last run date so that the same days can be compacted again.
+    ///
+    /// ### Parameters
+    /// - `root`: The folder holding the source, the destination and the recipe file.
+    /// - `operation`: The operation of the recipe.
+    /// - `compact_append`: If true, the existing archives are appended to.
+    fn run_compact_recipe(root: &Path, operation: &str, compact_append: bool) {
+        let recipes_file = root.join("compact.json");
+        let settings = serde_json::json!({
+            "recipes": [{
+                "name": "sensors",
+                "source_folder": root.join("sensors"),
+                "destination_folder": root.join("archives"),
+                "operation": operation,
+                "allowed_extensions": ["log"],
+                "mode": "Compact",
+                "compact_append": compact_append
+            }]
+        });
+        fs::write(&recipes_file, settings.to_string()).unwrap();
+        let options = RunOptions {
+            is_forced: true,
+            ..Default::default()
+        };
+        FileOrganizer::new(recipes_file, options)
+            .unwrap()
+            .run()
+            .unwrap();
+    }
+
+    #[test]
+    fn files_are_bundled_per_day_and_moved_originals_removed() {
+        let root = TempDir::new().unwrap();
+        let (sensors, archives) = (root.path().join("sensors"), root.path().join("archives"));
+        fs::create_dir(&archives).unwrap();
+        write_logs(
+            &sensors,
+            &[
+                ("kitchen.log", "21.5", "2024-03-01"),
+                ("garage.log", "12.0", "2024-03-01"),
+                ("attic.log", "30.1", "2024-03-02"),
+            ],
+        );
+
+        run_compact_recipe(root.path(), "Move", false);
+
+        assert_eq!(
+            read_members(&archives.join("2024-03-01.tar.gz")),
+            [
+                ("garage.log".to_string(), "12.0".to_string()),
+                ("kitchen.log".to_string(), "21.5".to_string())
+            ]
+        );
+        assert_eq!(
+            read_members(&archives.join("2024-03-02.tar.gz")),
+            [("attic.log".to_string(), "30.1".to_string())]
+        );
+        assert_eq!(fs::read_dir(&sensors).unwrap().count(), 0);
+        assert_eq!(fs::read_dir(&archives).unwrap().count(), 2);
+    }
+
+    #[test]
+    fn incremental_runs_number_a_new_archive_or_append() {
+        let root = TempDir::new().unwrap();
+        let (sensors, archives) = (root.path().join("sensors"), root.path().join("archives"));
+        fs::create_dir(&archives).unwrap();
+        write_logs(&sensors, &[("morning.log", "1", "2024-03-01")]);
+        run_compact_recipe(root.path(), "Move", false);
+
+        write_logs(&sensors, &[("noon.log", "2", "2024-03-01")]);
+        run_compact_recipe(root.path(), "Copy", false);
+        assert_eq!(
+            read_members(&archives.join("2024-03-01 (1).tar.gz")),
+            [("noon.log".to_string(), "2".to_string())]
+        );
+        assert!(sensors.join("noon.log").is_file());
+
+        fs::remove_file(archives.join("2024-03-01 (1).tar.gz")).unwrap();
+        run_compact_recipe(root.path(), "Move", true);
+        assert_eq!(
+            read_members(&archives.join("2024-03-01.tar.gz")),
+            [
+                ("morning.log".to_string(), "1".to_string()),
+                ("noon.log".to_string(), "2".to_string())
+            ]
+        );
+        assert_eq!(fs::read_dir(&archives).unwrap().count(), 1);
+    }
+
+    #[test]
+    fn appending_keeps_the_long_names_of_the_archived_files() {
+        let root = TempDir::new().unwrap();
+        let (sensors, archives) = (root.path().join("sensors"), root.path().join("archives"));
+        fs::create_dir(&archives).unwrap();
+        let long_name = format!("{}.log", "thermometer".repeat(12));
+        write_logs(&sensors, &[(long_name.as_str(), "1", "2024-03-01")]);
+        run_compact_recipe(root.path(), "Move", true);
+
+        write_logs(&sensors, &[("noon.log", "2", "2024-03-01")]);
+        run_compact_recipe(root.path(), "Move", true);
+
+        assert_eq!(
+            read_members(&archives.join("2024-03-01.tar.gz")),
+            [
+                (long_name, "1".to_string()),
+                ("noon.log".to_string(), "2".to_string())
+            ]
+        );
+    }
+}
@@ -0,0 +1,208 @@
+use crate::file_organizer::settings::Settings;
+use serde_json::{Map, Value};
+use std::collections::BTreeSet;
+
+/// The fields updated by the runs themselves, ignored when comparing recipe files.
+const RUN_STATE_FIELDS: [&str; 2] = ["last_run", "last_seen"];
+
+/// ConfigChange is an enum that contains a difference between two recipe files.
+#[derive(Debug, PartialEq)]
+pub enum ConfigChange {
+    RecipeAdded(String),
+    RecipeRemoved(String),
+    RecipesReordered(Vec<String>),
+    SettingChanged {
+        field: String,
+        old: Option<Value>,
+        new: Option<Value>,
+    },
+    FieldChanged {
+        recipe: String,
+        field: String,
+        old: Option<Value>,
+        new: Option<Value>,
+    },
+}
+
+/// Compares two recipe files field by field.
+/// The recipes are matched by name, and the fields updated by the runs themselves (last_run and last_seen) are ignored.
+///
+/// ### Parameters
+/// - `old`: The settings of the old recipe file.
+/// - `new`: The settings of the new recipe file.
+///
+/// ### Returns
+/// - `Result<Vec<ConfigChange>, anyhow::Error>`: The changes, empty if both files are equivalent.
+pub fn diff_settings(old: &Settings, new: &Settings) -> anyhow::Result<Vec<ConfigChange>> {
+    let mut changes = Vec::new();
+    for (field, old_value, new_value) in
+        diff_maps(&get_global_settings(old)?, &get_global_settings(new)?)
+    {
+        changes.push(ConfigChange::SettingChanged {
+            field,
+            old: old_value,
+            new: new_value,
+        });
+    }
+    for old_recipe in &old.recipes {
+        if !new.recipes.iter().any(|v| v.name == old_recipe.name) {
+            changes.push(ConfigChange::RecipeRemoved(old_recipe.name.clone()));
+        }
+    }
+    for new_recipe in &new.recipes {
+        let Some(old_recipe) = old.recipes.iter().find(|v| v.name == new_recipe.name) else {
+            changes.push(ConfigChange::RecipeAdded(new_recipe.name.clone()));
+            continue;
+        };
+        for (field, old_value, new_value) in diff_maps(&to_map(old_recipe)?, &to_map(new_recipe)?) {
+            changes.push(ConfigChange::FieldChanged {
+                recipe: new_recipe.name.clone(),
+                field,
+                old: old_value,
+                new: new_value,
+            });
+        }
+    }
+    let common_names = |settings: &Settings, other: &Settings| -> Vec<String> {
+        settings
+            .recipes
+            .iter()
+            .filter(|v| other.recipes.iter().any(|w| w.name == v.name))
+            .map(|v| v.name.clone())
+            .collect()
+    };
+    let new_order = common_names(new, old);
+    if common_names(old, new) != new_order {
+        changes.push(ConfigChange::RecipesReordered(new_order));
+    }
+    Ok(changes)
+}
+
+/// Gets the global settings of a recipe file, without its recipes.
+///
+/// ### Parameters
+/// - `settings`: The settings.
+///
+/// ### Returns
+/// - `Result<Map<String, Value>, anyhow::Error>`: The global settings, by field.
+fn get_global_settings(settings: &Settings) -> anyhow::Result<Map<String, Value>> {
+    let mut global_settings = to_map(settings)?;
+    global_settings.remove("recipes");
+    Ok(global_settings)
+}
+
+/// Serializes a value into its fields, the run state fields excepted.
+///
+/// ### Parameters
+/// - `value`: The value to serialize.
+///
+/// ### Returns
+/// - `Result<Map<String, Value>, anyhow::Error>`: The fields of the value.
+fn to_map<T: serde::Serialize>(value: &T) -> anyhow::Result<Map<String, Value>> {
+    let Value::Object(mut map) = serde_json::to_value(value)? else {
+        return Ok(Map::new());
+    };
+    for field in RUN_STATE_FIELDS {
+        map.remove(field);
+    }
+    Ok(map)
+}
+
+/// Compares two sets of fields.
+///
+/// ### Parameters
+/// - `old`: The old fields.
+/// - `new`: The new fields.
+///
+/// ### Returns
+/// - `Vec<(String, Option<Value>, Option<Value>)>`: The changed fields, by name, with their old and new values, None if not set.
+fn diff_maps(
+    old: &Map<String, Value>,
+    new: &Map<String, Value>,
+) -> Vec<(String, Option<Value>, Option<Value>)> {
+    let fields: BTreeSet<&String> = old.keys().chain(new.keys()).collect();
+    fields
+        .into_iter()
+        .filter(|v| old.get(*v) != new.get(*v))
+        .map(|v| (v.clone(), old.get(v).cloned(), new.get(v).cloned()))
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    /// Reads settings from their JSON.
+    ///
+    /// ### Parameters
+    /// - `settings`: The settings, as JSON.
+    ///
+    /// ### Returns
+    /// - `Settings`: The settings.
+    fn settings_from(settings: Value) -> Settings {
+        serde_json::from_value(settings).unwrap()
+    }
+
+    #[test]
+    fn diff_pinpoints_a_changed_field() {
+        let recipe = |allowed_extensions: Value, last_run: &str| {
+            json!({
+                "name": "photos",
+                "source_folder": "/home/me/Downloads",
+                "destination_folder": "/home/me/Pictures",
+                "operation": "Move",
+                "allowed_extensions": allowed_extensions,
+                "last_run": last_run
+            })
+        };
+        let old = settings_from(json!({"recipes": [recipe(json!(["jpg"]), "2024-01-01")]}));
+        let new = settings_from(json!({"recipes": [recipe(json!(["jpg", "heic"]), "2024-06-01")]}));
+
+        let changes = diff_settings(&old, &new).unwrap();
+
+        assert_eq!(
+            changes,
+            [ConfigChange::FieldChanged {
+                recipe: "photos".to_string(),
+                field: "allowed_extensions".to_string(),
+                old: Some(json!(["jpg"])),
+                new: Some(json!(["jpg", "heic"])),
+            }]
+        );
+    }
+
+    #[test]
+    fn diff_lists_added_removed_and_reordered_recipes() {
+        let recipe = |name: &str| {
+            json!({
+                "name": name,
+                "source_folder": "/in",
+                "destination_folder": "/out",
+                "operation": "Copy"
+            })
+        };
+        let old = settings_from(json!({"recipes": [recipe("a"), recipe("b"), recipe("c")]}));
+        let new = settings_from(json!({
+            "root_prefix": "/mnt/nas",
+            "recipes": [recipe("c"), recipe("d"), recipe("a")]
+        }));
+
+        let changes = diff_settings(&old, &new).unwrap();
+
+        assert_eq!(
+            changes,
+            [
+                ConfigChange::SettingChanged {
+                    field: "root_prefix".to_string(),
+                    old: None,
+                    new: Some(json!("/mnt/nas")),
+                },
+                ConfigChange::RecipeRemoved("b".to_string()),
+                ConfigChange::RecipeAdded("d".to_string()),
+                ConfigChange::RecipesReordered(vec!["c".to_string(), "a".to_string()]),
+            ]
+        );
+        assert!(diff_settings(&old, &old).unwrap().is_empty());
+    }
+}
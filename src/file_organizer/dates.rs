@@ -0,0 +1,593 @@
+use crate::file_organizer::exif_date;
+use crate::file_organizer::is_compact;
+use crate::file_organizer::settings::{DateComparator, DateStrategy, Recipe};
+use chrono::format::{Item, StrftimeItems};
+use chrono::{DateTime, TimeDelta, Utc, Weekday};
+use std::collections::HashSet;
+use std::fs;
+use std::path::Path;
+
+/// The start of the first day and the end of the last day of the files organized by a recipe, if set.
+pub type DateRange = (Option<DateTime<Utc>>, Option<DateTime<Utc>>);
+
+/// Gets the date boundary for a recipe.
+/// A recipe tracking the arrival of its files in last_seen is not bounded by the date of its files, which would skip the files arriving late with an old date.
+///
+/// ### Parameters
+/// - `recipe`: The recipe to get the date boundary for.
+///
+/// ### Returns
+/// - `Result<Option<DateTime<Utc>>, anyhow::Error>`: The date boundary, None if the recipe has never been run or has a last_seen date.
+pub fn get_date_boundary(recipe: &Recipe) -> anyhow::Result<Option<DateTime<Utc>>> {
+    if recipe.track_last_seen.unwrap_or(false) && recipe.last_seen.is_some() {
+        return Ok(None);
+    }
+    let Some(date_boundary) = &recipe.last_run else {
+        return Ok(None);
+    };
+    Ok(Some(parse_day(date_boundary)?))
+}
+
+/// Gets the date range of a recipe, from its date_from and date_to days, both inclusive.
+///
+/// ### Parameters
+/// - `recipe`: The recipe to get the date range for.
+///
+/// ### Returns
+/// - `Result<DateRange, anyhow::Error>`: The start of date_from and the start of the day after date_to, None if not set.
+pub fn get_date_range(recipe: &Recipe) -> anyhow::Result<DateRange> {
+    let parse = |field: &str, day: &Option<String>| -> anyhow::Result<Option<DateTime<Utc>>> {
+        day.as_deref()
+            .map(|day| {
+                parse_day(day).map_err(|e| {
+                    anyhow::Error::msg(format!(
+                        "{} - Invalid {} {}, expected YYYY-MM-DD: {}",
+                        recipe.name, field, day, e
+                    ))
+                })
+            })
+            .transpose()
+    };
+    let date_from = parse("date_from", &recipe.date_from)?;
+    let date_to = parse("date_to", &recipe.date_to)?.map(|v| v + TimeDelta::days(1));
+    Ok((date_from, date_to))
+}
+
+/// Parses a day, as written in the recipes.
+///
+/// ### Parameters
+/// - `day`: The day to parse, as YYYY-MM-DD.
+///
+/// ### Returns
+/// - `Result<DateTime<Utc>, anyhow::Error>`: The start of the day, in UTC.
+pub fn parse_day(day: &str) -> anyhow::Result<DateTime<Utc>> {
+    Ok(
+        DateTime::parse_from_str(&format!("{} 00:00:00 +0000", day), "%Y-%m-%d %H:%M:%S %z")?
+            .to_utc(),
+    )
+}
+
+/// Gets the date the previous run of a recipe tracking it listed its source folder, the files arrived since then being the new ones.
+///
+/// ### Parameters
+/// - `recipe`: The recipe to get the last seen date for.
+///
+/// ### Returns
+/// - `Result<Option<DateTime<Utc>>, anyhow::Error>`: The last seen date, None if the recipe does not track it or has never run.
+pub fn get_last_seen(recipe: &Recipe) -> anyhow::Result<Option<DateTime<Utc>>> {
+    if !recipe.track_last_seen.unwrap_or(false) {
+        return Ok(None);
+    }
+    let Some(last_seen) = &recipe.last_seen else {
+        return Ok(None);
+    };
+    let last_seen = DateTime::parse_from_rfc3339(last_seen).map_err(|e| {
+        anyhow::Error::msg(format!(
+            "{} - Invalid last_seen {}: {}",
+            recipe.name, last_seen, e
+        ))
+    })?;
+    Ok(Some(last_seen.to_utc()))
+}
+
+/// Gets the last seen date to save after a run of a recipe tracking it.
+/// The date the source folder was listed is moved back by min_age_seconds, so that the files too recent for this run are considered by the next one.
+///
+/// ### Parameters
+/// - `recipe`: The recipe run.
+/// - `scan_date`: The date the source folder was listed.
+///
+/// ### Returns
+/// - `DateTime<Utc>`: The last seen date.
+pub fn get_next_last_seen(recipe: &Recipe, scan_date: DateTime<Utc>) -> DateTime<Utc> {
+    match recipe.min_age_seconds {
+        Some(min_age_seconds) => scan_date - TimeDelta::seconds(min_age_seconds as i64),
+        None => scan_date,
+    }
+}
+
+/// Gets the date of the reference file of a recipe, the files being filtered by comparing their date to it.
+///
+/// ### Parameters
+/// - `recipe`: The recipe to get the reference date for.
+///
+/// ### Returns
+/// - `Result<Option<DateTime<Utc>>, anyhow::Error>`: The last modification date of the reference file, None if the recipe has no reference file.
+pub fn get_reference_date(recipe: &Recipe) -> anyhow::Result<Option<DateTime<Utc>>> {
+    let Some(reference_file) = &recipe.reference_file else {
+        return Ok(None);
+    };
+    let reference_date = get_last_modification_date(reference_file).map_err(|e| {
+        anyhow::Error::msg(format!(
+            "{} - Error reading the reference file {}: {}",
+            recipe.name,
+            reference_file.display(),
+            e
+        ))
+    })?;
+    Ok(Some(reference_date))
+}
+
+/// Parses the weekdays a recipe is limited to.
+///
+/// ### Parameters
+/// - `recipe`: The recipe to parse the weekday filter of.
+///
+/// ### Returns
+/// - `Result<Option<HashSet<Weekday>>, anyhow::Error>`: The allowed weekdays, None if the recipe has no weekday filter.
+pub fn parse_weekday_filter(recipe: &Recipe) -> anyhow::Result<Option<HashSet<Weekday>>> {
+    let Some(weekday_filter) = &recipe.weekday_filter else {
+        return Ok(None);
+    };
+    let mut weekdays = HashSet::new();
+    for weekday in weekday_filter {
+        weekdays.insert(weekday.parse::<Weekday>().map_err(|_| {
+            anyhow::Error::msg(format!("{} - Invalid weekday: {}", recipe.name, weekday))
+        })?);
+    }
+    Ok(Some(weekdays))
+}
+
+/// Checks if the date of the files is needed by a recipe, either to filter them or to build their destination folder or file name.
+/// When it is not, getting the date of each file can be skipped entirely.
+///
+/// ### Parameters
+/// - `recipe`: The recipe to check.
+///
+/// ### Returns
+/// - `bool`: True if the date of the files is needed, false otherwise.
+pub fn needs_file_date(recipe: &Recipe) -> bool {
+    recipe.last_run.is_some()
+        || recipe.date_from.is_some()
+        || recipe.date_to.is_some()
+        || recipe.reference_file.is_some()
+        || recipe.weekday_filter.is_some()
+        || recipe.year_roots.as_ref().is_some_and(|v| !v.is_empty())
+        || is_compact(recipe)
+        || recipe
+            .subfolders
+            .iter()
+            .flatten()
+            .chain(recipe.file_name_template.iter())
+            .any(|format| is_date_format(format))
+}
+
+/// Checks that the subfolder formats of a recipe are valid strftime formats.
+///
+/// ### Parameters
+/// - `recipe`: The recipe to check.
+///
+/// ### Returns
+/// - `Result<(), anyhow::Error>`: An error naming the empty or invalid formats, if any.
+pub fn validate_subfolders(recipe: &Recipe) -> anyhow::Result<()> {
+    if recipe.subfolders.iter().flatten().any(|v| v.is_empty()) {
+        return Err(anyhow::Error::msg(format!(
+            "{} - Empty subfolder format, remove it or set a format such as %Y",
+            recipe.name
+        )));
+    }
+    let invalid_formats: Vec<&str> = recipe
+        .subfolders
+        .iter()
+        .flatten()
+        .filter(|v| !is_valid_date_format(v))
+        .map(|v| v.as_str())
+        .collect();
+    if !invalid_formats.is_empty() {
+        return Err(anyhow::Error::msg(format!(
+            "{} - Invalid subfolder format: {}",
+            recipe.name,
+            invalid_formats.join(", ")
+        )));
+    }
+    if let Some(file_name_template) = &recipe.file_name_template
+        && !is_valid_date_format(file_name_template)
+    {
+        return Err(anyhow::Error::msg(format!(
+            "{} - Invalid file_name_template format: {}",
+            recipe.name, file_name_template
+        )));
+    }
+    Ok(())
+}
+
+/// Checks if a strftime format can be used, by parsing it and formatting a sample date with it, as chrono panics when formatting with an invalid format.
+///
+/// ### Parameters
+/// - `format`: The format to check.
+///
+/// ### Returns
+/// - `bool`: True if the format is valid, false if it has an unknown or incomplete specifier.
+fn is_valid_date_format(format: &str) -> bool {
+    use std::fmt::Write as _;
+    if StrftimeItems::new(format).any(|item| item == Item::Error) {
+        return false;
+    }
+    let mut sample = String::new();
+    write!(sample, "{}", DateTime::<Utc>::UNIX_EPOCH.format(format)).is_ok()
+}
+
+/// Checks if a format references a date, meaning it contains at least one strftime specifier.
+///
+/// ### Parameters
+/// - `format`: The format to check.
+///
+/// ### Returns
+/// - `bool`: True if the format references a date, false if it only contains literals.
+fn is_date_format(format: &str) -> bool {
+    StrftimeItems::new(format).any(|item| {
+        !matches!(
+            item,
+            Item::Literal(_) | Item::OwnedLiteral(_) | Item::Space(_) | Item::OwnedSpace(_)
+        )
+    })
+}
+
+/// Gets the date of a file using the date sources of a recipe.
+/// The date sources are tried in order and the first one succeeding is used. Without date sources, the date comparator is used.
+///
+/// ### Parameters
+/// - `file`: The file to get the date of.
+/// - `recipe`: The recipe defining the date sources.
+///
+/// ### Returns
+/// - `Result<DateTime<Utc>, anyhow::Error>`: The date of the file.
+pub fn get_file_date(file: &Path, recipe: &Recipe) -> anyhow::Result<DateTime<Utc>> {
+    if let Some(date_strategy) = &recipe.date_strategy {
+        return get_file_date_by_strategy(file, recipe, date_strategy);
+    }
+    let Some(date_sources) = recipe.date_sources.as_ref().filter(|v| !v.is_empty()) else {
+        return get_file_date_from_source(
+            file,
+            recipe,
+            recipe
+                .date_comparator
+                .as_ref()
+                .unwrap_or(&DateComparator::ModificationDate),
+        );
+    };
+    let mut errors = Vec::new();
+    for date_source in date_sources {
+        match get_file_date_from_source(file, recipe, date_source) {
+            Ok(file_date) => return Ok(file_date),
+            Err(e) => errors.push(e.to_string()),
+        }
+    }
+    Err(anyhow::Error::msg(format!(
+        "{} - No date source succeeded: {}",
+        recipe.name,
+        errors.join(", ")
+    )))
+}
+
+/// Gets the date of a file by picking, among the dates of its available date sources, the one chosen by a date strategy.
+/// The date sources are the ones of the recipe, or all of them if it has none, and an image without EXIF capture date has no ExifDate.
+///
+/// ### Parameters
+/// - `file`: The file to get the date of.
+/// - `recipe`: The recipe defining the date sources.
+/// - `date_strategy`: The strategy picking the date.
+///
+/// ### Returns
+/// - `Result<DateTime<Utc>, anyhow::Error>`: The date of the file, the first date source being preferred on ties.
+fn get_file_date_by_strategy(
+    file: &Path,
+    recipe: &Recipe,
+    date_strategy: &DateStrategy,
+) -> anyhow::Result<DateTime<Utc>> {
+    let all_date_sources = [
+        DateComparator::ExifDate,
+        DateComparator::CreationDate,
+        DateComparator::ModificationDate,
+    ];
+    let date_sources = match recipe.date_sources.as_ref().filter(|v| !v.is_empty()) {
+        Some(date_sources) => date_sources.as_slice(),
+        None => &all_date_sources,
+    };
+    let dates = date_sources
+        .iter()
+        .filter_map(|date_source| match date_source {
+            DateComparator::ExifDate => exif_date::get_exif_date(file),
+            date_source => get_file_date_from_source(file, recipe, date_source).ok(),
+        });
+    let file_date = match date_strategy {
+        DateStrategy::Earliest => dates.min(),
+        DateStrategy::Latest => dates.max(),
+        DateStrategy::ClosestTo(reference_date) => {
+            let reference_date = parse_day(reference_date)?;
+            dates.min_by_key(|v| (*v - reference_date).abs())
+        }
+    };
+    file_date.ok_or_else(|| {
+        anyhow::Error::msg(format!(
+            "{} - No date source succeeded for {}",
+            recipe.name,
+            file.display()
+        ))
+    })
+}
+
+/// Gets the date of a file from a date source.
+///
+/// ### Parameters
+/// - `file`: The file to get the date of.
+/// - `recipe`: The recipe the date is got for.
+/// - `date_source`: The date source.
+///
+/// ### Returns
+/// - `Result<DateTime<Utc>, anyhow::Error>`: The date of the file.
+fn get_file_date_from_source(
+    file: &Path,
+    recipe: &Recipe,
+    date_source: &DateComparator,
+) -> anyhow::Result<DateTime<Utc>> {
+    match date_source {
+        DateComparator::CreationDate => get_creation_date(file).map_err(|e| {
+            anyhow::Error::msg(format!(
+                "{} - Error getting creation date: {}",
+                recipe.name, e
+            ))
+        }),
+        DateComparator::ModificationDate => get_last_modification_date(file).map_err(|e| {
+            anyhow::Error::msg(format!(
+                "{} - Error getting last modification date: {}",
+                recipe.name, e
+            ))
+        }),
+        DateComparator::ExifDate => match exif_date::get_exif_date(file) {
+            Some(exif_date) => Ok(exif_date),
+            None => get_file_date_from_source(file, recipe, &DateComparator::ModificationDate),
+        },
+        DateComparator::AccessedDate => match get_last_access_date(file) {
+            Ok(last_access_date) => Ok(last_access_date),
+            Err(_) => get_file_date_from_source(file, recipe, &DateComparator::ModificationDate),
+        },
+    }
+}
+
+/// Gets the last modification date of a file.
+///
+/// ### Parameters
+/// - `file`: The file to get the last modification date of.
+///
+/// ### Returns
+/// - `Result<DateTime<Utc>, anyhow::Error>`: The last modification date of the file.
+pub fn get_last_modification_date(file: &Path) -> anyhow::Result<DateTime<Utc>> {
+    let metadata = fs::metadata(file)?;
+    let last_modification_date = metadata.modified()?;
+    let last_modification_date = DateTime::<Utc>::from(last_modification_date);
+    Ok(last_modification_date)
+}
+
+/// Gets the last access date of a file.
+/// The access dates are not updated on the filesystems mounted with `noatime`, and only once a day with `relatime`.
+///
+/// ### Parameters
+/// - `file`: The file to get the last access date of.
+///
+/// ### Returns
+/// - `Result<DateTime<Utc>, anyhow::Error>`: The last access date of the file, an error if the platform does not support it.
+fn get_last_access_date(file: &Path) -> anyhow::Result<DateTime<Utc>> {
+    let metadata = fs::metadata(file)?;
+    let last_access_date = metadata.accessed()?;
+    let last_access_date = DateTime::<Utc>::from(last_access_date);
+    Ok(last_access_date)
+}
+
+/// Gets the date a file arrived in its folder: its status change time, which moving or extracting the file sets unlike its modification time.
+///
+/// ### Parameters
+/// - `file`: The file to get the arrival date of.
+///
+/// ### Returns
+/// - `Result<DateTime<Utc>, anyhow::Error>`: The arrival date of the file.
+#[cfg(unix)]
+pub fn get_arrival_date(file: &Path) -> anyhow::Result<DateTime<Utc>> {
+    use std::os::unix::fs::MetadataExt;
+    let metadata = fs::metadata(file)?;
+    DateTime::from_timestamp(metadata.ctime(), metadata.ctime_nsec() as u32).ok_or_else(|| {
+        anyhow::Error::msg(format!("Invalid status change time of {}", file.display()))
+    })
+}
+
+/// Gets the date a file arrived in its folder: its creation date, which copying or extracting the file sets, unlike its modification time, often preserved.
+///
+/// ### Parameters
+/// - `file`: The file to get the arrival date of.
+///
+/// ### Returns
+/// - `Result<DateTime<Utc>, anyhow::Error>`: The arrival date of the file.
+#[cfg(not(unix))]
+pub fn get_arrival_date(file: &Path) -> anyhow::Result<DateTime<Utc>> {
+    get_creation_date(file)
+}
+
+/// Gets the creation date of a file.
+///
+/// ### Parameters
+/// - `file`: The file to get the creation date of.
+///
+/// ### Returns
+/// - `Result<DateTime<Utc>, anyhow::Error>`: The creation date of the file.
+fn get_creation_date(file: &Path) -> anyhow::Result<DateTime<Utc>> {
+    let metadata = fs::metadata(file)?;
+    let creation_date = metadata.created()?;
+    let creation_date = DateTime::<Utc>::from(creation_date);
+    Ok(creation_date)
+}
+
+/// Converts a date to a folder name.
+///
+/// ### Parameters
+/// - `date`: The date to convert.
+/// - `format`: The format to use.
+///
+/// ### Returns
+/// - `String`: The folder name.
+pub fn date_to_folder_name(date: &DateTime<Utc>, format: &Option<String>) -> String {
+    if let Some(format) = format {
+        date.format(format.as_str()).to_string()
+    } else {
+        "".to_string()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::file_organizer::tests::create_dated_file;
+    use tempfile::TempDir;
+
+    #[test]
+    fn date_tokens_need_the_file_date() {
+        let recipe = Recipe {
+            subfolders: Some(vec!["{ext}".to_string()]),
+            ..Default::default()
+        };
+        assert!(!needs_file_date(&recipe));
+        let recipe = Recipe {
+            subfolders: Some(vec!["{ext}".to_string(), "%Y".to_string()]),
+            ..recipe
+        };
+        assert!(needs_file_date(&recipe));
+        let recipe = Recipe {
+            subfolders: None,
+            date_to: Some("2024-01-01".to_string()),
+            ..recipe
+        };
+        assert!(needs_file_date(&recipe));
+    }
+
+    #[test]
+    fn exif_date_takes_precedence_over_modification_date() {
+        let root = TempDir::new().unwrap();
+        let (source, destination) = (root.path().join("camera"), root.path().join("photos"));
+        fs::create_dir_all(&source).unwrap();
+        fs::create_dir_all(&destination).unwrap();
+        fs::write(
+            source.join("exif.jpg"),
+            exif_date::tests::jpeg_with_capture_date("2015:08:09 10:11:12"),
+        )
+        .unwrap();
+        create_dated_file(&source.join("bare.jpg"), "no exif", "2021-01-01");
+        let recipe = Recipe {
+            name: "photos".to_string(),
+            source_folder: source.clone(),
+            destination_folder: destination,
+            date_sources: Some(vec![
+                DateComparator::ExifDate,
+                DateComparator::ModificationDate,
+            ]),
+            ..Default::default()
+        };
+
+        let exif_date = get_file_date(&source.join("exif.jpg"), &recipe).unwrap();
+        let fallback_date = get_file_date(&source.join("bare.jpg"), &recipe).unwrap();
+
+        assert_eq!(exif_date.format("%Y-%m-%d").to_string(), "2015-08-09");
+        assert_eq!(fallback_date.format("%Y-%m-%d").to_string(), "2021-01-01");
+    }
+
+    #[test]
+    fn modification_date_source_comes_first_when_listed_first() {
+        let root = TempDir::new().unwrap();
+        let photo = root.path().join("exif.jpg");
+        fs::write(
+            &photo,
+            exif_date::tests::jpeg_with_capture_date("2015:08:09 10:11:12"),
+        )
+        .unwrap();
+        let recipe = Recipe {
+            date_sources: Some(vec![
+                DateComparator::ModificationDate,
+                DateComparator::ExifDate,
+            ]),
+            ..Default::default()
+        };
+
+        assert_eq!(
+            get_file_date(&photo, &recipe).unwrap(),
+            get_last_modification_date(&photo).unwrap()
+        );
+    }
+
+    #[test]
+    fn invalid_weekday_is_rejected() {
+        let recipe = Recipe {
+            name: "weekdays".to_string(),
+            weekday_filter: Some(vec!["Caturday".to_string()]),
+            ..Default::default()
+        };
+
+        let error = parse_weekday_filter(&recipe).unwrap_err();
+
+        assert_eq!(error.to_string(), "weekdays - Invalid weekday: Caturday");
+    }
+
+    #[test]
+    fn accessed_date_errors_fall_back_to_the_modification_date() {
+        let recipe = Recipe {
+            name: "atime".to_string(),
+            ..Default::default()
+        };
+
+        let error = get_file_date_from_source(
+            Path::new("/nonexistent/file.txt"),
+            &recipe,
+            &DateComparator::AccessedDate,
+        )
+        .unwrap_err();
+
+        assert!(
+            error
+                .to_string()
+                .starts_with("atime - Error getting last modification date"),
+            "{error}"
+        );
+    }
+
+    #[test]
+    fn subfolder_formats_are_checked_when_loading_the_recipes() {
+        let with_formats = |subfolders: &[&str], file_name_template: Option<&str>| Recipe {
+            name: "formats".to_string(),
+            subfolders: Some(subfolders.iter().map(|v| v.to_string()).collect()),
+            file_name_template: file_name_template.map(String::from),
+            ..Default::default()
+        };
+        let error = |recipe: Recipe| validate_subfolders(&recipe).unwrap_err().to_string();
+
+        assert!(validate_subfolders(&with_formats(&["%Y", "%m-%B", "{ext}", "Q%q"], None)).is_ok());
+        assert_eq!(
+            error(with_formats(&["%Y", "%Q", "%"], None)),
+            "formats - Invalid subfolder format: %Q, %"
+        );
+        assert_eq!(
+            error(with_formats(&["%Y", ""], None)),
+            "formats - Empty subfolder format, remove it or set a format such as %Y"
+        );
+        assert_eq!(
+            error(with_formats(&["%Y"], Some("%Y_%K_{name}"))),
+            "formats - Invalid file_name_template format: %Y_%K_{name}"
+        );
+    }
+}
@@ -0,0 +1,457 @@
+use crate::file_organizer::dates::get_last_modification_date;
+use crate::file_organizer::file_names::get_free_file_name;
+use crate::file_organizer::patch::{self, PatchOperation};
+use crate::file_organizer::plan::{PlanOperation, PlanOperationKind};
+use crate::file_organizer::settings::{ConflictPolicy, DedupeKeeper, Recipe};
+use crate::file_organizer::{
+    FileOutcome, RecipeContext, hash_file, match_file, move_file, near_dupe,
+};
+use anyhow::{Context, Result};
+use colored::*;
+use rayon::prelude::*;
+use std::collections::HashMap;
+use std::fs;
+use std::fs::DirEntry;
+use std::path::{Path, PathBuf};
+use tracing::{info, info_span, warn};
+
+/// The name of the folder of the destination folder the duplicates are moved into.
+pub const DUPLICATES_FOLDER_NAME: &str = "duplicates";
+
+/// Runs a dedupe recipe.
+/// The matched files are grouped by content, one file of each group is kept in place and the others are moved into the duplicates folder.
+/// Files are first grouped by size so that only the files sharing their size with another file get hashed.
+/// With image_near_dupe, the images are grouped by perceptual hash instead, so that their visually similar copies are moved as well.
+///
+/// ### Parameters
+/// - `entries`: The entries of the source folder.
+/// - `recipe`: The recipe to run.
+/// - `context`: The context of the recipe run.
+/// - `is_iterative`: If true, the files are hashed iteratively instead of in parallel.
+///
+/// ### Returns
+/// - `Vec<Result<FileOutcome>>`: The results of the recipe run, one per entry.
+pub fn run_dedupe(
+    entries: Vec<DirEntry>,
+    recipe: &Recipe,
+    context: &RecipeContext,
+    is_iterative: bool,
+) -> Vec<Result<FileOutcome>> {
+    let mut results = Vec::new();
+    let mut matched_files = Vec::new();
+    for entry in entries {
+        let path = entry.path();
+        match match_file(&path, recipe, context).and_then(|v| Ok((v, fs::metadata(&path)?))) {
+            Ok((Some(match_result), metadata)) => {
+                matched_files.push((path, match_result.content_hash, metadata.len()))
+            }
+            Ok((None, _)) => results.push(Ok(FileOutcome::NotMatched)),
+            Err(e) => results.push(Err(e.context(path.display().to_string()))),
+        }
+    }
+    let mut groups = Vec::new();
+    if recipe.image_near_dupe.unwrap_or(false) {
+        let (images, others) = matched_files
+            .into_iter()
+            .partition(|(path, _, _)| near_dupe::is_image(path));
+        matched_files = others;
+        let hasher = near_dupe::build_hasher();
+        let get_image_hash = |(file, _, _): &(PathBuf, Option<String>, u64)| {
+            near_dupe::get_image_hash(file, &hasher)
+        };
+        let image_hashes: Vec<_> = if is_iterative {
+            images.iter().map(get_image_hash).collect()
+        } else {
+            context.install(|| images.par_iter().map(get_image_hash).collect())
+        };
+        let mut hashed_images = Vec::new();
+        for (image, image_hash) in images.into_iter().zip(image_hashes) {
+            match image_hash {
+                Ok(image_hash) => hashed_images.push((image.0, image_hash)),
+                Err(_) => matched_files.push(image),
+            }
+        }
+        groups.extend(near_dupe::group_near_duplicates(
+            hashed_images,
+            recipe
+                .near_dupe_threshold
+                .unwrap_or(near_dupe::DEFAULT_NEAR_DUPE_THRESHOLD),
+        ));
+    }
+    let mut files_by_size: HashMap<u64, Vec<(PathBuf, Option<String>)>> = HashMap::new();
+    for (path, content_hash, size) in matched_files {
+        files_by_size
+            .entry(size)
+            .or_default()
+            .push((path, content_hash));
+    }
+    let candidates: Vec<(PathBuf, Option<String>)> = files_by_size
+        .into_values()
+        .flat_map(|files| {
+            if files.len() < 2 {
+                results.extend(files.iter().map(|_| Ok(FileOutcome::NotMatched)));
+                Vec::new()
+            } else {
+                files
+            }
+        })
+        .collect();
+    let get_hash = |(file, hash): &(PathBuf, Option<String>)| match hash {
+        Some(hash) => Ok(hash.clone()),
+        None => hash_file(file),
+    };
+    let hashes: Vec<_> = if is_iterative {
+        candidates.iter().map(get_hash).collect()
+    } else {
+        context.install(|| candidates.par_iter().map(get_hash).collect())
+    };
+    let mut files_by_hash: HashMap<String, Vec<PathBuf>> = HashMap::new();
+    for ((file, _), hash) in candidates.into_iter().zip(hashes) {
+        match hash {
+            Ok(hash) => files_by_hash.entry(hash).or_default().push(file),
+            Err(e) => results.push(Err(anyhow::Error::msg(format!(
+                "{} - Error hashing file {}: {}",
+                recipe.name,
+                file.display(),
+                e
+            )))),
+        }
+    }
+    let duplicates_folder = recipe.destination_folder.join(DUPLICATES_FOLDER_NAME);
+    groups.extend(files_by_hash.into_values());
+    for group in &mut groups {
+        group.sort();
+    }
+    groups.sort();
+    for mut group in groups {
+        if group.len() < 2 {
+            results.extend(group.iter().map(|_| Ok(FileOutcome::NotMatched)));
+            continue;
+        }
+        let keeper = match pick_keeper(&mut group, recipe) {
+            Ok(keeper) => keeper,
+            Err(e) => {
+                results.extend(group.iter().map(|_| Err(anyhow::Error::msg(e.to_string()))));
+                continue;
+            }
+        };
+        results.push(Ok(FileOutcome::NotMatched));
+        for duplicate in group {
+            results.push(
+                move_duplicate(&duplicate, &keeper, &duplicates_folder, recipe, context)
+                    .with_context(|| duplicate.display().to_string()),
+            );
+        }
+    }
+    results
+}
+
+/// Picks the file to keep in place from a group of identical files and removes it from the group.
+///
+/// ### Parameters
+/// - `group`: The identical files, sorted by path.
+/// - `recipe`: The recipe defining the keeper.
+///
+/// ### Returns
+/// - `Result<PathBuf, anyhow::Error>`: The file to keep.
+fn pick_keeper(group: &mut Vec<PathBuf>, recipe: &Recipe) -> Result<PathBuf> {
+    let keeper = recipe
+        .dedupe_keeper
+        .as_ref()
+        .unwrap_or(&DedupeKeeper::Oldest);
+    let index = match keeper {
+        DedupeKeeper::FirstName => 0,
+        DedupeKeeper::Oldest | DedupeKeeper::Newest => {
+            let mut dates = Vec::new();
+            for (index, file) in group.iter().enumerate() {
+                dates.push((get_last_modification_date(file)?, index));
+            }
+            let picked = if *keeper == DedupeKeeper::Oldest {
+                dates.iter().min_by_key(|(date, index)| (*date, *index))
+            } else {
+                dates
+                    .iter()
+                    .max_by_key(|(date, index)| (*date, std::cmp::Reverse(*index)))
+            };
+            picked.map(|(_, index)| *index).unwrap_or(0)
+        }
+    };
+    Ok(group.remove(index))
+}
+
+/// Moves a duplicate into the duplicates folder.
+///
+/// ### Parameters
+/// - `duplicate`: The duplicate to move.
+/// - `keeper`: The identical file kept in place.
+/// - `duplicates_folder`: The folder the duplicates are moved into.
+/// - `recipe`: The recipe being run.
+/// - `context`: The context of the recipe run.
+///
+/// ### Returns
+/// - `Result<FileOutcome, anyhow::Error>`: What has been done with the duplicate.
+fn move_duplicate(
+    duplicate: &Path,
+    keeper: &Path,
+    duplicates_folder: &Path,
+    recipe: &Recipe,
+    context: &RecipeContext,
+) -> Result<FileOutcome> {
+    let _file_span =
+        info_span!(parent: &context.span, "file", path = %duplicate.display()).entered();
+    let mut dest_file = duplicates_folder.join(duplicate.file_name().unwrap());
+    let on_conflict = recipe.on_conflict.clone().unwrap_or_default();
+    let is_conflicting = {
+        let mut planned_files = context.planned_files.lock().unwrap();
+        let is_conflicting = planned_files.contains(&dest_file) || dest_file.exists();
+        if is_conflicting && on_conflict == ConflictPolicy::Rename {
+            dest_file = get_free_file_name(&dest_file, &planned_files);
+        }
+        planned_files.insert(dest_file.clone());
+        is_conflicting
+    };
+    if is_conflicting && on_conflict == ConflictPolicy::Skip {
+        if let Some(skipped_operations) = &context.skipped_operations {
+            skipped_operations.lock().unwrap().push(PlanOperation {
+                recipe: recipe.name.clone(),
+                operation: PlanOperationKind::Move,
+                source: duplicate.to_path_buf(),
+                destination: dest_file.clone(),
+                destination_exists: true,
+                conflict: Some(ConflictPolicy::Skip),
+            });
+        }
+        if !context.quiet {
+            warn!(
+                "{} {} {} - {} (duplicate of {})",
+                "⚠️".yellow(),
+                recipe.name.blue(),
+                "Duplicate name taken, skipped".yellow(),
+                dest_file.display(),
+                keeper.display()
+            );
+        }
+        return Ok(FileOutcome::ConflictSkipped);
+    }
+    let destination_exists = dest_file.exists();
+    if let Some(planned_operations) = &context.planned_operations {
+        planned_operations.lock().unwrap().push(PlanOperation {
+            recipe: recipe.name.clone(),
+            operation: PlanOperationKind::Move,
+            source: duplicate.to_path_buf(),
+            destination: dest_file.clone(),
+            destination_exists,
+            conflict: is_conflicting.then(|| on_conflict.clone()),
+        });
+    }
+    if !context.dry_run {
+        let created_folder = patch::get_created_folder(duplicates_folder);
+        fs::create_dir_all(duplicates_folder)?;
+        let backup = match &context.patch {
+            Some(patch) if destination_exists => Some(patch.backup(&dest_file)?),
+            _ => None,
+        };
+        if let Err(e) = move_file(duplicate, &dest_file) {
+            return Err(anyhow::Error::msg(format!(
+                "{} - Error moving duplicate: {}",
+                recipe.name, e
+            )));
+        }
+        if let Some(patch) = &context.patch {
+            patch.record(PatchOperation {
+                recipe: recipe.name.clone(),
+                operation: PlanOperationKind::Move,
+                source: duplicate.to_path_buf(),
+                destination: dest_file.clone(),
+                backup,
+                created_folder,
+                archive_member: None,
+                size: None,
+                modified: None,
+            });
+        }
+    }
+    if let Some(performed_operations) = &context.performed_operations {
+        performed_operations.lock().unwrap().push(PlanOperation {
+            recipe: recipe.name.clone(),
+            operation: PlanOperationKind::Move,
+            source: duplicate.to_path_buf(),
+            destination: dest_file.clone(),
+            destination_exists,
+            conflict: is_conflicting.then(|| on_conflict.clone()),
+        });
+    }
+    if let Some(folders_counts) = &context.folders_counts {
+        *folders_counts
+            .lock()
+            .unwrap()
+            .entry(duplicates_folder.to_path_buf())
+            .or_default() += 1;
+    }
+    if !context.quiet {
+        info!(
+            operation = "move",
+            destination = %dest_file.display(),
+            keeper = %keeper.display(),
+            "{} {} {} - {} (duplicate of {})",
+            "✅".green(),
+            recipe.name.blue(),
+            "Duplicate moved".green(),
+            dest_file.display(),
+            keeper.display()
+        );
+    }
+    if is_conflicting {
+        Ok(FileOutcome::Conflicted)
+    } else {
+        Ok(FileOutcome::Processed)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::file_organizer::settings::{FileOperation, RecipeMode};
+    use crate::file_organizer::{FileOrganizer, RunOptions};
+    use tempfile::TempDir;
+
+    /// Builds a dedupe recipe over a folder, the duplicates being moved into its duplicates subfolder.
+    ///
+    /// ### Parameters
+    /// - `folder`: The folder to dedupe.
+    /// - `dedupe_keeper`: Which file of each group is kept in place.
+    ///
+    /// ### Returns
+    /// - `Recipe`: The dedupe recipe.
+    fn dedupe_recipe(folder: &Path, dedupe_keeper: DedupeKeeper) -> Recipe {
+        Recipe {
+            name: "dedupe".to_string(),
+            source_folder: folder.to_path_buf(),
+            destination_folder: folder.to_path_buf(),
+            allowed_extensions: Some(Vec::new()),
+            operation: FileOperation::Move,
+            mode: Some(RecipeMode::Dedupe),
+            dedupe_keeper: Some(dedupe_keeper),
+            ..Default::default()
+        }
+    }
+
+    /// Runs a single dedupe recipe.
+    ///
+    /// ### Parameters
+    /// - `recipe`: The dedupe recipe to run.
+    fn run_dedupe_recipe(recipe: &Recipe) {
+        let recipes_file = recipe.source_folder.parent().unwrap().join("dedupe.json");
+        let settings = serde_json::json!({ "recipes": [recipe] });
+        fs::write(&recipes_file, settings.to_string()).unwrap();
+        FileOrganizer::new(recipes_file, RunOptions::default())
+            .unwrap()
+            .run()
+            .unwrap();
+    }
+
+    #[test]
+    fn identical_files_are_moved_aside_but_one() {
+        let root = TempDir::new().unwrap();
+        let folder = root.path().join("downloads");
+        fs::create_dir_all(&folder).unwrap();
+        for name in ["a.jpg", "b.jpg", "c.jpg"] {
+            fs::write(folder.join(name), "same content").unwrap();
+        }
+        fs::write(folder.join("unique.jpg"), "other content").unwrap();
+        fs::write(folder.join("same_size.jpg"), "same CONTENT").unwrap();
+
+        run_dedupe_recipe(&dedupe_recipe(&folder, DedupeKeeper::FirstName));
+
+        let duplicates_folder = folder.join(DUPLICATES_FOLDER_NAME);
+        assert!(folder.join("a.jpg").is_file());
+        assert!(duplicates_folder.join("b.jpg").is_file());
+        assert!(duplicates_folder.join("c.jpg").is_file());
+        assert!(folder.join("unique.jpg").is_file());
+        assert!(folder.join("same_size.jpg").is_file());
+        assert_eq!(fs::read_dir(&duplicates_folder).unwrap().count(), 2);
+    }
+
+    #[test]
+    fn duplicates_with_the_same_name_follow_on_conflict_across_runs() {
+        let root = TempDir::new().unwrap();
+        let folder = root.path().join("photos");
+        for subfolder in ["a", "b", "c"] {
+            fs::create_dir_all(folder.join(subfolder)).unwrap();
+            fs::write(folder.join(subfolder).join("IMG.jpg"), "same content").unwrap();
+        }
+        let recipe = Recipe {
+            recursive: Some(true),
+            on_conflict: Some(ConflictPolicy::Rename),
+            ..dedupe_recipe(&folder, DedupeKeeper::FirstName)
+        };
+
+        run_dedupe_recipe(&recipe);
+        fs::create_dir_all(folder.join("d")).unwrap();
+        fs::write(folder.join("d").join("IMG.jpg"), "same content").unwrap();
+        run_dedupe_recipe(&recipe);
+
+        let duplicates_folder = folder.join(DUPLICATES_FOLDER_NAME);
+        let mut names: Vec<_> = fs::read_dir(&duplicates_folder)
+            .unwrap()
+            .map(|v| v.unwrap().file_name().to_string_lossy().to_string())
+            .collect();
+        names.sort();
+        assert_eq!(names, ["IMG (1).jpg", "IMG (2).jpg", "IMG.jpg"]);
+        assert!(folder.join("a").join("IMG.jpg").is_file());
+        for subfolder in ["b", "c", "d"] {
+            assert!(!folder.join(subfolder).join("IMG.jpg").exists());
+        }
+    }
+
+    #[test]
+    fn duplicates_with_a_taken_name_are_left_in_place_by_default() {
+        let root = TempDir::new().unwrap();
+        let folder = root.path().join("photos");
+        for subfolder in ["a", "b", "c"] {
+            fs::create_dir_all(folder.join(subfolder)).unwrap();
+            fs::write(folder.join(subfolder).join("IMG.jpg"), "same content").unwrap();
+        }
+        let recipe = Recipe {
+            recursive: Some(true),
+            ..dedupe_recipe(&folder, DedupeKeeper::FirstName)
+        };
+
+        run_dedupe_recipe(&recipe);
+
+        let duplicates_folder = folder.join(DUPLICATES_FOLDER_NAME);
+        assert_eq!(fs::read_dir(&duplicates_folder).unwrap().count(), 1);
+        assert!(duplicates_folder.join("IMG.jpg").is_file());
+        assert!(folder.join("a").join("IMG.jpg").is_file());
+        assert!(folder.join("c").join("IMG.jpg").is_file());
+    }
+
+    #[test]
+    fn newest_keeper_keeps_the_latest_file() {
+        let root = TempDir::new().unwrap();
+        let folder = root.path().join("inbox");
+        fs::create_dir_all(&folder).unwrap();
+        for (name, modified) in [("old.txt", 1_000_000), ("new.txt", 2_000_000)] {
+            fs::write(folder.join(name), "same").unwrap();
+            fs::File::options()
+                .write(true)
+                .open(folder.join(name))
+                .unwrap()
+                .set_modified(
+                    std::time::SystemTime::UNIX_EPOCH + std::time::Duration::from_secs(modified),
+                )
+                .unwrap();
+        }
+        let mut group = vec![folder.join("old.txt"), folder.join("new.txt")];
+        let recipe = Recipe {
+            dedupe_keeper: Some(DedupeKeeper::Newest),
+            ..Default::default()
+        };
+
+        assert_eq!(
+            pick_keeper(&mut group, &recipe).unwrap(),
+            folder.join("new.txt")
+        );
+        assert_eq!(group, vec![folder.join("old.txt")]);
+    }
+}
@@ -0,0 +1,126 @@
+use crate::file_organizer::hash_file;
+use std::collections::HashMap;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::sync::Mutex;
+
+/// IndexedFile is a struct that contains a file of a destination folder, with the file its content hash is computed from.
+#[derive(Clone)]
+struct IndexedFile {
+    path: PathBuf,
+    content: PathBuf,
+}
+
+/// DestinationIndex is a struct that contains the files of the destination folders of a recipe run, by size, their content hashes being computed only when a file of the same size is organized.
+#[derive(Default)]
+pub struct DestinationIndex {
+    folders: Mutex<HashMap<PathBuf, HashMap<u64, Vec<IndexedFile>>>>,
+    hashes: Mutex<HashMap<PathBuf, String>>,
+}
+
+impl DestinationIndex {
+    /// Finds a file with the same content as a file in a destination folder, the folder being listed on its first use.
+    ///
+    /// ### Parameters
+    /// - `file`: The file to organize.
+    /// - `folder`: The destination folder of the file.
+    /// - `content_hash`: The content hash of the file, if already computed.
+    ///
+    /// ### Returns
+    /// - `Result<Option<PathBuf>, anyhow::Error>`: The file of the destination folder with the same content, None if there is none.
+    pub fn find_duplicate(
+        &self,
+        file: &Path,
+        folder: &Path,
+        content_hash: Option<&str>,
+    ) -> anyhow::Result<Option<PathBuf>> {
+        let size = fs::metadata(file)?.len();
+        let candidates: Vec<IndexedFile> = self
+            .folders
+            .lock()
+            .unwrap()
+            .entry(folder.to_path_buf())
+            .or_insert_with(|| list_files(folder))
+            .get(&size)
+            .cloned()
+            .unwrap_or_default();
+        if candidates.is_empty() {
+            return Ok(None);
+        }
+        let content_hash = match content_hash {
+            Some(content_hash) => content_hash.to_string(),
+            None => hash_file(file)?,
+        };
+        for candidate in candidates {
+            if candidate.path == file {
+                continue;
+            }
+            if self.get_hash(&candidate.content).as_deref() == Some(content_hash.as_str()) {
+                return Ok(Some(candidate.path));
+            }
+        }
+        Ok(None)
+    }
+
+    /// Adds a file organized by the run to its destination folder, so that the identical files organized after it are skipped as well.
+    ///
+    /// ### Parameters
+    /// - `path`: The destination of the file.
+    /// - `content`: The file its content hash is computed from: the destination, or the source in dry run.
+    pub fn add(&self, path: &Path, content: &Path) {
+        let (Some(folder), Ok(metadata)) = (path.parent(), fs::metadata(content)) else {
+            return;
+        };
+        if let Some(files) = self.folders.lock().unwrap().get_mut(folder) {
+            files.entry(metadata.len()).or_default().push(IndexedFile {
+                path: path.to_path_buf(),
+                content: content.to_path_buf(),
+            });
+        }
+    }
+
+    /// Gets the content hash of a file, computed once per run.
+    ///
+    /// ### Parameters
+    /// - `file`: The file.
+    ///
+    /// ### Returns
+    /// - `Option<String>`: The content hash, None if the file cannot be read.
+    fn get_hash(&self, file: &Path) -> Option<String> {
+        if let Some(hash) = self.hashes.lock().unwrap().get(file) {
+            return Some(hash.clone());
+        }
+        let hash = hash_file(file).ok()?;
+        self.hashes
+            .lock()
+            .unwrap()
+            .insert(file.to_path_buf(), hash.clone());
+        Some(hash)
+    }
+}
+
+/// Lists the files of a destination folder by size.
+///
+/// ### Parameters
+/// - `folder`: The destination folder.
+///
+/// ### Returns
+/// - `HashMap<u64, Vec<IndexedFile>>`: The files of the folder, not its subfolders, by size, empty if the folder does not exist.
+fn list_files(folder: &Path) -> HashMap<u64, Vec<IndexedFile>> {
+    let mut files: HashMap<u64, Vec<IndexedFile>> = HashMap::new();
+    let Ok(entries) = fs::read_dir(folder) else {
+        return files;
+    };
+    for entry in entries.flatten() {
+        let Ok(metadata) = entry.metadata() else {
+            continue;
+        };
+        if metadata.is_file() {
+            files.entry(metadata.len()).or_default().push(IndexedFile {
+                path: entry.path(),
+                content: entry.path(),
+            });
+        }
+    }
+    files
+}
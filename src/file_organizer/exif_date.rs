@@ -0,0 +1,81 @@
+use chrono::{DateTime, NaiveDate, Utc};
+use exif::{In, Tag, Value};
+use std::fs;
+use std::io::BufReader;
+use std::path::Path;
+
+/// Gets the capture date of an image from its EXIF `DateTimeOriginal` tag.
+/// The date is the one shown by the camera clock, read as UTC so that the date folders match it.
+///
+/// ### Parameters
+/// - `file`: The image file.
+///
+/// ### Returns
+/// - `Option<DateTime<Utc>>`: The capture date, None if the file has no EXIF data or no valid capture date.
+pub fn get_exif_date(file: &Path) -> Option<DateTime<Utc>> {
+    let mut reader = BufReader::new(fs::File::open(file).ok()?);
+    let exif = exif::Reader::new().read_from_container(&mut reader).ok()?;
+    let field = exif.get_field(Tag::DateTimeOriginal, In::PRIMARY)?;
+    let Value::Ascii(values) = &field.value else {
+        return None;
+    };
+    let date = exif::DateTime::from_ascii(values.first()?).ok()?;
+    let date = NaiveDate::from_ymd_opt(date.year as i32, date.month as u32, date.day as u32)?
+        .and_hms_opt(date.hour as u32, date.minute as u32, date.second as u32)?;
+    Some(date.and_utc())
+}
+
+#[cfg(test)]
+pub(crate) mod tests {
+    use super::*;
+    use tempfile::TempDir;
+
+    /// Builds a minimal JPEG holding only an EXIF `DateTimeOriginal` tag.
+    ///
+    /// ### Parameters
+    /// - `capture_date`: The capture date, as `YYYY:MM:DD HH:MM:SS`.
+    ///
+    /// ### Returns
+    /// - `Vec<u8>`: The bytes of the JPEG.
+    pub(crate) fn jpeg_with_capture_date(capture_date: &str) -> Vec<u8> {
+        let mut tiff = b"MM\x00\x2a\x00\x00\x00\x08".to_vec();
+        // IFD0, with a single entry pointing to the EXIF IFD at offset 26.
+        tiff.extend([0x00, 0x01, 0x87, 0x69, 0x00, 0x04, 0x00, 0x00, 0x00, 0x01]);
+        tiff.extend([0x00, 0x00, 0x00, 26, 0x00, 0x00, 0x00, 0x00]);
+        // EXIF IFD, with a single DateTimeOriginal entry whose value is at offset 44.
+        tiff.extend([0x00, 0x01, 0x90, 0x03, 0x00, 0x02, 0x00, 0x00, 0x00, 20]);
+        tiff.extend([0x00, 0x00, 0x00, 44, 0x00, 0x00, 0x00, 0x00]);
+        tiff.extend(capture_date.as_bytes());
+        tiff.push(0);
+        let mut jpeg = vec![0xFF, 0xD8, 0xFF, 0xE1];
+        jpeg.extend(((tiff.len() + 8) as u16).to_be_bytes());
+        jpeg.extend(b"Exif\x00\x00");
+        jpeg.extend(tiff);
+        jpeg.extend([0xFF, 0xD9]);
+        jpeg
+    }
+
+    #[test]
+    fn capture_date_is_read_from_exif() {
+        let folder = TempDir::new().unwrap();
+        let photo = folder.path().join("photo.jpg");
+        fs::write(&photo, jpeg_with_capture_date("2015:08:09 10:11:12")).unwrap();
+
+        assert_eq!(
+            get_exif_date(&photo).unwrap().to_rfc3339(),
+            "2015-08-09T10:11:12+00:00"
+        );
+    }
+
+    #[test]
+    fn files_without_exif_have_no_capture_date() {
+        let folder = TempDir::new().unwrap();
+        let (photo, text) = (folder.path().join("bare.jpg"), folder.path().join("a.txt"));
+        fs::write(&photo, [0xFF, 0xD8, 0xFF, 0xD9]).unwrap();
+        fs::write(&text, "not an image").unwrap();
+
+        assert_eq!(get_exif_date(&photo), None);
+        assert_eq!(get_exif_date(&text), None);
+        assert_eq!(get_exif_date(&folder.path().join("missing.jpg")), None);
+    }
+}
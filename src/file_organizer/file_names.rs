@@ -0,0 +1,344 @@
+use crate::file_organizer::dates::date_to_folder_name;
+use crate::file_organizer::settings::{Recipe, RenameStrategy};
+use crate::file_organizer::{
+    COUNTER_TOKEN, EXT_TOKEN, FORBIDDEN_FOLDER_CHARACTERS, HASH2_TOKEN, HASH8_TOKEN, NAME_TOKEN,
+    ORIGINAL_TOKEN,
+};
+use chrono::{DateTime, Utc};
+use std::collections::{HashMap, HashSet};
+use std::ffi::{OsStr, OsString};
+use std::path::{Path, PathBuf};
+
+/// Gets the destination of a file whose name has a counter token, with the first counter giving a destination neither existing nor planned.
+/// The next counter of each destination is cached, so that the counters taken are not probed again for each file.
+///
+/// ### Parameters
+/// - `dest_folder`: The destination folder.
+/// - `file_name`: The file name with its counter token.
+/// - `recipe`: The recipe normalizing the file name.
+/// - `planned_files`: The destinations already planned by the run.
+/// - `next_counters`: The next counter to try, by destination with its counter token.
+///
+/// ### Returns
+/// - `PathBuf`: The destination with the counter token replaced.
+pub fn get_counted_file_name(
+    dest_folder: &Path,
+    file_name: &OsStr,
+    recipe: &Recipe,
+    planned_files: &HashSet<PathBuf>,
+    next_counters: &mut HashMap<PathBuf, u32>,
+) -> PathBuf {
+    let next_counter = next_counters
+        .entry(dest_folder.join(file_name))
+        .or_insert(1);
+    let file_name = file_name.to_string_lossy();
+    loop {
+        let candidate = dest_folder.join(normalize_file_name(
+            OsString::from(file_name.replace(COUNTER_TOKEN, &next_counter.to_string())),
+            recipe,
+        ));
+        *next_counter += 1;
+        if !candidate.exists() && !planned_files.contains(&candidate) {
+            return candidate;
+        }
+    }
+}
+
+/// Gets a free name for a file whose destination is taken, by appending ` (1)`, ` (2)`... to its stem.
+///
+/// ### Parameters
+/// - `file`: The taken destination.
+/// - `planned_files`: The destinations already planned by the run.
+///
+/// ### Returns
+/// - `PathBuf`: The first destination neither existing nor planned.
+pub fn get_free_file_name(file: &Path, planned_files: &HashSet<PathBuf>) -> PathBuf {
+    let stem = file.file_stem().unwrap_or_default().to_string_lossy();
+    let extension = file
+        .extension()
+        .map(|v| format!(".{}", v.to_string_lossy()))
+        .unwrap_or_default();
+    let mut index = 1;
+    loop {
+        let candidate = file.with_file_name(format!("{} ({}){}", stem, index, extension));
+        if !candidate.exists() && !planned_files.contains(&candidate) {
+            return candidate;
+        }
+        index += 1;
+    }
+}
+
+/// Builds the destination file name of a file from the file name template of a recipe.
+/// The counter token is left in the file name, to be replaced once the destination folder is known to the run.
+///
+/// ### Parameters
+/// - `file`: The file to build the destination file name for.
+/// - `recipe`: The recipe defining the file name template.
+/// - `file_date`: The date of the file, for the strftime specifiers of the template.
+/// - `content_hash`: The content hash of the file, if the template uses it.
+///
+/// ### Returns
+/// - `OsString`: The destination file name, the original one if the recipe has no template.
+pub fn build_file_name(
+    file: &Path,
+    recipe: &Recipe,
+    file_date: &DateTime<Utc>,
+    content_hash: Option<&str>,
+) -> OsString {
+    let file_name = file.file_name().unwrap_or_default();
+    let Some(file_name_template) = &recipe.file_name_template else {
+        return file_name.to_os_string();
+    };
+    let mut new_file_name = date_to_folder_name(file_date, &Some(file_name_template.clone()))
+        .replace(NAME_TOKEN, &file_name.to_string_lossy())
+        .replace(
+            ORIGINAL_TOKEN,
+            &file.file_stem().unwrap_or_default().to_string_lossy(),
+        )
+        .replace(
+            EXT_TOKEN,
+            &file
+                .extension()
+                .map(|v| v.to_string_lossy())
+                .unwrap_or_default(),
+        );
+    if let Some(content_hash) = content_hash {
+        new_file_name = replace_hash_tokens(&new_file_name, content_hash);
+    }
+    OsString::from(new_file_name)
+}
+
+/// Checks if the file name template of a recipe numbers the files with a counter token.
+///
+/// ### Parameters
+/// - `recipe`: The recipe to check.
+///
+/// ### Returns
+/// - `bool`: True if the file name template has a counter token, false otherwise.
+pub fn uses_counter(recipe: &Recipe) -> bool {
+    recipe
+        .file_name_template
+        .as_ref()
+        .is_some_and(|v| v.contains(COUNTER_TOKEN))
+}
+
+/// Normalizes a file name built from the file name template, as set by a recipe.
+///
+/// ### Parameters
+/// - `file_name`: The file name.
+/// - `recipe`: The recipe.
+///
+/// ### Returns
+/// - `OsString`: The file name, sanitized and renamed according to the recipe.
+pub fn normalize_file_name(mut file_name: OsString, recipe: &Recipe) -> OsString {
+    if recipe.sanitize_filenames.unwrap_or(false) {
+        file_name = sanitize_file_name(file_name);
+    }
+    if let Some(rename_strategy) = &recipe.rename_strategy {
+        file_name = rename_file_name(file_name, rename_strategy);
+    }
+    file_name
+}
+
+/// Checks if a destination file keeps the extension of its source file, case insensitively.
+///
+/// ### Parameters
+/// - `from_file`: The source file.
+/// - `dest_file`: The destination file.
+///
+/// ### Returns
+/// - `bool`: True if both files have the same extension, or none, false otherwise.
+pub fn keeps_extension(from_file: &Path, dest_file: &Path) -> bool {
+    let to_lowercase = |file: &Path| file.extension().map(|v| v.to_string_lossy().to_lowercase());
+    to_lowercase(from_file) == to_lowercase(dest_file)
+}
+
+/// Replaces the content hash tokens of a subfolder or file name.
+///
+/// ### Parameters
+/// - `name`: The subfolder or file name.
+/// - `content_hash`: The hexadecimal content hash of the file.
+///
+/// ### Returns
+/// - `String`: The name with its `{hash8}` and `{hash2}` tokens replaced.
+pub fn replace_hash_tokens(name: &str, content_hash: &str) -> String {
+    name.replace(HASH8_TOKEN, &content_hash[..8])
+        .replace(HASH2_TOKEN, &content_hash[..2])
+}
+
+/// Checks if the content hash of the files is needed by a recipe, to build their destination folder or file name.
+///
+/// ### Parameters
+/// - `recipe`: The recipe to check.
+///
+/// ### Returns
+/// - `bool`: True if the subfolders or the file name template use a content hash token, false otherwise.
+pub fn needs_content_hash(recipe: &Recipe) -> bool {
+    recipe
+        .subfolders
+        .iter()
+        .flatten()
+        .chain(recipe.file_name_template.iter())
+        .any(|name| name.contains(HASH8_TOKEN) || name.contains(HASH2_TOKEN))
+}
+
+/// Sanitizes a folder name so that it is valid on all filesystems.
+/// The characters forbidden on Windows are replaced by underscores and the trailing dots and spaces are removed.
+/// Slashes are kept as they allow a single subfolder format to create nested folders.
+///
+/// ### Parameters
+/// - `name`: The folder name to sanitize.
+///
+/// ### Returns
+/// - `String`: The sanitized folder name.
+pub fn sanitize_folder_name(name: &str) -> String {
+    let sanitized: String = name
+        .chars()
+        .map(|c| {
+            if FORBIDDEN_FOLDER_CHARACTERS.contains(&c) || c.is_control() {
+                '_'
+            } else {
+                c
+            }
+        })
+        .collect();
+    let sanitized = sanitized.trim_end_matches([' ', '.']);
+    if sanitized.is_empty() {
+        "_".to_string()
+    } else {
+        sanitized.to_string()
+    }
+}
+
+/// Sanitizes a file name so that it is kept as is on Windows, which strips the trailing spaces and dots of the file names.
+/// The trailing whitespaces and dots are removed from the name, then from its stem, so that `report .pdf` becomes `report.pdf`.
+///
+/// ### Parameters
+/// - `name`: The file name to sanitize.
+///
+/// ### Returns
+/// - `OsString`: The sanitized file name, the original one if it is not valid UTF-8 or would be emptied.
+fn sanitize_file_name(name: OsString) -> OsString {
+    let Some(name_str) = name.to_str() else {
+        return name;
+    };
+    let is_trimmed = |c: char| c.is_whitespace() || c == '.';
+    let sanitized = name_str.trim_end_matches(is_trimmed);
+    let sanitized = match sanitized.rsplit_once('.') {
+        Some((stem, extension)) if !stem.trim_end_matches(is_trimmed).is_empty() => {
+            format!("{}.{}", stem.trim_end_matches(is_trimmed), extension)
+        }
+        _ => sanitized.to_string(),
+    };
+    if sanitized.is_empty() {
+        name
+    } else {
+        OsString::from(sanitized)
+    }
+}
+
+/// Normalizes a file name according to a rename strategy.
+/// Slugifying transliterates the name to lowercase ASCII, replaces its whitespaces with `_` and removes the characters other than letters, digits, `-`, `_` and `.`.
+///
+/// ### Parameters
+/// - `name`: The file name to normalize.
+/// - `rename_strategy`: The rename strategy.
+///
+/// ### Returns
+/// - `OsString`: The normalized file name, the original one if it is not valid UTF-8.
+fn rename_file_name(name: OsString, rename_strategy: &RenameStrategy) -> OsString {
+    let Some(name_str) = name.to_str() else {
+        return name;
+    };
+    match rename_strategy {
+        RenameStrategy::None => name,
+        RenameStrategy::Lowercase => OsString::from(name_str.to_lowercase()),
+        RenameStrategy::Slugify => {
+            let slugify = |value: &str| -> String {
+                let slug: String = deunicode::deunicode(value)
+                    .to_lowercase()
+                    .chars()
+                    .filter_map(|c| match c {
+                        c if c.is_ascii_alphanumeric() || c == '-' || c == '.' => Some(c),
+                        c if c.is_whitespace() || c == '_' => Some('_'),
+                        _ => None,
+                    })
+                    .collect();
+                slug.split('_')
+                    .filter(|v| !v.is_empty())
+                    .collect::<Vec<_>>()
+                    .join("_")
+            };
+            let (stem, extension) = match name_str.rsplit_once('.') {
+                Some((stem, extension)) if !stem.is_empty() => {
+                    (slugify(stem), Some(slugify(extension)))
+                }
+                _ => (slugify(name_str), None),
+            };
+            let stem = if stem.is_empty() {
+                "_".to_string()
+            } else {
+                stem
+            };
+            match extension.filter(|v| !v.is_empty()) {
+                Some(extension) => OsString::from(format!("{}.{}", stem, extension)),
+                None => OsString::from(stem),
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn sanitized_folder_names_are_never_empty() {
+        assert_eq!(sanitize_folder_name("a<b>c*"), "a_b_c_");
+        assert_eq!(sanitize_folder_name("trailing. ."), "trailing");
+        assert_eq!(sanitize_folder_name("..."), "_");
+        assert_eq!(sanitize_folder_name("nested/folder"), "nested/folder");
+    }
+
+    #[test]
+    fn trailing_spaces_and_dots_are_sanitized() {
+        for (name, sanitized) in [
+            ("report .pdf", "report.pdf"),
+            ("notes. ", "notes"),
+            ("archive.tar .gz", "archive.tar.gz"),
+            ("draft...", "draft"),
+            ("...", "..."),
+        ] {
+            assert_eq!(
+                sanitize_file_name(OsString::from(name)),
+                OsString::from(sanitized)
+            );
+        }
+    }
+
+    #[test]
+    fn rename_strategies_normalize_the_file_names() {
+        let rename = |name: &str, rename_strategy: RenameStrategy| {
+            rename_file_name(OsString::from(name), &rename_strategy)
+        };
+
+        assert_eq!(
+            rename("My Scan 01.PDF", RenameStrategy::None),
+            "My Scan 01.PDF"
+        );
+        assert_eq!(
+            rename("My Scan 01.PDF", RenameStrategy::Lowercase),
+            "my scan 01.pdf"
+        );
+        assert_eq!(
+            rename("My Scan 01.PDF", RenameStrategy::Slugify),
+            "my_scan_01.pdf"
+        );
+        assert_eq!(
+            rename("Été  à Paris!.JPG", RenameStrategy::Slugify),
+            "ete_a_paris.jpg"
+        );
+        assert_eq!(rename("???.txt", RenameStrategy::Slugify), "_.txt");
+        assert_eq!(rename(".bashrc", RenameStrategy::Slugify), ".bashrc");
+    }
+}
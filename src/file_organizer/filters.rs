@@ -0,0 +1,351 @@
+use crate::file_organizer::settings::{MagicRoute, Recipe};
+use crate::file_organizer::tag_rules::TagRules;
+use globset::{Glob, GlobSet, GlobSetBuilder};
+use std::fs;
+use std::io::Read;
+use std::path::Path;
+
+/// Checks if the extension of a file is allowed, case insensitively.
+///
+/// ### Parameters
+/// - `file`: The file to check.
+/// - `allowed_extensions`: The allowed extensions, lowercased.
+///
+/// ### Returns
+/// - `bool`: True if the extension is allowed, false otherwise.
+pub fn is_extension_allowed(file: &Path, allowed_extensions: &Option<Vec<String>>) -> bool {
+    if let Some(allowed_extensions) = allowed_extensions {
+        if allowed_extensions.is_empty() {
+            return true;
+        }
+        if let Some(ext) = file.extension() {
+            if let Some(ext_str) = ext.to_str() {
+                return allowed_extensions.contains(&ext_str.to_lowercase());
+            } else {
+                return false;
+            }
+        } else {
+            return false;
+        }
+    }
+    false
+}
+
+/// Checks if the size of a file is within the size bounds of a recipe, both bounds being inclusive.
+///
+/// ### Parameters
+/// - `size`: The size of the file, in bytes.
+/// - `recipe`: The recipe.
+///
+/// ### Returns
+/// - `bool`: True if the size is within the bounds, a missing bound not limiting it.
+pub fn is_size_allowed(size: u64, recipe: &Recipe) -> bool {
+    recipe.min_size_bytes.is_none_or(|v| size >= v)
+        && recipe.max_size_bytes.is_none_or(|v| size <= v)
+}
+
+/// Compiles the name patterns of a recipe.
+///
+/// ### Parameters
+/// - `recipe`: The recipe to compile the name patterns for.
+///
+/// ### Returns
+/// - `Result<Option<(GlobSet, Vec<String>)>, anyhow::Error>`: The compiled patterns with their definitions, None if the recipe matches all the names.
+pub fn compile_name_patterns(recipe: &Recipe) -> anyhow::Result<Option<(GlobSet, Vec<String>)>> {
+    let Some(name_patterns) = recipe.name_patterns.as_ref().filter(|v| !v.is_empty()) else {
+        return Ok(None);
+    };
+    let mut builder = GlobSetBuilder::new();
+    for pattern in name_patterns {
+        builder.add(Glob::new(pattern).map_err(|e| {
+            anyhow::Error::msg(format!(
+                "{} - Invalid name pattern {}: {}",
+                recipe.name, pattern, e
+            ))
+        })?);
+    }
+    Ok(Some((builder.build()?, name_patterns.clone())))
+}
+
+/// Parses the MIME types of a recipe, lowercased.
+///
+/// ### Parameters
+/// - `recipe`: The recipe to parse the MIME types of.
+///
+/// ### Returns
+/// - `Result<Option<Vec<String>>, anyhow::Error>`: The MIME types and groups, None if the recipe matches all the contents, an error naming an invalid MIME type.
+pub fn parse_mime_types(recipe: &Recipe) -> anyhow::Result<Option<Vec<String>>> {
+    let Some(mime_types) = recipe.mime_types.as_ref().filter(|v| !v.is_empty()) else {
+        return Ok(None);
+    };
+    let mut parsed_mime_types = Vec::new();
+    for mime_type in mime_types {
+        let mime_type = mime_type.trim().to_lowercase();
+        match mime_type.split_once('/') {
+            Some((kind, subtype)) if !kind.is_empty() && !subtype.is_empty() && kind != "*" => {
+                parsed_mime_types.push(mime_type)
+            }
+            _ => {
+                return Err(anyhow::Error::msg(format!(
+                    "{} - Invalid MIME type: {}, expected type/subtype or type/*",
+                    recipe.name, mime_type
+                )));
+            }
+        }
+    }
+    Ok(Some(parsed_mime_types))
+}
+
+/// Gets the MIME type of a file from its leading bytes, whatever its extension.
+///
+/// ### Parameters
+/// - `file`: The file.
+///
+/// ### Returns
+/// - `Result<Option<&'static str>, anyhow::Error>`: The MIME type, None if the content is not recognized.
+pub fn get_mime_type(file: &Path) -> anyhow::Result<Option<&'static str>> {
+    Ok(infer::get_from_path(file)?.map(|v| v.mime_type()))
+}
+
+/// Checks if a MIME type is one of the MIME types of a recipe.
+///
+/// ### Parameters
+/// - `mime_type`: The MIME type of a file.
+/// - `mime_types`: The MIME types and groups (`image/*`) of the recipe, lowercased.
+///
+/// ### Returns
+/// - `bool`: True if the MIME type is listed or in a listed group, false otherwise.
+pub fn is_mime_type_allowed(mime_type: &str, mime_types: &[String]) -> bool {
+    mime_types.iter().any(|v| match v.strip_suffix("/*") {
+        Some(kind) => mime_type.split_once('/').is_some_and(|(v, _)| v == kind),
+        None => v == mime_type,
+    })
+}
+
+/// Parses the magic routes of a recipe into their magic number bytes.
+///
+/// ### Parameters
+/// - `recipe`: The recipe to parse the magic routes of.
+///
+/// ### Returns
+/// - `Result<Vec<(Vec<u8>, String)>, anyhow::Error>`: The magic number bytes and subfolder of each route, in order.
+pub fn parse_magic_routes(recipe: &Recipe) -> anyhow::Result<Vec<(Vec<u8>, String)>> {
+    let mut magic_routes = Vec::new();
+    for MagicRoute { magic, subfolder } in recipe.magic_routes.iter().flatten() {
+        let magic_bytes = parse_hex(magic).ok_or_else(|| {
+            anyhow::Error::msg(format!("{} - Invalid magic number: {}", recipe.name, magic))
+        })?;
+        magic_routes.push((magic_bytes, subfolder.clone()));
+    }
+    Ok(magic_routes)
+}
+
+/// Parses a hexadecimal string, ignoring whitespaces.
+///
+/// ### Parameters
+/// - `hex`: The hexadecimal string to parse.
+///
+/// ### Returns
+/// - `Option<Vec<u8>>`: The bytes, None if the string is empty or not valid hexadecimal.
+fn parse_hex(hex: &str) -> Option<Vec<u8>> {
+    let digits: Vec<u8> = hex
+        .chars()
+        .filter(|c| !c.is_whitespace())
+        .map(|c| c.to_digit(16).map(|d| d as u8))
+        .collect::<Option<_>>()?;
+    if digits.is_empty() || !digits.len().is_multiple_of(2) {
+        return None;
+    }
+    Some(
+        digits
+            .chunks(2)
+            .map(|pair| pair[0] << 4 | pair[1])
+            .collect(),
+    )
+}
+
+/// Gets the subfolder of the first magic route matching the leading bytes of a file.
+/// The leading bytes are read once, up to the length of the longest magic number.
+///
+/// ### Parameters
+/// - `file`: The file to route.
+/// - `magic_routes`: The magic number bytes and subfolder of each route.
+///
+/// ### Returns
+/// - `Result<Option<&str>, anyhow::Error>`: The subfolder of the matching route, None if no route matches.
+pub fn get_magic_subfolder<'a>(
+    file: &Path,
+    magic_routes: &'a [(Vec<u8>, String)],
+) -> anyhow::Result<Option<&'a str>> {
+    let Some(magic_length) = magic_routes.iter().map(|(magic, _)| magic.len()).max() else {
+        return Ok(None);
+    };
+    let mut leading_bytes = Vec::with_capacity(magic_length);
+    fs::File::open(file)?
+        .take(magic_length as u64)
+        .read_to_end(&mut leading_bytes)?;
+    Ok(magic_routes
+        .iter()
+        .find(|(magic, _)| leading_bytes.starts_with(magic))
+        .map(|(_, subfolder)| subfolder.as_str()))
+}
+
+/// Loads the tag rules of a recipe.
+///
+/// ### Parameters
+/// - `recipe`: The recipe to load the tag rules for.
+///
+/// ### Returns
+/// - `Result<Option<TagRules>, anyhow::Error>`: The tag rules, None if the recipe has no tag rules file.
+pub fn load_tag_rules(recipe: &Recipe) -> anyhow::Result<Option<TagRules>> {
+    let Some(tag_rules) = &recipe.tag_rules else {
+        return Ok(None);
+    };
+    let tag_rules = TagRules::load(tag_rules).map_err(|e| {
+        anyhow::Error::msg(format!(
+            "{} - Error loading the tag rules file {}: {}",
+            recipe.name,
+            tag_rules.display(),
+            e
+        ))
+    })?;
+    Ok(Some(tag_rules))
+}
+
+/// Checks if a file has the Windows system attribute.
+///
+/// ### Parameters
+/// - `metadata`: The metadata of the file to check.
+///
+/// ### Returns
+/// - `bool`: True if the file is a system file, always false outside of Windows.
+#[cfg(windows)]
+pub fn is_system_file(metadata: &fs::Metadata) -> bool {
+    use std::os::windows::fs::MetadataExt;
+    const FILE_ATTRIBUTE_SYSTEM: u32 = 0x4;
+    metadata.file_attributes() & FILE_ATTRIBUTE_SYSTEM != 0
+}
+
+/// Checks if a file has the Windows system attribute.
+///
+/// ### Parameters
+/// - `metadata`: The metadata of the file to check.
+///
+/// ### Returns
+/// - `bool`: True if the file is a system file, always false outside of Windows.
+#[cfg(not(windows))]
+pub fn is_system_file(_metadata: &fs::Metadata) -> bool {
+    false
+}
+
+/// Gets the effective user id of the process.
+///
+/// ### Returns
+/// - `Option<u32>`: The effective user id, None outside of Unix.
+#[cfg(unix)]
+pub fn get_effective_uid() -> Option<u32> {
+    Some(unsafe { libc::geteuid() })
+}
+
+/// Gets the effective user id of the process.
+///
+/// ### Returns
+/// - `Option<u32>`: The effective user id, None outside of Unix.
+#[cfg(not(unix))]
+pub fn get_effective_uid() -> Option<u32> {
+    None
+}
+
+/// Checks if a file is owned by a user.
+///
+/// ### Parameters
+/// - `metadata`: The metadata of the file to check.
+/// - `uid`: The id of the user, usually the effective user of the process.
+///
+/// ### Returns
+/// - `bool`: True if the owner of the file is the user, always true without a user id or outside of Unix.
+#[cfg(unix)]
+pub fn is_owned_by(metadata: &fs::Metadata, uid: Option<u32>) -> bool {
+    use std::os::unix::fs::MetadataExt;
+    uid.is_none_or(|v| metadata.uid() == v)
+}
+
+/// Checks if a file is owned by a user.
+///
+/// ### Parameters
+/// - `metadata`: The metadata of the file to check.
+/// - `uid`: The id of the user, usually the effective user of the process.
+///
+/// ### Returns
+/// - `bool`: True if the owner of the file is the user, always true without a user id or outside of Unix.
+#[cfg(not(unix))]
+pub fn is_owned_by(_metadata: &fs::Metadata, _uid: Option<u32>) -> bool {
+    true
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::ffi::OsStr;
+
+    #[test]
+    fn magic_numbers_are_parsed_as_hexadecimal() {
+        assert_eq!(parse_hex("89 50 4e 47"), Some(vec![0x89, 0x50, 0x4E, 0x47]));
+        assert_eq!(parse_hex("255"), None);
+        assert_eq!(parse_hex("zz"), None);
+        assert_eq!(parse_hex(" "), None);
+    }
+
+    #[test]
+    fn extensions_are_allowed_case_insensitively() {
+        let allowed_extensions = Some(vec!["jpg".to_string(), "jpeg".to_string()]);
+
+        for name in ["a.jpg", "b.JPG", "c.Jpeg", "d.jPeG", "archive.tar.JPG"] {
+            assert!(
+                is_extension_allowed(Path::new(name), &allowed_extensions),
+                "{} is not allowed",
+                name
+            );
+        }
+        for name in ["e.png", "jpg", "f.", ".jpg", "g.jpg.bak"] {
+            assert!(
+                !is_extension_allowed(Path::new(name), &allowed_extensions),
+                "{} is allowed",
+                name
+            );
+        }
+        assert!(is_extension_allowed(Path::new("README"), &Some(Vec::new())));
+        assert!(!is_extension_allowed(Path::new("a.jpg"), &None));
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn non_utf8_extensions_are_not_allowed() {
+        use std::os::unix::ffi::OsStrExt;
+        let allowed_extensions = Some(vec!["jpg".to_string()]);
+
+        let file = Path::new(OsStr::from_bytes(b"photo.jp\xff"));
+
+        assert!(!is_extension_allowed(file, &allowed_extensions));
+        assert!(is_extension_allowed(file, &Some(Vec::new())));
+    }
+
+    #[test]
+    fn invalid_mime_types_are_rejected() {
+        let recipe = |mime_type: &str| Recipe {
+            name: "mime".to_string(),
+            mime_types: Some(vec![mime_type.to_string()]),
+            ..Default::default()
+        };
+
+        assert!(parse_mime_types(&recipe("video/*")).is_ok());
+        for mime_type in ["image", "*/*", "/png", "text/"] {
+            assert_eq!(
+                parse_mime_types(&recipe(mime_type))
+                    .unwrap_err()
+                    .to_string(),
+                format!("mime - Invalid MIME type: {mime_type}, expected type/subtype or type/*")
+            );
+        }
+    }
+}
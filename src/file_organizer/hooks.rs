@@ -0,0 +1,133 @@
+use crate::file_organizer::FileOrganizerStats;
+use crate::file_organizer::settings::Recipe;
+use colored::*;
+use std::process::Command;
+use tracing::{info, warn};
+
+/// Runs the pre hook of a recipe, if any, before the recipe processes its files.
+///
+/// ### Parameters
+/// - `recipe`: The recipe.
+///
+/// ### Returns
+/// - `Result<(), anyhow::Error>`: An error if the hook cannot be run or exits with a failure, aborting the recipe.
+pub fn run_pre_hook(recipe: &Recipe) -> anyhow::Result<()> {
+    let Some(pre_hook) = &recipe.pre_hook else {
+        return Ok(());
+    };
+    let mut command = build_command(pre_hook);
+    command.env("FILE_ORGANIZER_RECIPE", &recipe.name);
+    run_hook(recipe, "pre_hook", pre_hook, command)
+}
+
+/// Runs the post hook of a recipe, if any, once the recipe is done, whether it succeeded or not.
+/// The statistics of the recipe are passed in environment variables. A failing hook is reported as a warning, the run going on.
+///
+/// ### Parameters
+/// - `recipe`: The recipe.
+/// - `stats`: The statistics of the recipe run, None if the recipe failed.
+pub fn run_post_hook(recipe: &Recipe, stats: Option<&FileOrganizerStats>) {
+    let Some(post_hook) = &recipe.post_hook else {
+        return;
+    };
+    let mut command = build_command(post_hook);
+    command.env("FILE_ORGANIZER_RECIPE", &recipe.name).env(
+        "FILE_ORGANIZER_RESULT",
+        if stats.is_some() {
+            "success"
+        } else {
+            "failure"
+        },
+    );
+    if let Some(stats) = stats {
+        command
+            .env(
+                "FILE_ORGANIZER_FILES_MATCHED",
+                stats.files_matched.to_string(),
+            )
+            .env(
+                "FILE_ORGANIZER_FILES_PROCESSED",
+                stats.files_processed.to_string(),
+            )
+            .env(
+                "FILE_ORGANIZER_FILES_ERRORED",
+                stats.files_errored.to_string(),
+            )
+            .env("FILE_ORGANIZER_ELAPSED_MS", stats.elapsed_time.to_string());
+    }
+    if let Err(e) = run_hook(recipe, "post_hook", post_hook, command) {
+        warn!(
+            "{} {} {} - {}",
+            "⚠️".yellow(),
+            recipe.name.blue(),
+            "Post hook failed".yellow(),
+            e
+        );
+    }
+}
+
+/// Builds the command running a hook in the shell of the platform.
+///
+/// ### Parameters
+/// - `hook`: The shell command of the hook.
+///
+/// ### Returns
+/// - `Command`: The command, `sh -c` on Unix, `cmd /C` on Windows.
+fn build_command(hook: &str) -> Command {
+    let mut command = if cfg!(windows) {
+        let mut command = Command::new("cmd");
+        command.arg("/C");
+        command
+    } else {
+        let mut command = Command::new("sh");
+        command.arg("-c");
+        command
+    };
+    command.arg(hook);
+    command
+}
+
+/// Runs a hook, its output being printed line by line.
+///
+/// ### Parameters
+/// - `recipe`: The recipe of the hook.
+/// - `field`: The field of the hook, pre_hook or post_hook.
+/// - `hook`: The shell command of the hook.
+/// - `command`: The command running the hook.
+///
+/// ### Returns
+/// - `Result<(), anyhow::Error>`: An error if the hook cannot be run or exits with a failure.
+fn run_hook(recipe: &Recipe, field: &str, hook: &str, mut command: Command) -> anyhow::Result<()> {
+    info!(
+        "{} {} {} - {}",
+        "ℹ️".green(),
+        recipe.name.blue(),
+        "Running hook".purple(),
+        hook
+    );
+    let output = command.output().map_err(|e| {
+        anyhow::Error::msg(format!(
+            "{} - Error running {} {}: {}",
+            recipe.name, field, hook, e
+        ))
+    })?;
+    for line in String::from_utf8_lossy(&output.stdout)
+        .lines()
+        .chain(String::from_utf8_lossy(&output.stderr).lines())
+    {
+        info!(
+            "{} {} {} - {}",
+            "ℹ️".green(),
+            recipe.name.blue(),
+            field.purple(),
+            line
+        );
+    }
+    if !output.status.success() {
+        return Err(anyhow::Error::msg(format!(
+            "{} - {} failed ({}): {}",
+            recipe.name, field, output.status, hook
+        )));
+    }
+    Ok(())
+}
@@ -0,0 +1,240 @@
+use crate::file_organizer::plan::{PlanOperation, PlanOperationKind};
+use colored::*;
+use std::collections::HashMap;
+use std::io;
+use std::path::{Path, PathBuf};
+use tracing::warn;
+
+/// FilesystemUsage is a struct that contains the files a run creates on a filesystem.
+struct FilesystemUsage {
+    folder: PathBuf,
+    files_count: u64,
+}
+
+/// Checks that the destination filesystems have enough free inodes for the files planned by a dry run, a warning being printed for each filesystem short of inodes.
+/// Copies and symlinks create a file each, as do moves across filesystems, while hardlinks and moves within a filesystem do not.
+///
+/// ### Parameters
+/// - `operations`: The planned operations.
+pub fn check_inodes(operations: &[PlanOperation]) {
+    check_inodes_with(operations, get_available_inodes);
+}
+
+/// Checks that the destination filesystems have enough free inodes for the planned operations, with the given way of getting the free inodes of a filesystem.
+///
+/// ### Parameters
+/// - `operations`: The planned operations.
+/// - `get_available_inodes`: Gets the number of free inodes of the filesystem holding a folder.
+///
+/// ### Returns
+/// - `Vec<FilesystemUsage>`: The usages of the filesystems short of inodes.
+fn check_inodes_with(
+    operations: &[PlanOperation],
+    get_available_inodes: impl Fn(&Path) -> io::Result<Option<u64>>,
+) -> Vec<FilesystemUsage> {
+    let mut devices: HashMap<PathBuf, Option<u64>> = HashMap::new();
+    let mut usages: HashMap<u64, FilesystemUsage> = HashMap::new();
+    let mut get_cached_device = |folder: &Path| -> Option<u64> {
+        *devices
+            .entry(folder.to_path_buf())
+            .or_insert_with(|| get_device(folder))
+    };
+    for operation in operations {
+        let Some(dest_folder) = operation.destination.parent() else {
+            continue;
+        };
+        let Some(dest_device) = get_cached_device(dest_folder) else {
+            continue;
+        };
+        let creates_file = match operation.operation {
+            PlanOperationKind::Copy | PlanOperationKind::Symlink => true,
+            PlanOperationKind::Hardlink => false,
+            PlanOperationKind::Move => operation
+                .source
+                .parent()
+                .and_then(&mut get_cached_device)
+                .is_none_or(|v| v != dest_device),
+        };
+        if !creates_file || operation.destination_exists {
+            continue;
+        }
+        usages
+            .entry(dest_device)
+            .or_insert_with(|| FilesystemUsage {
+                folder: get_existing_folder(dest_folder).to_path_buf(),
+                files_count: 0,
+            })
+            .files_count += 1;
+    }
+    let mut short_usages = Vec::new();
+    for usage in usages.into_values() {
+        let Ok(Some(available_inodes)) = get_available_inodes(&usage.folder) else {
+            continue;
+        };
+        if usage.files_count > available_inodes {
+            warn!(
+                "{} {} {} - {}: {} files to create, {} inodes available, the run would fail with no space left even if bytes remain",
+                "⚠️".yellow(),
+                "file_organizer".blue(),
+                "Not enough inodes".yellow(),
+                usage.folder.display(),
+                usage.files_count,
+                available_inodes
+            );
+            short_usages.push(usage);
+        }
+    }
+    short_usages
+}
+
+/// Gets the closest existing folder of a folder, which the run may not have created yet.
+///
+/// ### Parameters
+/// - `folder`: The folder.
+///
+/// ### Returns
+/// - `&Path`: The folder itself or its closest existing ancestor.
+pub fn get_existing_folder(folder: &Path) -> &Path {
+    folder.ancestors().find(|v| v.exists()).unwrap_or(folder)
+}
+
+/// Gets the device of the filesystem holding a folder.
+///
+/// ### Parameters
+/// - `folder`: The folder, or a folder to create.
+///
+/// ### Returns
+/// - `Option<u64>`: The device of the filesystem, None if unknown, always None outside of Unix.
+#[cfg(unix)]
+pub fn get_device(folder: &Path) -> Option<u64> {
+    use std::os::unix::fs::MetadataExt;
+    std::fs::metadata(get_existing_folder(folder))
+        .ok()
+        .map(|v| v.dev())
+}
+
+/// Gets the device of the filesystem holding a folder.
+///
+/// ### Parameters
+/// - `folder`: The folder, or a folder to create.
+///
+/// ### Returns
+/// - `Option<u64>`: The device of the filesystem, None if unknown, always None outside of Unix.
+#[cfg(not(unix))]
+pub fn get_device(_folder: &Path) -> Option<u64> {
+    None
+}
+
+/// Gets the number of free inodes of the filesystem holding a folder, for an unprivileged user.
+///
+/// ### Parameters
+/// - `folder`: The existing folder.
+///
+/// ### Returns
+/// - `io::Result<Option<u64>>`: The number of free inodes, None if the filesystem does not limit them.
+#[cfg(unix)]
+fn get_available_inodes(folder: &Path) -> io::Result<Option<u64>> {
+    use std::ffi::CString;
+    use std::os::unix::ffi::OsStrExt;
+    let path = CString::new(folder.as_os_str().as_bytes())?;
+    let mut stat: libc::statvfs = unsafe { std::mem::zeroed() };
+    if unsafe { libc::statvfs(path.as_ptr(), &mut stat) } != 0 {
+        return Err(io::Error::last_os_error());
+    }
+    if stat.f_files == 0 {
+        return Ok(None);
+    }
+    Ok(Some(stat.f_favail as u64))
+}
+
+/// Gets the number of free inodes of the filesystem holding a folder, for an unprivileged user.
+///
+/// ### Parameters
+/// - `folder`: The existing folder.
+///
+/// ### Returns
+/// - `io::Result<Option<u64>>`: The number of free inodes, always None outside of Unix.
+#[cfg(not(unix))]
+fn get_available_inodes(_folder: &Path) -> io::Result<Option<u64>> {
+    Ok(None)
+}
+
+#[cfg(all(test, unix))]
+mod tests {
+    use super::*;
+    use tempfile::TempDir;
+
+    /// Builds a planned operation creating a file in a folder.
+    ///
+    /// ### Parameters
+    /// - `operation`: The kind of the operation.
+    /// - `source`: The source file.
+    /// - `destination`: The destination file.
+    ///
+    /// ### Returns
+    /// - `PlanOperation`: The operation, its destination not existing yet.
+    fn planned(operation: PlanOperationKind, source: &Path, destination: &Path) -> PlanOperation {
+        PlanOperation {
+            recipe: "backup".to_string(),
+            operation,
+            source: source.to_path_buf(),
+            destination: destination.to_path_buf(),
+            destination_exists: false,
+            conflict: None,
+        }
+    }
+
+    #[test]
+    fn only_the_files_created_are_counted_against_the_free_inodes() {
+        let root = TempDir::new().unwrap();
+        let (source, destination) = (root.path().join("photos"), root.path().join("backup"));
+        let mut existing = planned(
+            PlanOperationKind::Copy,
+            &source.join("old.jpg"),
+            &destination.join("old.jpg"),
+        );
+        existing.destination_exists = true;
+        let operations = [
+            planned(
+                PlanOperationKind::Copy,
+                &source.join("a.jpg"),
+                &destination.join("2024").join("a.jpg"),
+            ),
+            planned(
+                PlanOperationKind::Symlink,
+                &source.join("b.jpg"),
+                &destination.join("b.jpg"),
+            ),
+            planned(
+                PlanOperationKind::Hardlink,
+                &source.join("c.jpg"),
+                &destination.join("c.jpg"),
+            ),
+            planned(
+                PlanOperationKind::Move,
+                &source.join("d.jpg"),
+                &destination.join("d.jpg"),
+            ),
+            existing,
+        ];
+
+        let short_usages = check_inodes_with(&operations, |_| Ok(Some(1)));
+
+        assert_eq!(short_usages.len(), 1);
+        assert_eq!(short_usages[0].folder, root.path());
+        assert_eq!(short_usages[0].files_count, 2);
+        assert!(check_inodes_with(&operations, |_| Ok(Some(2))).is_empty());
+        assert!(check_inodes_with(&operations, |_| Ok(None)).is_empty());
+        assert!(
+            check_inodes_with(&operations, |_| Err(io::Error::other("statvfs failed"))).is_empty()
+        );
+    }
+
+    #[test]
+    fn free_inodes_of_a_real_filesystem_are_read() {
+        let root = TempDir::new().unwrap();
+
+        assert!(get_available_inodes(root.path()).is_ok());
+        assert!(get_available_inodes(&root.path().join("missing")).is_err());
+    }
+}
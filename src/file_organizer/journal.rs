@@ -0,0 +1,371 @@
+use crate::file_organizer::move_file;
+use crate::file_organizer::plan::PlanOperationKind;
+use chrono::{DateTime, SecondsFormat, Utc};
+use colored::*;
+use serde::{Deserialize, Serialize};
+use std::collections::HashSet;
+use std::fs;
+use std::fs::{File, OpenOptions};
+use std::io::Write;
+use std::path::{Path, PathBuf};
+use std::sync::Mutex;
+use tracing::{error, info, warn};
+
+/// The result of the journal entries of the files handled successfully.
+const RESULT_OK: &str = "ok";
+
+/// The result of the journal entries of the operations undone by --undo.
+const RESULT_UNDONE: &str = "undone";
+
+/// JournalEntry is a struct that contains a line of the journal: what has been done with a file, and when.
+#[derive(Serialize, Deserialize)]
+struct JournalEntry {
+    timestamp: String,
+    run: String,
+    recipe: String,
+    operation: PlanOperationKind,
+    source: PathBuf,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    destination: Option<PathBuf>,
+    result: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    size: Option<u64>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    modified: Option<String>,
+}
+
+/// Journal is a struct that contains the file the files handled by the runs are appended to, for auditing.
+pub struct Journal {
+    file: Mutex<File>,
+    run: String,
+}
+
+impl Journal {
+    /// Opens a journal, created if missing, the entries being appended to the ones of the previous runs.
+    ///
+    /// ### Parameters
+    /// - `path`: The path to the journal file.
+    ///
+    /// ### Returns
+    /// - `Result<Journal, anyhow::Error>`: The journal, its entries identifying the run by its start time.
+    pub fn open(path: &Path) -> anyhow::Result<Journal> {
+        let file = OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(path)
+            .map_err(|e| {
+                anyhow::Error::msg(format!(
+                    "Error opening the log file {}: {}",
+                    path.display(),
+                    e
+                ))
+            })?;
+        Ok(Journal {
+            file: Mutex::new(file),
+            run: Utc::now().to_rfc3339_opts(SecondsFormat::Nanos, true),
+        })
+    }
+
+    /// Appends what has been done with a file to the journal, as a JSON line written at once so that the lines of the files processed in parallel are never interleaved.
+    /// The size and modification date of the destination of a file handled successfully are recorded as well, so that --undo can tell if it changed since.
+    /// A failed write is reported as a warning, the run going on.
+    ///
+    /// ### Parameters
+    /// - `recipe`: The name of the recipe.
+    /// - `operation`: The operation of the recipe.
+    /// - `source`: The file.
+    /// - `destination`: The destination of the file, None if not known when the file failed.
+    /// - `result`: `ok`, `skipped: <reason>` or `error: <error>`.
+    pub fn record(
+        &self,
+        recipe: &str,
+        operation: &PlanOperationKind,
+        source: &Path,
+        destination: Option<&Path>,
+        result: &str,
+    ) {
+        let (size, modified) = match destination {
+            Some(destination) if result == RESULT_OK => get_fingerprint(destination),
+            _ => (None, None),
+        };
+        let entry = JournalEntry {
+            timestamp: Utc::now().to_rfc3339_opts(SecondsFormat::Millis, true),
+            run: self.run.clone(),
+            recipe: recipe.to_string(),
+            operation: operation.clone(),
+            source: source.to_path_buf(),
+            destination: destination.map(Path::to_path_buf),
+            result: result.to_string(),
+            size,
+            modified,
+        };
+        if let Err(e) = self.write(&entry) {
+            warn!(
+                "{} {} {} - {}: {}",
+                "⚠️".yellow(),
+                recipe.blue(),
+                "Log file not written".yellow(),
+                source.display(),
+                e
+            );
+        }
+    }
+
+    /// Writes an entry to the journal.
+    ///
+    /// ### Parameters
+    /// - `entry`: The entry to write.
+    ///
+    /// ### Returns
+    /// - `Result<(), anyhow::Error>`: The result of the write.
+    fn write(&self, entry: &JournalEntry) -> anyhow::Result<()> {
+        let mut line = serde_json::to_string(entry)?;
+        line.push('\n');
+        self.file.lock().unwrap().write_all(line.as_bytes())?;
+        Ok(())
+    }
+}
+
+/// Gets the size and modification date of a file, which --undo and --revert compare to tell if it changed since the run.
+///
+/// ### Parameters
+/// - `file`: The file, a symbolic link being not followed.
+///
+/// ### Returns
+/// - `(Option<u64>, Option<String>)`: The size and the modification date (RFC 3339, with nanoseconds), None if unknown.
+pub fn get_fingerprint(file: &Path) -> (Option<u64>, Option<String>) {
+    match fs::symlink_metadata(file) {
+        Ok(metadata) => (
+            Some(metadata.len()),
+            metadata
+                .modified()
+                .ok()
+                .map(|v| DateTime::<Utc>::from(v).to_rfc3339_opts(SecondsFormat::Nanos, true)),
+        ),
+        Err(_) => (None, None),
+    }
+}
+
+/// Undoes the last run recorded in a journal whose operations are not undone yet.
+/// The operations are undone in reverse order: moved files are moved back and the copies and links created by the run are deleted.
+/// An operation whose destination has been modified since the run, or whose source reappeared, is reported and left as is. Each undone operation is appended to the journal, so that the next undo goes on with the previous run.
+///
+/// ### Parameters
+/// - `path`: The path to the journal file.
+///
+/// ### Returns
+/// - `Result<(u32, u32), anyhow::Error>`: The number of undone and of refused operations.
+pub fn undo_last_run(path: &Path) -> anyhow::Result<(u32, u32)> {
+    let content = fs::read_to_string(path).map_err(|e| {
+        anyhow::Error::msg(format!(
+            "Error loading the log file {}: {}",
+            path.display(),
+            e
+        ))
+    })?;
+    let mut entries = Vec::new();
+    for (index, line) in content.lines().enumerate() {
+        if line.trim().is_empty() {
+            continue;
+        }
+        entries.push(serde_json::from_str::<JournalEntry>(line).map_err(|e| {
+            anyhow::Error::msg(format!(
+                "Error parsing line {} of the log file {}: {}",
+                index + 1,
+                path.display(),
+                e
+            ))
+        })?);
+    }
+    let undone: HashSet<(&str, &Path)> = entries
+        .iter()
+        .filter(|v| v.result == RESULT_UNDONE)
+        .map(|v| (v.run.as_str(), v.source.as_path()))
+        .collect();
+    let pending: Vec<&JournalEntry> = entries
+        .iter()
+        .filter(|v| {
+            v.result == RESULT_OK
+                && v.destination.is_some()
+                && !undone.contains(&(v.run.as_str(), v.source.as_path()))
+        })
+        .collect();
+    let Some(last_run) = pending.last().map(|v| v.run.clone()) else {
+        return Err(anyhow::Error::msg(format!(
+            "No run left to undo in the log file {}",
+            path.display()
+        )));
+    };
+    let journal = Journal {
+        file: Mutex::new(OpenOptions::new().append(true).open(path)?),
+        run: last_run.clone(),
+    };
+    let mut undone_count = 0;
+    let mut refused_count = 0;
+    for entry in pending.iter().rev().filter(|v| v.run == last_run) {
+        let Some(destination) = &entry.destination else {
+            continue;
+        };
+        match undo_entry(entry, destination) {
+            Ok(()) => {
+                undone_count += 1;
+                journal.write(&JournalEntry {
+                    timestamp: Utc::now().to_rfc3339_opts(SecondsFormat::Millis, true),
+                    run: last_run.clone(),
+                    recipe: entry.recipe.clone(),
+                    operation: entry.operation.clone(),
+                    source: entry.source.clone(),
+                    destination: entry.destination.clone(),
+                    result: RESULT_UNDONE.to_string(),
+                    size: None,
+                    modified: None,
+                })?;
+                info!(
+                    "{} {} {} - {}",
+                    "✅".green(),
+                    entry.recipe.blue(),
+                    "Operation undone".green(),
+                    entry.source.display()
+                );
+            }
+            Err(e) => {
+                refused_count += 1;
+                error!(
+                    "{} {} {} - {}: {}",
+                    "❌".red(),
+                    entry.recipe.blue(),
+                    "Undo refused".red(),
+                    destination.display(),
+                    e
+                );
+            }
+        }
+    }
+    Ok((undone_count, refused_count))
+}
+
+/// Undoes an operation of a journal, unless its destination changed since the run.
+///
+/// ### Parameters
+/// - `entry`: The journal entry of the operation.
+/// - `destination`: The destination of the operation.
+///
+/// ### Returns
+/// - `Result<(), anyhow::Error>`: The result of the undo.
+fn undo_entry(entry: &JournalEntry, destination: &Path) -> anyhow::Result<()> {
+    if fs::symlink_metadata(destination).is_err() {
+        return Err(anyhow::Error::msg("Destination no longer exists"));
+    }
+    if get_fingerprint(destination) != (entry.size, entry.modified.clone()) {
+        return Err(anyhow::Error::msg("Destination modified since the run"));
+    }
+    match entry.operation {
+        PlanOperationKind::Move => {
+            if entry.source.exists() {
+                return Err(anyhow::Error::msg(format!(
+                    "Source reappeared since the run: {}",
+                    entry.source.display()
+                )));
+            }
+            if let Some(source_folder) = entry.source.parent() {
+                fs::create_dir_all(source_folder)?;
+            }
+            move_file(destination, &entry.source)?;
+        }
+        PlanOperationKind::Copy | PlanOperationKind::Symlink | PlanOperationKind::Hardlink => {
+            fs::remove_file(destination)?
+        }
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::TempDir;
+
+    /// Records a run of a single operation already done on the disk, as the organizer would.
+    ///
+    /// ### Parameters
+    /// - `log_file`: The path to the journal file.
+    /// - `operation`: The operation done.
+    /// - `source`: The organized file.
+    /// - `destination`: The destination of the file, which must exist.
+    fn record_run(
+        log_file: &Path,
+        operation: PlanOperationKind,
+        source: &Path,
+        destination: &Path,
+    ) {
+        Journal::open(log_file).unwrap().record(
+            "undo",
+            &operation,
+            source,
+            Some(destination),
+            RESULT_OK,
+        );
+    }
+
+    #[test]
+    fn runs_are_undone_one_at_a_time_from_the_last() {
+        let root = TempDir::new().unwrap();
+        let log_file = root.path().join("journal.jsonl");
+        let (moved_from, moved_to) = (root.path().join("inbox/a.txt"), root.path().join("a.txt"));
+        let (copied_from, copied_to) =
+            (root.path().join("b.txt"), root.path().join("copies/b.txt"));
+        fs::create_dir_all(copied_to.parent().unwrap()).unwrap();
+        fs::write(&moved_to, "moved").unwrap();
+        fs::write(&copied_from, "copied").unwrap();
+        fs::write(&copied_to, "copied").unwrap();
+        record_run(&log_file, PlanOperationKind::Move, &moved_from, &moved_to);
+        record_run(&log_file, PlanOperationKind::Copy, &copied_from, &copied_to);
+
+        assert_eq!(undo_last_run(&log_file).unwrap(), (1, 0));
+        assert!(!copied_to.exists());
+        assert!(copied_from.is_file());
+        assert!(moved_to.is_file());
+
+        assert_eq!(undo_last_run(&log_file).unwrap(), (1, 0));
+        assert_eq!(fs::read_to_string(&moved_from).unwrap(), "moved");
+        assert!(!moved_to.exists());
+
+        let error = undo_last_run(&log_file).unwrap_err();
+        assert!(error.to_string().starts_with("No run left to undo"));
+    }
+
+    #[test]
+    fn modified_destinations_are_not_undone() {
+        let root = TempDir::new().unwrap();
+        let log_file = root.path().join("journal.jsonl");
+        let (source, destination) = (
+            root.path().join("draft.md"),
+            root.path().join("notes/draft.md"),
+        );
+        fs::create_dir_all(destination.parent().unwrap()).unwrap();
+        fs::write(&destination, "draft").unwrap();
+        record_run(&log_file, PlanOperationKind::Move, &source, &destination);
+        fs::write(&destination, "draft, edited since").unwrap();
+
+        assert_eq!(undo_last_run(&log_file).unwrap(), (0, 1));
+        assert_eq!(
+            fs::read_to_string(&destination).unwrap(),
+            "draft, edited since"
+        );
+        assert!(!source.exists());
+    }
+
+    #[test]
+    fn reappeared_sources_are_not_overwritten() {
+        let root = TempDir::new().unwrap();
+        let log_file = root.path().join("journal.jsonl");
+        let (source, destination) = (root.path().join("cv.pdf"), root.path().join("docs/cv.pdf"));
+        fs::create_dir_all(destination.parent().unwrap()).unwrap();
+        fs::write(&destination, "organized").unwrap();
+        record_run(&log_file, PlanOperationKind::Move, &source, &destination);
+        fs::write(&source, "new version").unwrap();
+
+        assert_eq!(undo_last_run(&log_file).unwrap(), (0, 1));
+        assert_eq!(fs::read_to_string(&source).unwrap(), "new version");
+        assert!(destination.is_file());
+    }
+}
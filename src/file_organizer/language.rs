@@ -0,0 +1,170 @@
+use std::fs::File;
+use std::io::{self, Read};
+use std::path::Path;
+
+/// The folder of the files whose language cannot be detected, binary files included.
+pub const UNKNOWN_LANGUAGE_FOLDER: &str = "unknown";
+
+/// The number of bytes read from the start of a file to detect its language.
+const SAMPLE_SIZE: u64 = 4096;
+
+/// The ISO 639-1 codes of the languages detected by whatlang, by their ISO 639-3 code.
+const ISO_639_1_CODES: &[(&str, &str)] = &[
+    ("afr", "af"),
+    ("aka", "ak"),
+    ("amh", "am"),
+    ("ara", "ar"),
+    ("aze", "az"),
+    ("bel", "be"),
+    ("ben", "bn"),
+    ("bul", "bg"),
+    ("cat", "ca"),
+    ("ces", "cs"),
+    ("cmn", "zh"),
+    ("cym", "cy"),
+    ("dan", "da"),
+    ("deu", "de"),
+    ("ell", "el"),
+    ("eng", "en"),
+    ("epo", "eo"),
+    ("est", "et"),
+    ("fin", "fi"),
+    ("fra", "fr"),
+    ("guj", "gu"),
+    ("heb", "he"),
+    ("hin", "hi"),
+    ("hrv", "hr"),
+    ("hun", "hu"),
+    ("hye", "hy"),
+    ("ind", "id"),
+    ("ita", "it"),
+    ("jav", "jv"),
+    ("jpn", "ja"),
+    ("kan", "kn"),
+    ("kat", "ka"),
+    ("khm", "km"),
+    ("kor", "ko"),
+    ("lat", "la"),
+    ("lav", "lv"),
+    ("lit", "lt"),
+    ("mal", "ml"),
+    ("mar", "mr"),
+    ("mkd", "mk"),
+    ("mya", "my"),
+    ("nep", "ne"),
+    ("nld", "nl"),
+    ("nob", "nb"),
+    ("ori", "or"),
+    ("pan", "pa"),
+    ("pes", "fa"),
+    ("pol", "pl"),
+    ("por", "pt"),
+    ("ron", "ro"),
+    ("rus", "ru"),
+    ("sin", "si"),
+    ("slk", "sk"),
+    ("slv", "sl"),
+    ("sna", "sn"),
+    ("spa", "es"),
+    ("srp", "sr"),
+    ("swe", "sv"),
+    ("tam", "ta"),
+    ("tel", "te"),
+    ("tgl", "tl"),
+    ("tha", "th"),
+    ("tuk", "tk"),
+    ("tur", "tr"),
+    ("ukr", "uk"),
+    ("urd", "ur"),
+    ("uzb", "uz"),
+    ("vie", "vi"),
+    ("yid", "yi"),
+    ("zul", "zu"),
+];
+
+/// Gets the folder of a file from the language of its content.
+/// Only text files are detected: a sample of the start of the file is read and files containing NUL bytes or invalid UTF-8 are treated as binary.
+///
+/// ### Parameters
+/// - `file`: The file to detect the language of.
+///
+/// ### Returns
+/// - `Result<&str, io::Error>`: The ISO 639-1 code of the language (ISO 639-3 if it has none), or the unknown folder if the file is binary or its language cannot be reliably detected.
+pub fn get_language_folder(file: &Path) -> io::Result<&'static str> {
+    let mut sample = Vec::new();
+    File::open(file)?
+        .take(SAMPLE_SIZE)
+        .read_to_end(&mut sample)?;
+    if sample.contains(&0) {
+        return Ok(UNKNOWN_LANGUAGE_FOLDER);
+    }
+    let text = match std::str::from_utf8(&sample) {
+        Ok(text) => text,
+        // The sample may end in the middle of a character
+        Err(e) if e.error_len().is_none() => {
+            std::str::from_utf8(&sample[..e.valid_up_to()]).unwrap_or_default()
+        }
+        Err(_) => return Ok(UNKNOWN_LANGUAGE_FOLDER),
+    };
+    let Some(info) = whatlang::detect(text).filter(|v| v.is_reliable()) else {
+        return Ok(UNKNOWN_LANGUAGE_FOLDER);
+    };
+    let code = info.lang().code();
+    Ok(ISO_639_1_CODES
+        .iter()
+        .find(|(iso_639_3, _)| *iso_639_3 == code)
+        .map(|(_, iso_639_1)| *iso_639_1)
+        .unwrap_or(code))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::fs;
+    use tempfile::TempDir;
+
+    #[test]
+    fn text_files_are_routed_by_language() {
+        let folder = TempDir::new().unwrap();
+        let samples = [
+            (
+                "letter_en.txt",
+                "Dear neighbours, we would like to thank everyone who helped organize the summer party in the park last weekend. The weather was wonderful and the children had a great time.",
+                "en",
+            ),
+            (
+                "lettre_fr.txt",
+                "Chers voisins, nous voudrions remercier toutes les personnes qui ont aidé à organiser la fête d'été dans le parc le week-end dernier. Le temps était magnifique et les enfants se sont bien amusés.",
+                "fr",
+            ),
+        ];
+        for (name, text, language) in samples {
+            let file = folder.path().join(name);
+            fs::write(&file, text).unwrap();
+
+            assert_eq!(get_language_folder(&file).unwrap(), language);
+        }
+    }
+
+    #[test]
+    fn binary_and_undetectable_files_are_routed_to_the_fallback() {
+        let folder = TempDir::new().unwrap();
+        let binary = folder.path().join("photo.jpg");
+        fs::write(
+            &binary,
+            [0xFF, 0xD8, 0xFF, 0xE0, 0x00, 0x10, b'J', b'F', b'I', b'F'],
+        )
+        .unwrap();
+        let numbers = folder.path().join("numbers.csv");
+        fs::write(&numbers, "1,2,3\n4,5,6\n").unwrap();
+
+        assert_eq!(
+            get_language_folder(&binary).unwrap(),
+            UNKNOWN_LANGUAGE_FOLDER
+        );
+        assert_eq!(
+            get_language_folder(&numbers).unwrap(),
+            UNKNOWN_LANGUAGE_FOLDER
+        );
+    }
+}
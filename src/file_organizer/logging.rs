@@ -0,0 +1,288 @@
+use std::cell::RefCell;
+use std::fmt;
+use std::io;
+use std::io::Write;
+use std::sync::atomic::{AtomicBool, Ordering};
+use tracing::field::{Field, Visit};
+use tracing::{Event, Level, Subscriber};
+use tracing_subscriber::fmt::format::Writer;
+use tracing_subscriber::fmt::writer::BoxMakeWriter;
+use tracing_subscriber::fmt::{FmtContext, FormatEvent, FormatFields};
+use tracing_subscriber::registry::LookupSpan;
+
+/// If true, the events are written to the standard error instead of the standard output.
+static IS_STDERR: AtomicBool = AtomicBool::new(false);
+
+thread_local! {
+    /// The buffer the events of the current thread are written to instead of the output, while buffering.
+    static BUFFER: RefCell<Option<Vec<u8>>> = const { RefCell::new(None) };
+}
+
+/// LogFormat is an enum that contains how the events of a run are written.
+#[derive(Default, Debug, Clone, PartialEq)]
+pub enum LogFormat {
+    #[default]
+    Text,
+    Json,
+}
+
+impl LogFormat {
+    /// Parses a log format.
+    ///
+    /// ### Parameters
+    /// - `format`: The format to parse: text or json.
+    ///
+    /// ### Returns
+    /// - `Result<LogFormat, anyhow::Error>`: The log format.
+    pub fn parse(format: &str) -> anyhow::Result<LogFormat> {
+        match format.trim().to_lowercase().as_str() {
+            "text" => Ok(LogFormat::Text),
+            "json" => Ok(LogFormat::Json),
+            _ => Err(anyhow::Error::msg(format!(
+                "Invalid log format: {}, expected text or json",
+                format
+            ))),
+        }
+    }
+}
+
+/// ConsoleFormat is a struct that formats the events as the console output of the tool: their message only, without level, time nor span.
+struct ConsoleFormat;
+
+impl<S, N> FormatEvent<S, N> for ConsoleFormat
+where
+    S: Subscriber + for<'a> LookupSpan<'a>,
+    N: for<'a> FormatFields<'a> + 'static,
+{
+    fn format_event(
+        &self,
+        _context: &FmtContext<'_, S, N>,
+        mut writer: Writer<'_>,
+        event: &Event<'_>,
+    ) -> fmt::Result {
+        let mut visitor = MessageVisitor {
+            writer: &mut writer,
+            result: Ok(()),
+        };
+        event.record(&mut visitor);
+        visitor.result?;
+        writeln!(writer)
+    }
+}
+
+/// MessageVisitor is a struct that writes the message field of an event.
+struct MessageVisitor<'a, 'b> {
+    writer: &'a mut Writer<'b>,
+    result: fmt::Result,
+}
+
+impl Visit for MessageVisitor<'_, '_> {
+    fn record_debug(&mut self, field: &Field, value: &dyn fmt::Debug) {
+        if field.name() == "message" {
+            self.result = write!(self.writer, "{:?}", value);
+        }
+    }
+}
+
+/// OutputWriter is a struct that writes the events to the buffer of the current thread while buffering, to the output otherwise.
+struct OutputWriter;
+
+impl io::Write for OutputWriter {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        let is_buffered = BUFFER.with_borrow_mut(|buffer| match buffer {
+            Some(buffer) => {
+                buffer.extend_from_slice(buf);
+                true
+            }
+            None => false,
+        });
+        if is_buffered {
+            return Ok(buf.len());
+        }
+        write_output(buf)?;
+        Ok(buf.len())
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        Ok(())
+    }
+}
+
+/// Writes to the output of the events at once, so that it is not interleaved with the writes of the other threads.
+///
+/// ### Parameters
+/// - `buf`: The bytes to write.
+///
+/// ### Returns
+/// - `io::Result<()>`: The result of the write.
+fn write_output(buf: &[u8]) -> io::Result<()> {
+    if IS_STDERR.load(Ordering::Relaxed) {
+        io::stderr().lock().write_all(buf)
+    } else {
+        io::stdout().lock().write_all(buf)
+    }
+}
+
+/// Starts buffering the events of the current thread, until flushed, so that the output of a recipe run concurrently with others is not interleaved with theirs.
+pub fn start_buffering() {
+    BUFFER.with_borrow_mut(|buffer| *buffer = Some(Vec::new()));
+}
+
+/// Writes the events buffered by the current thread to the output at once, and stops buffering.
+pub fn flush_buffer() {
+    if let Some(buffer) = BUFFER.with_borrow_mut(Option::take) {
+        let _ = write_output(&buffer);
+    }
+}
+
+/// Initializes the subscriber writing the events of the run to the standard output.
+/// The text format mirrors the console output of the tool, the JSON format writes one object per event, with its fields and the recipe and file spans it happened in, and without colors.
+/// When the standard output is kept for the JSON statistics, only the warnings and errors are written, to the standard error.
+/// The events of a thread can be buffered, see start_buffering.
+///
+/// ### Parameters
+/// - `log_format`: The format of the events.
+/// - `json_stats`: If true, the standard output is kept for the JSON statistics of the run.
+/// - `max_level`: The most verbose level of the events written, capped to WARN when the standard output is kept for the JSON statistics.
+pub fn init(log_format: &LogFormat, json_stats: bool, max_level: Level) {
+    IS_STDERR.store(json_stats, Ordering::Relaxed);
+    let subscriber = tracing_subscriber::fmt()
+        .with_max_level(if json_stats {
+            max_level.min(Level::WARN)
+        } else {
+            max_level
+        })
+        .with_writer(BoxMakeWriter::new(|| OutputWriter));
+    match log_format {
+        LogFormat::Text => subscriber.event_format(ConsoleFormat).init(),
+        LogFormat::Json => {
+            colored::control::set_override(false);
+            subscriber
+                .json()
+                .with_current_span(true)
+                .with_span_list(true)
+                .init()
+        }
+    }
+}
+
+#[cfg(test)]
+pub(crate) mod tests {
+    use super::*;
+    use std::sync::{Arc, Mutex};
+
+    /// Captures the console output of the events emitted by the current thread while running an operation, without its colors.
+    ///
+    /// ### Parameters
+    /// - `operation`: The operation to run.
+    ///
+    /// ### Returns
+    /// - `(R, String)`: The result of the operation and its output.
+    pub(crate) fn capture_output<R>(operation: impl FnOnce() -> R) -> (R, String) {
+        capture_output_at(Level::INFO, operation)
+    }
+
+    /// Captures the console output of the events emitted by the current thread down to a level while running an operation, without its colors.
+    ///
+    /// ### Parameters
+    /// - `max_level`: The most verbose level of the events captured.
+    /// - `operation`: The operation to run.
+    ///
+    /// ### Returns
+    /// - `(R, String)`: The result of the operation and its output.
+    pub(crate) fn capture_output_at<R>(
+        max_level: Level,
+        operation: impl FnOnce() -> R,
+    ) -> (R, String) {
+        let subscriber = tracing_subscriber::fmt()
+            .with_max_level(max_level)
+            .event_format(ConsoleFormat)
+            .with_writer(BoxMakeWriter::new(|| OutputWriter))
+            .finish();
+        let _guard = tracing::subscriber::set_default(subscriber);
+        start_buffering();
+        let result = operation();
+        let output = BUFFER.with_borrow_mut(Option::take).unwrap_or_default();
+        let colors = regex::Regex::new("\x1b\\[[0-9;]*m").unwrap();
+        let output = String::from_utf8_lossy(&output);
+        (result, colors.replace_all(&output, "").to_string())
+    }
+
+    /// SnapshotWriter is a struct that takes a snapshot each time an event containing a message is written.
+    struct SnapshotWriter<T> {
+        message: &'static str,
+        snapshot: Arc<dyn Fn() -> T + Send + Sync>,
+        snapshots: Arc<Mutex<Vec<T>>>,
+    }
+
+    impl<T> Write for SnapshotWriter<T> {
+        fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+            if String::from_utf8_lossy(buf).contains(self.message) {
+                self.snapshots.lock().unwrap().push((self.snapshot)());
+            }
+            Ok(buf.len())
+        }
+
+        fn flush(&mut self) -> io::Result<()> {
+            Ok(())
+        }
+    }
+
+    /// Takes a snapshot, e.g. of the files written by a run, each time the current thread emits an event containing a message while running an operation.
+    ///
+    /// ### Parameters
+    /// - `message`: The message of the events to take a snapshot at.
+    /// - `snapshot`: The snapshot to take.
+    /// - `operation`: The operation to run.
+    ///
+    /// ### Returns
+    /// - `(R, Vec<T>)`: The result of the operation and the snapshots, in order.
+    pub(crate) fn capture_snapshots<R, T: Send + 'static>(
+        message: &'static str,
+        snapshot: impl Fn() -> T + Send + Sync + 'static,
+        operation: impl FnOnce() -> R,
+    ) -> (R, Vec<T>) {
+        let snapshot: Arc<dyn Fn() -> T + Send + Sync> = Arc::new(snapshot);
+        let snapshots = Arc::new(Mutex::new(Vec::new()));
+        let writer_snapshots = snapshots.clone();
+        let subscriber = tracing_subscriber::fmt()
+            .event_format(ConsoleFormat)
+            .with_writer(BoxMakeWriter::new(move || SnapshotWriter {
+                message,
+                snapshot: snapshot.clone(),
+                snapshots: writer_snapshots.clone(),
+            }))
+            .finish();
+        let _guard = tracing::subscriber::set_default(subscriber);
+        let result = operation();
+        let snapshots = std::mem::take(&mut *snapshots.lock().unwrap());
+        (result, snapshots)
+    }
+
+    /// Captures the JSON events emitted by the current thread while running an operation, down to the debug level, with the spans they happened in.
+    ///
+    /// ### Parameters
+    /// - `operation`: The operation to run.
+    ///
+    /// ### Returns
+    /// - `(R, Vec<serde_json::Value>)`: The result of the operation and its events.
+    pub(crate) fn capture_json_events<R>(
+        operation: impl FnOnce() -> R,
+    ) -> (R, Vec<serde_json::Value>) {
+        let subscriber = tracing_subscriber::fmt()
+            .json()
+            .with_span_list(true)
+            .with_max_level(Level::DEBUG)
+            .with_writer(BoxMakeWriter::new(|| OutputWriter))
+            .finish();
+        let _guard = tracing::subscriber::set_default(subscriber);
+        start_buffering();
+        let result = operation();
+        let output = BUFFER.with_borrow_mut(Option::take).unwrap_or_default();
+        let events = String::from_utf8_lossy(&output)
+            .lines()
+            .map(|v| serde_json::from_str(v).unwrap())
+            .collect();
+        (result, events)
+    }
+}
@@ -0,0 +1,179 @@
+use crate::file_organizer::FileOrganizerStats;
+use std::fmt::Write as _;
+use std::fs;
+use std::path::Path;
+
+/// MetricDefinition is a type that contains the per recipe metric name, the all recipes metric name, the help and the value getter of a metric.
+type MetricDefinition = (
+    &'static str,
+    &'static str,
+    &'static str,
+    fn(&FileOrganizerStats) -> f64,
+);
+
+/// Writes the statistics of a run as metrics in the Prometheus text format, for the node_exporter textfile collector.
+/// The file is written under a temporary name then renamed so that a scrape never reads a partial file.
+///
+/// ### Parameters
+/// - `path`: The path to the metrics file.
+/// - `recipes_stats`: The name and statistics of each recipe run.
+/// - `last_run`: The Unix timestamp of the run.
+///
+/// ### Returns
+/// - `Result<(), anyhow::Error>`: The result of the write.
+pub fn write_metrics(
+    path: &Path,
+    recipes_stats: &[(String, FileOrganizerStats)],
+    last_run: i64,
+) -> anyhow::Result<()> {
+    let metrics = format_metrics(recipes_stats, last_run);
+    let mut temporary_path = path.as_os_str().to_owned();
+    temporary_path.push(".tmp");
+    fs::write(&temporary_path, metrics)?;
+    fs::rename(&temporary_path, path)?;
+    Ok(())
+}
+
+/// Formats the statistics of a run as metrics in the Prometheus text format.
+/// The metrics are gauges, as the file is rewritten with the statistics of each run rather than accumulated.
+///
+/// ### Parameters
+/// - `recipes_stats`: The name and statistics of each recipe run.
+/// - `last_run`: The Unix timestamp of the run.
+///
+/// ### Returns
+/// - `String`: The metrics.
+fn format_metrics(recipes_stats: &[(String, FileOrganizerStats)], last_run: i64) -> String {
+    let metrics_definitions: [MetricDefinition; 5] = [
+        (
+            "organizer_files_processed",
+            "organizer_run_files_processed",
+            "Files scanned during the last run.",
+            |stats| stats.files_processed as f64,
+        ),
+        (
+            "organizer_files_matched",
+            "organizer_run_files_matched",
+            "Files matched and processed during the last run.",
+            |stats| stats.files_matched as f64,
+        ),
+        (
+            "organizer_files_conflicted",
+            "organizer_run_files_conflicted",
+            "Files whose destination already existed during the last run.",
+            |stats| stats.files_conflicted as f64,
+        ),
+        (
+            "organizer_files_errored",
+            "organizer_run_files_errored",
+            "Files that failed to be processed during the last run.",
+            |stats| stats.files_errored as f64,
+        ),
+        (
+            "organizer_elapsed_seconds",
+            "organizer_run_elapsed_seconds",
+            "Duration of the last run.",
+            |stats| stats.elapsed_time as f64 / 1000.0,
+        ),
+    ];
+    let mut metrics = String::new();
+    for (name, total_name, help, value) in metrics_definitions {
+        let _ = writeln!(metrics, "# HELP {} {} Per recipe.", name, help);
+        let _ = writeln!(metrics, "# TYPE {} gauge", name);
+        for (recipe, stats) in recipes_stats {
+            let _ = writeln!(
+                metrics,
+                "{}{{recipe=\"{}\"}} {}",
+                name,
+                escape_label_value(recipe),
+                value(stats)
+            );
+        }
+        let total: f64 = recipes_stats.iter().map(|(_, stats)| value(stats)).sum();
+        let _ = writeln!(metrics, "# HELP {} {} All recipes.", total_name, help);
+        let _ = writeln!(metrics, "# TYPE {} gauge", total_name);
+        let _ = writeln!(metrics, "{} {}", total_name, total);
+    }
+    let _ = writeln!(
+        metrics,
+        "# HELP organizer_last_run_timestamp_seconds Unix timestamp of the last run."
+    );
+    let _ = writeln!(metrics, "# TYPE organizer_last_run_timestamp_seconds gauge");
+    let _ = writeln!(metrics, "organizer_last_run_timestamp_seconds {}", last_run);
+    metrics
+}
+
+/// Escapes a Prometheus label value.
+///
+/// ### Parameters
+/// - `value`: The label value to escape.
+///
+/// ### Returns
+/// - `String`: The escaped label value.
+fn escape_label_value(value: &str) -> String {
+    value
+        .replace('\\', "\\\\")
+        .replace('"', "\\\"")
+        .replace('\n', "\\n")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::collections::HashMap;
+    use tempfile::TempDir;
+
+    #[test]
+    fn metrics_file_matches_the_run_statistics() {
+        let folder = TempDir::new().unwrap();
+        let metrics_file = folder.path().join("organizer.prom");
+        let photos = FileOrganizerStats {
+            files_processed: 10,
+            files_matched: 4,
+            files_conflicted: 1,
+            elapsed_time: 1500,
+            ..Default::default()
+        };
+        let documents = FileOrganizerStats {
+            files_processed: 3,
+            files_matched: 2,
+            files_errored: 1,
+            ..Default::default()
+        };
+        let recipes_stats = vec![
+            ("Photos".to_string(), photos),
+            ("Documents \"pdf\"".to_string(), documents),
+        ];
+
+        write_metrics(&metrics_file, &recipes_stats, 1700000000).unwrap();
+
+        let content = fs::read_to_string(&metrics_file).unwrap();
+        let samples: HashMap<&str, f64> = content
+            .lines()
+            .filter(|v| !v.starts_with('#'))
+            .map(|v| {
+                let (name, value) = v.rsplit_once(' ').unwrap();
+                (name, value.parse().unwrap())
+            })
+            .collect();
+        assert_eq!(
+            samples["organizer_files_processed{recipe=\"Photos\"}"],
+            10.0
+        );
+        assert_eq!(
+            samples["organizer_files_errored{recipe=\"Documents \\\"pdf\\\"\"}"],
+            1.0
+        );
+        assert_eq!(samples["organizer_run_files_matched"], 6.0);
+        assert_eq!(samples["organizer_run_files_conflicted"], 1.0);
+        assert_eq!(samples["organizer_elapsed_seconds{recipe=\"Photos\"}"], 1.5);
+        assert_eq!(
+            samples["organizer_last_run_timestamp_seconds"],
+            1700000000.0
+        );
+        assert!(content.contains("# TYPE organizer_elapsed_seconds gauge\n"));
+        assert!(content.contains("# TYPE organizer_run_files_processed gauge\n"));
+        assert!(!content.contains("_total"));
+        assert!(!folder.path().join("organizer.prom.tmp").exists());
+    }
+}
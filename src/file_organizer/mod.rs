@@ -1,505 +1,8192 @@
-use crate::file_organizer::settings::{DateComparator, Recipe, Settings};
-use anyhow::Result;
-use chrono::{DateTime, Utc};
+use crate::file_organizer::dates::{
+    DateRange, date_to_folder_name, get_arrival_date, get_date_boundary, get_date_range,
+    get_file_date, get_last_modification_date, get_last_seen, get_next_last_seen,
+    get_reference_date, needs_file_date, parse_day, parse_weekday_filter, validate_subfolders,
+};
+use crate::file_organizer::dest_index::DestinationIndex;
+use crate::file_organizer::file_names::{
+    build_file_name, get_counted_file_name, get_free_file_name, keeps_extension,
+    needs_content_hash, normalize_file_name, replace_hash_tokens, sanitize_folder_name,
+    uses_counter,
+};
+use crate::file_organizer::filters::{
+    compile_name_patterns, get_effective_uid, get_magic_subfolder, get_mime_type,
+    is_extension_allowed, is_mime_type_allowed, is_owned_by, is_size_allowed, is_system_file,
+    load_tag_rules, parse_magic_routes, parse_mime_types,
+};
+use crate::file_organizer::journal::Journal;
+use crate::file_organizer::music::MusicTags;
+use crate::file_organizer::overlap::OverlapPolicy;
+use crate::file_organizer::patch::{PatchOperation, PatchRecorder};
+use crate::file_organizer::plan::{PlanOperation, PlanOperationKind};
+use crate::file_organizer::settings::{
+    ConflictPolicy, DateComparator, DateStrategy, FileOperation, FilterLogic, LockedPolicy, Recipe,
+    RecipeMode, ReferenceFilter, Settings,
+};
+use crate::file_organizer::shard::Shard;
+use crate::file_organizer::tag_rules::TagRules;
+use crate::file_organizer::watch::Debouncer;
+use anyhow::{Context, Result};
+use chrono::format::{Parsed, StrftimeItems, parse};
+use chrono::{DateTime, Datelike, SecondsFormat, TimeDelta, Utc, Weekday};
 use colored::*;
+use globset::GlobSet;
+use notify::Watcher;
 use rayon::prelude::*;
+use rayon::{ThreadPool, ThreadPoolBuilder};
+use serde::Serialize;
+use sha2::{Digest, Sha256};
+use std::collections::{HashMap, HashSet};
+use std::ffi::OsString;
 use std::fs;
 use std::fs::DirEntry;
+use std::io;
+use std::io::{Read, Write};
 use std::path::Path;
 use std::path::PathBuf;
+use std::sync::mpsc::{self, RecvTimeoutError};
+use std::sync::{Arc, Mutex};
+use std::thread;
+use std::time::{Duration, Instant, SystemTime};
+use tracing::{Span, debug, error, info, info_span, warn};
+mod compact;
+pub mod config_diff;
+mod dates;
+mod dedupe;
+mod dest_index;
+mod exif_date;
+mod file_names;
+mod filters;
+mod hooks;
+mod inodes;
+pub mod journal;
+mod language;
+pub mod logging;
+pub mod metrics;
+mod music;
+mod near_dupe;
+pub mod overlap;
+pub mod patch;
+mod pipeline;
+pub mod plan;
+pub mod registry;
+mod remote;
 pub mod settings;
+pub mod shard;
+mod space;
+mod tag_rules;
+pub mod timer;
+mod tree;
+mod watch;
+
+/// The name of the file listing the pinned files of a folder.
+const PIN_FILE_NAME: &str = ".organizer-pin";
+
+/// The characters forbidden in folder names on Windows, besides the path separators.
+const FORBIDDEN_FOLDER_CHARACTERS: [char; 7] = [':', '<', '>', '|', '?', '*', '"'];
+
+/// The name of the checksums manifest written in the destination folder.
+const MANIFEST_FILE_NAME: &str = "manifest.sha256";
+
+/// An organized file to add to the manifest, with its content hash if already computed.
+type ManifestEntry = (PathBuf, Option<String>);
+
+/// The outcome of matching a file against a recipe, None if the file is not matched.
+type FileMatch = anyhow::Result<Option<MatchResult>>;
+
+/// The token replaced by the first 8 hexadecimal characters of the content hash of a file.
+pub const HASH8_TOKEN: &str = "{hash8}";
+
+/// The token replaced by the first 2 hexadecimal characters of the content hash of a file.
+pub const HASH2_TOKEN: &str = "{hash2}";
+
+/// The file name template token replaced by the original name of a file.
+pub const NAME_TOKEN: &str = "{name}";
+
+/// The file name template and subfolder token replaced by the extension of a file, lowercased in the subfolders.
+pub const EXT_TOKEN: &str = "{ext}";
+
+/// The file name template token replaced by the original name of a file, without its extension.
+pub const ORIGINAL_TOKEN: &str = "{original}";
+
+/// The file name template token replaced by the first number, from 1, giving a name free in the destination folder.
+pub const COUNTER_TOKEN: &str = "{counter}";
+
+/// The subfolder token replaced by the size bucket of a file: small, medium or large.
+pub const SIZE_BUCKET_TOKEN: &str = "{size_bucket}";
+
+/// The default size, in bytes, from which a file is no longer in the small bucket.
+const DEFAULT_SMALL_SIZE_BYTES: u64 = 1024 * 1024;
+
+/// The default size, in bytes, from which a file is in the large bucket.
+const DEFAULT_MEDIUM_SIZE_BYTES: u64 = 100 * 1024 * 1024;
+
+/// The subfolder replacing the extension token for the files without extension.
+const NO_EXTENSION_FOLDER: &str = "no_extension";
+
+/// The default size of the buffer of the streamed copies of the large files.
+const DEFAULT_COPY_BUFFER_BYTES: usize = 8 * 1024 * 1024;
+
+/// The number of progress reports of a streamed copy, one every tenth of the file.
+const COPY_PROGRESS_STEPS: u64 = 10;
+
+/// The default number of seconds the `Wait` locked files policy waits for a file to be unlocked.
+const DEFAULT_LOCKED_TIMEOUT: u64 = 30;
+
+/// The number of attempts of the `Retry` locked files policy.
+const LOCKED_RETRY_ATTEMPTS: u32 = 5;
+
+/// The delay before retrying an operation on a locked file, doubled after each attempt by the `Retry` policy.
+const LOCKED_RETRY_DELAY: Duration = Duration::from_millis(200);
+
+/// The extension of the sidecar files pinning the file they are named after.
+const KEEP_SIDECAR_EXTENSION: &str = ".keep";
+
+/// The extension of the progress file written next to the recipe file by a chunked run without metrics file.
+const PROGRESS_FILE_EXTENSION: &str = "progress.json";
+
+/// The separator joining the levels of a destination folder exceeding max_dest_depth.
+const DEST_DEPTH_SEPARATOR: &str = "_";
 
 /// FileOrganizer is a struct that contains the settings and the state of the file organizer.
 pub struct FileOrganizer {
     settings: Settings,
-    is_dry_run: bool,
-    is_iterative: bool,
+    options: RunOptions,
+    plan: Mutex<Vec<PlanOperation>>,
+    patch: Option<Arc<PatchRecorder>>,
+    journal: Option<Arc<Journal>>,
+    last_seen: Mutex<HashMap<String, DateTime<Utc>>>,
+}
+
+/// RunOptions is a struct that contains the command line options of a run.
+#[derive(Default)]
+pub struct RunOptions {
+    /// If true, no files will be moved or copied.
+    pub is_dry_run: bool,
+    /// If true, the files are processed iteratively instead of in parallel.
+    pub is_iterative: bool,
+    /// If true, the last_run and last_seen dates of the recipes are ignored, all the matching files being processed.
+    pub is_forced: bool,
+    /// The path to the Prometheus metrics file to write after the run, if any.
+    pub metrics_file: Option<PathBuf>,
+    /// The number of files processed between two progress reports, if any.
+    pub chunk_size: Option<usize>,
+    /// The path to the file the operations are saved to instead of being executed, if any.
+    pub plan_file: Option<PathBuf>,
+    /// The hash of the plan previewed by a dry run, the run aborting if the plan changed since, if any.
+    pub confirm_plan: Option<String>,
+    /// If true, the recipes are planned again after the run, the run failing if any file would be processed again.
+    pub assert_idempotent: bool,
+    /// If true, the tree of the destination folders is printed after each recipe.
+    pub show_tree: bool,
+    /// The name of the only recipe to run, if any.
+    pub recipe_name: Option<String>,
+    /// The part of the files to process when the run is split across several invocations, if any.
+    pub shard: Option<Shard>,
+    /// What the run does when the destination of a recipe overlaps the source of another one.
+    pub on_overlap: OverlapPolicy,
+    /// The path to the patch file the executed operations are recorded to, so that the run can be reverted, if any.
+    pub patch_file: Option<PathBuf>,
+    /// The path to the log file a line is appended to for each file handled by the run, if any.
+    pub log_file: Option<PathBuf>,
+    /// If true, the free space of the destination drives is checked before running each recipe, the recipe being aborted if its files do not fit.
+    pub check_space: bool,
+    /// If true, the statistics and the operations of each recipe are printed as JSON at the end of the run.
+    pub json_stats: bool,
+    /// The number of threads processing the files of each recipe in parallel, if set, 0 meaning all the cores.
+    pub threads: Option<usize>,
+    /// If true, the files skipped as their destination exists are recorded for the plan report.
+    pub plan_report: bool,
+    /// If true, the recipes not sharing folders run concurrently, the files of each recipe being processed iteratively.
+    pub parallel_recipes: bool,
+    /// If true, the hooks of a recipe file fetched from a URL are run, which are refused otherwise.
+    pub allow_remote_hooks: bool,
+}
+
+/// RecipeContext is a struct that contains the values computed once per recipe run.
+struct RecipeContext {
+    date_boundary: Option<DateTime<Utc>>,
+    last_seen: Option<DateTime<Utc>>,
+    date_range: DateRange,
+    reference_date: Option<DateTime<Utc>>,
+    weekdays: Option<HashSet<Weekday>>,
+    needs_file_date: bool,
+    pinned_files: HashSet<PathBuf>,
+    recent_files: HashSet<PathBuf>,
+    effective_uid: Option<u32>,
+    max_modification_time: Option<SystemTime>,
+    magic_routes: Vec<(Vec<u8>, String)>,
+    tag_rules: Option<TagRules>,
+    name_patterns: Option<(GlobSet, Vec<String>)>,
+    mime_types: Option<Vec<String>>,
+    shard: Option<Shard>,
+    planned_files: Mutex<HashSet<PathBuf>>,
+    next_counters: Mutex<HashMap<PathBuf, u32>>,
+    destination_names: Option<HashSet<OsString>>,
+    destination_index: Option<DestinationIndex>,
+    needs_music_tags: bool,
+    needs_content_hash: bool,
+    planned_operations: Option<Mutex<Vec<PlanOperation>>>,
+    skipped_operations: Option<Mutex<Vec<PlanOperation>>>,
+    performed_operations: Option<Mutex<Vec<PlanOperation>>>,
+    manifest_files: Option<Mutex<Vec<ManifestEntry>>>,
+    folders_counts: Option<Mutex<HashMap<PathBuf, u32>>>,
+    patch: Option<Arc<PatchRecorder>>,
+    journal: Option<Arc<Journal>>,
+    unknown_extensions: Option<Mutex<HashMap<String, u32>>>,
+    thread_pool: Option<ThreadPool>,
+    span: Span,
+    dry_run: bool,
+    quiet: bool,
+}
+
+impl RecipeContext {
+    /// Runs a parallel operation in the thread pool of the recipe, or in the global thread pool if the recipe has none.
+    ///
+    /// ### Parameters
+    /// - `operation`: The operation to run.
+    ///
+    /// ### Returns
+    /// - `R`: The result of the operation.
+    fn install<R: Send>(&self, operation: impl FnOnce() -> R + Send) -> R {
+        match &self.thread_pool {
+            Some(thread_pool) => thread_pool.install(operation),
+            None => operation(),
+        }
+    }
+}
+
+/// FileOutcome is an enum that describes what has been done, or would be done in dry run, with a file.
+#[derive(PartialEq)]
+enum FileOutcome {
+    NotMatched,
+    Processed,
+    Conflicted,
+    ConflictSkipped,
+    DuplicateSkipped,
+    LockedSkipped,
+}
+
+/// FileFilter is an enum that contains a filter selecting the files of a recipe, combined with the other filters by its filter logic.
+enum FileFilter<'a> {
+    Size,
+    NamePatterns(&'a GlobSet, &'a [String]),
+    Extension,
+    MimeType(&'a [String]),
+    Weekday(&'a HashSet<Weekday>),
+    Reference(&'a DateTime<Utc>),
+}
+
+/// MatchResult is a struct that contains the outcome of a file matched by a recipe.
+struct MatchResult {
+    dest_folder: PathBuf,
+    file_name: OsString,
+    content_hash: Option<String>,
+    file_date: DateTime<Utc>,
+    criteria: Vec<String>,
+    counted_file: Option<PathBuf>,
+}
+
+impl MatchResult {
+    /// Describes the recipe criteria satisfied by the file.
+    ///
+    /// ### Returns
+    /// - `String`: The description of the satisfied criteria.
+    fn describe_criteria(&self) -> String {
+        if self.criteria.is_empty() {
+            "matched all files".to_string()
+        } else {
+            format!("matched {}", self.criteria.join(", "))
+        }
+    }
 }
 
 /// FileOrganizerStats is a struct that contains the statistics of the file organizer.
+#[derive(Default, Clone, Serialize)]
 pub struct FileOrganizerStats {
     files_matched: u32,
     files_processed: u32,
+    files_conflicted: u32,
+    files_conflict_skipped: u32,
+    files_duplicate_skipped: u32,
+    files_locked_skipped: u32,
+    files_errored: u32,
+    recipes_failed: u32,
     elapsed_time: i64,
+    operations: Vec<PlanOperation>,
+    errors: Vec<String>,
+}
+
+/// RecipeStats is a struct that contains the statistics of a recipe run, as printed by --json.
+#[derive(Serialize)]
+struct RecipeStats<'a> {
+    name: &'a str,
+    #[serde(flatten)]
+    stats: &'a FileOrganizerStats,
+}
+
+impl FileOrganizerStats {
+    /// Adds the outcomes of processed files to the statistics.
+    ///
+    /// ### Parameters
+    /// - `results`: The results of the processed files.
+    fn add_results(&mut self, results: Vec<Result<FileOutcome>>) {
+        self.files_processed += results.len() as u32;
+        for result in results {
+            let outcome = match result {
+                Ok(outcome) => outcome,
+                Err(e) => {
+                    self.files_errored += 1;
+                    self.errors.push(format!("{:#}", e));
+                    continue;
+                }
+            };
+            if outcome != FileOutcome::NotMatched
+                && outcome != FileOutcome::DuplicateSkipped
+                && outcome != FileOutcome::LockedSkipped
+            {
+                self.files_matched += 1;
+            }
+            if outcome == FileOutcome::Conflicted || outcome == FileOutcome::ConflictSkipped {
+                self.files_conflicted += 1;
+            }
+            if outcome == FileOutcome::ConflictSkipped {
+                self.files_conflict_skipped += 1;
+            }
+            if outcome == FileOutcome::DuplicateSkipped {
+                self.files_duplicate_skipped += 1;
+            }
+            if outcome == FileOutcome::LockedSkipped {
+                self.files_locked_skipped += 1;
+            }
+        }
+    }
+
+    /// Gets the failure of a run, which makes the process exit with an error.
+    /// The files errored by a dry run are not a failure, as no file was moved or copied.
+    ///
+    /// ### Parameters
+    /// - `is_dry_run`: If true, the run was a dry run.
+    ///
+    /// ### Returns
+    /// - `Option<String>`: The message of the failure, None if the run succeeded.
+    pub fn get_failure(&self, is_dry_run: bool) -> Option<String> {
+        if self.files_errored > 0 && !is_dry_run {
+            Some(format!(
+                "{} file(s) failed, see the errors above",
+                self.files_errored
+            ))
+        } else if self.recipes_failed > 0 {
+            Some(format!(
+                "{} recipe(s) failed, see the errors above",
+                self.recipes_failed
+            ))
+        } else {
+            None
+        }
+    }
+}
+
+/// EntriesWalker is an iterator over the entries of the source folder of a recipe, each folder being read as the entries are consumed.
+/// In place and recursive recipes are walked down to max_depth, skipping hidden folders, the destination and the subfolders of an in place recipe.
+struct EntriesWalker<'a> {
+    recipe: &'a Recipe,
+    is_recursive: bool,
+    skipped_folders: HashSet<PathBuf>,
+    folders: Vec<(PathBuf, u32)>,
+    current: Option<(PathBuf, u32, fs::ReadDir)>,
+}
+
+impl<'a> EntriesWalker<'a> {
+    /// Creates a walker over the entries of the source folder of a recipe.
+    ///
+    /// ### Parameters
+    /// - `recipe`: The recipe to walk the source folder of.
+    ///
+    /// ### Returns
+    /// - `EntriesWalker`: The walker, no folder being read yet.
+    fn new(recipe: &'a Recipe) -> EntriesWalker<'a> {
+        let is_recursive = recipe.in_place.unwrap_or(false) || recipe.recursive.unwrap_or(false);
+        // The folders skipped are compared canonicalized, a destination given through a symlink or with `..` being skipped as well.
+        // The duplicates folder of a dedupe recipe is skipped too, as it is inside the source folder when the destination is the source.
+        let duplicates_folder = is_dedupe(recipe).then(|| {
+            recipe
+                .destination_folder
+                .join(dedupe::DUPLICATES_FOLDER_NAME)
+        });
+        let skipped_folders = if is_recursive {
+            std::iter::once(&recipe.destination_folder)
+                .chain(recipe.year_roots.iter().flat_map(|v| v.values()))
+                .chain(&duplicates_folder)
+                .map(|v| fs::canonicalize(v).unwrap_or_else(|_| v.clone()))
+                .collect()
+        } else {
+            HashSet::new()
+        };
+        let mut folders: Vec<(PathBuf, u32)> = recipe
+            .get_source_folders()
+            .into_iter()
+            .map(|v| (v.clone(), 0))
+            .collect();
+        // The folders are popped from the end, the source folders of a flat recipe being read in order.
+        if !is_recursive {
+            folders.reverse();
+        }
+        EntriesWalker {
+            recipe,
+            is_recursive,
+            skipped_folders,
+            folders,
+            current: None,
+        }
+    }
+}
+
+impl Iterator for EntriesWalker<'_> {
+    type Item = io::Result<DirEntry>;
+
+    fn next(&mut self) -> Option<io::Result<DirEntry>> {
+        loop {
+            let Some((folder, depth, read_dir)) = &mut self.current else {
+                let (folder, depth) = self.folders.pop()?;
+                match fs::read_dir(&folder) {
+                    Ok(read_dir) => self.current = Some((folder, depth, read_dir)),
+                    Err(e) => return Some(Err(e)),
+                }
+                continue;
+            };
+            let entry = match read_dir.next() {
+                Some(Ok(entry)) => entry,
+                Some(Err(e)) => return Some(Err(e)),
+                None => {
+                    self.current = None;
+                    continue;
+                }
+            };
+            if !self.is_recursive {
+                return Some(Ok(entry));
+            }
+            match entry.file_type() {
+                Ok(file_type) if !file_type.is_dir() => return Some(Ok(entry)),
+                Ok(_) => {}
+                Err(e) => return Some(Err(e)),
+            }
+            if is_walked_folder(self.recipe, &self.skipped_folders, folder, *depth, &entry) {
+                self.folders.push((entry.path(), *depth + 1));
+            }
+        }
+    }
 }
 
 impl FileOrganizer {
     /// Creates a new FileOrganizer.
+    /// The subfolder formats of the enabled recipes are checked once here, before any recipe runs.
     ///
     /// ### Parameters
     /// - `settings_file_path`: The path to the settings file.
+    /// - `options`: The options of the run.
     ///
     /// ### Returns
-    /// - `FileOrganizer`: The FileOrganizer.
-    pub fn new(settings_file_path: PathBuf, is_dry_run: bool, is_iterative: bool) -> Result<Self> {
-        let mut settings = Settings::load_from_file(&settings_file_path)?;
-        for recipe in &mut settings.recipes {
-            if let Some(allowed_extensions) = &mut recipe.allowed_extensions {
-                for extension in allowed_extensions {
-                    *extension = extension.to_lowercase();
-                }
-            }
+    /// - `FileOrganizer`: The FileOrganizer, an error if the settings cannot be loaded or a subfolder format is invalid.
+    pub fn new(settings_file_path: PathBuf, options: RunOptions) -> Result<Self> {
+        let settings = load_settings(&settings_file_path)?;
+        for recipe in settings.recipes.iter().filter(|v| is_enabled(v)) {
+            validate_subfolders(recipe)?;
         }
+        let patch = match &options.patch_file {
+            Some(patch_file) if !options.is_dry_run => {
+                Some(Arc::new(PatchRecorder::new(patch_file, &settings)?))
+            }
+            _ => None,
+        };
+        let journal = match &options.log_file {
+            Some(log_file) if !options.is_dry_run => Some(Arc::new(Journal::open(log_file)?)),
+            _ => None,
+        };
         Ok(Self {
             settings,
-            is_dry_run,
-            is_iterative,
+            options,
+            plan: Mutex::new(Vec::new()),
+            patch,
+            journal,
+            last_seen: Mutex::new(HashMap::new()),
+        })
+    }
+
+    /// Creates a new FileOrganizer validating its recipes.
+    /// Unlike new, the subfolder formats are not checked here, so that validate reports them along with the other problems of all the recipes.
+    ///
+    /// ### Parameters
+    /// - `settings_file_path`: The path to the settings file.
+    ///
+    /// ### Returns
+    /// - `FileOrganizer`: The FileOrganizer, an error if the settings cannot be loaded.
+    pub fn new_for_validation(settings_file_path: PathBuf) -> Result<Self> {
+        Ok(Self {
+            settings: load_settings(&settings_file_path)?,
+            options: RunOptions::default(),
+            plan: Mutex::new(Vec::new()),
+            patch: None,
+            journal: None,
+            last_seen: Mutex::new(HashMap::new()),
         })
     }
 
     /// Runs all recipes.
     ///
     /// ### Returns
-    /// - `Result<(), anyhow::Error>`: The result of the recipes run.
-    pub fn run(&mut self) -> anyhow::Result<()> {
-        println!(
+    /// - `Result<FileOrganizerStats, anyhow::Error>`: The statistics of all the recipes added up, with the number of files errored, or an error if a recipe failed.
+    pub fn run(&mut self) -> anyhow::Result<FileOrganizerStats> {
+        let recipes = self.select_recipe(self.get_recipes())?;
+        if !self.options.is_dry_run {
+            self.check_remote_hooks(&recipes)?;
+        }
+        self.print_disabled_recipes();
+        info!(
             "ℹ️ {} - Running {} recipe(s)",
             "file_organizer".blue(),
-            self.settings.recipes.len()
+            recipes.len()
         );
-        for (i, recipe) in self.settings.recipes.iter().enumerate() {
-            let stats = self.run_recipe(&recipe)?;
-            println!(
+        let recipes = self.order_recipes(recipes)?;
+        let stage_dir = self.get_stage_dir().filter(|_| !self.options.is_dry_run);
+        if let Some(stage_dir) = &stage_dir {
+            pipeline::create_stage_folders(&recipes, stage_dir)?;
+        }
+        if let Some(confirm_plan) = &self.options.confirm_plan {
+            let plan_hash = plan::hash_plan(&self.compute_plan(&recipes)?);
+            if plan_hash != *confirm_plan {
+                return Err(anyhow::Error::msg(format!(
+                    "The plan changed since it was previewed (hash {} instead of {}), aborting",
+                    plan_hash, confirm_plan
+                )));
+            }
+            info!(
                 "{} {} {} - {}",
                 "✅".green(),
-                recipe.name.blue(),
-                "Files matched".purple(),
-                stats.files_matched
+                "file_organizer".blue(),
+                "Plan confirmed".purple(),
+                plan_hash
             );
-            println!(
-                "{} {} {} - {}",
+        }
+        let batches = if self.options.parallel_recipes && !self.is_pipeline() {
+            overlap::batch_recipes(&self.resolve_recipes(&recipes))
+        } else {
+            (0..recipes.len()).map(|v| vec![v]).collect()
+        };
+        let mut recipes_stats = Vec::new();
+        for batch in batches {
+            let results = if batch.len() == 1 {
+                vec![self.run_and_print_recipe(&recipes, batch[0], &recipes_stats)]
+            } else {
+                info!(
+                    "ℹ️ {} {} - {}",
+                    "file_organizer".blue(),
+                    "Recipes run concurrently".purple(),
+                    batch
+                        .iter()
+                        .map(|v| recipes[*v].name.as_str())
+                        .collect::<Vec<_>>()
+                        .join(", ")
+                );
+                thread::scope(|scope| {
+                    let handles: Vec<_> = batch
+                        .iter()
+                        .map(|index| {
+                            let (file_organizer, recipes, recipes_stats) =
+                                (&*self, &recipes, &recipes_stats);
+                            scope.spawn(move || {
+                                logging::start_buffering();
+                                let result = file_organizer.run_and_print_recipe(
+                                    recipes,
+                                    *index,
+                                    recipes_stats,
+                                );
+                                logging::flush_buffer();
+                                result
+                            })
+                        })
+                        .collect();
+                    handles
+                        .into_iter()
+                        .map(|v| v.join().unwrap())
+                        .collect::<Vec<_>>()
+                })
+            };
+            for (index, result) in batch.into_iter().zip(results) {
+                match result {
+                    Ok(stats) => recipes_stats.push((recipes[index].name.clone(), stats)),
+                    Err(e) => {
+                        self.save_patch()?;
+                        return Err(e);
+                    }
+                }
+            }
+        }
+
+        if let Some(stage_dir) = &stage_dir {
+            pipeline::clean_stage_folders(&recipes, stage_dir);
+        }
+
+        print_total_stats(&recipes_stats);
+        self.write_metrics(&recipes_stats)?;
+        if let Some(progress_file) = self.get_progress_file() {
+            let _ = fs::remove_file(progress_file);
+        }
+        self.save_patch()?;
+        if self.options.json_stats {
+            print_json_stats(&recipes_stats)?;
+        }
+
+        if self.options.is_dry_run {
+            inodes::check_inodes(&self.plan.lock().unwrap());
+            let plan_hash = plan::hash_plan(&self.plan.lock().unwrap());
+            info!(
+                "ℹ️ {} {} - {} (run with --confirm_plan {} to execute exactly this plan)",
+                "file_organizer".blue(),
+                "Plan hash".purple(),
+                plan_hash,
+                plan_hash
+            );
+        }
+
+        if let Some(plan_file) = &self.options.plan_file {
+            let plan = self.plan.lock().unwrap();
+            plan::save_plan(plan_file, &plan).map_err(|e| {
+                anyhow::Error::msg(format!(
+                    "Error writing the plan file {}: {}",
+                    plan_file.display(),
+                    e
+                ))
+            })?;
+            info!(
+                "{} {} {} - {} ({} operation(s))",
                 "✅".green(),
-                recipe.name.blue(),
-                "Files processed".purple(),
-                stats.files_processed
+                "file_organizer".blue(),
+                "Plan written".purple(),
+                plan_file.display(),
+                plan.len()
+            );
+        }
+
+        if !self.options.is_dry_run
+            && self.options.shard.is_none()
+            && remote::is_url(&self.settings.path)
+            && self.settings.state_file.is_none()
+        {
+            info!(
+                "ℹ️ {} - The recipe file has been fetched from a URL, last_run is not saved without a state_file",
+                "file_organizer".blue()
             );
-            println!(
+        }
+        self.save_last_run(&recipes_stats)?;
+
+        if self.options.assert_idempotent {
+            self.assert_idempotent(&recipes)?;
+        }
+        Ok(sum_stats(&recipes_stats))
+    }
+
+    /// Plans the recipes again after a run, checking that the run left nothing to do.
+    /// Any planned operation means that a second run would process files again, e.g. by scanning the files it organized.
+    ///
+    /// ### Parameters
+    /// - `recipes`: The recipes that have been run, in order.
+    ///
+    /// ### Returns
+    /// - `Result<(), anyhow::Error>`: An error listing the files that would be processed again, if any.
+    fn assert_idempotent(&self, recipes: &[Recipe]) -> anyhow::Result<()> {
+        let operations = self.compute_plan(recipes)?;
+        if operations.is_empty() {
+            info!(
                 "{} {} {} - {}",
                 "✅".green(),
-                recipe.name.blue(),
-                "Elapsed time".purple(),
-                seconds_to_string(stats.elapsed_time / 1000)
+                "file_organizer".blue(),
+                "Idempotent".purple(),
+                "a second run would process no file"
             );
-            if i < self.settings.recipes.len() - 1 {
-                println!("{}", "----------------------------------------".blue());
-            }
+            return Ok(());
         }
+        for operation in &operations {
+            error!(
+                "{} {} {} - {} -> {}",
+                "❌".red(),
+                operation.recipe.blue(),
+                "Processed again".red(),
+                operation.source.display(),
+                operation.destination.display()
+            );
+        }
+        Err(anyhow::Error::msg(format!(
+            "The run is not idempotent, a second run would process {} file(s) again",
+            operations.len()
+        )))
+    }
 
-        // Update last_run for all recipes if not in dry run mode
-        if !self.is_dry_run {
-            let last_run = Utc::now();
-            let last_run = Some(last_run.format("%Y-%m-%d").to_string());
-            for recipe in &mut self.settings.recipes {
-                recipe.last_run = last_run.clone();
-            }
-
-            self.settings.save()?;
+    /// Writes the metrics file, if any.
+    ///
+    /// ### Parameters
+    /// - `recipes_stats`: The name and statistics of each recipe run.
+    ///
+    /// ### Returns
+    /// - `Result<(), anyhow::Error>`: The result of the write.
+    fn write_metrics(&self, recipes_stats: &[(String, FileOrganizerStats)]) -> anyhow::Result<()> {
+        if let Some(metrics_file) = &self.options.metrics_file {
+            metrics::write_metrics(metrics_file, recipes_stats, Utc::now().timestamp()).map_err(
+                |e| {
+                    anyhow::Error::msg(format!(
+                        "Error writing the metrics file {}: {}",
+                        metrics_file.display(),
+                        e
+                    ))
+                },
+            )?;
         }
         Ok(())
     }
 
-    /// Runs a recipe.
+    /// Writes the partial statistics of a chunked run: into the metrics file if any, or else into the progress file next to a local recipe file.
     ///
     /// ### Parameters
-    /// - `recipe`: The recipe to run.
-    /// - `dry_run`: If true, the recipe will not be run.
+    /// - `recipes_stats`: The name and statistics of the recipes run so far, the last one being partial.
     ///
     /// ### Returns
-    /// - `Result<(), anyhow::Error>`: The result of the recipe run.
-    fn run_recipe(&self, recipe: &Recipe) -> anyhow::Result<FileOrganizerStats> {
-        if !recipe.source_folder.is_dir() {
-            return Err(anyhow::Error::msg(format!(
-                "{} - Source folder not a directory: {}",
-                recipe.name,
-                recipe.source_folder.display()
-            )));
+    /// - `Result<(), anyhow::Error>`: The result of the write.
+    fn write_partial_stats(
+        &self,
+        recipes_stats: &[(String, FileOrganizerStats)],
+    ) -> anyhow::Result<()> {
+        if self.options.metrics_file.is_some() {
+            return self.write_metrics(recipes_stats);
         }
-        if !recipe.destination_folder.is_dir() {
-            return Err(anyhow::Error::msg(format!(
-                "{} - Target folder not a directory: {}",
-                recipe.name,
-                recipe.destination_folder.display()
-            )));
+        if let Some(progress_file) = self.get_progress_file() {
+            fs::write(&progress_file, format_json_stats(recipes_stats)?).map_err(|e| {
+                anyhow::Error::msg(format!(
+                    "Error writing the progress file {}: {}",
+                    progress_file.display(),
+                    e
+                ))
+            })?;
         }
-        print_recipe_info(recipe);
+        Ok(())
+    }
 
-        let start_time = Utc::now().timestamp_millis();
-        let date_boundary = get_date_boundary(recipe)?;
-        let results: Vec<_> = if self.is_iterative {
-            run_recipe_iterative(recipe, &date_boundary, self.is_dry_run)?
-        } else {
-            run_recipe_parallel(recipe, &date_boundary, self.is_dry_run)?
-        };
+    /// Gets the progress file of a chunked run, next to the recipe file, which is removed once all the recipes have run.
+    ///
+    /// ### Returns
+    /// - `Option<PathBuf>`: The progress file, None if the run is not chunked, is a dry run or the recipe file is remote.
+    fn get_progress_file(&self) -> Option<PathBuf> {
+        if self.options.chunk_size.is_none()
+            || self.options.is_dry_run
+            || remote::is_url(&self.settings.path)
+        {
+            return None;
+        }
+        Some(self.settings.path.with_extension(PROGRESS_FILE_EXTENSION))
+    }
+
+    /// Saves the patch file, if any.
+    ///
+    /// ### Returns
+    /// - `Result<(), anyhow::Error>`: The result of the save.
+    fn save_patch(&self) -> anyhow::Result<()> {
+        if let (Some(patch), Some(patch_file)) = (&self.patch, &self.options.patch_file) {
+            let operations_count = patch.save()?;
+            info!(
+                "{} {} {} - {} ({} operation(s), run with --revert {} to undo this run)",
+                "✅".green(),
+                "file_organizer".blue(),
+                "Patch written".purple(),
+                patch_file.display(),
+                operations_count,
+                patch_file.display()
+            );
+        }
+        Ok(())
+    }
 
-        let files_processed = results.len() as u32;
-        let mut files_matched = 0;
+    /// Computes the operations of all recipes, without executing nor printing them.
+    ///
+    /// ### Parameters
+    /// - `recipes`: The recipes to run, in order.
+    ///
+    /// ### Returns
+    /// - `Result<Vec<PlanOperation>, anyhow::Error>`: The planned operations.
+    fn compute_plan(&self, recipes: &[Recipe]) -> anyhow::Result<Vec<PlanOperation>> {
+        let mut operations = Vec::new();
+        for recipe in recipes {
+            operations.extend(take_planned_operations(self.plan_recipe(recipe)?));
+        }
+        Ok(operations)
+    }
 
-        for result in results {
-            if let Ok(is_file_valid) = result {
-                if is_file_valid {
-                    files_matched += 1;
-                }
+    /// Computes the operations of a recipe, without executing nor printing them.
+    ///
+    /// ### Parameters
+    /// - `recipe`: The recipe to plan.
+    ///
+    /// ### Returns
+    /// - `Result<RecipeContext, anyhow::Error>`: The context of the recipe run, holding its planned operations.
+    fn plan_recipe(&self, recipe: &Recipe) -> anyhow::Result<RecipeContext> {
+        let recipe = &self.prepare_recipe(recipe)?;
+        let entries = collect_entries(recipe)?;
+        let context = self.build_context(recipe, &entries, true, true)?;
+        if is_dedupe(recipe) {
+            dedupe::run_dedupe(entries, recipe, &context, self.options.is_iterative);
+        } else if is_compact(recipe) {
+            compact::run_compact(entries, recipe, &context);
+        } else {
+            run_recipe_parallel(entries, recipe, &context);
+        }
+        Ok(context)
+    }
+
+    /// Prints the report of the operations the recipes would execute, grouped by recipe with their totals, without executing them.
+    /// Unlike a dry run, the files are not printed as they are processed, only the report once all the recipes are planned.
+    ///
+    /// ### Returns
+    /// - `Result<(), anyhow::Error>`: The result of the planning.
+    pub fn print_plan_report(&self) -> anyhow::Result<()> {
+        let recipes = self.order_recipes(self.select_recipe(self.get_recipes())?)?;
+        let mut operations_count = 0;
+        for (i, recipe) in recipes.iter().enumerate() {
+            let mut context = self.plan_recipe(recipe)?;
+            let skipped_operations = context
+                .skipped_operations
+                .take()
+                .map(|v| v.into_inner().unwrap())
+                .unwrap_or_default();
+            let operations = take_planned_operations(context);
+            plan::print_report(&recipe.name, &operations, &skipped_operations);
+            operations_count += operations.len();
+            if i < recipes.len() - 1 {
+                info!("{}", "----------------------------------------".blue());
             }
         }
-        let elapsed_time = Utc::now().timestamp_millis() - start_time;
-        Ok(FileOrganizerStats {
-            files_matched,
-            files_processed,
-            elapsed_time,
+        info!(
+            "ℹ️ {} {} - {} operation(s) in {} recipe(s)",
+            "file_organizer".blue(),
+            "Plan report".purple(),
+            operations_count,
+            recipes.len()
+        );
+        Ok(())
+    }
+
+    /// Checks if the recipes are run as a pipeline.
+    ///
+    /// ### Returns
+    /// - `bool`: True if the destination folder of each recipe is the source folder of the next one, false otherwise.
+    fn is_pipeline(&self) -> bool {
+        self.settings.pipeline.unwrap_or(false)
+    }
+
+    /// Gets the staging folder shared by the recipes of a pipeline.
+    ///
+    /// ### Returns
+    /// - `Option<PathBuf>`: The staging folder, relative folders being relative to the root prefix, None if the recipes are not run as a pipeline or without staging.
+    fn get_stage_dir(&self) -> Option<PathBuf> {
+        if !self.is_pipeline() {
+            return None;
+        }
+        let stage_dir = self.settings.stage_dir.as_ref()?;
+        Some(match &self.settings.root_prefix {
+            Some(root_prefix) => root_prefix.join(stage_dir),
+            None => stage_dir.clone(),
         })
     }
-}
 
-/// Runs a recipe iteratively.
-///
-/// ### Parameters
-/// - `recipe`: The recipe to run.
-/// - `date_boundary`: The date boundary.
-/// - `dry_run`: If true, the recipe will not be run.
-///
-/// ### Returns
-/// - `Result<Vec<Result<bool>>>`: The results of the recipe run.
-fn run_recipe_iterative(
-    recipe: &Recipe,
-    date_boundary: &DateTime<Utc>,
-    dry_run: bool,
-) -> Result<Vec<Result<bool>>> {
-    let mut entries: Vec<_> =
-        fs::read_dir(&recipe.source_folder)?.collect::<Result<Vec<_>, _>>()?;
-    entries.sort_by_key(|entry| entry.path());
-    let results: Vec<_> = entries
-        .iter()
-        .map(|entry| run_for_file(entry, recipe, &date_boundary, dry_run))
-        .collect();
-    Ok(results)
-}
+    /// Gets the recipes to run, chained if they are run as a pipeline.
+    /// The disabled recipes are left out, a pipeline chaining the enabled ones.
+    ///
+    /// ### Returns
+    /// - `Vec<Recipe>`: The recipes to run, in order.
+    fn get_recipes(&self) -> Vec<Recipe> {
+        let recipes: Vec<Recipe> = self
+            .settings
+            .recipes
+            .iter()
+            .filter(|v| is_enabled(v))
+            .cloned()
+            .collect();
+        if self.is_pipeline() {
+            pipeline::chain_recipes(&recipes, self.get_stage_dir().as_deref())
+        } else {
+            recipes
+        }
+    }
 
-/// Runs a recipe in parallel.
-///
-/// ### Parameters
-/// - `recipe`: The recipe to run.
-/// - `date_boundary`: The date boundary.
-/// - `dry_run`: If true, the recipe will not be run.
-///
-/// ### Returns
-/// - `Result<Vec<Result<bool>>>`: The results of the recipe run.
-fn run_recipe_parallel(
-    recipe: &Recipe,
-    date_boundary: &DateTime<Utc>,
-    dry_run: bool,
-) -> Result<Vec<Result<bool>>> {
-    let entries: Vec<_> = fs::read_dir(&recipe.source_folder)?.collect::<Result<Vec<_>, _>>()?;
-    let results: Vec<_> = entries
-        .par_iter()
-        .map(|entry| run_for_file(entry, recipe, &date_boundary, dry_run))
-        .collect();
-    Ok(results)
-}
+    /// Prints the disabled recipes skipped by the run, among the selected ones.
+    fn print_disabled_recipes(&self) {
+        for recipe in self.settings.recipes.iter().filter(|v| {
+            !is_enabled(v)
+                && self
+                    .options
+                    .recipe_name
+                    .as_ref()
+                    .is_none_or(|name| *name == v.name)
+        }) {
+            info!(
+                "{} {} {}",
+                "⚠️".yellow(),
+                recipe.name.blue(),
+                "Recipe disabled, skipped".yellow()
+            );
+        }
+    }
 
-/// Runs a recipe for a file.
-/// The date boundary is passed as parameter in order to do not get recalculated for each call.
-///
-/// ### Parameters
-/// - `entry`: The entry to run the recipe for.
-/// - `recipe`: The recipe to run.
-/// - `date_boundary`: The date boundary.
-/// - `dry_run`: If true, the recipe will not be run.
-///
-/// ### Returns
-/// - `bool`: True if the file is matched by the recipe and has been processed, false otherwise.
-fn run_for_file(
-    entry: &DirEntry,
-    recipe: &Recipe,
-    date_boundary: &DateTime<Utc>,
-    dry_run: bool,
-) -> anyhow::Result<bool> {
-    let from_file = entry.path();
-    if from_file.is_file() {
-        if let Some(filename) = from_file.file_name() {
-            if filename.to_str().unwrap().starts_with(".") {
-                return Ok(false);
+    /// Checks that the recipes have no hooks when the recipe file was fetched from a URL, unless --allow_remote_hooks is set.
+    /// The hooks are shell commands, which a remote recipe file must not run unless the user trusts it.
+    ///
+    /// ### Parameters
+    /// - `recipes`: The recipes to run.
+    ///
+    /// ### Returns
+    /// - `Result<(), anyhow::Error>`: An error naming the first recipe with a hook, before any recipe runs.
+    fn check_remote_hooks(&self, recipes: &[Recipe]) -> anyhow::Result<()> {
+        if !remote::is_url(&self.settings.path) || self.options.allow_remote_hooks {
+            return Ok(());
+        }
+        match recipes
+            .iter()
+            .find(|v| v.pre_hook.is_some() || v.post_hook.is_some())
+        {
+            Some(recipe) => Err(anyhow::Error::msg(format!(
+                "{} - The hooks of a recipe file fetched from a URL are not run, use --allow_remote_hooks if the recipe file is trusted",
+                recipe.name
+            ))),
+            None => Ok(()),
+        }
+    }
+
+    /// Runs a recipe and prints its statistics.
+    ///
+    /// ### Parameters
+    /// - `recipes`: The recipes run, in order.
+    /// - `index`: The index of the recipe to run.
+    /// - `previous_stats`: The name and statistics of the recipes already run.
+    ///
+    /// ### Returns
+    /// - `Result<FileOrganizerStats, anyhow::Error>`: The statistics of the recipe run.
+    fn run_and_print_recipe(
+        &self,
+        recipes: &[Recipe],
+        index: usize,
+        previous_stats: &[(String, FileOrganizerStats)],
+    ) -> anyhow::Result<FileOrganizerStats> {
+        let recipe = &recipes[index];
+        let _recipe_span = info_span!("recipe", recipe = %recipe.name).entered();
+        if self.options.is_dry_run
+            && self.is_pipeline()
+            && index > 0
+            && !recipe.source_folder.is_dir()
+        {
+            info!(
+                "ℹ️ {} - Stage not previewed, its staging folder is only created by a real run: {}",
+                recipe.name.blue(),
+                recipe.source_folder.display()
+            );
+            return Ok(FileOrganizerStats::default());
+        }
+        let result = if self.options.is_dry_run {
+            self.run_recipe(recipe, previous_stats, None)
+        } else {
+            match hooks::run_pre_hook(recipe) {
+                Ok(()) => {
+                    let result = self.run_recipe(recipe, previous_stats, None);
+                    hooks::run_post_hook(recipe, result.as_ref().ok());
+                    result
+                }
+                // A failing pre hook fails its recipe only, the other recipes still running, except in a pipeline whose next stages need this one.
+                Err(e) if !self.is_pipeline() => {
+                    error!(
+                        "{} {} {} - {:#}",
+                        "❌".red(),
+                        recipe.name.blue(),
+                        "Recipe failed".red(),
+                        e
+                    );
+                    return Ok(FileOrganizerStats {
+                        recipes_failed: 1,
+                        errors: vec![format!("{:#}", e)],
+                        ..Default::default()
+                    });
+                }
+                Err(e) => Err(e),
             }
-            if !is_extension_allowed(&from_file, &recipe.allowed_extensions) {
-                return Ok(false);
+        };
+        let stats = match result {
+            Ok(stats) => stats,
+            Err(e) if self.is_pipeline() => {
+                return Err(anyhow::Error::msg(format!(
+                    "Pipeline stopped at stage {}/{} ({}): {}",
+                    index + 1,
+                    recipes.len(),
+                    recipe.name,
+                    e
+                )));
             }
-            let file_date = match recipe
-                .date_comparator
-                .as_ref()
-                .unwrap_or(&DateComparator::ModificationDate)
-            {
-                DateComparator::CreationDate => get_creation_date(&from_file).map_err(|e| {
-                    anyhow::Error::msg(format!(
-                        "{} - Error getting creation date: {}",
-                        recipe.name, e
-                    ))
-                })?,
-                DateComparator::ModificationDate => get_last_modification_date(&from_file)
-                    .map_err(|e| {
-                        anyhow::Error::msg(format!(
-                            "{} - Error getting last modification date: {}",
-                            recipe.name, e
-                        ))
-                    })?,
-            };
+            Err(e) => return Err(e),
+        };
+        info!(
+            "{} {} {} - {}",
+            "✅".green(),
+            recipe.name.blue(),
+            "Files matched".purple(),
+            stats.files_matched
+        );
+        info!(
+            "{} {} {} - {}",
+            "✅".green(),
+            recipe.name.blue(),
+            "Files processed".purple(),
+            stats.files_processed
+        );
+        if stats.files_duplicate_skipped > 0 {
+            info!(
+                "{} {} {} - {}",
+                "✅".green(),
+                recipe.name.blue(),
+                "Duplicates skipped".purple(),
+                stats.files_duplicate_skipped
+            );
+        }
+        if stats.files_locked_skipped > 0 {
+            warn!(
+                "{} {} {} - {}",
+                "⚠️".yellow(),
+                recipe.name.blue(),
+                "Locked files skipped".yellow(),
+                stats.files_locked_skipped
+            );
+        }
+        if stats.files_errored > 0 {
+            error!(
+                "{} {} {} - {}",
+                "❌".red(),
+                recipe.name.blue(),
+                "Files errored".red(),
+                stats.files_errored
+            );
+        }
+        info!(
+            "{} {} {} - {}",
+            "✅".green(),
+            recipe.name.blue(),
+            "Elapsed time".purple(),
+            seconds_to_string(stats.elapsed_time / 1000)
+        );
+        if self.options.is_dry_run {
+            info!(
+                "{} {} {} - {} clean, {} conflicting, {} skipped by the conflict strategy",
+                "✅".green(),
+                recipe.name.blue(),
+                "Destinations".purple(),
+                stats.files_matched - stats.files_conflicted,
+                stats.files_conflicted,
+                stats.files_conflict_skipped
+            );
+        }
+        if index < recipes.len() - 1 {
+            info!("{}", "----------------------------------------".blue());
+        }
+        Ok(stats)
+    }
 
-            if file_date.with_timezone(&date_boundary.timezone()) < *date_boundary {
-                return Ok(false);
+    /// Resolves the folders of the recipes under the root prefix, if any, the recipes failing to resolve being kept as they are.
+    ///
+    /// ### Parameters
+    /// - `recipes`: The recipes.
+    ///
+    /// ### Returns
+    /// - `Vec<Recipe>`: The recipes with their folders resolved.
+    fn resolve_recipes(&self, recipes: &[Recipe]) -> Vec<Recipe> {
+        recipes
+            .iter()
+            .map(|v| match &self.settings.root_prefix {
+                Some(root_prefix) => v.resolve_under(root_prefix).unwrap_or(v.clone()),
+                None => v.clone(),
+            })
+            .collect()
+    }
+
+    /// Checks if the source folder of a recipe is read chunk by chunk rather than at once, for the chunked runs of the recipes organizing the files in any order.
+    /// The recipes keeping the most recent files, numbering the files or processing them in order need the whole source folder first.
+    ///
+    /// ### Parameters
+    /// - `recipe`: The recipe.
+    /// - `watched_files`: The only files of the source folder to process, when watching it.
+    ///
+    /// ### Returns
+    /// - `bool`: True if the source folder is streamed, false if it is collected at once.
+    fn is_streamed(&self, recipe: &Recipe, watched_files: Option<&HashSet<PathBuf>>) -> bool {
+        self.options.chunk_size.is_some()
+            && watched_files.is_none()
+            && !is_dedupe(recipe)
+            && !is_compact(recipe)
+            && !self.is_iterative()
+            && !uses_counter(recipe)
+            && recipe.keep_recent.is_none()
+            && !recipe.in_place.unwrap_or(false)
+    }
+
+    /// Checks if the files of each recipe are processed iteratively, either as requested or as the recipes themselves run concurrently.
+    ///
+    /// ### Returns
+    /// - `bool`: True if the files are processed iteratively, false if in parallel.
+    fn is_iterative(&self) -> bool {
+        self.options.is_iterative || self.options.parallel_recipes
+    }
+
+    /// Updates last_run (and last_seen if tracked) for all the recipes run and saves the recipe file, if not in dry run mode, nor processing a shard only.
+    /// The recipes with failed files keep their dates, so that their next run retries the failed files.
+    ///
+    /// ### Parameters
+    /// - `recipes_stats`: The name and statistics of each recipe run.
+    ///
+    /// ### Returns
+    /// - `Result<(), anyhow::Error>`: The result of the save.
+    fn save_last_run(
+        &mut self,
+        recipes_stats: &[(String, FileOrganizerStats)],
+    ) -> anyhow::Result<()> {
+        if self.options.is_dry_run || self.options.shard.is_some() {
+            return Ok(());
+        }
+        let last_run = Utc::now();
+        let last_run = Some(last_run.format("%Y-%m-%d").to_string());
+        let last_seen = self.last_seen.lock().unwrap();
+        for recipe in &mut self.settings.recipes {
+            let Some((_, stats)) = recipes_stats.iter().find(|v| v.0 == recipe.name) else {
+                continue;
+            };
+            if stats.recipes_failed > 0 {
+                warn!(
+                    "{} {} {} - the recipe failed, to be retried by the next run",
+                    "⚠️".yellow(),
+                    recipe.name.blue(),
+                    "Last run not updated".yellow()
+                );
+                continue;
             }
-            let dest_folder = build_dest_folder(recipe, &file_date);
+            if stats.files_errored > 0 {
+                warn!(
+                    "{} {} {} - {} file(s) failed, to be retried by the next run",
+                    "⚠️".yellow(),
+                    recipe.name.blue(),
+                    "Last run not updated".yellow(),
+                    stats.files_errored
+                );
+                continue;
+            }
+            recipe.last_run = last_run.clone();
+            if let Some(last_seen) = last_seen.get(&recipe.name) {
+                recipe.last_seen = Some(last_seen.to_rfc3339_opts(SecondsFormat::Nanos, true));
+            }
+        }
+        drop(last_seen);
+        self.settings.save()
+    }
 
-            if !dry_run {
-                if !dest_folder.exists() {
-                    fs::create_dir_all(&dest_folder)?;
+    /// Watches the source folders of the recipes after the run, organizing the files created or moved into them.
+    /// A file is organized once unchanged for a while, and requeued while too recent for min_age_seconds.
+    /// The watched files are not filtered by last_run and last_seen, which are updated after each batch.
+    /// Dedupe and Compact recipes are not watched.
+    ///
+    /// ### Returns
+    /// - `Result<(), anyhow::Error>`: An error if the source folders cannot be watched, the watch never ending otherwise.
+    pub fn watch(&mut self) -> anyhow::Result<()> {
+        if self.is_pipeline() {
+            return Err(anyhow::Error::msg(
+                "The recipes of a pipeline cannot be watched",
+            ));
+        }
+        let recipes: Vec<Recipe> = self
+            .select_recipe(self.get_recipes())?
+            .into_iter()
+            .filter(|v| !is_dedupe(v) && !is_compact(v))
+            .collect();
+        let (sender, receiver) = mpsc::channel();
+        let mut watcher = notify::recommended_watcher(sender)?;
+        let mut recipes_source_folders = Vec::new();
+        for recipe in &recipes {
+            let prepared_recipe = self.prepare_recipe(recipe)?;
+            let source_folders: Vec<PathBuf> = prepared_recipe
+                .get_source_folders()
+                .into_iter()
+                .cloned()
+                .collect();
+            for source_folder in &source_folders {
+                watcher.watch(source_folder, watch::get_recursive_mode(recipe))?;
+                info!(
+                    "ℹ️ {} {} - {}",
+                    recipe.name.blue(),
+                    "Watching".purple(),
+                    source_folder.display()
+                );
+            }
+            recipes_source_folders.push(source_folders);
+        }
+        self.options.is_forced = true;
+        let mut debouncer = Debouncer::default();
+        loop {
+            match receiver.recv_timeout(watch::WATCH_POLL_INTERVAL) {
+                Ok(Ok(event)) => debouncer.add_event(&event),
+                Ok(Err(e)) => warn!(
+                    "{} {} {} - {}",
+                    "⚠️".yellow(),
+                    "file_organizer".blue(),
+                    "Watch error".yellow(),
+                    e
+                ),
+                Err(RecvTimeoutError::Timeout) => {}
+                Err(RecvTimeoutError::Disconnected) => {
+                    return Err(anyhow::Error::msg(
+                        "The watch of the source folders stopped",
+                    ));
                 }
             }
-            let dest_file = dest_folder.join(filename.to_str().unwrap());
-            if recipe.move_files {
-                if !dry_run {
-                    if let Err(e) = fs::rename(&from_file, &dest_file) {
-                        return Err(anyhow::Error::msg(format!(
-                            "{} - Error moving file: {}",
-                            recipe.name, e
-                        )));
-                    }
+            let files: HashSet<PathBuf> = debouncer.take_ready().into_iter().collect();
+            if files.is_empty() {
+                continue;
+            }
+            let mut watched_recipes = Vec::new();
+            for (recipe, source_folders) in recipes.iter().zip(&recipes_source_folders) {
+                if !files
+                    .iter()
+                    .any(|v| source_folders.iter().any(|w| v.starts_with(w)))
+                {
+                    continue;
                 }
-                println!(
-                    "{} {} {} - {}",
-                    "✅".green(),
-                    recipe.name.blue(),
-                    "File moved".green(),
-                    dest_file.to_str().unwrap()
-                );
-            } else {
-                if !dry_run {
-                    if let Err(e) = fs::copy(&from_file, &dest_file) {
-                        return Err(anyhow::Error::msg(format!(
-                            "{} - Error copying file: {}",
-                            recipe.name, e
-                        )));
+                let _recipe_span = info_span!("recipe", recipe = %recipe.name).entered();
+                match self.run_recipe(recipe, &[], Some(&files)) {
+                    Ok(stats) => watched_recipes.push((recipe.name.clone(), stats)),
+                    Err(e) => error!("{} {}", "❌Error:".red().bold(), e),
+                }
+                if let Some(min_age_seconds) = recipe.min_age_seconds {
+                    for file in files
+                        .iter()
+                        .filter(|v| source_folders.iter().any(|w| v.starts_with(w)))
+                    {
+                        debouncer.requeue_recent(file, min_age_seconds);
                     }
                 }
-                println!(
-                    "{} {} {} - {}",
-                    "✅".green(),
-                    recipe.name.blue(),
-                    "File copied".green(),
-                    dest_file.to_str().unwrap()
-                );
             }
-            return Ok(true);
+            if let Err(e) = self
+                .save_last_run(&watched_recipes)
+                .and_then(|_| self.save_patch())
+            {
+                error!("{} {}", "❌Error:".red().bold(), e);
+            }
         }
-        return Ok(false);
     }
-    Ok(false)
-}
-
-/// Prints the recipe info.
-///
-/// ### Parameters
-/// - `recipe`: The recipe to print the info for.
-fn print_recipe_info(recipe: &Recipe) {
-    println!(
-        "{} {} {} - {}",
-        "ℹ️".green(),
-        recipe.name.blue(),
-        "Source folder".purple(),
-        recipe.source_folder.display()
-    );
-    println!(
-        "{} {} {} - {}",
-        "ℹ️".green(),
-        recipe.name.blue(),
-        "Target folder".purple(),
-        recipe.destination_folder.display()
-    );
-    println!(
-        "{} {} {} - {}",
-        "ℹ️".green(),
-        recipe.name.blue(),
-        "Last run".purple(),
-        recipe.last_run.as_ref().unwrap_or(&"Never".to_string())
-    );
-    println!(
-        "{} {} {} - {}",
-        "ℹ️".green(),
-        recipe.name.blue(),
-        "Mode".purple(),
-        if recipe.move_files { "Move" } else { "Copy" }
-    );
-    println!(
-        "{} {} {} - {}",
-        "ℹ️".green(),
-        recipe.name.blue(),
-        "Allowed extensions".purple(),
-        recipe
-            .allowed_extensions
-            .as_ref()
-            .map(|v| v.join(", "))
-            .as_ref()
-            .unwrap_or(&"All".to_string())
-    );
-    println!(
-        "{} {} {} - {}",
-        "ℹ️".green(),
-        recipe.name.blue(),
-        "Subfolders".purple(),
-        recipe
-            .subfolders
-            .as_ref()
-            .map(|v| v.join(", "))
-            .as_ref()
-            .unwrap_or(&"None".to_string())
-    );
-    println!(
-        "{} {} {} - {:?}",
-        "ℹ️".green(),
-        recipe.name.blue(),
-        "Date comparator".purple(),
-        recipe
-            .date_comparator
-            .as_ref()
-            .unwrap_or(&DateComparator::ModificationDate)
-    );
-}
 
-/// Gets the date boundary for a recipe.
-///
-/// ### Parameters
-/// - `recipe`: The recipe to get the date boundary for.
-///
-/// ### Returns
-/// - `Result<DateTime<Utc>, anyhow::Error>`: The date boundary.
-fn get_date_boundary(recipe: &Recipe) -> anyhow::Result<DateTime<Utc>> {
-    let date_boundary = recipe
-        .last_run
-        .clone()
-        .unwrap_or_else(|| "1970-01-01".to_string());
-    let date_boundary = DateTime::parse_from_str(
-        &format!("{} 00:00:00 +0000", date_boundary),
-        "%Y-%m-%d %H:%M:%S %z",
-    )?
-    .to_utc();
-    Ok(date_boundary)
-}
+    /// Resets the state of the recipes, or of the recipe selected by name only, so that their next run processes their whole source folder.
+    /// The last_run and last_seen dates are cleared and the recipe file is saved.
+    ///
+    /// ### Returns
+    /// - `Result<Vec<String>, anyhow::Error>`: The names of the recipes reset.
+    pub fn reset(&mut self) -> anyhow::Result<Vec<String>> {
+        let names: Vec<String> = self
+            .select_recipe(self.settings.recipes.clone())?
+            .into_iter()
+            .map(|v| v.name)
+            .collect();
+        for recipe in &mut self.settings.recipes {
+            if names.contains(&recipe.name) {
+                recipe.last_run = None;
+                recipe.last_seen = None;
+            }
+        }
+        if remote::is_url(&self.settings.path) && self.settings.state_file.is_none() {
+            return Err(anyhow::Error::msg(
+                "The recipe file has been fetched from a URL, it cannot be reset without a state_file",
+            ));
+        }
+        self.settings.save()?;
+        Ok(names)
+    }
 
-/// Gets the last modification date of a file.
-///
-/// ### Parameters
-/// - `file`: The file to get the last modification date of.
-///
-/// ### Returns
-/// - `Result<DateTime<Utc>, anyhow::Error>`: The last modification date of the file.
-fn get_last_modification_date(file: &Path) -> anyhow::Result<DateTime<Utc>> {
-    let metadata = fs::metadata(file)?;
-    let last_modification_date = metadata.modified()?;
-    let last_modification_date = DateTime::<Utc>::from(last_modification_date);
-    Ok(last_modification_date)
-}
+    /// Validates the recipes without running them, reporting all their problems instead of stopping at the first one.
+    ///
+    /// ### Returns
+    /// - `Vec<anyhow::Error>`: The problems of the recipes, empty if they are all valid.
+    pub fn validate(&self) -> Vec<anyhow::Error> {
+        self.settings
+            .recipes
+            .iter()
+            .flat_map(|v| self.validate_recipe(v))
+            .collect()
+    }
 
-/// Gets the creation date of a file.
-///
-/// ### Parameters
-/// - `file`: The file to get the creation date of.
-///
-/// ### Returns
-/// - `Result<DateTime<Utc>, anyhow::Error>`: The creation date of the file.
-fn get_creation_date(file: &Path) -> anyhow::Result<DateTime<Utc>> {
-    let metadata = fs::metadata(file)?;
-    let creation_date = metadata.created()?;
-    let creation_date = DateTime::<Utc>::from(creation_date);
-    Ok(creation_date)
-}
+    /// Validates a recipe without running it.
+    /// Its folders and subfolder formats are checked first, then the rest of its settings once they are valid.
+    ///
+    /// ### Parameters
+    /// - `recipe`: The recipe to validate.
+    ///
+    /// ### Returns
+    /// - `Vec<anyhow::Error>`: The problems of the recipe, empty if it is valid.
+    fn validate_recipe(&self, recipe: &Recipe) -> Vec<anyhow::Error> {
+        let recipe = match &self.settings.root_prefix {
+            Some(root_prefix) => match recipe.resolve_under(root_prefix) {
+                Ok(recipe) => recipe,
+                Err(e) => return vec![e],
+            },
+            None => recipe.clone(),
+        };
+        let mut problems = Vec::new();
+        for source_folder in recipe.get_source_folders() {
+            if !source_folder.is_dir() {
+                problems.push(anyhow::Error::msg(format!(
+                    "{} - Source folder not a directory: {}",
+                    recipe.name,
+                    source_folder.display()
+                )));
+            }
+        }
+        if !recipe.destination_folder.is_dir() {
+            problems.push(anyhow::Error::msg(format!(
+                "{} - Target folder not a directory: {}",
+                recipe.name,
+                recipe.destination_folder.display()
+            )));
+        }
+        if let Err(e) = validate_subfolders(&recipe) {
+            problems.push(e);
+        }
+        if !problems.is_empty() {
+            return problems;
+        }
+        let checks = [
+            self.prepare_recipe(&recipe).map(|_| ()),
+            load_tag_rules(&recipe).map(|_| ()),
+            compile_name_patterns(&recipe).map(|_| ()),
+            build_thread_pool(&recipe, None).map(|_| ()),
+        ];
+        checks.into_iter().filter_map(|v| v.err()).collect()
+    }
 
-/// Builds the destination folder.
-///
-/// ### Parameters
-/// - `recipe`: The recipe to build the destination folder for.
-/// - `last_modification_date`: The last modification date of the file.
-///
-/// ### Returns
-/// - `PathBuf`: The destination folder.
-/// - `Result<PathBuf, anyhow::Error>`: The destination folder.
-fn build_dest_folder(recipe: &Recipe, last_modification_date: &DateTime<Utc>) -> PathBuf {
-    let mut dest_folder = recipe.destination_folder.clone();
-    if let Some(subfolders) = &recipe.subfolders {
-        for subfolder in subfolders {
-            let subfolder_name =
-                date_to_folder_name(&last_modification_date, &Some(subfolder.clone()));
-            dest_folder = dest_folder.join(subfolder_name);
+    /// Selects the recipe to run when a recipe name is given.
+    ///
+    /// ### Parameters
+    /// - `recipes`: The recipes of the recipe file.
+    ///
+    /// ### Returns
+    /// - `Result<Vec<Recipe>, anyhow::Error>`: The recipe with the given name, none if it is disabled, all the recipes if no name is given.
+    fn select_recipe(&self, recipes: Vec<Recipe>) -> anyhow::Result<Vec<Recipe>> {
+        let Some(recipe_name) = &self.options.recipe_name else {
+            return Ok(recipes);
+        };
+        let names: Vec<&str> = self
+            .settings
+            .recipes
+            .iter()
+            .map(|v| v.name.as_str())
+            .collect();
+        if !names.contains(&recipe_name.as_str()) {
+            return Err(anyhow::Error::msg(format!(
+                "No recipe named {}, available recipes: {}",
+                recipe_name,
+                names.join(", ")
+            )));
         }
+        Ok(recipes
+            .into_iter()
+            .filter(|v| v.name == *recipe_name)
+            .collect())
     }
-    dest_folder
-}
 
-/// Checks if the extension of a file is allowed.
-///
-/// ### Parameters
-/// - `file`: The file to check.
-/// - `allowed_extensions`: The allowed extensions.
-///
-/// ### Returns
-/// - `bool`: True if the extension is allowed, false otherwise.
-fn is_extension_allowed(file: &Path, allowed_extensions: &Option<Vec<String>>) -> bool {
-    if let Some(allowed_extensions) = allowed_extensions {
-        if allowed_extensions.is_empty() {
-            return true;
+    /// Orders the recipes according to the overlap policy, when the destination of a recipe overlaps the source of another one.
+    /// The recipes of a pipeline are chained on purpose and kept in order.
+    ///
+    /// ### Parameters
+    /// - `recipes`: The recipes to run, in the order of the recipe file.
+    ///
+    /// ### Returns
+    /// - `Result<Vec<Recipe>, anyhow::Error>`: The recipes to run, in order.
+    fn order_recipes(&self, recipes: Vec<Recipe>) -> anyhow::Result<Vec<Recipe>> {
+        if self.is_pipeline() {
+            return Ok(recipes);
         }
-        if let Some(ext) = file.extension() {
-            if let Some(ext_str) = ext.to_str() {
-                return allowed_extensions.contains(&ext_str.to_string());
-            } else {
-                return false;
+        let resolved_recipes = self.resolve_recipes(&recipes);
+        let overlaps = overlap::find_overlaps(&resolved_recipes);
+        if overlaps.is_empty() {
+            return Ok(recipes);
+        }
+        let to_names = |indexes: &mut dyn Iterator<Item = usize>| {
+            indexes
+                .map(|v| recipes[v].name.as_str())
+                .collect::<Vec<_>>()
+                .join(", ")
+        };
+        let order = overlap::sort_recipes(recipes.len(), &overlaps);
+        match self.options.on_overlap {
+            OverlapPolicy::Warn => {
+                for overlap in &overlaps {
+                    warn!(
+                        "{} {} - Destination folder overlaps the source folder of {}, whose run may organize its files again",
+                        "⚠️".yellow(),
+                        recipes[overlap.from].name.blue(),
+                        recipes[overlap.to].name.blue()
+                    );
+                }
+                if let Err(cycle) = order {
+                    warn!(
+                        "{} {} - Circular recipe destinations - {}",
+                        "⚠️".yellow(),
+                        "file_organizer".blue(),
+                        to_names(&mut cycle.into_iter())
+                    );
+                }
+                Ok(recipes)
             }
-        } else {
-            return false;
+            OverlapPolicy::Reorder => match order {
+                Ok(order) => {
+                    info!(
+                        "ℹ️ {} {} - {}",
+                        "file_organizer".blue(),
+                        "Recipes ordered".purple(),
+                        to_names(&mut order.iter().copied())
+                    );
+                    Ok(order.into_iter().map(|v| recipes[v].clone()).collect())
+                }
+                Err(cycle) => Err(anyhow::Error::msg(format!(
+                    "Circular recipe destinations, the recipes cannot be ordered: {}",
+                    to_names(&mut cycle.into_iter())
+                ))),
+            },
+            OverlapPolicy::Error => Err(anyhow::Error::msg(format!(
+                "Recipe destinations overlap recipe sources, aborting: {}",
+                overlaps
+                    .iter()
+                    .map(|v| format!("{} -> {}", recipes[v.from].name, recipes[v.to].name))
+                    .collect::<Vec<_>>()
+                    .join(", ")
+            ))),
         }
     }
-    return false;
-}
 
-/// Converts a date to a folder name.
-///
-/// ### Parameters
-/// - `date`: The date to convert.
-/// - `format`: The format to use.
-///
-/// ### Returns
-/// - `String`: The folder name.
-fn date_to_folder_name(date: &DateTime<Utc>, format: &Option<String>) -> String {
-    if let Some(format) = format {
-        return date.format(format.as_str()).to_string();
-    } else {
-        return "".to_string();
+    /// Gets the destination folder of a recipe as written in the recipe file, joined to the root prefix if any but with its symlinks left unresolved.
+    ///
+    /// ### Parameters
+    /// - `recipe`: The recipe, not resolved under the root prefix.
+    ///
+    /// ### Returns
+    /// - `PathBuf`: The unresolved destination folder, whose symbolic link status can be checked.
+    fn get_unresolved_destination(&self, recipe: &Recipe) -> PathBuf {
+        match &self.settings.root_prefix {
+            Some(root_prefix) => root_prefix.join(&recipe.destination_folder),
+            None => recipe.destination_folder.clone(),
+        }
     }
-}
 
-/// Converts seconds to a string.
+    /// Prepares a recipe to be run, resolving its folders under the root prefix and validating them.
+    ///
+    /// ### Parameters
+    /// - `recipe`: The recipe to prepare.
+    ///
+    /// ### Returns
+    /// - `Result<Recipe, anyhow::Error>`: The prepared recipe.
+    fn prepare_recipe(&self, recipe: &Recipe) -> anyhow::Result<Recipe> {
+        let unresolved_destination = self.get_unresolved_destination(recipe);
+        let is_previewed_stage = self.options.is_dry_run
+            && self
+                .get_stage_dir()
+                .is_some_and(|v| pipeline::is_stage_folder(recipe, &v));
+        let mut recipe = if let Some(root_prefix) = &self.settings.root_prefix {
+            recipe.resolve_under(root_prefix)?
+        } else {
+            recipe.clone()
+        };
+        if let Some(source_folder) = recipe
+            .get_source_folders()
+            .into_iter()
+            .find(|v| !v.is_dir())
+        {
+            return Err(anyhow::Error::msg(format!(
+                "{} - Source folder not a directory: {}",
+                recipe.name,
+                source_folder.display()
+            )));
+        }
+        if recipe.in_place.unwrap_or(false) && recipe.source_folders.is_some() {
+            return Err(anyhow::Error::msg(format!(
+                "{} - An in place recipe cannot have source_folders",
+                recipe.name
+            )));
+        }
+        dedupe_source_folders(&mut recipe)?;
+        let is_missing_stage = is_previewed_stage && !recipe.destination_folder.exists();
+        if !recipe.destination_folder.is_dir() && !is_missing_stage {
+            return Err(anyhow::Error::msg(format!(
+                "{} - Target folder not a directory: {}",
+                recipe.name,
+                recipe.destination_folder.display()
+            )));
+        }
+        if !recipe.allow_symlink_dest.unwrap_or(true) && unresolved_destination.is_symlink() {
+            return Err(anyhow::Error::msg(format!(
+                "{} - Target folder is a symlink and allow_symlink_dest is false: {}",
+                recipe.name,
+                unresolved_destination.display()
+            )));
+        }
+        if let (Some(min_size_bytes), Some(max_size_bytes)) =
+            (recipe.min_size_bytes, recipe.max_size_bytes)
+            && min_size_bytes > max_size_bytes
+        {
+            return Err(anyhow::Error::msg(format!(
+                "{} - min_size_bytes {} is greater than max_size_bytes {}",
+                recipe.name, min_size_bytes, max_size_bytes
+            )));
+        }
+        let (small_size_bytes, medium_size_bytes) = get_size_bucket_thresholds(&recipe);
+        if small_size_bytes > medium_size_bytes {
+            return Err(anyhow::Error::msg(format!(
+                "{} - small_size_bytes {} is greater than medium_size_bytes {}",
+                recipe.name, small_size_bytes, medium_size_bytes
+            )));
+        }
+        if let Some(year) = recipe
+            .year_roots
+            .iter()
+            .flat_map(|v| v.keys())
+            .find(|v| v.parse::<i32>().is_err())
+        {
+            return Err(anyhow::Error::msg(format!(
+                "{} - Invalid year in year_roots: {}",
+                recipe.name, year
+            )));
+        }
+        if let Some(DateStrategy::ClosestTo(reference_date)) = &recipe.date_strategy
+            && let Err(e) = parse_day(reference_date)
+        {
+            return Err(anyhow::Error::msg(format!(
+                "{} - Invalid ClosestTo date {}, expected YYYY-MM-DD: {}",
+                recipe.name, reference_date, e
+            )));
+        }
+        if let (Some(date_from), Some(date_to)) = get_date_range(&recipe)?
+            && date_from >= date_to
+        {
+            return Err(anyhow::Error::msg(format!(
+                "{} - date_from must not be after date_to",
+                recipe.name
+            )));
+        }
+        if recipe.max_dest_depth == Some(0) {
+            return Err(anyhow::Error::msg(format!(
+                "{} - max_dest_depth must be at least 1",
+                recipe.name
+            )));
+        }
+        validate_in_place(&recipe)?;
+        if recipe.infer_extensions_from_dest.unwrap_or(false) {
+            let extensions = collect_extensions(&recipe.destination_folder)?;
+            recipe.allowed_extensions = if extensions.is_empty() {
+                None
+            } else {
+                Some(extensions)
+            };
+        }
+        Ok(recipe)
+    }
+
+    /// Builds the context of a recipe run.
+    ///
+    /// ### Parameters
+    /// - `recipe`: The recipe to run.
+    /// - `entries`: The entries of the source folder.
+    /// - `dry_run`: If true, no files will be moved or copied.
+    /// - `quiet`: If true, the processed files are not printed.
+    ///
+    /// ### Returns
+    /// - `Result<RecipeContext, anyhow::Error>`: The context of the recipe run.
+    fn build_context(
+        &self,
+        recipe: &Recipe,
+        entries: &[DirEntry],
+        dry_run: bool,
+        quiet: bool,
+    ) -> anyhow::Result<RecipeContext> {
+        Ok(RecipeContext {
+            date_boundary: if self.options.is_forced {
+                None
+            } else {
+                get_date_boundary(recipe)?
+            },
+            last_seen: if self.options.is_forced {
+                None
+            } else {
+                get_last_seen(recipe)?
+            },
+            date_range: get_date_range(recipe)?,
+            reference_date: get_reference_date(recipe)?,
+            weekdays: parse_weekday_filter(recipe)?,
+            needs_file_date: needs_file_date(recipe),
+            pinned_files: get_pinned_files(recipe, entries)?,
+            recent_files: get_recent_files(recipe, entries)?,
+            effective_uid: get_effective_uid(),
+            max_modification_time: recipe.min_age_seconds.map(|v| {
+                SystemTime::now()
+                    .checked_sub(Duration::from_secs(v))
+                    .unwrap_or(SystemTime::UNIX_EPOCH)
+            }),
+            magic_routes: parse_magic_routes(recipe)?,
+            tag_rules: load_tag_rules(recipe)?,
+            name_patterns: compile_name_patterns(recipe)?,
+            mime_types: parse_mime_types(recipe)?,
+            shard: self.options.shard.clone(),
+            planned_files: Mutex::new(HashSet::new()),
+            next_counters: Mutex::new(HashMap::new()),
+            destination_names: if recipe.skip_if_name_exists_anywhere.unwrap_or(false) {
+                Some(collect_file_names(&recipe.destination_folder)?)
+            } else {
+                None
+            },
+            destination_index: if recipe.skip_duplicates.unwrap_or(false) {
+                Some(DestinationIndex::default())
+            } else {
+                None
+            },
+            needs_music_tags: music::uses_music_tokens(recipe),
+            needs_content_hash: needs_content_hash(recipe),
+            planned_operations: if dry_run || self.options.plan_file.is_some() {
+                Some(Mutex::new(Vec::new()))
+            } else {
+                None
+            },
+            skipped_operations: if self.options.plan_report {
+                Some(Mutex::new(Vec::new()))
+            } else {
+                None
+            },
+            performed_operations: if self.options.json_stats && !quiet {
+                Some(Mutex::new(Vec::new()))
+            } else {
+                None
+            },
+            manifest_files: if recipe.write_manifest.unwrap_or(false) && !dry_run {
+                Some(Mutex::new(Vec::new()))
+            } else {
+                None
+            },
+            folders_counts: if self.options.show_tree && !quiet {
+                Some(Mutex::new(HashMap::new()))
+            } else {
+                None
+            },
+            patch: if dry_run { None } else { self.patch.clone() },
+            journal: if dry_run { None } else { self.journal.clone() },
+            unknown_extensions: if recipe.report_unknown_extensions.unwrap_or(false) && !quiet {
+                Some(Mutex::new(HashMap::new()))
+            } else {
+                None
+            },
+            thread_pool: if self.is_iterative() {
+                None
+            } else {
+                build_thread_pool(recipe, self.options.threads)?
+            },
+            span: Span::current(),
+            dry_run,
+            quiet,
+        })
+    }
+
+    /// Runs a recipe.
+    /// With a chunk size, the files are processed in chunks, the partial statistics and the patch file being flushed and the progress reported after each chunk, so that an interrupted run still leaves meaningful partial statistics.
+    ///
+    /// ### Parameters
+    /// - `recipe`: The recipe to run.
+    /// - `previous_stats`: The name and statistics of the recipes already run, flushed along with the partial statistics of the recipe.
+    /// - `watched_files`: The only files of the source folder to process, when watching it, None to process all of them.
+    ///
+    /// ### Returns
+    /// - `Result<FileOrganizerStats, anyhow::Error>`: The statistics of the recipe run.
+    fn run_recipe(
+        &self,
+        recipe: &Recipe,
+        previous_stats: &[(String, FileOrganizerStats)],
+        watched_files: Option<&HashSet<PathBuf>>,
+    ) -> anyhow::Result<FileOrganizerStats> {
+        if self.options.check_space && !self.options.is_dry_run && watched_files.is_none() {
+            let operations = take_planned_operations(self.plan_recipe(recipe)?);
+            space::check_space(&recipe.name, &operations)?;
+        }
+        let unresolved_destination = self.get_unresolved_destination(recipe);
+        let recipe = &self.prepare_recipe(recipe)?;
+        if watched_files.is_none() {
+            print_recipe_info(recipe, &unresolved_destination);
+        }
+
+        let start_time = Utc::now().timestamp_millis();
+        let scan_date = Utc::now();
+        let is_streamed = self.is_streamed(recipe, watched_files);
+        let mut entries = if is_streamed {
+            Vec::new()
+        } else {
+            collect_entries(recipe)?
+        };
+        // The context is built from the whole source folder, the pins and keep_recent applying to the watched files as to a full run.
+        // A streamed source folder has its pins read chunk by chunk instead.
+        let mut context = self.build_context(recipe, &entries, self.options.is_dry_run, false)?;
+        if let Some(watched_files) = watched_files {
+            entries.retain(|entry| watched_files.contains(&entry.path()));
+        }
+        let mut source_folders_times = HashMap::new();
+        let mut entries_folders = HashSet::new();
+        if !self.options.is_dry_run {
+            add_source_folders_times(recipe, &entries, &mut source_folders_times);
+            entries_folders.extend(get_entries_folders(recipe, &entries));
+        }
+        let mut stats = FileOrganizerStats::default();
+        if is_dedupe(recipe) {
+            stats.add_results(dedupe::run_dedupe(
+                entries,
+                recipe,
+                &context,
+                self.is_iterative(),
+            ));
+        } else if is_compact(recipe) {
+            stats.add_results(compact::run_compact(entries, recipe, &context));
+        } else {
+            let chunk_size = self.options.chunk_size.unwrap_or(entries.len()).max(1);
+            let chunks_count = entries.len().div_ceil(chunk_size);
+            if self.is_iterative() || uses_counter(recipe) {
+                entries.sort_by_key(|entry| entry.path());
+            }
+            let mut entries: Box<dyn Iterator<Item = io::Result<DirEntry>>> = if is_streamed {
+                Box::new(EntriesWalker::new(recipe))
+            } else {
+                Box::new(entries.into_iter().map(Ok))
+            };
+            let mut pin_lists = HashMap::new();
+            for chunk_index in 0.. {
+                let chunk = entries
+                    .by_ref()
+                    .take(chunk_size)
+                    .collect::<io::Result<Vec<_>>>()?;
+                if chunk.is_empty() {
+                    break;
+                }
+                if is_streamed {
+                    context.pinned_files = get_chunk_pinned_files(recipe, &chunk, &mut pin_lists)?;
+                    if !self.options.is_dry_run {
+                        add_source_folders_times(recipe, &chunk, &mut source_folders_times);
+                        entries_folders.extend(get_entries_folders(recipe, &chunk));
+                    }
+                }
+                stats.add_results(if self.is_iterative() {
+                    run_recipe_iterative(chunk, recipe, &context)
+                } else {
+                    run_recipe_parallel(chunk, recipe, &context)
+                });
+                if let Some(manifest_files) = &context.manifest_files {
+                    let mut manifest_files = std::mem::take(&mut *manifest_files.lock().unwrap());
+                    manifest_files.sort();
+                    append_to_manifest(recipe, &manifest_files)?;
+                }
+                if self.options.chunk_size.is_some() {
+                    stats.elapsed_time = Utc::now().timestamp_millis() - start_time;
+                    let mut partial_stats = previous_stats.to_vec();
+                    partial_stats.push((recipe.name.clone(), stats.clone()));
+                    self.write_partial_stats(&partial_stats)?;
+                    if let Some(patch) = &context.patch {
+                        patch.save()?;
+                    }
+                    info!(
+                        "{} {} {} - {} ({} files processed, {} matched)",
+                        "ℹ️".green(),
+                        recipe.name.blue(),
+                        "Chunk".purple(),
+                        if is_streamed {
+                            (chunk_index + 1).to_string()
+                        } else {
+                            format!("{}/{}", chunk_index + 1, chunks_count)
+                        },
+                        stats.files_processed,
+                        stats.files_matched
+                    );
+                }
+            }
+        }
+        remove_empty_source_folders(recipe, &entries_folders);
+        restore_folders_times(recipe, &source_folders_times);
+        for error in &stats.errors {
+            error!(
+                "{} {} {} - {}",
+                "❌".red(),
+                recipe.name.blue(),
+                "File failed".red(),
+                error
+            );
+        }
+        if let Some(folders_counts) = &context.folders_counts {
+            info!(
+                "{}",
+                tree::render_tree(&recipe.destination_folder, &folders_counts.lock().unwrap())
+                    .trim_end()
+            );
+        }
+        if let Some(unknown_extensions) = &context.unknown_extensions {
+            print_unknown_extensions(recipe, &unknown_extensions.lock().unwrap());
+        }
+        if recipe.track_last_seen.unwrap_or(false) && !self.options.is_dry_run {
+            self.last_seen
+                .lock()
+                .unwrap()
+                .insert(recipe.name.clone(), get_next_last_seen(recipe, scan_date));
+        }
+        if let Some(performed_operations) = &context.performed_operations {
+            let mut performed_operations =
+                std::mem::take(&mut *performed_operations.lock().unwrap());
+            performed_operations.sort_by(|a, b| a.source.cmp(&b.source));
+            stats.operations = performed_operations;
+        }
+        self.plan
+            .lock()
+            .unwrap()
+            .extend(take_planned_operations(context));
+        stats.elapsed_time = Utc::now().timestamp_millis() - start_time;
+        Ok(stats)
+    }
+}
+
+/// Prints the statistics and the operations of the recipes run as a JSON array, on the standard output.
 ///
 /// ### Parameters
-/// - `seconds`: The seconds to convert.
+/// - `recipes_stats`: The name and statistics of the recipes run.
 ///
 /// ### Returns
-/// - `String`: The string.
-fn seconds_to_string(seconds: i64) -> String {
-    if seconds < 60 {
-        return format!("{}s", seconds);
+/// - `Result<(), anyhow::Error>`: The result of the serialization.
+fn print_json_stats(recipes_stats: &[(String, FileOrganizerStats)]) -> anyhow::Result<()> {
+    println!("{}", format_json_stats(recipes_stats)?);
+    Ok(())
+}
+
+/// Formats the statistics and the operations of the recipes run as a pretty printed JSON array.
+///
+/// ### Parameters
+/// - `recipes_stats`: The name and statistics of the recipes run.
+///
+/// ### Returns
+/// - `Result<String, anyhow::Error>`: The JSON array, an object for each recipe.
+fn format_json_stats(recipes_stats: &[(String, FileOrganizerStats)]) -> anyhow::Result<String> {
+    let recipes_stats: Vec<RecipeStats> = recipes_stats
+        .iter()
+        .map(|(name, stats)| RecipeStats { name, stats })
+        .collect();
+    Ok(serde_json::to_string_pretty(&recipes_stats)?)
+}
+
+/// Takes the operations planned during a recipe run, sorted by source.
+///
+/// ### Parameters
+/// - `context`: The context of the recipe run.
+///
+/// ### Returns
+/// - `Vec<PlanOperation>`: The planned operations, empty if they were not recorded.
+fn take_planned_operations(context: RecipeContext) -> Vec<PlanOperation> {
+    let Some(planned_operations) = context.planned_operations else {
+        return Vec::new();
+    };
+    let mut planned_operations = planned_operations.into_inner().unwrap();
+    planned_operations.sort_by(|a, b| a.source.cmp(&b.source));
+    planned_operations
+}
+
+/// Runs a recipe iteratively.
+///
+/// ### Parameters
+/// - `entries`: The entries of the source folder.
+/// - `recipe`: The recipe to run.
+/// - `context`: The context of the recipe run.
+///
+/// ### Returns
+/// - `Vec<Result<FileOutcome>>`: The results of the recipe run.
+fn run_recipe_iterative(
+    entries: Vec<DirEntry>,
+    recipe: &Recipe,
+    context: &RecipeContext,
+) -> Vec<Result<FileOutcome>> {
+    entries
+        .iter()
+        .map(|entry| run_and_journal_file(entry, None, recipe, context))
+        .collect()
+}
+
+/// Runs a recipe for a file, the error of the file being added to the journal of the run, if any.
+///
+/// ### Parameters
+/// - `entry`: The entry to run the recipe for.
+/// - `file_match`: The outcome of matching the file, if already matched.
+/// - `recipe`: The recipe to run.
+/// - `context`: The context of the recipe run.
+///
+/// ### Returns
+/// - `Result<FileOutcome, anyhow::Error>`: What has been done with the file, the error labeled with its path.
+fn run_and_journal_file(
+    entry: &DirEntry,
+    file_match: Option<FileMatch>,
+    recipe: &Recipe,
+    context: &RecipeContext,
+) -> Result<FileOutcome> {
+    let result = run_for_file(entry, file_match, recipe, context);
+    if let (Err(e), Some(journal)) = (&result, &context.journal) {
+        journal.record(
+            &recipe.name,
+            &PlanOperationKind::from(&recipe.operation),
+            &entry.path(),
+            None,
+            &format!("error: {:#}", e),
+        );
     }
-    if seconds < 3600 {
-        return format!("{}m {}s", seconds / 60, seconds % 60);
+    result.with_context(|| get_file_label(entry))
+}
+
+/// Gets the label of a file in the errors of a recipe run.
+///
+/// ### Parameters
+/// - `entry`: The entry of the file.
+///
+/// ### Returns
+/// - `String`: The path of the file.
+fn get_file_label(entry: &DirEntry) -> String {
+    entry.path().display().to_string()
+}
+
+/// Runs a recipe in parallel.
+/// With a counter token, the matched files are numbered in the order of their paths before being organized, so that their counters do not depend on the threads.
+///
+/// ### Parameters
+/// - `entries`: The entries of the source folder.
+/// - `recipe`: The recipe to run.
+/// - `context`: The context of the recipe run.
+///
+/// ### Returns
+/// - `Vec<Result<FileOutcome>>`: The results of the recipe run.
+fn run_recipe_parallel(
+    entries: Vec<DirEntry>,
+    recipe: &Recipe,
+    context: &RecipeContext,
+) -> Vec<Result<FileOutcome>> {
+    context.install(|| {
+        if !uses_counter(recipe) {
+            return entries
+                .par_iter()
+                .map(|entry| run_and_journal_file(entry, None, recipe, context))
+                .collect();
+        }
+        let mut file_matches: Vec<(&DirEntry, FileMatch)> = entries
+            .par_iter()
+            .map(|entry| (entry, match_file(&entry.path(), recipe, context)))
+            .collect();
+        file_matches.sort_by_key(|(entry, _)| entry.path());
+        for (_, file_match) in &mut file_matches {
+            if let Ok(Some(match_result)) = file_match {
+                match_result.counted_file = Some(plan_counted_file(match_result, recipe, context));
+            }
+        }
+        file_matches
+            .into_par_iter()
+            .map(|(entry, file_match)| {
+                run_and_journal_file(entry, Some(file_match), recipe, context)
+            })
+            .collect()
+    })
+}
+
+/// Builds the thread pool capping the number of threads of a recipe.
+/// The recipe_threads of the recipe take precedence over the number of threads of the run.
+///
+/// ### Parameters
+/// - `recipe`: The recipe to build the thread pool for.
+/// - `threads`: The number of threads of the run, if set, 0 meaning all the cores.
+///
+/// ### Returns
+/// - `Result<Option<ThreadPool>, anyhow::Error>`: The thread pool, None if the recipe shares the global thread pool.
+fn build_thread_pool(
+    recipe: &Recipe,
+    threads: Option<usize>,
+) -> anyhow::Result<Option<ThreadPool>> {
+    if recipe.recipe_threads == Some(0) {
+        return Err(anyhow::Error::msg(format!(
+            "{} - recipe_threads must be at least 1",
+            recipe.name
+        )));
     }
-    if seconds < 86400 {
-        return format!(
-            "{}h {}m {}s",
-            seconds / 3600,
-            (seconds % 3600) / 60,
-            seconds % 60
-        );
+    let Some(threads) = recipe.recipe_threads.or(threads.filter(|v| *v > 0)) else {
+        return Ok(None);
+    };
+    let thread_pool = ThreadPoolBuilder::new()
+        .num_threads(threads)
+        .thread_name(|i| format!("recipe-{}", i))
+        .build()
+        .map_err(|e| {
+            anyhow::Error::msg(format!(
+                "{} - Error creating the thread pool: {}",
+                recipe.name, e
+            ))
+        })?;
+    Ok(Some(thread_pool))
+}
+
+/// Collects the entries of the source folder of a recipe, see EntriesWalker.
+///
+/// ### Parameters
+/// - `recipe`: The recipe to collect the entries for.
+///
+/// ### Returns
+/// - `Result<Vec<DirEntry>>`: The entries of the source folder.
+fn collect_entries(recipe: &Recipe) -> Result<Vec<DirEntry>> {
+    Ok(EntriesWalker::new(recipe).collect::<io::Result<Vec<_>>>()?)
+}
+
+/// Checks if a subfolder of the source folder of a recursive or in place recipe is walked.
+///
+/// ### Parameters
+/// - `recipe`: The recipe walking the source folder.
+/// - `skipped_folders`: The canonicalized folders never walked.
+/// - `folder`: The folder holding the subfolder.
+/// - `depth`: The depth of the folder, 0 for a source folder.
+/// - `entry`: The subfolder.
+///
+/// ### Returns
+/// - `bool`: True if the subfolder is walked, false if it is hidden, too deep or skipped.
+fn is_walked_folder(
+    recipe: &Recipe,
+    skipped_folders: &HashSet<PathBuf>,
+    folder: &Path,
+    depth: u32,
+    entry: &DirEntry,
+) -> bool {
+    let name = entry.file_name();
+    let name = name.to_string_lossy();
+    if name.starts_with('.') || recipe.max_depth.is_some_and(|v| depth >= v) {
+        return false;
+    }
+    if recipe.in_place.unwrap_or(false) {
+        return folder != recipe.source_folder || !is_recipe_subfolder(recipe, &name);
+    }
+    !skipped_folders.contains(&fs::canonicalize(entry.path()).unwrap_or_else(|_| entry.path()))
+}
+
+/// Gets the subpath of a file relative to the source folder of a recipe, for recursive recipes preserving it.
+///
+/// ### Parameters
+/// - `from_file`: The file to get the subpath of.
+/// - `recipe`: The recipe defining the source folder.
+///
+/// ### Returns
+/// - `Option<&Path>`: The folder of the file relative to the source folder, None if the recipe does not preserve it or the file is directly in the source folder.
+fn get_preserved_subpath<'a>(from_file: &'a Path, recipe: &Recipe) -> Option<&'a Path> {
+    if !recipe.recursive.unwrap_or(false)
+        || !recipe.preserve_subpath.unwrap_or(false)
+        || recipe.in_place.unwrap_or(false)
+    {
+        return None;
+    }
+    let folder = from_file.parent()?;
+    recipe
+        .get_source_folders()
+        .into_iter()
+        .find_map(|v| folder.strip_prefix(v).ok())
+        .filter(|v| !v.as_os_str().is_empty())
+}
+
+/// Checks if a folder name matches the first level of subfolders created by a recipe.
+///
+/// ### Parameters
+/// - `recipe`: The recipe to check the folder name against.
+/// - `name`: The folder name.
+///
+/// ### Returns
+/// - `bool`: True if the folder has been created by the recipe, false otherwise.
+fn is_recipe_subfolder(recipe: &Recipe, name: &str) -> bool {
+    let is_route_subfolder = recipe.magic_routes.iter().flatten().any(|route| {
+        Path::new(&route.subfolder)
+            .components()
+            .next()
+            .is_some_and(|component| component.as_os_str() == name)
+    });
+    if is_route_subfolder {
+        return true;
+    }
+    if let Some(format) = recipe.subfolders.as_ref().and_then(|v| v.first()) {
+        let mut parsed = Parsed::new();
+        return parse(&mut parsed, name, StrftimeItems::new(format)).is_ok();
+    }
+    false
+}
+
+/// Validates the in place settings of a recipe.
+/// A recipe can only write into its own source folder when it is flagged as in place, moves the files and creates subfolders.
+///
+/// ### Parameters
+/// - `recipe`: The recipe to validate.
+///
+/// ### Returns
+/// - `Result<(), anyhow::Error>`: An error if the recipe settings are inconsistent.
+fn validate_in_place(recipe: &Recipe) -> anyhow::Result<()> {
+    let source_folder = fs::canonicalize(&recipe.source_folder)?;
+    let destination_folder = fs::canonicalize(&recipe.destination_folder).ok();
+    let is_same_folder = destination_folder.as_ref() == Some(&source_folder);
+    if !recipe.in_place.unwrap_or(false) {
+        if is_same_folder && !is_dedupe(recipe) {
+            return Err(anyhow::Error::msg(format!(
+                "{} - Source and target folders are the same, set in_place to organize in place",
+                recipe.name
+            )));
+        }
+        if !is_dedupe(recipe)
+            && let Some(source_folder) = recipe.source_folders.iter().flatten().find(|v| {
+                destination_folder.is_some() && fs::canonicalize(v).ok() == destination_folder
+            })
+        {
+            return Err(anyhow::Error::msg(format!(
+                "{} - Source folder {} is the target folder",
+                recipe.name,
+                source_folder.display()
+            )));
+        }
+        return Ok(());
+    }
+    if !is_same_folder {
+        return Err(anyhow::Error::msg(format!(
+            "{} - In place recipes must have the same source and target folders",
+            recipe.name
+        )));
+    }
+    if recipe.operation != FileOperation::Move {
+        return Err(anyhow::Error::msg(format!(
+            "{} - In place recipes must move files",
+            recipe.name
+        )));
+    }
+    if recipe.subfolders.as_ref().is_none_or(|v| v.is_empty()) {
+        return Err(anyhow::Error::msg(format!(
+            "{} - In place recipes must define subfolders",
+            recipe.name
+        )));
+    }
+    if recipe.skip_if_name_exists_anywhere.unwrap_or(false) {
+        return Err(anyhow::Error::msg(format!(
+            "{} - In place recipes cannot skip the names existing in the target folder",
+            recipe.name
+        )));
+    }
+    Ok(())
+}
+
+/// Removes the additional source folders of a recipe which are the same as another of its source folders once canonicalized, so that no folder is scanned twice.
+/// The source folders of a recursive recipe cannot be nested either, the files of the inner folder being scanned twice otherwise.
+///
+/// ### Parameters
+/// - `recipe`: The recipe, whose source folders must exist.
+///
+/// ### Returns
+/// - `Result<(), anyhow::Error>`: An error if the source folders of a recursive recipe are nested.
+fn dedupe_source_folders(recipe: &mut Recipe) -> anyhow::Result<()> {
+    let Some(source_folders) = &recipe.source_folders else {
+        return Ok(());
+    };
+    let mut canonical_folders = vec![fs::canonicalize(&recipe.source_folder)?];
+    let mut deduped_folders = Vec::new();
+    for source_folder in source_folders {
+        let canonical_folder = fs::canonicalize(source_folder)?;
+        if canonical_folders.contains(&canonical_folder) {
+            continue;
+        }
+        if recipe.recursive.unwrap_or(false)
+            && let Some(other_folder) = canonical_folders
+                .iter()
+                .find(|v| canonical_folder.starts_with(v) || v.starts_with(&canonical_folder))
+        {
+            return Err(anyhow::Error::msg(format!(
+                "{} - Source folders {} and {} are nested, which a recursive recipe would scan twice",
+                recipe.name,
+                canonical_folder.display(),
+                other_folder.display()
+            )));
+        }
+        canonical_folders.push(canonical_folder);
+        deduped_folders.push(source_folder.clone());
+    }
+    recipe.source_folders = Some(deduped_folders);
+    Ok(())
+}
+
+/// Collects the names of all the files of a folder tree.
+///
+/// ### Parameters
+/// - `folder`: The root of the folder tree.
+///
+/// ### Returns
+/// - `Result<HashSet<OsString>, anyhow::Error>`: The names of the files.
+fn collect_file_names(folder: &Path) -> anyhow::Result<HashSet<OsString>> {
+    let mut names = HashSet::new();
+    let mut folders = vec![folder.to_path_buf()];
+    while let Some(folder) = folders.pop() {
+        for entry in fs::read_dir(&folder)? {
+            let entry = entry?;
+            if entry.file_type()?.is_dir() {
+                folders.push(entry.path());
+            } else {
+                names.insert(entry.file_name());
+            }
+        }
+    }
+    Ok(names)
+}
+
+/// Collects the extensions of the files of a folder tree, lowercased, the hidden files and the manifest excepted.
+///
+/// ### Parameters
+/// - `folder`: The root of the folder tree.
+///
+/// ### Returns
+/// - `Result<Vec<String>, anyhow::Error>`: The sorted extensions of the files.
+fn collect_extensions(folder: &Path) -> anyhow::Result<Vec<String>> {
+    let mut extensions: Vec<String> = collect_file_names(folder)?
+        .iter()
+        .filter(|v| *v != MANIFEST_FILE_NAME && !v.to_string_lossy().starts_with('.'))
+        .filter_map(|v| Path::new(v).extension())
+        .map(|v| v.to_string_lossy().to_lowercase())
+        .collect::<HashSet<String>>()
+        .into_iter()
+        .collect();
+    extensions.sort();
+    Ok(extensions)
+}
+
+/// Gets the files pinned in the source folder of a recipe.
+/// A file is pinned either by a `<filename>.keep` sidecar next to it, or by being listed in a `.organizer-pin` file of its folder.
+/// The `.keep` sidecars are pinned as well so that they stay next to their file.
+///
+/// ### Parameters
+/// - `recipe`: The recipe to get the pinned files for.
+/// - `entries`: The entries of the source folder.
+///
+/// ### Returns
+/// - `Result<HashSet<PathBuf>, anyhow::Error>`: The paths of the pinned files.
+fn get_pinned_files(recipe: &Recipe, entries: &[DirEntry]) -> anyhow::Result<HashSet<PathBuf>> {
+    let mut pinned_files = HashSet::new();
+    if !recipe.honor_pins.unwrap_or(true) {
+        return Ok(pinned_files);
+    }
+    for entry in entries {
+        let path = entry.path();
+        let Some(filename) = path.file_name().and_then(|v| v.to_str()) else {
+            continue;
+        };
+        if filename == PIN_FILE_NAME {
+            let folder = path.parent().unwrap_or(&recipe.source_folder);
+            for line in fs::read_to_string(&path)?.lines() {
+                let pinned_name = line.trim();
+                if !pinned_name.is_empty() {
+                    pinned_files.insert(folder.join(pinned_name));
+                }
+            }
+        } else if let Some(pinned_name) = filename.strip_suffix(KEEP_SIDECAR_EXTENSION) {
+            let pinned_file = path.with_file_name(pinned_name);
+            if pinned_file.is_file() {
+                pinned_files.insert(pinned_file);
+                pinned_files.insert(path);
+            }
+        }
+    }
+    Ok(pinned_files)
+}
+
+/// Gets the pinned files of a chunk of the source folder, the sidecars and pin files being read from the disk as the chunk may not hold them.
+///
+/// ### Parameters
+/// - `recipe`: The recipe to get the pinned files for.
+/// - `chunk`: The entries of the chunk.
+/// - `pin_lists`: The files listed by the `.organizer-pin` file of each folder already read.
+///
+/// ### Returns
+/// - `Result<HashSet<PathBuf>, anyhow::Error>`: The paths of the pinned files of the chunk.
+fn get_chunk_pinned_files(
+    recipe: &Recipe,
+    chunk: &[DirEntry],
+    pin_lists: &mut HashMap<PathBuf, HashSet<PathBuf>>,
+) -> anyhow::Result<HashSet<PathBuf>> {
+    let mut pinned_files = HashSet::new();
+    if !recipe.honor_pins.unwrap_or(true) {
+        return Ok(pinned_files);
+    }
+    for entry in chunk {
+        let path = entry.path();
+        let folder = path.parent().unwrap_or(&recipe.source_folder).to_path_buf();
+        if !pin_lists.contains_key(&folder) {
+            let pin_file = folder.join(PIN_FILE_NAME);
+            let pin_list = if pin_file.is_file() {
+                fs::read_to_string(&pin_file)?
+                    .lines()
+                    .map(str::trim)
+                    .filter(|v| !v.is_empty())
+                    .map(|v| folder.join(v))
+                    .collect()
+            } else {
+                HashSet::new()
+            };
+            pin_lists.insert(folder.clone(), pin_list);
+        }
+        let mut keep_sidecar = path.clone().into_os_string();
+        keep_sidecar.push(KEEP_SIDECAR_EXTENSION);
+        let is_pinned_sidecar = path
+            .file_name()
+            .and_then(|v| v.to_str())
+            .and_then(|v| v.strip_suffix(KEEP_SIDECAR_EXTENSION))
+            .is_some_and(|v| path.with_file_name(v).is_file());
+        if pin_lists[&folder].contains(&path)
+            || Path::new(&keep_sidecar).exists()
+            || is_pinned_sidecar
+        {
+            pinned_files.insert(path);
+        }
+    }
+    Ok(pinned_files)
+}
+
+/// Gets the most recently modified files of the source folder of a recipe, which are kept in the source.
+///
+/// ### Parameters
+/// - `recipe`: The recipe to get the recent files for.
+/// - `entries`: The entries of the source folder.
+///
+/// ### Returns
+/// - `Result<HashSet<PathBuf>, anyhow::Error>`: The paths of the `keep_recent` most recently modified files.
+fn get_recent_files(recipe: &Recipe, entries: &[DirEntry]) -> anyhow::Result<HashSet<PathBuf>> {
+    let Some(keep_recent) = recipe.keep_recent else {
+        return Ok(HashSet::new());
+    };
+    let mut files = Vec::new();
+    for entry in entries {
+        let path = entry.path();
+        let is_hidden = path
+            .file_name()
+            .is_some_and(|v| v.to_string_lossy().starts_with('.'));
+        if path.is_file() && !is_hidden {
+            files.push((get_last_modification_date(&path)?, path));
+        }
+    }
+    files.sort_by(|a, b| b.cmp(a));
+    Ok(files
+        .into_iter()
+        .take(keep_recent as usize)
+        .map(|(_, path)| path)
+        .collect())
+}
+
+/// Matches a file against a recipe.
+/// The values shared by all files are passed in the context in order to do not get recalculated for each call.
+///
+/// ### Parameters
+/// - `from_file`: The file to match.
+/// - `recipe`: The recipe to match the file against.
+/// - `context`: The context of the recipe run.
+///
+/// ### Returns
+/// - `Result<Option<MatchResult>, anyhow::Error>`: The match result, None if the file is not matched by the recipe.
+fn match_file(
+    from_file: &Path,
+    recipe: &Recipe,
+    context: &RecipeContext,
+) -> anyhow::Result<Option<MatchResult>> {
+    if !from_file.is_file() {
+        return Ok(None);
+    }
+    let Some(filename) = from_file.file_name() else {
+        return Ok(None);
+    };
+    if filename == PIN_FILE_NAME
+        || (filename.to_string_lossy().starts_with(".") && !recipe.include_hidden.unwrap_or(false))
+    {
+        return Ok(None);
+    }
+    if let Some(shard) = &context.shard
+        && !shard.contains(filename)
+    {
+        return Ok(None);
+    }
+    if context.pinned_files.contains(from_file) || context.recent_files.contains(from_file) {
+        return Ok(None);
+    }
+    if filename == MANIFEST_FILE_NAME
+        && from_file.parent() == Some(recipe.destination_folder.as_path())
+    {
+        return Ok(None);
+    }
+    let mut criteria = Vec::new();
+    let skip_readonly = recipe.skip_readonly.unwrap_or(false);
+    let skip_system = recipe.skip_system.unwrap_or(false);
+    let only_own_files = recipe.only_own_files.unwrap_or(false);
+    if skip_readonly || skip_system || only_own_files {
+        let metadata = fs::metadata(from_file)?;
+        if skip_readonly {
+            if metadata.permissions().readonly() {
+                return Ok(None);
+            }
+            criteria.push("not read-only".to_string());
+        }
+        if skip_system {
+            if is_system_file(&metadata) {
+                return Ok(None);
+            }
+            criteria.push("not system".to_string());
+        }
+        if only_own_files {
+            if !is_owned_by(&metadata, context.effective_uid) {
+                return Ok(None);
+            }
+            criteria.push("owned".to_string());
+        }
+    }
+    if let Some(max_modification_time) = context.max_modification_time {
+        if fs::metadata(from_file)?.modified()? > max_modification_time {
+            return Ok(None);
+        }
+        criteria.push(format!(
+            "age>={}s",
+            recipe.min_age_seconds.unwrap_or_default()
+        ));
+    }
+    if let Some(destination_names) = &context.destination_names {
+        if destination_names.contains(filename) {
+            return Ok(None);
+        }
+        criteria.push("name not in destination".to_string());
+    }
+    let magic_subfolder = get_magic_subfolder(from_file, &context.magic_routes)?;
+    let mut file_date = None;
+    let filter_logic = recipe.filter_logic.clone().unwrap_or_default();
+    let filters = get_active_filters(recipe, context);
+    // Without any active filter, every file matches whatever the logic, as Any would otherwise match none.
+    let mut is_matched = filter_logic == FilterLogic::All || filters.is_empty();
+    let mut is_extension_rejected = false;
+    for filter in filters {
+        let criterion = match filter {
+            FileFilter::Size => {
+                let size = fs::metadata(from_file)?.len();
+                is_size_allowed(size, recipe).then(|| format!("size={}", size))
+            }
+            FileFilter::NamePatterns(name_patterns, patterns) => name_patterns
+                .matches(filename)
+                .first()
+                .map(|v| format!("name={}", patterns[*v])),
+            FileFilter::Extension => {
+                if let Some(magic_subfolder) = magic_subfolder {
+                    Some(format!("magic={}", magic_subfolder))
+                } else if is_extension_allowed(from_file, &recipe.allowed_extensions) {
+                    Some(format!(
+                        "ext={}",
+                        from_file.extension().unwrap_or_default().to_string_lossy()
+                    ))
+                } else {
+                    is_extension_rejected = true;
+                    None
+                }
+            }
+            FileFilter::MimeType(mime_types) => get_mime_type(from_file)?.and_then(|mime_type| {
+                is_mime_type_allowed(mime_type, mime_types).then(|| format!("mime={}", mime_type))
+            }),
+            FileFilter::Weekday(weekdays) => {
+                let weekday = get_cached_file_date(&mut file_date, from_file, recipe)?.weekday();
+                weekdays
+                    .contains(&weekday)
+                    .then(|| format!("weekday={}", weekday))
+            }
+            FileFilter::Reference(reference_date) => {
+                // A file dated exactly at the reference counts as newer, so that NewerThan and OlderThan split the files without overlap.
+                let is_newer =
+                    get_cached_file_date(&mut file_date, from_file, recipe)? >= *reference_date;
+                match recipe.reference_filter.clone().unwrap_or_default() {
+                    ReferenceFilter::NewerThan if is_newer => {
+                        Some("newer than reference".to_string())
+                    }
+                    ReferenceFilter::OlderThan if !is_newer => {
+                        Some("older than reference".to_string())
+                    }
+                    _ => None,
+                }
+            }
+        };
+        match (criterion, &filter_logic) {
+            (Some(criterion), FilterLogic::All) => criteria.push(criterion),
+            (None, FilterLogic::All) => {
+                is_matched = false;
+                break;
+            }
+            (Some(criterion), FilterLogic::Any) => {
+                criteria.push(criterion);
+                is_matched = true;
+                break;
+            }
+            (None, FilterLogic::Any) => {}
+        }
+    }
+    if !is_matched {
+        if is_extension_rejected && let Some(unknown_extensions) = &context.unknown_extensions {
+            let extension = from_file
+                .extension()
+                .map(|v| format!(".{}", v.to_string_lossy().to_lowercase()))
+                .unwrap_or("(none)".to_string());
+            *unknown_extensions
+                .lock()
+                .unwrap()
+                .entry(extension)
+                .or_default() += 1;
+        }
+        return Ok(None);
+    }
+    let file_date = match file_date {
+        Some(file_date) => file_date,
+        None if context.needs_file_date => get_file_date(from_file, recipe)?,
+        None => DateTime::<Utc>::default(),
+    };
+    if let Some(date_boundary) = &context.date_boundary {
+        if file_date < *date_boundary {
+            return Ok(None);
+        }
+        criteria.push(format!("date>={}", date_boundary.format("%Y-%m-%d")));
+    }
+    if let (Some(date_from), _) = &context.date_range {
+        if file_date < *date_from {
+            return Ok(None);
+        }
+        criteria.push(format!("date>={}", date_from.format("%Y-%m-%d")));
+    }
+    if let (_, Some(date_to)) = &context.date_range {
+        if file_date >= *date_to {
+            return Ok(None);
+        }
+        criteria.push(format!(
+            "date<={}",
+            (*date_to - TimeDelta::days(1)).format("%Y-%m-%d")
+        ));
+    }
+    if let Some(last_seen) = &context.last_seen {
+        if get_arrival_date(from_file)? < *last_seen {
+            return Ok(None);
+        }
+        criteria.push(format!(
+            "arrived>={}",
+            last_seen.to_rfc3339_opts(SecondsFormat::Secs, true)
+        ));
+    }
+    let music_tags = if context.needs_music_tags {
+        let music_tags = MusicTags::read(from_file);
+        if music_tags.is_empty() {
+            criteria.push("no music tags".to_string());
+        }
+        Some(music_tags)
+    } else {
+        None
+    };
+    let route_subfolder = match (magic_subfolder, &context.tag_rules) {
+        (None, Some(tag_rules)) => {
+            let tag_folder = tag_rules.get_folder(&filename.to_string_lossy());
+            criteria.push(format!("tag={}", tag_folder));
+            Some(tag_folder)
+        }
+        (None, None) if recipe.detect_language.unwrap_or(false) => {
+            let language_folder = language::get_language_folder(from_file)?;
+            criteria.push(format!("language={}", language_folder));
+            Some(language_folder)
+        }
+        _ => magic_subfolder,
+    };
+    let content_hash = if context.needs_content_hash {
+        Some(hash_file(from_file)?)
+    } else {
+        None
+    };
+    let mut dest_folder = build_dest_folder(
+        from_file,
+        recipe,
+        route_subfolder,
+        &file_date,
+        music_tags.as_ref(),
+        content_hash.as_deref(),
+        creates_subfolders(from_file, recipe),
+    );
+    if let Some(subpath) = get_preserved_subpath(from_file, recipe) {
+        dest_folder.push(subpath);
+    }
+    if let Some(max_dest_depth) = recipe.max_dest_depth {
+        dest_folder = flatten_dest_folder(
+            &dest_folder,
+            get_destination_root(recipe, &file_date),
+            max_dest_depth,
+        );
+    }
+    if !is_dedupe(recipe) && from_file.parent() == Some(dest_folder.as_path()) {
+        return Ok(None);
+    }
+    let mut file_name = build_file_name(from_file, recipe, &file_date, content_hash.as_deref());
+    if !uses_counter(recipe) {
+        file_name = normalize_file_name(file_name, recipe);
+    }
+    Ok(Some(MatchResult {
+        dest_folder,
+        file_name,
+        content_hash,
+        file_date,
+        criteria,
+        counted_file: None,
+    }))
+}
+
+/// Gets the filters set by a recipe, combined by its filter logic.
+/// The MIME types filter replaces the extension filter when the recipe sets no extensions.
+///
+/// ### Parameters
+/// - `recipe`: The recipe.
+/// - `context`: The context of the recipe run.
+///
+/// ### Returns
+/// - `Vec<FileFilter>`: The filters, the cheapest first.
+fn get_active_filters<'a>(recipe: &Recipe, context: &'a RecipeContext) -> Vec<FileFilter<'a>> {
+    let mut filters = Vec::new();
+    if recipe.min_size_bytes.is_some() || recipe.max_size_bytes.is_some() {
+        filters.push(FileFilter::Size);
+    }
+    if let Some((name_patterns, patterns)) = &context.name_patterns {
+        filters.push(FileFilter::NamePatterns(name_patterns, patterns));
+    }
+    if (recipe
+        .allowed_extensions
+        .as_ref()
+        .is_none_or(|v| !v.is_empty())
+        && (recipe.allowed_extensions.is_some() || context.mime_types.is_none()))
+        || !context.magic_routes.is_empty()
+    {
+        filters.push(FileFilter::Extension);
+    }
+    if let Some(mime_types) = &context.mime_types {
+        filters.push(FileFilter::MimeType(mime_types));
+    }
+    if let Some(weekdays) = &context.weekdays {
+        filters.push(FileFilter::Weekday(weekdays));
+    }
+    if let Some(reference_date) = &context.reference_date {
+        filters.push(FileFilter::Reference(reference_date));
+    }
+    filters
+}
+
+/// Gets the date of a file, computed once and cached for the filters needing it.
+///
+/// ### Parameters
+/// - `file_date`: The cached date of the file, if already computed.
+/// - `file`: The file.
+/// - `recipe`: The recipe.
+///
+/// ### Returns
+/// - `Result<DateTime<Utc>, anyhow::Error>`: The date of the file.
+fn get_cached_file_date(
+    file_date: &mut Option<DateTime<Utc>>,
+    file: &Path,
+    recipe: &Recipe,
+) -> anyhow::Result<DateTime<Utc>> {
+    if let Some(file_date) = file_date {
+        return Ok(*file_date);
+    }
+    let date = get_file_date(file, recipe)?;
+    *file_date = Some(date);
+    Ok(date)
+}
+
+/// Runs a recipe for a file.
+///
+/// ### Parameters
+/// - `entry`: The entry to run the recipe for.
+/// - `file_match`: The outcome of matching the file, if already matched, the file being matched otherwise.
+/// - `recipe`: The recipe to run.
+/// - `context`: The context of the recipe run.
+///
+/// ### Returns
+/// - `Result<FileOutcome, anyhow::Error>`: What has been done with the file.
+fn run_for_file(
+    entry: &DirEntry,
+    file_match: Option<FileMatch>,
+    recipe: &Recipe,
+    context: &RecipeContext,
+) -> anyhow::Result<FileOutcome> {
+    let dry_run = context.dry_run;
+    let from_file = entry.path();
+    let _file_span =
+        info_span!(parent: &context.span, "file", path = %from_file.display()).entered();
+    let file_match = match file_match {
+        Some(file_match) => file_match,
+        None => match_file(&from_file, recipe, context),
+    };
+    let Some(match_result) = file_match? else {
+        debug!(
+            "{} {} {} - {}",
+            "➖".dimmed(),
+            recipe.name.blue(),
+            "File not matched".dimmed(),
+            from_file.display()
+        );
+        return Ok(FileOutcome::NotMatched);
+    };
+    let dest_folder = &match_result.dest_folder;
+    if let Some(destination_index) = &context.destination_index
+        && let Some(duplicate) = destination_index.find_duplicate(
+            &from_file,
+            dest_folder,
+            match_result.content_hash.as_deref(),
+        )?
+    {
+        if !context.quiet {
+            info!(
+                "{} {} {} - {}: {}",
+                "➖".dimmed(),
+                recipe.name.blue(),
+                "Identical file exists, skipped".purple(),
+                from_file.display(),
+                duplicate.display()
+            );
+        }
+        if let Some(journal) = &context.journal {
+            journal.record(
+                &recipe.name,
+                &PlanOperationKind::from(&recipe.operation),
+                &from_file,
+                Some(&duplicate),
+                "skipped: identical file exists",
+            );
+        }
+        return Ok(FileOutcome::DuplicateSkipped);
+    }
+    let created_folder = context
+        .patch
+        .as_ref()
+        .and_then(|_| patch::get_created_folder(dest_folder));
+    if !dry_run
+        && !dest_folder.exists()
+        && let Err(e) = fs::create_dir_all(dest_folder)
+    {
+        let hint = if !recipe.sanitize_folder_names.unwrap_or(false)
+            && dest_folder
+                .to_string_lossy()
+                .contains(FORBIDDEN_FOLDER_CHARACTERS)
+        {
+            ", set sanitize_folder_names to replace the forbidden characters"
+        } else {
+            ""
+        };
+        error!(
+            "{} {} {} - {}: {}{}",
+            "❌".red(),
+            recipe.name.blue(),
+            "Folder creation failed".red(),
+            dest_folder.display(),
+            e,
+            hint
+        );
+        return Err(anyhow::Error::msg(format!(
+            "{} - Error creating folder {}: {}",
+            recipe.name,
+            dest_folder.display(),
+            e
+        )));
+    }
+    let mut dest_file = dest_folder.join(&match_result.file_name);
+    if recipe.require_dest_extension.unwrap_or(false) && !keeps_extension(&from_file, &dest_file) {
+        error!(
+            "{} {} {} - {}: {}",
+            "❌".red(),
+            recipe.name.blue(),
+            "Extension lost".red(),
+            from_file.display(),
+            dest_file.display()
+        );
+        return Err(anyhow::Error::msg(format!(
+            "{} - Destination does not keep the extension of {}, check file_name_template: {}",
+            recipe.name,
+            from_file.display(),
+            dest_file.display()
+        )));
+    }
+    let on_conflict = recipe.on_conflict.clone().unwrap_or_default();
+    let is_conflicting = if let Some(counted_file) = &match_result.counted_file {
+        dest_file = counted_file.clone();
+        false
+    } else {
+        let mut planned_files = context.planned_files.lock().unwrap();
+        if uses_counter(recipe) {
+            dest_file = get_counted_file_name(
+                dest_folder,
+                &match_result.file_name,
+                recipe,
+                &planned_files,
+                &mut context.next_counters.lock().unwrap(),
+            );
+        }
+        let is_conflicting = planned_files.contains(&dest_file) || dest_file.exists();
+        if is_conflicting && on_conflict == ConflictPolicy::Rename {
+            dest_file = get_free_file_name(&dest_file, &planned_files);
+        }
+        planned_files.insert(dest_file.clone());
+        is_conflicting
+    };
+    let annotation = match (dry_run, is_conflicting) {
+        (true, true) => format!(
+            " ({}, on_conflict={:?})",
+            match_result.describe_criteria(),
+            on_conflict
+        ),
+        (true, false) => format!(" ({})", match_result.describe_criteria()),
+        _ => "".to_string(),
+    };
+    if is_conflicting && on_conflict == ConflictPolicy::Skip {
+        if let Some(skipped_operations) = &context.skipped_operations {
+            skipped_operations.lock().unwrap().push(PlanOperation {
+                recipe: recipe.name.clone(),
+                operation: PlanOperationKind::from(&recipe.operation),
+                source: from_file.clone(),
+                destination: dest_file.clone(),
+                destination_exists: true,
+                conflict: Some(ConflictPolicy::Skip),
+            });
+        }
+        if !context.quiet {
+            warn!(
+                "{} {} {} - {}{}",
+                "⚠️".yellow(),
+                recipe.name.blue(),
+                "Destination exists, skipped".yellow(),
+                dest_file.display(),
+                annotation.dimmed()
+            );
+        }
+        if let Some(journal) = &context.journal {
+            journal.record(
+                &recipe.name,
+                &PlanOperationKind::from(&recipe.operation),
+                &from_file,
+                Some(&dest_file),
+                "skipped: destination exists",
+            );
+        }
+        return Ok(FileOutcome::ConflictSkipped);
+    }
+    let destination_exists = dest_file.exists();
+    if let Some(planned_operations) = &context.planned_operations {
+        planned_operations.lock().unwrap().push(PlanOperation {
+            recipe: recipe.name.clone(),
+            operation: PlanOperationKind::from(&recipe.operation),
+            source: from_file.clone(),
+            destination: dest_file.clone(),
+            destination_exists,
+            conflict: is_conflicting.then(|| on_conflict.clone()),
+        });
+    }
+    let backup = match &context.patch {
+        Some(patch) if dest_file.exists() => Some(patch.backup(&dest_file).map_err(|e| {
+            anyhow::Error::msg(format!(
+                "{} - Error backing up overwritten file {}: {}",
+                recipe.name,
+                dest_file.display(),
+                e
+            ))
+        })?),
+        _ => None,
+    };
+    let restore_backup = || -> anyhow::Result<()> {
+        if let Some(backup) = &backup {
+            move_file(backup, &dest_file).map_err(|e| {
+                anyhow::Error::msg(format!(
+                    "{} - Error restoring overwritten file {} from its backup {}: {}",
+                    recipe.name,
+                    dest_file.display(),
+                    backup.display(),
+                    e
+                ))
+            })?;
+        }
+        Ok(())
+    };
+    let fail = |e: anyhow::Error| match restore_backup() {
+        Ok(()) => e,
+        Err(restore_error) => anyhow::Error::msg(format!("{}, {}", e, restore_error)),
+    };
+    let skip_locked_file = || {
+        restore_backup()?;
+        if let Some(journal) = &context.journal {
+            journal.record(
+                &recipe.name,
+                &PlanOperationKind::from(&recipe.operation),
+                &from_file,
+                Some(&dest_file),
+                "skipped: file locked",
+            );
+        }
+        warn!(
+            "{} {} {} - {}",
+            "⚠️".yellow(),
+            recipe.name.blue(),
+            "File locked, skipped".yellow(),
+            from_file.display()
+        );
+        Ok(FileOutcome::LockedSkipped)
+    };
+    if recipe.operation == FileOperation::Move {
+        if !dry_run {
+            match run_with_locked_policy(recipe, || move_file(&from_file, &dest_file)) {
+                Ok(true) => {}
+                Ok(false) => return skip_locked_file(),
+                Err(e) => {
+                    return Err(fail(anyhow::Error::msg(format!(
+                        "{} - Error moving file: {}",
+                        recipe.name, e
+                    ))));
+                }
+            }
+        }
+        if !context.quiet {
+            info!(
+                operation = "move",
+                destination = %dest_file.display(),
+                "{} {} {} - {}{}",
+                "✅".green(),
+                recipe.name.blue(),
+                "File moved".green(),
+                dest_file.display(),
+                annotation.dimmed()
+            );
+        }
+    } else if recipe.operation == FileOperation::Copy {
+        if !dry_run {
+            match run_with_locked_policy(recipe, || {
+                copy_file(&from_file, &dest_file, recipe, |copied, total| {
+                    if !context.quiet {
+                        info!(
+                            "{} {} {} - {}: {}/{}",
+                            "⏳".green(),
+                            recipe.name.blue(),
+                            "Copy progress".purple(),
+                            from_file.display(),
+                            space::format_size(copied),
+                            space::format_size(total)
+                        );
+                    }
+                })
+            }) {
+                Ok(true) => {}
+                Ok(false) => return skip_locked_file(),
+                Err(e) => {
+                    return Err(fail(anyhow::Error::msg(format!(
+                        "{} - Error copying file: {}",
+                        recipe.name, e
+                    ))));
+                }
+            }
+            if recipe.verify_copy.unwrap_or(false)
+                && let Err(e) =
+                    verify_copy(&from_file, &dest_file, match_result.content_hash.as_deref())
+            {
+                let _ = fs::remove_file(&dest_file);
+                error!(
+                    "{} {} {} - {}: {}",
+                    "❌".red(),
+                    recipe.name.blue(),
+                    "Copy verification failed".red(),
+                    dest_file.display(),
+                    e
+                );
+                return Err(fail(anyhow::Error::msg(format!(
+                    "{} - Copy of {} failed verification, removed: {}",
+                    recipe.name,
+                    from_file.display(),
+                    e
+                ))));
+            }
+            if recipe.preserve_timestamps.unwrap_or(false)
+                && let Err(e) = copy_timestamps(&from_file, &dest_file)
+            {
+                warn!(
+                    "{} {} {} - {}: {}",
+                    "⚠️".yellow(),
+                    recipe.name.blue(),
+                    "Timestamps not preserved".yellow(),
+                    dest_file.display(),
+                    e
+                );
+            }
+        }
+        if !context.quiet {
+            info!(
+                operation = "copy",
+                destination = %dest_file.display(),
+                "{} {} {} - {}{}",
+                "✅".green(),
+                recipe.name.blue(),
+                "File copied".green(),
+                dest_file.display(),
+                annotation.dimmed()
+            );
+        }
+    } else {
+        let operation = PlanOperationKind::from(&recipe.operation);
+        if !dry_run {
+            if backup.is_none()
+                && dest_file.exists()
+                && let Err(e) = fs::remove_file(&dest_file)
+            {
+                return Err(anyhow::Error::msg(format!(
+                    "{} - Error removing overwritten file {}: {}",
+                    recipe.name,
+                    dest_file.display(),
+                    e
+                )));
+            }
+            if let Err(e) = link_file(&from_file, &dest_file, &operation) {
+                return Err(fail(anyhow::Error::msg(format!(
+                    "{} - Error linking file: {}",
+                    recipe.name, e
+                ))));
+            }
+        }
+        if !context.quiet {
+            let (operation, label) = if operation == PlanOperationKind::Symlink {
+                ("symlink", "File symlinked")
+            } else {
+                ("hardlink", "File hardlinked")
+            };
+            info!(
+                operation,
+                destination = %dest_file.display(),
+                "{} {} {} - {}{}",
+                "✅".green(),
+                recipe.name.blue(),
+                label.green(),
+                dest_file.display(),
+                annotation.dimmed()
+            );
+        }
+    }
+    if let Some(performed_operations) = &context.performed_operations {
+        performed_operations.lock().unwrap().push(PlanOperation {
+            recipe: recipe.name.clone(),
+            operation: PlanOperationKind::from(&recipe.operation),
+            source: from_file.clone(),
+            destination: dest_file.clone(),
+            destination_exists,
+            conflict: is_conflicting.then(|| on_conflict.clone()),
+        });
+    }
+    if let Some(destination_index) = &context.destination_index {
+        destination_index.add(&dest_file, if dry_run { &from_file } else { &dest_file });
+    }
+    if let Some(journal) = &context.journal {
+        journal.record(
+            &recipe.name,
+            &PlanOperationKind::from(&recipe.operation),
+            &from_file,
+            Some(&dest_file),
+            "ok",
+        );
+    }
+    if let Some(patch) = &context.patch {
+        patch.record(PatchOperation {
+            recipe: recipe.name.clone(),
+            operation: PlanOperationKind::from(&recipe.operation),
+            source: from_file.clone(),
+            destination: dest_file.clone(),
+            backup,
+            created_folder,
+            archive_member: None,
+            size: None,
+            modified: None,
+        });
+    }
+    if let Some(manifest_files) = &context.manifest_files {
+        manifest_files
+            .lock()
+            .unwrap()
+            .push((dest_file, match_result.content_hash));
+    }
+    if let Some(folders_counts) = &context.folders_counts {
+        *folders_counts
+            .lock()
+            .unwrap()
+            .entry(dest_folder.clone())
+            .or_default() += 1;
+    }
+    if is_conflicting {
+        Ok(FileOutcome::Conflicted)
+    } else {
+        Ok(FileOutcome::Processed)
+    }
+}
+
+/// Applies the modification and access dates of a file to its copy, and its creation date on Windows.
+///
+/// ### Parameters
+/// - `from_file`: The copied file.
+/// - `to_file`: The copy.
+///
+/// ### Returns
+/// - `io::Result<()>`: The result of the update.
+fn copy_timestamps(from_file: &Path, to_file: &Path) -> io::Result<()> {
+    let metadata = fs::metadata(from_file)?;
+    let times = fs::FileTimes::new()
+        .set_modified(metadata.modified()?)
+        .set_accessed(metadata.accessed()?);
+    #[cfg(windows)]
+    let times = {
+        use std::os::windows::fs::FileTimesExt;
+        times.set_created(metadata.created()?)
+    };
+    fs::File::options()
+        .write(true)
+        .open(to_file)?
+        .set_times(times)
+}
+
+/// Plans the numbered destination of a file matched by a recipe with a counter token, before the files are organized in parallel.
+///
+/// ### Parameters
+/// - `match_result`: The outcome of matching the file.
+/// - `recipe`: The recipe.
+/// - `context`: The context of the recipe run.
+///
+/// ### Returns
+/// - `PathBuf`: The destination with the counter token replaced, planned so that no other file of the run takes it.
+fn plan_counted_file(
+    match_result: &MatchResult,
+    recipe: &Recipe,
+    context: &RecipeContext,
+) -> PathBuf {
+    let mut planned_files = context.planned_files.lock().unwrap();
+    let counted_file = get_counted_file_name(
+        &match_result.dest_folder,
+        &match_result.file_name,
+        recipe,
+        &planned_files,
+        &mut context.next_counters.lock().unwrap(),
+    );
+    planned_files.insert(counted_file.clone());
+    counted_file
+}
+
+/// Links a file, with a symbolic link to its absolute path or a hard link.
+///
+/// ### Parameters
+/// - `from_file`: The file to link.
+/// - `to_file`: The link to create.
+/// - `operation`: The kind of link, Symlink or Hardlink.
+///
+/// ### Returns
+/// - `Result<(), anyhow::Error>`: The result of the link.
+pub fn link_file(
+    from_file: &Path,
+    to_file: &Path,
+    operation: &PlanOperationKind,
+) -> anyhow::Result<()> {
+    match operation {
+        PlanOperationKind::Symlink => symlink_file(&std::path::absolute(from_file)?, to_file)?,
+        PlanOperationKind::Hardlink => fs::hard_link(from_file, to_file)?,
+        _ => {
+            return Err(anyhow::Error::msg(format!(
+                "Not a link operation: {:?}",
+                operation
+            )));
+        }
+    }
+    Ok(())
+}
+
+/// Creates a symbolic link to a file.
+///
+/// ### Parameters
+/// - `original`: The file to link.
+/// - `link`: The link to create.
+///
+/// ### Returns
+/// - `io::Result<()>`: The result of the link.
+#[cfg(unix)]
+fn symlink_file(original: &Path, link: &Path) -> io::Result<()> {
+    std::os::unix::fs::symlink(original, link)
+}
+
+/// Creates a symbolic link to a file, which requires the developer mode or administrator rights on Windows.
+///
+/// ### Parameters
+/// - `original`: The file to link.
+/// - `link`: The link to create.
+///
+/// ### Returns
+/// - `io::Result<()>`: The result of the link.
+#[cfg(windows)]
+fn symlink_file(original: &Path, link: &Path) -> io::Result<()> {
+    std::os::windows::fs::symlink_file(original, link)
+}
+
+/// Copies a file, the files from the stream copy size of a recipe being copied through a buffer of its copy buffer size, with their progress reported.
+///
+/// ### Parameters
+/// - `from_file`: The file to copy.
+/// - `to_file`: The copy.
+/// - `recipe`: The recipe defining the stream copy size and the copy buffer size.
+/// - `on_progress`: Called with the bytes copied and the size of the file every tenth of a streamed copy.
+///
+/// ### Returns
+/// - `Result<(), anyhow::Error>`: The result of the copy.
+fn copy_file(
+    from_file: &Path,
+    to_file: &Path,
+    recipe: &Recipe,
+    on_progress: impl Fn(u64, u64),
+) -> anyhow::Result<()> {
+    let metadata = fs::metadata(from_file)?;
+    if recipe.stream_copy_bytes.is_none_or(|v| metadata.len() < v) {
+        fs::copy(from_file, to_file)?;
+        return Ok(());
+    }
+    let buffer_size = recipe
+        .copy_buffer_bytes
+        .filter(|v| *v > 0)
+        .unwrap_or(DEFAULT_COPY_BUFFER_BYTES);
+    let result = stream_copy(from_file, to_file, metadata.len(), buffer_size, on_progress)
+        .and_then(|_| fs::set_permissions(to_file, metadata.permissions()));
+    if let Err(e) = result {
+        let _ = fs::remove_file(to_file);
+        return Err(e.into());
+    }
+    Ok(())
+}
+
+/// Copies a file through a buffer, reporting its progress.
+///
+/// ### Parameters
+/// - `from_file`: The file to copy.
+/// - `to_file`: The copy.
+/// - `size`: The size of the file.
+/// - `buffer_size`: The size of the buffer.
+/// - `on_progress`: Called with the bytes copied and the size of the file every tenth of the copy.
+///
+/// ### Returns
+/// - `io::Result<()>`: The result of the copy.
+fn stream_copy(
+    from_file: &Path,
+    to_file: &Path,
+    size: u64,
+    buffer_size: usize,
+    on_progress: impl Fn(u64, u64),
+) -> io::Result<()> {
+    let mut reader = fs::File::open(from_file)?;
+    let mut writer = io::BufWriter::with_capacity(buffer_size, fs::File::create(to_file)?);
+    let mut buffer = vec![0; buffer_size];
+    let progress_step = (size / COPY_PROGRESS_STEPS).max(1);
+    let mut next_progress = progress_step;
+    let mut copied = 0;
+    loop {
+        let read = reader.read(&mut buffer)?;
+        if read == 0 {
+            break;
+        }
+        writer.write_all(&buffer[..read])?;
+        copied += read as u64;
+        if copied >= next_progress && copied < size {
+            on_progress(copied, size);
+            next_progress = (copied / progress_step + 1) * progress_step;
+        }
+    }
+    writer.flush()?;
+    on_progress(copied, size);
+    Ok(())
+}
+
+/// Moves a file.
+/// When the destination is on another device, where a rename is impossible, the file is copied and the source removed instead.
+///
+/// ### Parameters
+/// - `from_file`: The file to move.
+/// - `to_file`: The destination of the file.
+///
+/// ### Returns
+/// - `Result<(), anyhow::Error>`: The result of the move.
+fn move_file(from_file: &Path, to_file: &Path) -> anyhow::Result<()> {
+    move_file_with(from_file, to_file, |from, to| fs::rename(from, to))
+}
+
+/// Moves a file with a rename operation, falling back to a copy and a removal of the source when the rename crosses devices.
+///
+/// ### Parameters
+/// - `from_file`: The file to move.
+/// - `to_file`: The destination of the file.
+/// - `rename`: The rename operation.
+///
+/// ### Returns
+/// - `Result<(), anyhow::Error>`: The result of the move.
+fn move_file_with(
+    from_file: &Path,
+    to_file: &Path,
+    rename: impl Fn(&Path, &Path) -> io::Result<()>,
+) -> anyhow::Result<()> {
+    match rename(from_file, to_file) {
+        Err(e) if e.kind() == io::ErrorKind::CrossesDevices => {
+            if let Err(e) = fs::copy(from_file, to_file) {
+                let _ = fs::remove_file(to_file);
+                return Err(anyhow::Error::msg(format!(
+                    "Error copying across devices: {}",
+                    e
+                )));
+            }
+            fs::remove_file(from_file).map_err(|e| {
+                anyhow::Error::msg(format!(
+                    "Copied across devices to {} but the source could not be removed, the file now exists twice: {}",
+                    to_file.display(),
+                    e
+                ))
+            })
+        }
+        result => Ok(result?),
+    }
+}
+
+/// Runs an operation on a file, applying the locked files policy of a recipe when the file is locked by another process.
+/// Without a policy, a locked file fails like any other error.
+///
+/// ### Parameters
+/// - `recipe`: The recipe defining the locked files policy.
+/// - `operation`: The operation to run.
+///
+/// ### Returns
+/// - `Result<bool, anyhow::Error>`: True if the operation has been run, false if the file has been skipped because it is locked.
+fn run_with_locked_policy(
+    recipe: &Recipe,
+    operation: impl Fn() -> anyhow::Result<()>,
+) -> anyhow::Result<bool> {
+    let Some(on_locked) = &recipe.on_locked else {
+        return operation().map(|_| true);
+    };
+    let timeout = Duration::from_secs(recipe.locked_timeout.unwrap_or(DEFAULT_LOCKED_TIMEOUT));
+    let start_time = Instant::now();
+    let mut delay = LOCKED_RETRY_DELAY;
+    let mut attempts = 1;
+    loop {
+        match operation() {
+            Err(e) if is_locked_error(&e) => {
+                let can_retry = match on_locked {
+                    LockedPolicy::Skip => false,
+                    LockedPolicy::Retry => attempts < LOCKED_RETRY_ATTEMPTS,
+                    LockedPolicy::Wait => start_time.elapsed() + delay <= timeout,
+                };
+                if !can_retry {
+                    return Ok(false);
+                }
+                thread::sleep(delay);
+                if *on_locked == LockedPolicy::Retry {
+                    delay *= 2;
+                }
+                attempts += 1;
+            }
+            result => return result.map(|_| true),
+        }
+    }
+}
+
+/// Checks if an error is caused by a file locked by another process.
+///
+/// ### Parameters
+/// - `error`: The error to check.
+///
+/// ### Returns
+/// - `bool`: True if the error is a sharing or lock violation, always false outside of Windows.
+#[cfg(windows)]
+fn is_locked_error(error: &anyhow::Error) -> bool {
+    const ERROR_SHARING_VIOLATION: i32 = 32;
+    const ERROR_LOCK_VIOLATION: i32 = 33;
+    error
+        .downcast_ref::<io::Error>()
+        .and_then(|v| v.raw_os_error())
+        .is_some_and(|v| v == ERROR_SHARING_VIOLATION || v == ERROR_LOCK_VIOLATION)
+}
+
+/// Checks if an error is caused by a file locked by another process.
+///
+/// ### Parameters
+/// - `error`: The error to check.
+///
+/// ### Returns
+/// - `bool`: True if the error is a sharing or lock violation, always false outside of Windows.
+#[cfg(not(windows))]
+fn is_locked_error(_error: &anyhow::Error) -> bool {
+    false
+}
+
+/// Adds the modification dates of the source folders of a recipe moving files, to restore them once the files are moved out.
+/// A folder already added keeps its date, read before any of its files was moved.
+///
+/// ### Parameters
+/// - `recipe`: The recipe.
+/// - `entries`: The entries of the source folder.
+/// - `folders_times`: The folders with their modification date, added to.
+fn add_source_folders_times(
+    recipe: &Recipe,
+    entries: &[DirEntry],
+    folders_times: &mut HashMap<PathBuf, SystemTime>,
+) {
+    if !recipe.preserve_source_dir_times.unwrap_or(false) || recipe.operation != FileOperation::Move
+    {
+        return;
+    }
+    let folders = entries
+        .iter()
+        .filter_map(|entry| entry.path().parent().map(Path::to_path_buf))
+        .chain(recipe.get_source_folders().into_iter().cloned());
+    for folder in folders {
+        if folders_times.contains_key(&folder) {
+            continue;
+        }
+        if let Ok(modified) = fs::metadata(&folder).and_then(|v| v.modified()) {
+            folders_times.insert(folder, modified);
+        }
+    }
+}
+
+/// Restores the modification dates of folders, a warning being printed for the folders failing.
+///
+/// ### Parameters
+/// - `recipe`: The recipe.
+/// - `folders_times`: The folders with their modification date to restore.
+fn restore_folders_times(recipe: &Recipe, folders_times: &HashMap<PathBuf, SystemTime>) {
+    for (folder, modified) in folders_times {
+        if !folder.exists() {
+            continue;
+        }
+        if let Err(e) = set_folder_modified(folder, *modified) {
+            warn!(
+                "{} {} {} - {}: {}",
+                "⚠️".yellow(),
+                recipe.name.blue(),
+                "Folder date not restored".yellow(),
+                folder.display(),
+                e
+            );
+        }
+    }
+}
+
+/// Sets the modification date of a folder.
+///
+/// ### Parameters
+/// - `folder`: The folder.
+/// - `modified`: The modification date.
+///
+/// ### Returns
+/// - `io::Result<()>`: The result of the update.
+#[cfg(not(windows))]
+fn set_folder_modified(folder: &Path, modified: SystemTime) -> io::Result<()> {
+    fs::File::open(folder)?.set_modified(modified)
+}
+
+/// Sets the modification date of a folder, opened with the backup semantics required for folders.
+///
+/// ### Parameters
+/// - `folder`: The folder.
+/// - `modified`: The modification date.
+///
+/// ### Returns
+/// - `io::Result<()>`: The result of the update.
+#[cfg(windows)]
+fn set_folder_modified(folder: &Path, modified: SystemTime) -> io::Result<()> {
+    use std::os::windows::fs::OpenOptionsExt;
+    const FILE_FLAG_BACKUP_SEMANTICS: u32 = 0x02000000;
+    fs::OpenOptions::new()
+        .write(true)
+        .custom_flags(FILE_FLAG_BACKUP_SEMANTICS)
+        .open(folder)?
+        .set_modified(modified)
+}
+
+/// Gets the folders holding the entries of a Move recipe removing the empty folders, the only ones its run can leave empty.
+///
+/// ### Parameters
+/// - `recipe`: The recipe.
+/// - `entries`: The entries of the source folder.
+///
+/// ### Returns
+/// - `HashSet<PathBuf>`: The folders of the entries, empty if the recipe does not remove the empty folders.
+fn get_entries_folders(recipe: &Recipe, entries: &[DirEntry]) -> HashSet<PathBuf> {
+    if !recipe.remove_empty_dirs.unwrap_or(false) || recipe.operation != FileOperation::Move {
+        return HashSet::new();
+    }
+    entries
+        .iter()
+        .filter_map(|entry| entry.path().parent().map(Path::to_path_buf))
+        .collect()
+}
+
+/// Removes the folders left empty by a Move recipe, along with their parents left empty in turn, the source folders themselves being kept.
+/// Only the folders which held the files of the run are removed, the empty folders it did not touch being left as they are.
+///
+/// ### Parameters
+/// - `recipe`: The recipe.
+/// - `entries_folders`: The folders which held the files of the run.
+fn remove_empty_source_folders(recipe: &Recipe, entries_folders: &HashSet<PathBuf>) {
+    let source_folders = recipe.get_source_folders();
+    let mut entries_folders: Vec<&PathBuf> = entries_folders.iter().collect();
+    entries_folders.sort_by_key(|v| std::cmp::Reverse(v.components().count()));
+    for folder in entries_folders {
+        let mut folder = folder.as_path();
+        while !source_folders.iter().any(|v| folder == v.as_path())
+            && source_folders.iter().any(|v| folder.starts_with(v))
+            && fs::remove_dir(folder).is_ok()
+        {
+            let Some(parent) = folder.parent() else {
+                break;
+            };
+            folder = parent;
+        }
+    }
+}
+
+/// Removes a folder and its subfolders if they contain no files.
+///
+/// ### Parameters
+/// - `folder`: The folder to remove.
+///
+/// ### Returns
+/// - `bool`: True if the folder has been removed, false if it still contains files.
+fn remove_empty_folders(folder: &Path) -> bool {
+    let Ok(entries) = fs::read_dir(folder) else {
+        return false;
+    };
+    let mut is_empty = true;
+    for entry in entries.flatten() {
+        let path = entry.path();
+        if !path.is_dir() || path.is_symlink() || !remove_empty_folders(&path) {
+            is_empty = false;
+        }
+    }
+    is_empty && fs::remove_dir(folder).is_ok()
+}
+
+/// Prints the recipe info.
+///
+/// ### Parameters
+/// - `recipe`: The recipe to print the info for.
+/// - `unresolved_destination`: The destination folder before its resolution under the root prefix, checked for a symbolic link.
+fn print_recipe_info(recipe: &Recipe, unresolved_destination: &Path) {
+    info!(
+        "{} {} {} - {}",
+        "ℹ️".green(),
+        recipe.name.blue(),
+        "Source folder".purple(),
+        recipe
+            .get_source_folders()
+            .iter()
+            .map(|v| v.display().to_string())
+            .collect::<Vec<_>>()
+            .join(", ")
+    );
+    info!(
+        "{} {} {} - {}",
+        "ℹ️".green(),
+        recipe.name.blue(),
+        "Target folder".purple(),
+        recipe.destination_folder.display()
+    );
+    if unresolved_destination.is_symlink() {
+        warn!(
+            "{} {} {} - {} resolves to {}",
+            "⚠️".yellow(),
+            recipe.name.blue(),
+            "Target folder is a symlink".yellow(),
+            unresolved_destination.display(),
+            fs::canonicalize(unresolved_destination)
+                .map(|v| v.display().to_string())
+                .unwrap_or_else(|e| e.to_string())
+        );
+    }
+    info!(
+        "{} {} {} - {}",
+        "ℹ️".green(),
+        recipe.name.blue(),
+        "Last run".purple(),
+        recipe.last_run.as_ref().unwrap_or(&"Never".to_string())
+    );
+    info!(
+        "{} {} {} - {}",
+        "ℹ️".green(),
+        recipe.name.blue(),
+        "Mode".purple(),
+        if is_dedupe(recipe) {
+            "Dedupe".to_string()
+        } else if is_compact(recipe) {
+            format!("Compact ({:?})", recipe.operation)
+        } else {
+            format!("{:?}", recipe.operation)
+        }
+    );
+    info!(
+        "{} {} {} - {}",
+        "ℹ️".green(),
+        recipe.name.blue(),
+        "Allowed extensions".purple(),
+        match (
+            &recipe.allowed_extensions,
+            recipe.infer_extensions_from_dest.unwrap_or(false)
+        ) {
+            (Some(allowed_extensions), true) => format!(
+                "{} (inferred from the target folder)",
+                allowed_extensions.join(", ")
+            ),
+            (None, true) => "None (no file in the target folder to infer them from)".to_string(),
+            (Some(allowed_extensions), false) => allowed_extensions.join(", "),
+            (None, false) => "All".to_string(),
+        }
+    );
+    if let Some(name_patterns) = recipe.name_patterns.as_ref().filter(|v| !v.is_empty()) {
+        info!(
+            "{} {} {} - {}",
+            "ℹ️".green(),
+            recipe.name.blue(),
+            "Name patterns".purple(),
+            name_patterns.join(", ")
+        );
+    }
+    info!(
+        "{} {} {} - {}",
+        "ℹ️".green(),
+        recipe.name.blue(),
+        "Subfolders".purple(),
+        recipe
+            .subfolders
+            .as_ref()
+            .map(|v| v.join(", "))
+            .as_ref()
+            .unwrap_or(&"None".to_string())
+    );
+    info!(
+        "{} {} {} - {}",
+        "ℹ️".green(),
+        recipe.name.blue(),
+        "Magic routes".purple(),
+        recipe
+            .magic_routes
+            .as_ref()
+            .map(|v| v
+                .iter()
+                .map(|route| format!("{} → {}", route.magic, route.subfolder))
+                .collect::<Vec<_>>()
+                .join(", "))
+            .as_ref()
+            .unwrap_or(&"None".to_string())
+    );
+    info!(
+        "{} {} {} - {}",
+        "ℹ️".green(),
+        recipe.name.blue(),
+        "In place".purple(),
+        if recipe.in_place.unwrap_or(false) {
+            "Yes"
+        } else {
+            "No"
+        }
+    );
+    if recipe.recursive.unwrap_or(false) {
+        info!(
+            "{} {} {} - {}{}",
+            "ℹ️".green(),
+            recipe.name.blue(),
+            "Recursive".purple(),
+            recipe
+                .max_depth
+                .map(|v| format!("Yes, {} level(s) deep", v))
+                .unwrap_or("Yes".to_string()),
+            if recipe.preserve_subpath.unwrap_or(false) {
+                ", subpaths preserved"
+            } else {
+                ", flattened"
+            }
+        );
+    }
+    if let Some(date_sources) = recipe.date_sources.as_ref().filter(|v| !v.is_empty()) {
+        info!(
+            "{} {} {} - {}",
+            "ℹ️".green(),
+            recipe.name.blue(),
+            "Date sources".purple(),
+            date_sources
+                .iter()
+                .map(|v| format!("{:?}", v))
+                .collect::<Vec<_>>()
+                .join(", ")
+        );
+    } else {
+        info!(
+            "{} {} {} - {:?}",
+            "ℹ️".green(),
+            recipe.name.blue(),
+            "Date comparator".purple(),
+            recipe
+                .date_comparator
+                .as_ref()
+                .unwrap_or(&DateComparator::ModificationDate)
+        );
+    }
+}
+
+/// Prints the extensions of the files skipped because they are not allowed, with their number of files, most frequent first.
+///
+/// ### Parameters
+/// - `recipe`: The recipe that skipped the files.
+/// - `unknown_extensions`: The number of skipped files by extension.
+fn print_unknown_extensions(recipe: &Recipe, unknown_extensions: &HashMap<String, u32>) {
+    if unknown_extensions.is_empty() {
+        return;
+    }
+    let mut unknown_extensions: Vec<_> = unknown_extensions.iter().collect();
+    unknown_extensions.sort_by(|a, b| b.1.cmp(a.1).then(a.0.cmp(b.0)));
+    warn!(
+        "{} {} {} - {}",
+        "⚠️".yellow(),
+        recipe.name.blue(),
+        "Skipped unknown extensions".yellow(),
+        unknown_extensions
+            .iter()
+            .map(|(extension, count)| format!("{} ({})", extension, count))
+            .collect::<Vec<_>>()
+            .join(", ")
+    );
+}
+
+/// Loads the settings of a run, with the extensions of their recipes lowercased.
+///
+/// ### Parameters
+/// - `settings_file_path`: The path to the settings file.
+///
+/// ### Returns
+/// - `Result<Settings, anyhow::Error>`: The settings.
+fn load_settings(settings_file_path: &PathBuf) -> Result<Settings> {
+    let mut settings = Settings::load_from_file(settings_file_path)?;
+    for recipe in &mut settings.recipes {
+        if let Some(allowed_extensions) = &mut recipe.allowed_extensions {
+            for extension in allowed_extensions {
+                *extension = extension.to_lowercase();
+            }
+        }
+        if let Some(create_folders_only_for) = &mut recipe.create_folders_only_for {
+            for extension in create_folders_only_for {
+                *extension = extension.to_lowercase();
+            }
+        }
+    }
+    Ok(settings)
+}
+
+/// Checks if a recipe is enabled.
+///
+/// ### Parameters
+/// - `recipe`: The recipe to check.
+///
+/// ### Returns
+/// - `bool`: True if the recipe is run, which is the default, false if it is disabled.
+fn is_enabled(recipe: &Recipe) -> bool {
+    recipe.enabled.unwrap_or(true)
+}
+
+/// Checks if a recipe is a dedupe recipe.
+///
+/// ### Parameters
+/// - `recipe`: The recipe to check.
+///
+/// ### Returns
+/// - `bool`: True if the recipe moves duplicates aside instead of organizing files, false otherwise.
+fn is_dedupe(recipe: &Recipe) -> bool {
+    recipe.mode.as_ref() == Some(&RecipeMode::Dedupe)
+}
+
+/// Checks if a recipe is a compact recipe.
+///
+/// ### Parameters
+/// - `recipe`: The recipe to check.
+///
+/// ### Returns
+/// - `bool`: True if the recipe bundles the files into dated archives instead of organizing them one by one, false otherwise.
+fn is_compact(recipe: &Recipe) -> bool {
+    recipe.mode.as_ref() == Some(&RecipeMode::Compact)
+}
+
+/// Verifies that a copy has the same content as its source, by comparing their hashes.
+///
+/// ### Parameters
+/// - `from_file`: The source file.
+/// - `dest_file`: The copy.
+/// - `source_hash`: The hash of the source file, if already computed.
+///
+/// ### Returns
+/// - `Result<(), anyhow::Error>`: An error if the hashes differ or cannot be computed.
+fn verify_copy(
+    from_file: &Path,
+    dest_file: &Path,
+    source_hash: Option<&str>,
+) -> anyhow::Result<()> {
+    let source_hash = match source_hash {
+        Some(source_hash) => source_hash.to_string(),
+        None => hash_file(from_file)?,
+    };
+    let dest_hash = hash_file(dest_file)?;
+    if source_hash != dest_hash {
+        return Err(anyhow::Error::msg(format!(
+            "checksum mismatch, {} instead of {}",
+            dest_hash, source_hash
+        )));
+    }
+    Ok(())
+}
+
+/// Adds up the statistics of all the recipes of a run.
+///
+/// ### Parameters
+/// - `recipes_stats`: The name and statistics of each recipe run.
+///
+/// ### Returns
+/// - `FileOrganizerStats`: The files matched, processed and errored, the recipes failed and the elapsed time of all the recipes.
+fn sum_stats(recipes_stats: &[(String, FileOrganizerStats)]) -> FileOrganizerStats {
+    let mut total_stats = FileOrganizerStats::default();
+    for (_, stats) in recipes_stats {
+        total_stats.files_matched += stats.files_matched;
+        total_stats.files_processed += stats.files_processed;
+        total_stats.files_conflicted += stats.files_conflicted;
+        total_stats.files_conflict_skipped += stats.files_conflict_skipped;
+        total_stats.files_duplicate_skipped += stats.files_duplicate_skipped;
+        total_stats.files_locked_skipped += stats.files_locked_skipped;
+        total_stats.files_errored += stats.files_errored;
+        total_stats.recipes_failed += stats.recipes_failed;
+        total_stats.elapsed_time += stats.elapsed_time;
+        total_stats
+            .operations
+            .extend(stats.operations.iter().cloned());
+        total_stats.errors.extend(stats.errors.iter().cloned());
+    }
+    total_stats
+}
+
+/// Prints the statistics of all the recipes of a run added up, when more than one recipe ran.
+///
+/// ### Parameters
+/// - `recipes_stats`: The name and statistics of each recipe run.
+fn print_total_stats(recipes_stats: &[(String, FileOrganizerStats)]) {
+    if recipes_stats.len() < 2 {
+        return;
+    }
+    let total_stats = sum_stats(recipes_stats);
+    info!("{}", "----------------------------------------".blue());
+    info!(
+        "{} {} {} - {}",
+        "✅".green(),
+        "file_organizer".blue(),
+        "Total files matched".purple(),
+        total_stats.files_matched
+    );
+    info!(
+        "{} {} {} - {}",
+        "✅".green(),
+        "file_organizer".blue(),
+        "Total files processed".purple(),
+        total_stats.files_processed
+    );
+    if total_stats.files_duplicate_skipped > 0 {
+        info!(
+            "{} {} {} - {}",
+            "✅".green(),
+            "file_organizer".blue(),
+            "Total duplicates skipped".purple(),
+            total_stats.files_duplicate_skipped
+        );
+    }
+    if total_stats.files_locked_skipped > 0 {
+        warn!(
+            "{} {} {} - {}",
+            "⚠️".yellow(),
+            "file_organizer".blue(),
+            "Total locked files skipped".yellow(),
+            total_stats.files_locked_skipped
+        );
+    }
+    if total_stats.files_errored > 0 {
+        error!(
+            "{} {} {} - {}",
+            "❌".red(),
+            "file_organizer".blue(),
+            "Total files errored".red(),
+            total_stats.files_errored
+        );
+    }
+    if total_stats.recipes_failed > 0 {
+        error!(
+            "{} {} {} - {}",
+            "❌".red(),
+            "file_organizer".blue(),
+            "Total recipes failed".red(),
+            total_stats.recipes_failed
+        );
+    }
+    info!(
+        "{} {} {} - {}",
+        "✅".green(),
+        "file_organizer".blue(),
+        "Total elapsed time".purple(),
+        seconds_to_string(total_stats.elapsed_time / 1000)
+    );
+}
+
+/// Hashes the content of a file.
+///
+/// ### Parameters
+/// - `file`: The file to hash.
+///
+/// ### Returns
+/// - `Result<String, anyhow::Error>`: The hexadecimal SHA-256 hash of the file.
+fn hash_file(file: &Path) -> anyhow::Result<String> {
+    let mut hasher = Sha256::new();
+    io::copy(&mut fs::File::open(file)?, &mut hasher)?;
+    Ok(format!("{:x}", hasher.finalize()))
+}
+
+/// Appends the hashes of organized files to the checksums manifest of the destination folder.
+/// The lines use the `sha256sum` format, with paths relative to the destination folder, so that the manifest can be verified with `sha256sum -c` from there.
+///
+/// ### Parameters
+/// - `recipe`: The recipe the files have been organized by.
+/// - `files`: The organized files, in the destination folder, with their content hash if already computed.
+///
+/// ### Returns
+/// - `Result<(), anyhow::Error>`: The result of the append.
+fn append_to_manifest(recipe: &Recipe, files: &[ManifestEntry]) -> anyhow::Result<()> {
+    if files.is_empty() {
+        return Ok(());
+    }
+    let mut lines = String::new();
+    for (file, hash) in files {
+        let hash = match hash {
+            Some(hash) => hash.clone(),
+            None => hash_file(file)?,
+        };
+        let relative_path = file
+            .strip_prefix(&recipe.destination_folder)
+            .unwrap_or(file)
+            .to_string_lossy();
+        if relative_path.contains(['\\', '\n']) {
+            let escaped_path = relative_path.replace('\\', "\\\\").replace('\n', "\\n");
+            lines.push_str(&format!("\\{}  {}\n", hash, escaped_path));
+        } else {
+            lines.push_str(&format!("{}  {}\n", hash, relative_path));
+        }
+    }
+    let manifest_path = recipe.destination_folder.join(MANIFEST_FILE_NAME);
+    let mut manifest = fs::OpenOptions::new()
+        .create(true)
+        .append(true)
+        .open(&manifest_path)
+        .map_err(|e| {
+            anyhow::Error::msg(format!(
+                "{} - Error opening the manifest {}: {}",
+                recipe.name,
+                manifest_path.display(),
+                e
+            ))
+        })?;
+    manifest.write_all(lines.as_bytes())?;
+    Ok(())
+}
+
+/// Builds the destination folder.
+///
+/// ### Parameters
+/// - `file`: The file to build the destination folder for.
+/// - `recipe`: The recipe to build the destination folder for.
+/// - `route_subfolder`: The subfolder the file has been routed to, if any.
+/// - `last_modification_date`: The last modification date of the file.
+/// - `music_tags`: The music tags of the file, if the subfolders use them. Files without tags go into the fallback folder instead of the subfolders.
+/// - `content_hash`: The content hash of the file, if the subfolders or the file name use it.
+/// - `creates_subfolders`: If false, the subfolders are not created and the file goes into the destination folder, or its route subfolder.
+///
+/// ### Returns
+/// - `PathBuf`: The destination folder.
+fn build_dest_folder(
+    file: &Path,
+    recipe: &Recipe,
+    route_subfolder: Option<&str>,
+    last_modification_date: &DateTime<Utc>,
+    music_tags: Option<&MusicTags>,
+    content_hash: Option<&str>,
+    creates_subfolders: bool,
+) -> PathBuf {
+    let mut dest_folder = get_destination_root(recipe, last_modification_date).to_path_buf();
+    if let Some(route_subfolder) = route_subfolder {
+        dest_folder = dest_folder.join(route_subfolder);
+    }
+    if !creates_subfolders {
+        return dest_folder;
+    }
+    if let Some(music_tags) = music_tags
+        && music_tags.is_empty()
+    {
+        return dest_folder.join(music::get_fallback_folder(recipe));
+    }
+    if let Some(subfolders) = &recipe.subfolders {
+        for subfolder in subfolders {
+            let mut subfolder_name =
+                date_to_folder_name(last_modification_date, &Some(subfolder.clone()));
+            if let Some(music_tags) = music_tags {
+                subfolder_name = music_tags.replace_tokens(&subfolder_name, recipe);
+            }
+            if let Some(content_hash) = content_hash {
+                subfolder_name = replace_hash_tokens(&subfolder_name, content_hash);
+            }
+            if subfolder_name.contains(EXT_TOKEN) {
+                subfolder_name = subfolder_name.replace(
+                    EXT_TOKEN,
+                    &file
+                        .extension()
+                        .map(|v| v.to_string_lossy().to_lowercase())
+                        .unwrap_or(NO_EXTENSION_FOLDER.to_string()),
+                );
+            }
+            if subfolder_name.contains(SIZE_BUCKET_TOKEN) {
+                subfolder_name =
+                    subfolder_name.replace(SIZE_BUCKET_TOKEN, get_size_bucket(file, recipe));
+            }
+            if recipe.ascii_fold_folders.unwrap_or(false) {
+                subfolder_name = deunicode::deunicode(&subfolder_name);
+            }
+            if recipe.sanitize_folder_names.unwrap_or(false) {
+                subfolder_name = sanitize_folder_name(&subfolder_name);
+            }
+            dest_folder = dest_folder.join(subfolder_name);
+        }
+    }
+    dest_folder
+}
+
+/// Gets the size thresholds of the size buckets of a recipe.
+///
+/// ### Parameters
+/// - `recipe`: The recipe.
+///
+/// ### Returns
+/// - `(u64, u64)`: The sizes, in bytes, from which a file is medium and from which it is large.
+fn get_size_bucket_thresholds(recipe: &Recipe) -> (u64, u64) {
+    (
+        recipe.small_size_bytes.unwrap_or(DEFAULT_SMALL_SIZE_BYTES),
+        recipe
+            .medium_size_bytes
+            .unwrap_or(DEFAULT_MEDIUM_SIZE_BYTES),
+    )
+}
+
+/// Gets the size bucket of a file, the subfolder replacing the `{size_bucket}` token.
+///
+/// ### Parameters
+/// - `file`: The file.
+/// - `recipe`: The recipe, setting the thresholds of the buckets.
+///
+/// ### Returns
+/// - `&str`: `small` under small_size_bytes, `medium` under medium_size_bytes, `large` otherwise, a file whose size cannot be read being small.
+fn get_size_bucket(file: &Path, recipe: &Recipe) -> &'static str {
+    let size = fs::metadata(file).map(|v| v.len()).unwrap_or_default();
+    let (small_size_bytes, medium_size_bytes) = get_size_bucket_thresholds(recipe);
+    if size < small_size_bytes {
+        "small"
+    } else if size < medium_size_bytes {
+        "medium"
+    } else {
+        "large"
+    }
+}
+
+/// Flattens the levels of a destination folder exceeding the maximum depth under its root.
+/// The first levels are kept up to the maximum depth, the last one holding the names of the deeper levels joined with `_`, e.g. `2024/03/07/Photos` limited to 2 levels becomes `2024/03_07_Photos`.
+///
+/// ### Parameters
+/// - `dest_folder`: The destination folder.
+/// - `dest_root`: The destination root the depth is counted from.
+/// - `max_dest_depth`: The maximum number of levels under the destination root, at least 1.
+///
+/// ### Returns
+/// - `PathBuf`: The destination folder, unchanged if within the maximum depth or outside of the root.
+fn flatten_dest_folder(dest_folder: &Path, dest_root: &Path, max_dest_depth: usize) -> PathBuf {
+    let Ok(subpath) = dest_folder.strip_prefix(dest_root) else {
+        return dest_folder.to_path_buf();
+    };
+    let levels: Vec<String> = subpath
+        .components()
+        .map(|v| v.as_os_str().to_string_lossy().to_string())
+        .collect();
+    if max_dest_depth == 0 || levels.len() <= max_dest_depth {
+        return dest_folder.to_path_buf();
+    }
+    let (kept_levels, flattened_levels) = levels.split_at(max_dest_depth - 1);
+    let mut flattened_folder = dest_root.to_path_buf();
+    flattened_folder.extend(kept_levels);
+    flattened_folder.push(flattened_levels.join(DEST_DEPTH_SEPARATOR));
+    flattened_folder
+}
+
+/// Gets the destination root of a file, from the year roots of a recipe.
+///
+/// ### Parameters
+/// - `recipe`: The recipe.
+/// - `file_date`: The date of the file.
+///
+/// ### Returns
+/// - `&Path`: The root mapped to the year of the file, the destination folder if the year is not mapped.
+fn get_destination_root<'a>(recipe: &'a Recipe, file_date: &DateTime<Utc>) -> &'a Path {
+    recipe
+        .year_roots
+        .as_ref()
+        .and_then(|v| v.get(&file_date.year().to_string()))
+        .unwrap_or(&recipe.destination_folder)
+}
+
+/// Checks if a file gets the subfolders of a recipe.
+/// When `create_folders_only_for` is set, only the files with one of its extensions do, so that incidental files don't create new subfolders.
+///
+/// ### Parameters
+/// - `file`: The file to check.
+/// - `recipe`: The recipe to check.
+///
+/// ### Returns
+/// - `bool`: True if the file gets the subfolders, false if it goes into the destination folder.
+fn creates_subfolders(file: &Path, recipe: &Recipe) -> bool {
+    let Some(create_folders_only_for) = &recipe.create_folders_only_for else {
+        return true;
+    };
+    file.extension()
+        .and_then(|ext| ext.to_str())
+        .is_some_and(|ext| create_folders_only_for.contains(&ext.to_lowercase()))
+}
+
+/// Converts seconds to a string.
+///
+/// ### Parameters
+/// - `seconds`: The seconds to convert.
+///
+/// ### Returns
+/// - `String`: The string.
+fn seconds_to_string(seconds: i64) -> String {
+    if seconds < 60 {
+        return format!("{}s", seconds);
+    }
+    if seconds < 3600 {
+        return format!("{}m {}s", seconds / 60, seconds % 60);
+    }
+    if seconds < 86400 {
+        return format!(
+            "{}h {}m {}s",
+            seconds / 3600,
+            (seconds % 3600) / 60,
+            seconds % 60
+        );
+    }
+    format!(
+        "{}d {}h {}m {}s",
+        seconds / 86400,
+        (seconds % 86400) / 3600,
+        (seconds % 3600) / 60,
+        seconds % 60
+    )
+}
+
+#[cfg(test)]
+pub(crate) mod tests {
+    use super::*;
+    use serde_json::json;
+    use tempfile::TempDir;
+    use tracing::Level;
+
+    /// Writes a recipe file holding the given recipes.
+    ///
+    /// ### Parameters
+    /// - `folder`: The folder to write the recipe file into.
+    /// - `recipes`: The recipes, as JSON.
+    ///
+    /// ### Returns
+    /// - `PathBuf`: The path to the recipe file.
+    fn write_recipes(folder: &Path, recipes: serde_json::Value) -> PathBuf {
+        let recipes_file = folder.join("recipes.json");
+        fs::write(&recipes_file, json!({ "recipes": recipes }).to_string()).unwrap();
+        recipes_file
+    }
+
+    /// Creates a file with the given content and modification date.
+    ///
+    /// ### Parameters
+    /// - `file`: The path to the file, whose parent folders are created.
+    /// - `content`: The content of the file.
+    /// - `modified`: The modification date, as `YYYY-MM-DD`.
+    pub(crate) fn create_dated_file(file: &Path, content: &str, modified: &str) {
+        fs::create_dir_all(file.parent().unwrap()).unwrap();
+        fs::write(file, content).unwrap();
+        fs::File::options()
+            .write(true)
+            .open(file)
+            .unwrap()
+            .set_modified(parse_day(modified).unwrap().into())
+            .unwrap();
+    }
+
+    /// Prepares the first recipe of a recipe file and builds the context of its dry run, for matching files one by one.
+    ///
+    /// ### Parameters
+    /// - `recipes_file`: The path to the recipe file.
+    ///
+    /// ### Returns
+    /// - `(Recipe, RecipeContext)`: The prepared recipe and its context.
+    fn dry_run_context(recipes_file: PathBuf) -> (Recipe, RecipeContext) {
+        let file_organizer = FileOrganizer::new(recipes_file, RunOptions::default()).unwrap();
+        let recipe = file_organizer
+            .prepare_recipe(&file_organizer.settings.recipes[0])
+            .unwrap();
+        let entries = collect_entries(&recipe).unwrap();
+        let context = file_organizer
+            .build_context(&recipe, &entries, true, false)
+            .unwrap();
+        (recipe, context)
+    }
+
+    /// Plans the recipes of a recipe file and gets the names of the files they would organize.
+    ///
+    /// ### Parameters
+    /// - `recipes_file`: The path to the recipe file.
+    ///
+    /// ### Returns
+    /// - `Vec<String>`: The names of the planned source files, sorted.
+    fn planned_names(recipes_file: PathBuf) -> Vec<String> {
+        let file_organizer = FileOrganizer::new(recipes_file, RunOptions::default()).unwrap();
+        let mut names: Vec<String> = file_organizer
+            .compute_plan(&file_organizer.settings.recipes)
+            .unwrap()
+            .iter()
+            .map(|v| v.source.file_name().unwrap().to_string_lossy().to_string())
+            .collect();
+        names.sort();
+        names
+    }
+
+    #[test]
+    fn in_place_recipe_is_stable_on_second_run() {
+        let root = TempDir::new().unwrap();
+        let inbox = root.path().join("inbox");
+        create_dated_file(&inbox.join("a.txt"), "a", "2021-03-04");
+        create_dated_file(&inbox.join("nested").join("b.txt"), "b", "2022-05-06");
+        let recipes_file = write_recipes(
+            root.path(),
+            json!([{
+                "name": "reorg",
+                "source_folder": inbox,
+                "destination_folder": inbox,
+                "operation": "Move",
+                "in_place": true,
+                "allowed_extensions": ["txt"],
+                "subfolders": ["%Y"]
+            }]),
+        );
+        let options = || RunOptions {
+            is_forced: true,
+            ..Default::default()
+        };
+
+        let stats = FileOrganizer::new(recipes_file.clone(), options())
+            .unwrap()
+            .run()
+            .unwrap();
+        assert_eq!(stats.files_matched, 2);
+        assert!(inbox.join("2021").join("a.txt").is_file());
+        assert!(inbox.join("2022").join("b.txt").is_file());
+
+        let stats = FileOrganizer::new(recipes_file, options())
+            .unwrap()
+            .run()
+            .unwrap();
+        assert_eq!(stats.files_matched, 0);
+        assert_eq!(stats.files_errored, 0);
+        assert!(inbox.join("2021").join("a.txt").is_file());
+        assert!(!inbox.join("2021").join("2021").exists());
+    }
+
+    #[test]
+    fn pinned_files_stay_in_source() {
+        let root = TempDir::new().unwrap();
+        let (source, destination) = (root.path().join("source"), root.path().join("sorted"));
+        fs::create_dir_all(&destination).unwrap();
+        for name in ["listed.pdf", "sidecar.pdf", "free.pdf"] {
+            create_dated_file(&source.join(name), name, "2024-01-01");
+        }
+        fs::write(source.join(PIN_FILE_NAME), "listed.pdf\n").unwrap();
+        fs::write(source.join("sidecar.pdf.keep"), "").unwrap();
+        let recipes_file = write_recipes(
+            root.path(),
+            json!([{
+                "name": "pins",
+                "source_folder": source,
+                "destination_folder": destination,
+                "operation": "Move",
+                "allowed_extensions": ["pdf"]
+            }]),
+        );
+
+        FileOrganizer::new(recipes_file, RunOptions::default())
+            .unwrap()
+            .run()
+            .unwrap();
+
+        assert!(destination.join("free.pdf").is_file());
+        assert!(source.join("listed.pdf").is_file());
+        assert!(source.join("sidecar.pdf").is_file());
+        assert!(!destination.join("listed.pdf").exists());
+        assert!(!destination.join("sidecar.pdf").exists());
+    }
+
+    #[test]
+    fn pinned_files_stay_in_source_when_streamed_in_chunks() {
+        let root = TempDir::new().unwrap();
+        let (source, destination) = (root.path().join("source"), root.path().join("sorted"));
+        fs::create_dir_all(&destination).unwrap();
+        for name in ["listed.pdf", "sidecar.pdf", "free.pdf"] {
+            create_dated_file(&source.join(name), name, "2024-01-01");
+        }
+        fs::write(source.join(PIN_FILE_NAME), "listed.pdf\n").unwrap();
+        fs::write(source.join("sidecar.pdf.keep"), "").unwrap();
+        let recipes_file = write_recipes(
+            root.path(),
+            json!([{
+                "name": "pins",
+                "source_folder": source,
+                "destination_folder": destination,
+                "operation": "Move",
+                "allowed_extensions": ["pdf", "keep"]
+            }]),
+        );
+        let options = RunOptions {
+            chunk_size: Some(1),
+            ..Default::default()
+        };
+
+        // Each chunk holds a single entry, the pin file and the sidecars being read in other chunks.
+        FileOrganizer::new(recipes_file, options)
+            .unwrap()
+            .run()
+            .unwrap();
+
+        assert!(destination.join("free.pdf").is_file());
+        assert!(source.join("listed.pdf").is_file());
+        assert!(source.join("sidecar.pdf").is_file());
+        assert!(source.join("sidecar.pdf.keep").is_file());
+    }
+
+    #[test]
+    fn pins_are_ignored_without_honor_pins() {
+        let root = TempDir::new().unwrap();
+        let source = root.path().join("source");
+        create_dated_file(&source.join("listed.pdf"), "listed", "2024-01-01");
+        fs::write(source.join(PIN_FILE_NAME), "listed.pdf\n").unwrap();
+        let entries: Vec<DirEntry> = fs::read_dir(&source).unwrap().map(|v| v.unwrap()).collect();
+        let recipe = Recipe {
+            source_folder: source.clone(),
+            ..Default::default()
+        };
+
+        let pinned_files = get_pinned_files(&recipe, &entries).unwrap();
+        assert!(pinned_files.contains(&source.join("listed.pdf")));
+
+        let recipe = Recipe {
+            honor_pins: Some(false),
+            ..recipe
+        };
+        assert!(get_pinned_files(&recipe, &entries).unwrap().is_empty());
+    }
+
+    #[test]
+    fn fast_path_places_files_like_the_dated_path() {
+        let root = TempDir::new().unwrap();
+        let (source, destination) = (root.path().join("in"), root.path().join("out"));
+        fs::create_dir_all(&destination).unwrap();
+        create_dated_file(&source.join("photo.JPG"), "jpg", "2019-07-01");
+        create_dated_file(&source.join("notes.md"), "md", "2023-02-01");
+        let recipes_file = write_recipes(
+            root.path(),
+            json!([{
+                "name": "by_extension",
+                "source_folder": source,
+                "destination_folder": destination,
+                "operation": "Copy",
+                "allowed_extensions": ["jpg", "md"],
+                "subfolders": ["{ext}"]
+            }]),
+        );
+        let file_organizer = FileOrganizer::new(recipes_file, RunOptions::default()).unwrap();
+        let recipe = file_organizer
+            .prepare_recipe(&file_organizer.settings.recipes[0])
+            .unwrap();
+        let entries = collect_entries(&recipe).unwrap();
+        let fast_context = file_organizer
+            .build_context(&recipe, &entries, true, true)
+            .unwrap();
+        let mut dated_context = file_organizer
+            .build_context(&recipe, &entries, true, true)
+            .unwrap();
+        dated_context.needs_file_date = true;
+        assert!(!fast_context.needs_file_date);
+
+        for entry in &entries {
+            let fast = match_file(&entry.path(), &recipe, &fast_context)
+                .unwrap()
+                .unwrap();
+            let dated = match_file(&entry.path(), &recipe, &dated_context)
+                .unwrap()
+                .unwrap();
+            assert_eq!(fast.dest_folder, dated.dest_folder);
+            assert_eq!(fast.file_name, dated.file_name);
+        }
+    }
+
+    #[test]
+    fn magic_routes_route_by_leading_bytes() {
+        let root = TempDir::new().unwrap();
+        let (source, destination) = (root.path().join("downloads"), root.path().join("sorted"));
+        fs::create_dir_all(&source).unwrap();
+        fs::create_dir_all(&destination).unwrap();
+        fs::write(source.join("download.bin"), b"\x89PNG\r\n\x1a\nrest").unwrap();
+        fs::write(source.join("invoice"), b"%PDF-1.7 rest").unwrap();
+        fs::write(source.join("notes.txt"), b"plain text").unwrap();
+        fs::write(source.join("unknown.bin"), b"\x00\x01\x02").unwrap();
+        let recipes_file = write_recipes(
+            root.path(),
+            json!([{
+                "name": "magic",
+                "source_folder": source,
+                "destination_folder": destination,
+                "operation": "Move",
+                "allowed_extensions": ["txt"],
+                "magic_routes": [
+                    { "magic": "89 50 4E 47", "subfolder": "Images" },
+                    { "magic": "25504446", "subfolder": "Documents" }
+                ]
+            }]),
+        );
+
+        FileOrganizer::new(recipes_file, RunOptions::default())
+            .unwrap()
+            .run()
+            .unwrap();
+
+        assert!(destination.join("Images").join("download.bin").is_file());
+        assert!(destination.join("Documents").join("invoice").is_file());
+        assert!(destination.join("notes.txt").is_file());
+        assert!(source.join("unknown.bin").is_file());
+    }
+
+    #[test]
+    fn dry_run_annotation_lists_every_satisfied_filter() {
+        let root = TempDir::new().unwrap();
+        let source = root.path().join("reports");
+        fs::create_dir_all(&source).unwrap();
+        fs::write(source.join("report_01.txt"), "twelve bytes").unwrap();
+        let recipes_file = write_recipes(
+            root.path(),
+            json!([{
+                "name": "reports",
+                "source_folder": source,
+                "destination_folder": root.path(),
+                "operation": "Copy",
+                "allowed_extensions": ["txt"],
+                "name_patterns": ["report_*"],
+                "min_size_bytes": 10
+            }]),
+        );
+        let (recipe, context) = dry_run_context(recipes_file);
+
+        let match_result = match_file(&source.join("report_01.txt"), &recipe, &context)
+            .unwrap()
+            .unwrap();
+
+        assert_eq!(
+            match_result.describe_criteria(),
+            "matched size=12, name=report_*, ext=txt"
+        );
+    }
+
+    #[test]
+    fn dry_run_annotation_without_filters() {
+        let match_result = MatchResult {
+            dest_folder: PathBuf::new(),
+            file_name: OsString::new(),
+            content_hash: None,
+            file_date: DateTime::<Utc>::default(),
+            criteria: Vec::new(),
+            counted_file: None,
+        };
+        assert_eq!(match_result.describe_criteria(), "matched all files");
+    }
+
+    #[test]
+    fn forbidden_characters_are_sanitized_in_folder_names() {
+        let root = TempDir::new().unwrap();
+        let (source, destination) = (root.path().join("in"), root.path().join("out"));
+        fs::create_dir_all(&destination).unwrap();
+        create_dated_file(&source.join("scan.png"), "png", "2024-01-15");
+        let recipes_file = write_recipes(
+            root.path(),
+            json!([{
+                "name": "sanitized",
+                "source_folder": source,
+                "destination_folder": destination,
+                "operation": "Copy",
+                "allowed_extensions": ["png"],
+                "subfolders": ["%Y:%m|?"],
+                "sanitize_folder_names": true
+            }]),
+        );
+
+        let stats = FileOrganizer::new(recipes_file, RunOptions::default())
+            .unwrap()
+            .run()
+            .unwrap();
+
+        assert_eq!(stats.files_errored, 0);
+        assert!(destination.join("2024_01__").join("scan.png").is_file());
+    }
+
+    #[test]
+    fn dry_run_counts_clean_conflicting_and_skipped_destinations() {
+        let root = TempDir::new().unwrap();
+        let (source, destination) = (root.path().join("in"), root.path().join("out"));
+        for name in ["fresh.txt", "taken.txt", "also_taken.txt"] {
+            create_dated_file(&source.join(name), "new", "2024-01-01");
+        }
+        create_dated_file(&destination.join("taken.txt"), "old", "2024-01-01");
+        create_dated_file(&destination.join("also_taken.txt"), "old", "2024-01-01");
+
+        for (on_conflict, files_conflict_skipped) in [("Rename", 0), ("Skip", 2)] {
+            let recipes_file = write_recipes(
+                root.path(),
+                json!([{
+                    "name": "conflicts",
+                    "source_folder": source,
+                    "destination_folder": destination,
+                    "operation": "Move",
+                    "allowed_extensions": ["txt"],
+                    "on_conflict": on_conflict
+                }]),
+            );
+            let options = RunOptions {
+                is_dry_run: true,
+                ..Default::default()
+            };
+
+            let file_organizer = FileOrganizer::new(recipes_file, options).unwrap();
+
+            let stats = file_organizer
+                .run_recipe(&file_organizer.settings.recipes[0], &[], None)
+                .unwrap();
+
+            assert_eq!(stats.files_matched - stats.files_conflicted, 1);
+            assert_eq!(stats.files_conflicted, 2);
+            assert_eq!(stats.files_conflict_skipped, files_conflict_skipped);
+        }
+        assert!(source.join("fresh.txt").is_file());
+        assert_eq!(
+            fs::read_to_string(destination.join("taken.txt")).unwrap(),
+            "old"
+        );
+    }
+
+    #[test]
+    fn names_existing_deep_in_the_destination_are_skipped() {
+        let root = TempDir::new().unwrap();
+        let (source, destination) = (root.path().join("in"), root.path().join("out"));
+        create_dated_file(
+            &destination.join("2020").join("03").join("report.pdf"),
+            "organized long ago",
+            "2020-03-01",
+        );
+        create_dated_file(&source.join("report.pdf"), "downloaded again", "2024-06-01");
+        create_dated_file(&source.join("novel.pdf"), "new", "2024-06-01");
+        let recipes_file = write_recipes(
+            root.path(),
+            json!([{
+                "name": "once",
+                "source_folder": source,
+                "destination_folder": destination,
+                "operation": "Move",
+                "allowed_extensions": ["pdf"],
+                "subfolders": ["%Y", "%m"],
+                "skip_if_name_exists_anywhere": true
+            }]),
+        );
+
+        FileOrganizer::new(recipes_file, RunOptions::default())
+            .unwrap()
+            .run()
+            .unwrap();
+
+        assert!(source.join("report.pdf").is_file());
+        assert!(
+            !destination
+                .join("2024")
+                .join("06")
+                .join("report.pdf")
+                .exists()
+        );
+        assert!(
+            destination
+                .join("2024")
+                .join("06")
+                .join("novel.pdf")
+                .is_file()
+        );
+    }
+
+    #[test]
+    fn chunked_run_flushes_metrics_and_log_after_each_chunk() {
+        let root = TempDir::new().unwrap();
+        let (source, destination) = (root.path().join("in"), root.path().join("out"));
+        fs::create_dir_all(&destination).unwrap();
+        for i in 0..5 {
+            create_dated_file(&source.join(format!("{}.log", i)), "log", "2024-02-02");
+        }
+        let recipes_file = write_recipes(
+            root.path(),
+            json!([{
+                "name": "chunks",
+                "source_folder": source,
+                "destination_folder": destination,
+                "operation": "Copy",
+                "allowed_extensions": ["log"]
+            }]),
+        );
+        let (metrics_file, log_file) = (root.path().join("run.prom"), root.path().join("run.log"));
+        let options = RunOptions {
+            chunk_size: Some(2),
+            metrics_file: Some(metrics_file.clone()),
+            log_file: Some(log_file.clone()),
+            ..Default::default()
+        };
+        let file_organizer = FileOrganizer::new(recipes_file, options).unwrap();
+        let processed =
+            regex::Regex::new(r#"organizer_files_processed\{recipe="chunks"\} (\d+)"#).unwrap();
+
+        let (stats, snapshots) = logging::tests::capture_snapshots(
+            "Chunk",
+            move || {
+                let metrics = fs::read_to_string(&metrics_file).unwrap();
+                let log_lines = fs::read_to_string(&log_file).unwrap().lines().count();
+                (
+                    processed.captures(&metrics).unwrap()[1].to_string(),
+                    log_lines,
+                )
+            },
+            || {
+                file_organizer
+                    .run_recipe(&file_organizer.settings.recipes[0], &[], None)
+                    .unwrap()
+            },
+        );
+
+        // The metrics are only written by the chunks here, the run writing them once all the recipes are done.
+        assert_eq!(stats.files_processed, 5);
+        assert_eq!(
+            snapshots,
+            vec![
+                ("2".to_string(), 2),
+                ("4".to_string(), 4),
+                ("5".to_string(), 5)
+            ]
+        );
+    }
+
+    #[test]
+    fn chunked_run_without_metrics_file_flushes_progress_and_patch_after_each_chunk() {
+        let root = TempDir::new().unwrap();
+        let (source, destination) = (root.path().join("in"), root.path().join("out"));
+        fs::create_dir_all(&destination).unwrap();
+        for i in 0..5 {
+            create_dated_file(&source.join(format!("{}.log", i)), "log", "2024-02-02");
+        }
+        let recipes_file = write_recipes(
+            root.path(),
+            json!([{
+                "name": "chunks",
+                "source_folder": source,
+                "destination_folder": destination,
+                "operation": "Move",
+                "allowed_extensions": ["log"]
+            }]),
+        );
+        let progress_file = recipes_file.with_extension(PROGRESS_FILE_EXTENSION);
+        let patch_file = root.path().join("run.patch");
+        let options = RunOptions {
+            chunk_size: Some(2),
+            patch_file: Some(patch_file.clone()),
+            ..Default::default()
+        };
+        let mut file_organizer = FileOrganizer::new(recipes_file, options).unwrap();
+        let snapshot_progress_file = progress_file.clone();
+
+        let (stats, snapshots) = logging::tests::capture_snapshots(
+            "Chunk",
+            move || {
+                let progress: serde_json::Value =
+                    serde_json::from_str(&fs::read_to_string(&snapshot_progress_file).unwrap())
+                        .unwrap();
+                let patch_lines = fs::read_to_string(&patch_file).unwrap().lines().count();
+                (
+                    progress[0]["files_processed"].as_u64().unwrap(),
+                    patch_lines,
+                )
+            },
+            || file_organizer.run().unwrap(),
+        );
+
+        // The first line of the patch file holds the patch, the next ones the operations done so far.
+        assert_eq!(stats.files_processed, 5);
+        assert_eq!(snapshots, vec![(2, 3), (4, 5), (5, 6)]);
+        assert!(!progress_file.exists());
+    }
+
+    #[test]
+    fn unchunked_recipe_does_not_flush_metrics() {
+        let root = TempDir::new().unwrap();
+        let source = root.path().join("in");
+        create_dated_file(&source.join("0.log"), "log", "2024-02-02");
+        let metrics_file = root.path().join("run.prom");
+        let recipes_file = write_recipes(
+            root.path(),
+            json!([{
+                "name": "whole",
+                "source_folder": source,
+                "destination_folder": root.path(),
+                "operation": "Copy",
+                "allowed_extensions": ["log"]
+            }]),
+        );
+        let options = RunOptions {
+            metrics_file: Some(metrics_file.clone()),
+            is_dry_run: true,
+            ..Default::default()
+        };
+        let file_organizer = FileOrganizer::new(recipes_file, options).unwrap();
+
+        file_organizer
+            .run_recipe(&file_organizer.settings.recipes[0], &[], None)
+            .unwrap();
+
+        assert!(!metrics_file.exists());
+    }
+
+    #[test]
+    fn applied_plan_runs_only_the_planned_operations() {
+        let root = TempDir::new().unwrap();
+        let (source, destination) = (root.path().join("in"), root.path().join("out"));
+        fs::create_dir_all(&destination).unwrap();
+        create_dated_file(&source.join("planned.csv"), "planned", "2023-09-09");
+        let recipes_file = write_recipes(
+            root.path(),
+            json!([{
+                "name": "plan",
+                "source_folder": source,
+                "destination_folder": destination,
+                "operation": "Move",
+                "allowed_extensions": ["csv"],
+                "subfolders": ["%Y"]
+            }]),
+        );
+        let plan_file = root.path().join("plan.json");
+        let options = RunOptions {
+            is_dry_run: true,
+            plan_file: Some(plan_file.clone()),
+            ..Default::default()
+        };
+        FileOrganizer::new(recipes_file, options)
+            .unwrap()
+            .run()
+            .unwrap();
+        assert!(source.join("planned.csv").is_file());
+        create_dated_file(&source.join("late.csv"), "late", "2023-09-09");
+
+        let (applied, failed) = plan::apply_plan(&plan_file).unwrap();
+
+        assert_eq!((applied, failed), (1, 0));
+        assert!(destination.join("2023").join("planned.csv").is_file());
+        assert!(source.join("late.csv").is_file());
+        assert!(!destination.join("2023").join("late.csv").exists());
+    }
+
+    #[test]
+    fn read_only_files_are_skipped_with_skip_readonly() {
+        let root = TempDir::new().unwrap();
+        let source = root.path().join("shared");
+        create_dated_file(&source.join("locked.doc"), "read-only", "2024-01-01");
+        create_dated_file(&source.join("open.doc"), "writable", "2024-01-01");
+        let mut permissions = fs::metadata(source.join("locked.doc"))
+            .unwrap()
+            .permissions();
+        permissions.set_readonly(true);
+        fs::set_permissions(source.join("locked.doc"), permissions).unwrap();
+        let recipes_file = write_recipes(
+            root.path(),
+            json!([{
+                "name": "attributes",
+                "source_folder": source,
+                "destination_folder": root.path(),
+                "operation": "Copy",
+                "allowed_extensions": ["doc"],
+                "skip_readonly": true
+            }]),
+        );
+        let (recipe, context) = dry_run_context(recipes_file);
+
+        let locked = match_file(&source.join("locked.doc"), &recipe, &context).unwrap();
+        let open = match_file(&source.join("open.doc"), &recipe, &context).unwrap();
+
+        assert!(locked.is_none());
+        assert!(
+            open.unwrap()
+                .criteria
+                .contains(&"not read-only".to_string())
+        );
+        let recipe = Recipe {
+            skip_readonly: None,
+            ..recipe
+        };
+        assert!(
+            match_file(&source.join("locked.doc"), &recipe, &context)
+                .unwrap()
+                .is_some()
+        );
+    }
+
+    #[test]
+    fn manifest_lines_verify_against_the_organized_files() {
+        let root = TempDir::new().unwrap();
+        let (source, archive) = (root.path().join("scans"), root.path().join("archive"));
+        fs::create_dir_all(&archive).unwrap();
+        create_dated_file(&source.join("one.tif"), "first scan", "2022-04-01");
+        create_dated_file(&source.join("two.tif"), "second scan", "2023-04-01");
+        let recipes_file = write_recipes(
+            root.path(),
+            json!([{
+                "name": "manifest",
+                "source_folder": source,
+                "destination_folder": archive,
+                "operation": "Move",
+                "allowed_extensions": ["tif"],
+                "subfolders": ["%Y"],
+                "write_manifest": true
+            }]),
+        );
+
+        FileOrganizer::new(recipes_file, RunOptions::default())
+            .unwrap()
+            .run()
+            .unwrap();
+
+        let manifest = fs::read_to_string(archive.join(MANIFEST_FILE_NAME)).unwrap();
+        let mut verified: Vec<&str> = Vec::new();
+        for line in manifest.lines() {
+            let (hash, relative_path) = line.split_once("  ").unwrap();
+            let content = fs::read(archive.join(relative_path)).unwrap();
+            assert_eq!(hash, format!("{:x}", Sha256::digest(&content)));
+            verified.push(relative_path);
+        }
+        verified.sort();
+        assert_eq!(verified, ["2022/one.tif", "2023/two.tif"]);
+    }
+
+    #[test]
+    fn accented_folder_names_are_folded_to_ascii() {
+        let recipe = Recipe {
+            destination_folder: PathBuf::from("/music"),
+            subfolders: Some(vec!["Beyoncé".to_string(), "%Y".to_string()]),
+            ascii_fold_folders: Some(true),
+            ..Default::default()
+        };
+        let file_date = parse_day("2024-03-03").unwrap();
+        let build = |recipe: &Recipe| {
+            build_dest_folder(
+                Path::new("/in/song.flac"),
+                recipe,
+                None,
+                &file_date,
+                None,
+                None,
+                true,
+            )
+        };
+
+        assert_eq!(build(&recipe), PathBuf::from("/music/Beyonce/2024"));
+        let recipe = Recipe {
+            ascii_fold_folders: None,
+            ..recipe
+        };
+        assert_eq!(build(&recipe), PathBuf::from("/music/Beyoncé/2024"));
+    }
+
+    #[test]
+    fn only_listed_types_create_date_folders() {
+        let root = TempDir::new().unwrap();
+        let (source, destination) = (root.path().join("camera"), root.path().join("library"));
+        fs::create_dir_all(&destination).unwrap();
+        create_dated_file(&source.join("IMG_1.JPG"), "photo", "2020-12-24");
+        create_dated_file(&source.join("readme.txt"), "text", "2020-12-24");
+        let recipes_file = write_recipes(
+            root.path(),
+            json!([{
+                "name": "folders",
+                "source_folder": source,
+                "destination_folder": destination,
+                "operation": "Copy",
+                "allowed_extensions": ["jpg", "txt"],
+                "subfolders": ["%Y", "%m"],
+                "create_folders_only_for": ["JPG"]
+            }]),
+        );
+
+        FileOrganizer::new(recipes_file, RunOptions::default())
+            .unwrap()
+            .run()
+            .unwrap();
+
+        assert!(
+            destination
+                .join("2020")
+                .join("12")
+                .join("IMG_1.JPG")
+                .is_file()
+        );
+        assert!(destination.join("readme.txt").is_file());
+        assert!(
+            !destination
+                .join("2020")
+                .join("12")
+                .join("readme.txt")
+                .exists()
+        );
+    }
+
+    #[test]
+    fn confirmed_plan_runs_only_while_unchanged() {
+        let root = TempDir::new().unwrap();
+        let (source, destination) = (root.path().join("in"), root.path().join("out"));
+        fs::create_dir_all(&destination).unwrap();
+        create_dated_file(&source.join("a.mkv"), "a", "2024-01-01");
+        let recipes_file = write_recipes(
+            root.path(),
+            json!([{
+                "name": "confirm",
+                "source_folder": source,
+                "destination_folder": destination,
+                "operation": "Move",
+                "allowed_extensions": ["mkv"]
+            }]),
+        );
+        let preview_hash = || {
+            let file_organizer =
+                FileOrganizer::new(recipes_file.clone(), RunOptions::default()).unwrap();
+            plan::hash_plan(
+                &file_organizer
+                    .compute_plan(&file_organizer.settings.recipes)
+                    .unwrap(),
+            )
+        };
+        let run_confirmed = |plan_hash: String| {
+            let options = RunOptions {
+                confirm_plan: Some(plan_hash),
+                ..Default::default()
+            };
+            FileOrganizer::new(recipes_file.clone(), options)
+                .unwrap()
+                .run()
+        };
+
+        let plan_hash = preview_hash();
+        create_dated_file(&source.join("b.mkv"), "b", "2024-01-01");
+        let error = run_confirmed(plan_hash).map(|_| ()).unwrap_err();
+        assert!(
+            error
+                .to_string()
+                .starts_with("The plan changed since it was previewed")
+        );
+        assert!(source.join("a.mkv").is_file());
+
+        run_confirmed(preview_hash()).unwrap();
+        assert!(destination.join("a.mkv").is_file());
+        assert!(destination.join("b.mkv").is_file());
+    }
+
+    #[test]
+    fn reference_file_splits_newer_and_older_files() {
+        let root = TempDir::new().unwrap();
+        let source = root.path().join("in");
+        let reference_file = root.path().join("last_backup");
+        create_dated_file(&reference_file, "", "2023-06-01");
+        create_dated_file(&source.join("before.dat"), "", "2023-01-01");
+        create_dated_file(&source.join("same.dat"), "", "2023-06-01");
+        create_dated_file(&source.join("after.dat"), "", "2023-12-01");
+        let recipe = |reference_filter: &str| {
+            json!([{
+                "name": reference_filter,
+                "source_folder": source,
+                "destination_folder": root.path(),
+                "operation": "Copy",
+                "allowed_extensions": ["dat"],
+                "reference_file": reference_file,
+                "reference_filter": reference_filter
+            }])
+        };
+
+        let newer = planned_names(write_recipes(root.path(), recipe("NewerThan")));
+        let older = planned_names(write_recipes(root.path(), recipe("OlderThan")));
+
+        assert_eq!(newer, ["after.dat", "same.dat"]);
+        assert_eq!(older, ["before.dat"]);
+    }
+
+    #[test]
+    fn weekday_filter_keeps_only_weekend_files() {
+        let root = TempDir::new().unwrap();
+        let source = root.path().join("in");
+        for (name, day) in [
+            ("saturday.jpg", "2024-06-01"),
+            ("sunday.jpg", "2024-06-02"),
+            ("monday.jpg", "2024-06-03"),
+            ("wednesday.jpg", "2024-06-05"),
+        ] {
+            create_dated_file(&source.join(name), name, day);
+        }
+        let recipes_file = write_recipes(
+            root.path(),
+            json!([{
+                "name": "weekends",
+                "source_folder": source,
+                "destination_folder": root.path(),
+                "operation": "Copy",
+                "allowed_extensions": ["jpg"],
+                "weekday_filter": ["Sat", "sunday"]
+            }]),
+        );
+
+        assert_eq!(planned_names(recipes_file), ["saturday.jpg", "sunday.jpg"]);
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn symlinked_destination_is_followed_with_a_warning() {
+        let root = TempDir::new().unwrap();
+        let (source, target) = (root.path().join("in"), root.path().join("nas"));
+        fs::create_dir_all(&target).unwrap();
+        std::os::unix::fs::symlink(&target, root.path().join("link")).unwrap();
+        create_dated_file(&source.join("movie.mp4"), "movie", "2024-01-01");
+        let recipe = |allow_symlink_dest: bool| {
+            json!([{
+                "name": "symlinked",
+                "source_folder": source,
+                "destination_folder": root.path().join("link"),
+                "operation": "Copy",
+                "allowed_extensions": ["mp4"],
+                "allow_symlink_dest": allow_symlink_dest
+            }])
+        };
+
+        let recipes_file = write_recipes(root.path(), recipe(false));
+        let error = FileOrganizer::new(recipes_file, RunOptions::default())
+            .unwrap()
+            .run()
+            .map(|_| ())
+            .unwrap_err();
+        assert!(
+            error
+                .to_string()
+                .contains("Target folder is a symlink and allow_symlink_dest is false")
+        );
+
+        let recipes_file = write_recipes(root.path(), recipe(true));
+        let (stats, output) = logging::tests::capture_output(|| {
+            FileOrganizer::new(recipes_file, RunOptions::default())
+                .unwrap()
+                .run()
+                .unwrap()
+        });
+        assert_eq!(stats.files_matched, 1);
+        assert!(output.contains(&format!(
+            "Target folder is a symlink - {} resolves to {}",
+            root.path().join("link").display(),
+            fs::canonicalize(&target).unwrap().display()
+        )));
+        assert!(target.join("movie.mp4").is_file());
+    }
+
+    #[test]
+    fn tag_rules_route_files_into_the_folder_of_their_first_matching_rule() {
+        let root = TempDir::new().unwrap();
+        let inbox = root.path().join("inbox");
+        let sorted = root.path().join("sorted");
+        fs::create_dir(&sorted).unwrap();
+        create_dated_file(&inbox.join("invoice_march.pdf"), "a", "2024-03-01");
+        create_dated_file(&inbox.join("scan_receipt.pdf"), "b", "2024-03-02");
+        create_dated_file(&inbox.join("notes.pdf"), "c", "2024-03-03");
+        let rules_file = root.path().join("rules.json");
+        fs::write(
+            &rules_file,
+            json!({
+                "rules": [
+                    {"pattern": "^invoice_", "folder": "Invoices"},
+                    {"pattern": "^scan_", "folder": "Scans"}
+                ],
+                "default_folder": "Unsorted"
+            })
+            .to_string(),
+        )
+        .unwrap();
+        let recipes_file = write_recipes(
+            root.path(),
+            json!([{
+                "name": "tags",
+                "source_folder": inbox,
+                "destination_folder": sorted,
+                "operation": "Move",
+                "allowed_extensions": ["pdf"],
+                "tag_rules": rules_file
+            }]),
+        );
+
+        let stats = FileOrganizer::new(recipes_file, RunOptions::default())
+            .unwrap()
+            .run()
+            .unwrap();
+
+        assert_eq!(stats.files_processed, 3);
+        assert!(sorted.join("Invoices").join("invoice_march.pdf").is_file());
+        assert!(sorted.join("Scans").join("scan_receipt.pdf").is_file());
+        assert!(sorted.join("Unsorted").join("notes.pdf").is_file());
+    }
+
+    #[test]
+    fn watched_files_are_organized_with_the_pins_and_keep_recent_of_their_folder() {
+        let root = TempDir::new().unwrap();
+        let (downloads, archive) = (root.path().join("downloads"), root.path().join("archive"));
+        fs::create_dir(&archive).unwrap();
+        create_dated_file(&downloads.join("recent.pdf"), "recent", "2999-01-01");
+        fs::write(downloads.join("pinned.pdf.keep"), "").unwrap();
+        let recipes_file = write_recipes(
+            root.path(),
+            json!([{
+                "name": "downloads",
+                "source_folder": downloads,
+                "destination_folder": archive,
+                "operation": "Move",
+                "allowed_extensions": ["pdf"],
+                "keep_recent": 1
+            }]),
+        );
+
+        std::thread::spawn(move || {
+            FileOrganizer::new(recipes_file, RunOptions::default())
+                .unwrap()
+                .watch()
+        });
+        std::thread::sleep(Duration::from_secs(1));
+        fs::write(downloads.join("pinned.pdf"), "pinned").unwrap();
+        fs::write(downloads.join("scan.pdf"), "scan").unwrap();
+        let deadline = Instant::now() + Duration::from_secs(20);
+        while !archive.join("scan.pdf").is_file() && Instant::now() < deadline {
+            std::thread::sleep(watch::WATCH_POLL_INTERVAL);
+        }
+
+        assert!(archive.join("scan.pdf").is_file());
+        assert!(downloads.join("pinned.pdf").is_file());
+        assert!(downloads.join("recent.pdf").is_file());
+        assert!(!archive.join("pinned.pdf").exists());
+    }
+
+    #[test]
+    fn keep_recent_leaves_the_newest_files_in_the_source() {
+        let root = TempDir::new().unwrap();
+        let downloads = root.path().join("downloads");
+        let archive = root.path().join("archive");
+        fs::create_dir(&archive).unwrap();
+        for day in 1..=8 {
+            create_dated_file(
+                &downloads.join(format!("file{}.zip", day)),
+                "zip",
+                &format!("2024-05-0{}", day),
+            );
+        }
+        let recipes_file = write_recipes(
+            root.path(),
+            json!([{
+                "name": "downloads",
+                "source_folder": downloads,
+                "destination_folder": archive,
+                "operation": "Move",
+                "allowed_extensions": ["zip"],
+                "keep_recent": 3
+            }]),
+        );
+
+        let stats = FileOrganizer::new(recipes_file, RunOptions::default())
+            .unwrap()
+            .run()
+            .unwrap();
+
+        assert_eq!(stats.files_matched, 5);
+        for day in 1..=5 {
+            assert!(archive.join(format!("file{}.zip", day)).is_file());
+        }
+        for day in 6..=8 {
+            assert!(downloads.join(format!("file{}.zip", day)).is_file());
+        }
+    }
+
+    #[test]
+    fn hash_tokens_use_the_content_hash_of_the_file() {
+        let root = TempDir::new().unwrap();
+        let (photos, store) = (root.path().join("photos"), root.path().join("store"));
+        fs::create_dir(&store).unwrap();
+        create_dated_file(&photos.join("beach.jpg"), "sand", "2024-07-01");
+        create_dated_file(&photos.join("beach_copy.jpg"), "sand", "2024-07-02");
+        create_dated_file(&photos.join("forest.jpg"), "trees", "2024-07-03");
+        let recipes_file = write_recipes(
+            root.path(),
+            json!([{
+                "name": "content_addressed",
+                "source_folder": photos,
+                "destination_folder": store,
+                "operation": "Copy",
+                "allowed_extensions": ["jpg"],
+                "subfolders": ["{hash2}"],
+                "file_name_template": "{hash8}_{name}"
+            }]),
+        );
+
+        FileOrganizer::new(recipes_file, RunOptions::default())
+            .unwrap()
+            .run()
+            .unwrap();
+
+        let sand_hash = format!("{:x}", Sha256::digest("sand"));
+        let trees_hash = format!("{:x}", Sha256::digest("trees"));
+        for (hash, name) in [
+            (&sand_hash, "beach.jpg"),
+            (&sand_hash, "beach_copy.jpg"),
+            (&trees_hash, "forest.jpg"),
+        ] {
+            let organized = store
+                .join(&hash[..2])
+                .join(format!("{}_{}", &hash[..8], name));
+            assert!(organized.is_file(), "{} is missing", organized.display());
+        }
+    }
+
+    #[test]
+    fn pipeline_chains_the_recipes_through_the_staging_folder() {
+        let root = TempDir::new().unwrap();
+        let (inbox, library) = (root.path().join("inbox"), root.path().join("library"));
+        let stage_dir = root.path().join("stage");
+        fs::create_dir(&library).unwrap();
+        create_dated_file(&inbox.join("song.mp3"), "mp3", "2023-11-20");
+        create_dated_file(&inbox.join("clip.mp4"), "mp4", "2024-02-14");
+        create_dated_file(&inbox.join("readme.txt"), "txt", "2024-02-14");
+        let recipes_file = root.path().join("recipes.json");
+        fs::write(
+            &recipes_file,
+            json!({
+                "pipeline": true,
+                "stage_dir": stage_dir,
+                "recipes": [
+                    {
+                        "name": "extract",
+                        "source_folder": inbox,
+                        "destination_folder": library,
+                        "operation": "Move",
+                        "allowed_extensions": ["mp3", "mp4"],
+                        "subfolders": ["{ext}"]
+                    },
+                    {
+                        "name": "classify",
+                        "source_folder": inbox,
+                        "destination_folder": library,
+                        "operation": "Move",
+                        "allowed_extensions": [],
+                        "subfolders": ["%Y"]
+                    }
+                ]
+            })
+            .to_string(),
+        )
+        .unwrap();
+
+        let stats = FileOrganizer::new(recipes_file, RunOptions::default())
+            .unwrap()
+            .run()
+            .unwrap();
+
+        assert_eq!(stats.recipes_failed, 0);
+        assert!(library.join("2023").join("song.mp3").is_file());
+        assert!(library.join("2024").join("clip.mp4").is_file());
+        assert!(inbox.join("readme.txt").is_file());
+        assert!(!stage_dir.exists());
+    }
+
+    /// Lists the folders and files under a folder with the content of the files, to compare the state of the folder before and after a run.
+    ///
+    /// ### Parameters
+    /// - `folder`: The folder to list.
+    ///
+    /// ### Returns
+    /// - `Vec<(PathBuf, Option<Vec<u8>>)>`: The paths relative to the folder, with the content of the files and None for the folders, sorted.
+    fn snapshot_folder(folder: &Path) -> Vec<(PathBuf, Option<Vec<u8>>)> {
+        let mut snapshot = Vec::new();
+        let mut folders = vec![folder.to_path_buf()];
+        while let Some(current) = folders.pop() {
+            for entry in fs::read_dir(&current).unwrap() {
+                let path = entry.unwrap().path();
+                let relative = path.strip_prefix(folder).unwrap().to_path_buf();
+                if path.is_dir() {
+                    snapshot.push((relative, None));
+                    folders.push(path);
+                } else {
+                    snapshot.push((relative, Some(fs::read(&path).unwrap())));
+                }
+            }
+        }
+        snapshot.sort();
+        snapshot
+    }
+
+    #[test]
+    fn reverting_the_patch_restores_the_state_before_the_run() {
+        let root = TempDir::new().unwrap();
+        let (inbox, sorted) = (root.path().join("inbox"), root.path().join("sorted"));
+        create_dated_file(&inbox.join("report.txt"), "new report", "2024-04-10");
+        create_dated_file(&inbox.join("todo.txt"), "todo", "2023-01-05");
+        create_dated_file(
+            &sorted.join("2024").join("report.txt"),
+            "old report",
+            "2024-01-01",
+        );
+        let recipes_file = write_recipes(
+            root.path(),
+            json!([{
+                "name": "yearly",
+                "source_folder": inbox,
+                "destination_folder": sorted,
+                "operation": "Move",
+                "allowed_extensions": ["txt"],
+                "subfolders": ["%Y"],
+                "on_conflict": "Overwrite"
+            }]),
+        );
+        let last_run = || {
+            Settings::load_from_file(&recipes_file).unwrap().recipes[0]
+                .last_run
+                .clone()
+        };
+        let (inbox_before, sorted_before) = (snapshot_folder(&inbox), snapshot_folder(&sorted));
+        let last_run_before = last_run();
+        let patch_file = root.path().join("run.patch");
+
+        let stats = FileOrganizer::new(
+            recipes_file.clone(),
+            RunOptions {
+                patch_file: Some(patch_file.clone()),
+                ..Default::default()
+            },
+        )
+        .unwrap()
+        .run()
+        .unwrap();
+        assert_eq!(stats.files_matched, 2);
+        assert_eq!(
+            fs::read_to_string(sorted.join("2024").join("report.txt")).unwrap(),
+            "new report"
+        );
+        assert!(sorted.join("2023").join("todo.txt").is_file());
+        assert_ne!(last_run(), last_run_before);
+
+        assert_eq!(patch::revert_patch(&patch_file).unwrap(), (2, 0));
+
+        assert_eq!(snapshot_folder(&inbox), inbox_before);
+        assert_eq!(snapshot_folder(&sorted), sorted_before);
+        assert_eq!(last_run(), last_run_before);
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn only_own_files_skips_the_files_of_other_users() {
+        let root = TempDir::new().unwrap();
+        let (shared, mine) = (root.path().join("shared"), root.path().join("mine"));
+        fs::create_dir(&mine).unwrap();
+        create_dated_file(&shared.join("budget.ods"), "budget", "2024-06-01");
+        let recipes_file = write_recipes(
+            root.path(),
+            json!([{
+                "name": "own_files",
+                "source_folder": shared,
+                "destination_folder": mine,
+                "operation": "Move",
+                "allowed_extensions": ["ods"],
+                "only_own_files": true
+            }]),
+        );
+        let (recipe, mut context) = dry_run_context(recipes_file);
+        let budget = shared.join("budget.ods");
+
+        let own_match = match_file(&budget, &recipe, &context).unwrap().unwrap();
+        assert!(own_match.criteria.contains(&"owned".to_string()));
+
+        context.effective_uid = context.effective_uid.map(|v| v.wrapping_add(1));
+        assert!(match_file(&budget, &recipe, &context).unwrap().is_none());
+    }
+
+    #[test]
+    fn file_events_are_recorded_in_their_recipe_and_file_spans() {
+        let root = TempDir::new().unwrap();
+        let (scans, documents) = (root.path().join("scans"), root.path().join("documents"));
+        fs::create_dir(&documents).unwrap();
+        create_dated_file(&scans.join("lease.pdf"), "lease", "2024-08-01");
+        create_dated_file(&scans.join("cover.png"), "cover", "2024-08-02");
+        let recipes_file = write_recipes(
+            root.path(),
+            json!([{
+                "name": "scans",
+                "source_folder": scans,
+                "destination_folder": documents,
+                "operation": "Move",
+                "allowed_extensions": ["pdf"]
+            }]),
+        );
+        let options = RunOptions {
+            is_iterative: true,
+            ..Default::default()
+        };
+
+        let (stats, events) = logging::tests::capture_json_events(|| {
+            FileOrganizer::new(recipes_file, options)
+                .unwrap()
+                .run()
+                .unwrap()
+        });
+
+        assert_eq!(stats.files_matched, 1);
+        for file in [scans.join("lease.pdf"), scans.join("cover.png")] {
+            let file_events: Vec<&serde_json::Value> = events
+                .iter()
+                .filter(|v| {
+                    v["spans"]
+                        .as_array()
+                        .is_some_and(|v| v.iter().any(|v| v["path"] == *file.to_string_lossy()))
+                })
+                .collect();
+            assert!(!file_events.is_empty(), "no event for {}", file.display());
+            for event in file_events {
+                let spans = event["spans"].as_array().unwrap();
+                assert_eq!(spans.len(), 2);
+                assert_eq!(spans[0]["name"], "recipe");
+                assert_eq!(spans[0]["recipe"], "scans");
+                assert_eq!(spans[1]["name"], "file");
+            }
+        }
+    }
+
+    #[test]
+    fn templates_dropping_the_extension_are_rejected() {
+        let root = TempDir::new().unwrap();
+        let (exports, archive) = (root.path().join("exports"), root.path().join("archive"));
+        fs::create_dir(&archive).unwrap();
+        create_dated_file(&exports.join("Budget.CSV"), "a,b", "2024-09-09");
+        let run_with_template = |template: &str| {
+            let recipes_file = write_recipes(
+                root.path(),
+                json!([{
+                    "name": "exports",
+                    "source_folder": exports,
+                    "destination_folder": archive,
+                    "operation": "Copy",
+                    "allowed_extensions": ["csv"],
+                    "file_name_template": template,
+                    "require_dest_extension": true
+                }]),
+            );
+            FileOrganizer::new(recipes_file, RunOptions::default())
+                .unwrap()
+                .run()
+                .unwrap()
+        };
+
+        let stats = run_with_template("{original}_final");
+        assert_eq!(stats.files_errored, 1);
+        assert_eq!(fs::read_dir(&archive).unwrap().count(), 0);
+
+        let stats = run_with_template("{original}_final.{ext}");
+        assert_eq!(stats.files_errored, 0);
+        assert!(archive.join("Budget_final.CSV").is_file());
+    }
+
+    #[test]
+    fn second_same_day_run_only_processes_new_arrivals() {
+        let root = TempDir::new().unwrap();
+        let (camera, backup) = (root.path().join("camera"), root.path().join("backup"));
+        fs::create_dir_all(&camera).unwrap();
+        fs::create_dir(&backup).unwrap();
+        fs::write(camera.join("morning.jpg"), "morning").unwrap();
+        let recipes_file = write_recipes(
+            root.path(),
+            json!([{
+                "name": "camera_backup",
+                "source_folder": camera,
+                "destination_folder": backup,
+                "operation": "Copy",
+                "allowed_extensions": ["jpg"],
+                "track_last_seen": true
+            }]),
+        );
+        let run = || {
+            FileOrganizer::new(recipes_file.clone(), RunOptions::default())
+                .unwrap()
+                .run()
+                .unwrap()
+        };
+
+        assert_eq!(run().files_matched, 1);
+        // The status change times come from a coarse clock, which may lag behind the scan date of the first run.
+        std::thread::sleep(Duration::from_millis(50));
+        fs::write(camera.join("evening.jpg"), "evening").unwrap();
+        fs::remove_file(backup.join("morning.jpg")).unwrap();
+
+        let stats = run();
+
+        assert_eq!(stats.files_matched, 1);
+        assert!(backup.join("evening.jpg").is_file());
+        assert!(!backup.join("morning.jpg").exists());
+    }
+
+    #[test]
+    fn cross_device_moves_fall_back_to_copy_and_remove() {
+        let root = TempDir::new().unwrap();
+        let (from_file, to_file) = (root.path().join("video.mkv"), root.path().join("moved.mkv"));
+        fs::write(&from_file, "frames").unwrap();
+        let cross_device = |_: &Path, _: &Path| Err(io::Error::from(io::ErrorKind::CrossesDevices));
+
+        move_file_with(&from_file, &to_file, cross_device).unwrap();
+
+        assert!(!from_file.exists());
+        assert_eq!(fs::read_to_string(&to_file).unwrap(), "frames");
+    }
+
+    #[test]
+    fn failed_cross_device_copy_keeps_the_source() {
+        let root = TempDir::new().unwrap();
+        let from_file = root.path().join("video.mkv");
+        let to_file = root.path().join("missing").join("moved.mkv");
+        fs::write(&from_file, "frames").unwrap();
+        let cross_device = |_: &Path, _: &Path| Err(io::Error::from(io::ErrorKind::CrossesDevices));
+
+        let error = move_file_with(&from_file, &to_file, cross_device).unwrap_err();
+
+        assert!(
+            error
+                .to_string()
+                .starts_with("Error copying across devices")
+        );
+        assert!(from_file.is_file());
+    }
+
+    #[test]
+    fn other_rename_errors_are_not_retried_as_copies() {
+        let root = TempDir::new().unwrap();
+        let (from_file, to_file) = (root.path().join("video.mkv"), root.path().join("moved.mkv"));
+        fs::write(&from_file, "frames").unwrap();
+        let denied = |_: &Path, _: &Path| Err(io::Error::from(io::ErrorKind::PermissionDenied));
+
+        assert!(move_file_with(&from_file, &to_file, denied).is_err());
+
+        assert!(from_file.is_file());
+        assert!(!to_file.exists());
+    }
+
+    #[test]
+    fn recursive_scan_honors_the_depth_cap_and_skips_hidden_folders() {
+        let root = TempDir::new().unwrap();
+        let (projects, notes) = (root.path().join("projects"), root.path().join("notes"));
+        fs::create_dir(&notes).unwrap();
+        for file in [
+            "top.md",
+            "level1/one.md",
+            "level1/level2/two.md",
+            ".cache/hidden.md",
+            "level1/.git/config.md",
+        ] {
+            create_dated_file(&projects.join(file), file, "2024-10-01");
+        }
+        let recipe = |max_depth: Option<u32>| {
+            json!([{
+                "name": "notes",
+                "source_folder": projects,
+                "destination_folder": notes,
+                "operation": "Move",
+                "allowed_extensions": ["md"],
+                "recursive": true,
+                "max_depth": max_depth
+            }])
+        };
+
+        assert_eq!(
+            planned_names(write_recipes(root.path(), recipe(None))),
+            ["one.md", "top.md", "two.md"]
+        );
+        assert_eq!(
+            planned_names(write_recipes(root.path(), recipe(Some(1)))),
+            ["one.md", "top.md"]
+        );
+        assert_eq!(
+            planned_names(write_recipes(root.path(), recipe(Some(0)))),
+            ["top.md"]
+        );
+    }
+
+    #[test]
+    fn recursive_scan_flattens_or_preserves_the_subpath() {
+        let root = TempDir::new().unwrap();
+        let projects = root.path().join("projects");
+        create_dated_file(
+            &projects.join("a").join("b").join("deep.md"),
+            "deep",
+            "2024-10-01",
+        );
+        create_dated_file(&projects.join("flat.md"), "flat", "2024-10-01");
+        for preserve_subpath in [false, true] {
+            let notes = root.path().join(format!("notes_{}", preserve_subpath));
+            fs::create_dir(&notes).unwrap();
+            let recipes_file = write_recipes(
+                root.path(),
+                json!([{
+                    "name": "notes",
+                    "source_folder": projects,
+                    "destination_folder": notes,
+                    "operation": "Copy",
+                    "allowed_extensions": ["md"],
+                    "recursive": true,
+                    "preserve_subpath": preserve_subpath,
+                    "subfolders": ["%Y"]
+                }]),
+            );
+
+            FileOrganizer::new(recipes_file, RunOptions::default())
+                .unwrap()
+                .run()
+                .unwrap();
+
+            let year = notes.join("2024");
+            assert!(year.join("flat.md").is_file());
+            if preserve_subpath {
+                assert!(year.join("a").join("b").join("deep.md").is_file());
+            } else {
+                assert!(year.join("deep.md").is_file());
+            }
+        }
+    }
+
+    /// Builds an operation failing with a sharing violation a number of times before succeeding, counting its attempts.
+    ///
+    /// ### Parameters
+    /// - `failures`: The number of attempts failing.
+    /// - `attempts`: The counter of the attempts.
+    ///
+    /// ### Returns
+    /// - `impl Fn() -> anyhow::Result<()>`: The operation.
+    #[cfg(windows)]
+    fn locked_operation(
+        failures: u32,
+        attempts: &std::cell::Cell<u32>,
+    ) -> impl Fn() -> anyhow::Result<()> + '_ {
+        move || {
+            attempts.set(attempts.get() + 1);
+            if attempts.get() <= failures {
+                Err(io::Error::from_raw_os_error(32).into())
+            } else {
+                Ok(())
+            }
+        }
+    }
+
+    #[cfg(windows)]
+    #[test]
+    fn locked_files_are_skipped_retried_or_waited_for() {
+        let recipe = |on_locked: LockedPolicy, locked_timeout: u64| Recipe {
+            on_locked: Some(on_locked),
+            locked_timeout: Some(locked_timeout),
+            ..Default::default()
+        };
+        let attempts = std::cell::Cell::new(0);
+
+        let skipped = run_with_locked_policy(
+            &recipe(LockedPolicy::Skip, 30),
+            locked_operation(1, &attempts),
+        );
+        assert!(!skipped.unwrap());
+        assert_eq!(attempts.get(), 1);
+
+        attempts.set(0);
+        let retried = run_with_locked_policy(
+            &recipe(LockedPolicy::Retry, 30),
+            locked_operation(2, &attempts),
+        );
+        assert!(retried.unwrap());
+        assert_eq!(attempts.get(), 3);
+
+        attempts.set(0);
+        let given_up = run_with_locked_policy(
+            &recipe(LockedPolicy::Retry, 30),
+            locked_operation(u32::MAX, &attempts),
+        );
+        assert!(!given_up.unwrap());
+        assert_eq!(attempts.get(), LOCKED_RETRY_ATTEMPTS);
+
+        attempts.set(0);
+        let timed_out = run_with_locked_policy(
+            &recipe(LockedPolicy::Wait, 0),
+            locked_operation(u32::MAX, &attempts),
+        );
+        assert!(!timed_out.unwrap());
+        assert_eq!(attempts.get(), 1);
+    }
+
+    #[test]
+    fn errors_other_than_locks_are_not_retried() {
+        let recipe = Recipe {
+            on_locked: Some(LockedPolicy::Retry),
+            ..Default::default()
+        };
+        let attempts = std::cell::Cell::new(0);
+
+        let result = run_with_locked_policy(&recipe, || {
+            attempts.set(attempts.get() + 1);
+            Err(io::Error::from(io::ErrorKind::NotFound).into())
+        });
+
+        assert!(result.is_err());
+        assert_eq!(attempts.get(), 1);
+    }
+
+    #[test]
+    fn unknown_extensions_summary_counts_the_skipped_files() {
+        let root = TempDir::new().unwrap();
+        let (downloads, pictures) = (root.path().join("downloads"), root.path().join("pictures"));
+        fs::create_dir(&pictures).unwrap();
+        for name in ["a.jpg", "b.webp", "c.WEBP", "d.webp", "e.avif", "LICENSE"] {
+            create_dated_file(&downloads.join(name), name, "2024-11-11");
+        }
+        let recipes_file = write_recipes(
+            root.path(),
+            json!([{
+                "name": "pictures",
+                "source_folder": downloads,
+                "destination_folder": pictures,
+                "operation": "Move",
+                "allowed_extensions": ["jpg"],
+                "report_unknown_extensions": true
+            }]),
+        );
+
+        let (stats, output) = logging::tests::capture_output(|| {
+            FileOrganizer::new(recipes_file, RunOptions::default())
+                .unwrap()
+                .run()
+                .unwrap()
+        });
+
+        assert_eq!(stats.files_matched, 1);
+        assert!(
+            output
+                .contains("pictures Skipped unknown extensions - .webp (3), (none) (1), .avif (1)")
+        );
+    }
+
+    #[test]
+    fn name_patterns_match_globs_and_character_classes() {
+        let root = TempDir::new().unwrap();
+        let (inbox, matched) = (root.path().join("inbox"), root.path().join("matched"));
+        fs::create_dir(&matched).unwrap();
+        for name in [
+            "db.backup",
+            "db.backup.old",
+            "report_01.txt",
+            "report_1.txt",
+            "report_123.txt",
+            "INV-2023-7.pdf",
+            "INV-2019-1.pdf",
+            "RCP-4.pdf",
+        ] {
+            create_dated_file(&inbox.join(name), name, "2024-12-01");
+        }
+        let recipe = |name_patterns: serde_json::Value| {
+            json!([{
+                "name": "patterns",
+                "source_folder": inbox,
+                "destination_folder": matched,
+                "operation": "Move",
+                "allowed_extensions": [],
+                "name_patterns": name_patterns
+            }])
+        };
+
+        assert_eq!(
+            planned_names(write_recipes(
+                root.path(),
+                recipe(json!(["*.backup", "report_??.txt"]))
+            )),
+            ["db.backup", "report_01.txt"]
+        );
+        assert_eq!(
+            planned_names(write_recipes(
+                root.path(),
+                recipe(json!(["INV-202[0-9]-*.pdf", "[!I]CP-*"]))
+            )),
+            ["INV-2023-7.pdf", "RCP-4.pdf"]
+        );
+        assert_eq!(
+            planned_names(write_recipes(root.path(), recipe(json!([])))).len(),
+            8
+        );
+    }
+
+    #[test]
+    fn year_roots_route_mapped_years_to_their_root() {
+        let root = TempDir::new().unwrap();
+        let camera = root.path().join("camera");
+        let (default_root, disk_a, disk_b) = (
+            root.path().join("pictures"),
+            root.path().join("disk_a"),
+            root.path().join("disk_b"),
+        );
+        for folder in [&default_root, &disk_a, &disk_b] {
+            fs::create_dir(folder).unwrap();
+        }
+        create_dated_file(&camera.join("ski.jpg"), "ski", "2022-02-10");
+        create_dated_file(&camera.join("beach.jpg"), "beach", "2023-08-15");
+        create_dated_file(&camera.join("city.jpg"), "city", "2024-05-20");
+        let recipes_file = write_recipes(
+            root.path(),
+            json!([{
+                "name": "photos",
+                "source_folder": camera,
+                "destination_folder": default_root,
+                "operation": "Move",
+                "allowed_extensions": ["jpg"],
+                "subfolders": ["%Y"],
+                "year_roots": {"2022": disk_a, "2023": disk_b}
+            }]),
+        );
+
+        FileOrganizer::new(recipes_file, RunOptions::default())
+            .unwrap()
+            .run()
+            .unwrap();
+
+        assert!(disk_a.join("2022").join("ski.jpg").is_file());
+        assert!(disk_b.join("2023").join("beach.jpg").is_file());
+        assert!(default_root.join("2024").join("city.jpg").is_file());
+    }
+
+    #[test]
+    fn size_bounds_are_inclusive_and_may_be_set_alone() {
+        let root = TempDir::new().unwrap();
+        let (uploads, media) = (root.path().join("uploads"), root.path().join("media"));
+        fs::create_dir(&media).unwrap();
+        for (name, size) in [
+            ("empty.bin", 0),
+            ("min.bin", 10),
+            ("max.bin", 100),
+            ("big.bin", 101),
+        ] {
+            create_dated_file(&uploads.join(name), &"x".repeat(size), "2024-01-15");
+        }
+        let recipe = |min_size_bytes: Option<u64>, max_size_bytes: Option<u64>| {
+            json!([{
+                "name": "sizes",
+                "source_folder": uploads,
+                "destination_folder": media,
+                "operation": "Move",
+                "allowed_extensions": ["bin"],
+                "min_size_bytes": min_size_bytes,
+                "max_size_bytes": max_size_bytes
+            }])
+        };
+
+        assert_eq!(
+            planned_names(write_recipes(root.path(), recipe(Some(10), Some(100)))),
+            ["max.bin", "min.bin"]
+        );
+        assert_eq!(
+            planned_names(write_recipes(root.path(), recipe(None, Some(100)))),
+            ["empty.bin", "max.bin", "min.bin"]
+        );
+        assert_eq!(
+            planned_names(write_recipes(root.path(), recipe(Some(101), None))),
+            ["big.bin"]
+        );
+    }
+
+    #[test]
+    fn conflict_policies_overwrite_skip_or_rename() {
+        let run_with_policy = |on_conflict: &str, is_dry_run: bool| {
+            let root = TempDir::new().unwrap();
+            let (inbox, photos) = (root.path().join("inbox"), root.path().join("photos"));
+            create_dated_file(&inbox.join("photo.jpg"), "new", "2024-02-01");
+            create_dated_file(&photos.join("photo.jpg"), "first", "2024-01-01");
+            create_dated_file(&photos.join("photo (1).jpg"), "second", "2024-01-02");
+            let recipes_file = write_recipes(
+                root.path(),
+                json!([{
+                    "name": "photos",
+                    "source_folder": inbox,
+                    "destination_folder": photos,
+                    "operation": "Move",
+                    "allowed_extensions": ["jpg"],
+                    "on_conflict": on_conflict
+                }]),
+            );
+            let options = RunOptions {
+                is_dry_run,
+                ..Default::default()
+            };
+            let file_organizer = FileOrganizer::new(recipes_file, options).unwrap();
+            let stats = file_organizer
+                .run_recipe(&file_organizer.settings.recipes[0], &[], None)
+                .unwrap();
+            let mut contents: Vec<(String, String)> = fs::read_dir(&photos)
+                .unwrap()
+                .map(|v| v.unwrap().path())
+                .map(|v| {
+                    let name = v.file_name().unwrap().to_string_lossy().to_string();
+                    (name, fs::read_to_string(&v).unwrap())
+                })
+                .collect();
+            contents.sort();
+            (stats, contents, inbox.join("photo.jpg").exists())
+        };
+        let pair = |name: &str, content: &str| (name.to_string(), content.to_string());
+
+        let (stats, contents, is_source_left) = run_with_policy("Overwrite", false);
+        assert_eq!(stats.files_conflicted, 1);
+        assert_eq!(
+            contents,
+            [pair("photo (1).jpg", "second"), pair("photo.jpg", "new")]
+        );
+        assert!(!is_source_left);
+
+        let (stats, contents, is_source_left) = run_with_policy("Skip", false);
+        assert_eq!(stats.files_conflicted, 1);
+        assert_eq!(
+            contents,
+            [pair("photo (1).jpg", "second"), pair("photo.jpg", "first")]
+        );
+        assert!(is_source_left);
+
+        let (stats, contents, is_source_left) = run_with_policy("Rename", false);
+        assert_eq!(stats.files_conflicted, 1);
+        assert_eq!(
+            contents,
+            [
+                pair("photo (1).jpg", "second"),
+                pair("photo (2).jpg", "new"),
+                pair("photo.jpg", "first")
+            ]
+        );
+        assert!(!is_source_left);
+
+        for on_conflict in ["Overwrite", "Skip", "Rename"] {
+            let (stats, contents, is_source_left) = run_with_policy(on_conflict, true);
+            assert_eq!(stats.files_conflicted, 1);
+            assert_eq!(
+                contents,
+                [pair("photo (1).jpg", "second"), pair("photo.jpg", "first")]
+            );
+            assert!(is_source_left);
+        }
+    }
+
+    #[test]
+    fn idempotency_check_passes_for_an_in_place_recipe() {
+        let root = TempDir::new().unwrap();
+        let library = root.path().join("library");
+        create_dated_file(&library.join("song.flac"), "song", "2020-06-21");
+        create_dated_file(&library.join("old").join("tune.flac"), "tune", "2019-03-03");
+        let recipes_file = write_recipes(
+            root.path(),
+            json!([{
+                "name": "library",
+                "source_folder": library,
+                "destination_folder": library,
+                "operation": "Move",
+                "in_place": true,
+                "allowed_extensions": ["flac"],
+                "subfolders": ["%Y"]
+            }]),
+        );
+        let options = RunOptions {
+            assert_idempotent: true,
+            ..Default::default()
+        };
+
+        let stats = FileOrganizer::new(recipes_file, options)
+            .unwrap()
+            .run()
+            .unwrap();
+
+        assert_eq!(stats.files_matched, 2);
+        assert!(library.join("2020").join("song.flac").is_file());
+        assert!(library.join("2019").join("tune.flac").is_file());
+    }
+
+    #[test]
+    fn idempotency_check_fails_when_files_would_be_processed_again() {
+        let root = TempDir::new().unwrap();
+        let (inbox, sorted, archive) = (
+            root.path().join("inbox"),
+            root.path().join("sorted"),
+            root.path().join("archive"),
+        );
+        fs::create_dir_all(&inbox).unwrap();
+        fs::create_dir(&sorted).unwrap();
+        fs::create_dir(&archive).unwrap();
+        fs::write(inbox.join("today.log"), "log").unwrap();
+        let recipes_file = write_recipes(
+            root.path(),
+            json!([
+                {
+                    "name": "archive_sorted",
+                    "source_folder": sorted,
+                    "destination_folder": archive,
+                    "operation": "Move",
+                    "allowed_extensions": ["log"]
+                },
+                {
+                    "name": "sort_inbox",
+                    "source_folder": inbox,
+                    "destination_folder": sorted,
+                    "operation": "Move",
+                    "allowed_extensions": ["log"]
+                }
+            ]),
+        );
+        let options = RunOptions {
+            assert_idempotent: true,
+            ..Default::default()
+        };
+
+        let error = FileOrganizer::new(recipes_file, options)
+            .unwrap()
+            .run()
+            .err()
+            .unwrap();
+
+        assert_eq!(
+            error.to_string(),
+            "The run is not idempotent, a second run would process 1 file(s) again"
+        );
+        assert!(sorted.join("today.log").is_file());
+    }
+
+    #[test]
+    fn sanitized_names_collisions_follow_the_conflict_policy() {
+        let root = TempDir::new().unwrap();
+        let (shared, documents) = (root.path().join("shared"), root.path().join("documents"));
+        fs::create_dir(&documents).unwrap();
+        create_dated_file(&shared.join("report.pdf"), "clean", "2024-03-01");
+        create_dated_file(&shared.join("report .pdf"), "spaced", "2024-03-02");
+        let recipes_file = write_recipes(
+            root.path(),
+            json!([{
+                "name": "documents",
+                "source_folder": shared,
+                "destination_folder": documents,
+                "operation": "Copy",
+                "allowed_extensions": ["pdf"],
+                "sanitize_filenames": true,
+                "on_conflict": "Rename"
+            }]),
+        );
+        let file_organizer = FileOrganizer::new(recipes_file, RunOptions::default()).unwrap();
+
+        let stats = file_organizer
+            .run_recipe(&file_organizer.settings.recipes[0], &[], None)
+            .unwrap();
+
+        assert_eq!(stats.files_conflicted, 1);
+        let mut contents = [
+            fs::read_to_string(documents.join("report.pdf")).unwrap(),
+            fs::read_to_string(documents.join("report (1).pdf")).unwrap(),
+        ];
+        contents.sort();
+        assert_eq!(contents, ["clean", "spaced"]);
+        assert!(!documents.join("report .pdf").exists());
+    }
+
+    #[test]
+    fn verified_copies_are_kept_and_mismatched_ones_removed() {
+        let root = TempDir::new().unwrap();
+        let (originals, backup) = (root.path().join("originals"), root.path().join("backup"));
+        fs::create_dir(&backup).unwrap();
+        create_dated_file(&originals.join("thesis.odt"), "chapter one", "2024-04-04");
+        create_dated_file(&originals.join("slides.odp"), "slide one", "2024-04-05");
+        let recipes_file = write_recipes(
+            root.path(),
+            json!([{
+                "name": "backup",
+                "source_folder": originals,
+                "destination_folder": backup,
+                "operation": "Copy",
+                "allowed_extensions": ["odt", "odp"],
+                "verify_copy": true
+            }]),
+        );
+        let file_organizer = FileOrganizer::new(recipes_file, RunOptions::default()).unwrap();
+        let recipe = file_organizer
+            .prepare_recipe(&file_organizer.settings.recipes[0])
+            .unwrap();
+        let entries = collect_entries(&recipe).unwrap();
+        let context = file_organizer
+            .build_context(&recipe, &entries, false, true)
+            .unwrap();
+        let entry = |name: &str| entries.iter().find(|v| v.file_name() == name).unwrap();
+
+        let outcome = run_for_file(entry("thesis.odt"), None, &recipe, &context).unwrap();
+        assert!(matches!(outcome, FileOutcome::Processed));
+        assert_eq!(
+            fs::read_to_string(backup.join("thesis.odt")).unwrap(),
+            "chapter one"
+        );
+
+        let slides = originals.join("slides.odp");
+        let mut match_result = match_file(&slides, &recipe, &context).unwrap().unwrap();
+        match_result.content_hash = Some(format!("{:x}", Sha256::digest("slide two")));
+        let result = run_for_file(
+            entry("slides.odp"),
+            Some(Ok(Some(match_result))),
+            &recipe,
+            &context,
+        );
+        assert!(
+            result
+                .err()
+                .unwrap()
+                .to_string()
+                .contains("failed verification")
+        );
+        assert!(!backup.join("slides.odp").exists());
+        assert!(slides.is_file());
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn link_operations_point_at_the_original_and_dry_run_links_nothing() {
+        use std::os::unix::fs::MetadataExt;
+        let run_with_operation = |operation: &str, is_dry_run: bool| {
+            let root = TempDir::new().unwrap();
+            let (shows, library) = (root.path().join("shows"), root.path().join("library"));
+            fs::create_dir(&library).unwrap();
+            create_dated_file(&shows.join("pilot.mkv"), "pilot", "2024-09-01");
+            let recipes_file = write_recipes(
+                root.path(),
+                json!([{
+                    "name": "links",
+                    "source_folder": shows,
+                    "destination_folder": library,
+                    "operation": operation,
+                    "allowed_extensions": ["mkv"]
+                }]),
+            );
+            let options = RunOptions {
+                is_dry_run,
+                ..Default::default()
+            };
+            FileOrganizer::new(recipes_file, options)
+                .unwrap()
+                .run()
+                .unwrap();
+            (root, shows.join("pilot.mkv"), library.join("pilot.mkv"))
+        };
+
+        let (_root, original, link) = run_with_operation("Symlink", false);
+        assert!(
+            fs::symlink_metadata(&link)
+                .unwrap()
+                .file_type()
+                .is_symlink()
+        );
+        assert_eq!(
+            fs::read_link(&link).unwrap(),
+            fs::canonicalize(&original).unwrap()
+        );
+        assert!(original.is_file());
+
+        let (_root, original, link) = run_with_operation("Hardlink", false);
+        let (original_metadata, link_metadata) = (
+            fs::metadata(&original).unwrap(),
+            fs::metadata(&link).unwrap(),
+        );
+        assert_eq!(original_metadata.ino(), link_metadata.ino());
+        assert_eq!(original_metadata.nlink(), 2);
+
+        for operation in ["Symlink", "Hardlink"] {
+            let (_root, original, link) = run_with_operation(operation, true);
+            assert!(fs::symlink_metadata(&link).is_err());
+            assert_eq!(fs::metadata(&original).unwrap().nlink(), 1);
+        }
+    }
+
+    #[test]
+    fn extensions_are_inferred_from_the_destination() {
+        let root = TempDir::new().unwrap();
+        let (camera, gallery) = (root.path().join("camera"), root.path().join("gallery"));
+        create_dated_file(&gallery.join("2023").join("old.jpg"), "jpg", "2023-01-01");
+        create_dated_file(&gallery.join("icons").join("logo.PNG"), "png", "2023-01-02");
+        for name in ["new.jpg", "shot.png", "clip.mov", "notes.txt"] {
+            create_dated_file(&camera.join(name), name, "2024-06-06");
+        }
+        let recipes_file = write_recipes(
+            root.path(),
+            json!([{
+                "name": "gallery",
+                "source_folder": camera,
+                "destination_folder": gallery,
+                "operation": "Move",
+                "infer_extensions_from_dest": true
+            }]),
+        );
+
+        assert_eq!(planned_names(recipes_file), ["new.jpg", "shot.png"]);
+    }
+
+    #[test]
+    fn exif_date_comparator_falls_back_to_the_modification_date() {
+        let root = TempDir::new().unwrap();
+        let (phone, photos) = (root.path().join("phone"), root.path().join("photos"));
+        fs::create_dir(&photos).unwrap();
+        fs::create_dir(&phone).unwrap();
+        fs::write(
+            phone.join("with_exif.jpg"),
+            exif_date::tests::jpeg_with_capture_date("2015:08:09 10:11:12"),
+        )
+        .unwrap();
+        create_dated_file(&phone.join("without_exif.jpg"), "\u{FF}", "2021-02-02");
+        create_dated_file(&phone.join("caption.txt"), "caption", "2022-03-03");
+        let recipes_file = write_recipes(
+            root.path(),
+            json!([{
+                "name": "photos",
+                "source_folder": phone,
+                "destination_folder": photos,
+                "operation": "Move",
+                "allowed_extensions": ["jpg", "txt"],
+                "date_comparator": "ExifDate",
+                "subfolders": ["%Y-%m-%d"]
+            }]),
+        );
+        let (recipe, context) = dry_run_context(recipes_file);
+
+        for (name, day) in [
+            ("with_exif.jpg", "2015-08-09"),
+            ("without_exif.jpg", "2021-02-02"),
+            ("caption.txt", "2022-03-03"),
+        ] {
+            let match_result = match_file(&phone.join(name), &recipe, &context)
+                .unwrap()
+                .unwrap();
+            assert_eq!(match_result.dest_folder, photos.join(day), "{}", name);
+        }
+    }
+
+    #[test]
+    fn extension_token_mixes_with_date_formats() {
+        let root = TempDir::new().unwrap();
+        let (desktop, sorted) = (root.path().join("desktop"), root.path().join("sorted"));
+        fs::create_dir(&sorted).unwrap();
+        create_dated_file(&desktop.join("Scan.PDF"), "pdf", "2023-05-05");
+        create_dated_file(&desktop.join("cat.jpg"), "jpg", "2024-06-06");
+        create_dated_file(&desktop.join("Makefile"), "make", "2024-07-07");
+        let recipes_file = write_recipes(
+            root.path(),
+            json!([{
+                "name": "by_type",
+                "source_folder": desktop,
+                "destination_folder": sorted,
+                "operation": "Move",
+                "allowed_extensions": [],
+                "subfolders": ["{ext}", "%Y"]
+            }]),
+        );
+
+        FileOrganizer::new(recipes_file, RunOptions::default())
+            .unwrap()
+            .run()
+            .unwrap();
+
+        assert!(sorted.join("pdf").join("2023").join("Scan.PDF").is_file());
+        assert!(sorted.join("jpg").join("2024").join("cat.jpg").is_file());
+        assert!(
+            sorted
+                .join(NO_EXTENSION_FOLDER)
+                .join("2024")
+                .join("Makefile")
+                .is_file()
+        );
+    }
+
+    #[test]
+    fn date_strategies_pick_among_disagreeing_dates() {
+        let root = TempDir::new().unwrap();
+        let (scans, sorted) = (root.path().join("scans"), root.path().join("sorted"));
+        fs::create_dir_all(&scans).unwrap();
+        fs::create_dir(&sorted).unwrap();
+        let photo = scans.join("grandma.jpg");
+        fs::write(
+            &photo,
+            exif_date::tests::jpeg_with_capture_date("1998:12:24 18:00:00"),
+        )
+        .unwrap();
+        let dest_folder = |date_strategy: serde_json::Value| {
+            // Reading the EXIF data of the file updates its access date, which is read first and set back before each match.
+            fs::File::options()
+                .write(true)
+                .open(&photo)
+                .unwrap()
+                .set_times(
+                    fs::FileTimes::new()
+                        .set_modified(parse_day("2021-03-10").unwrap().into())
+                        .set_accessed(parse_day("2023-06-15").unwrap().into()),
+                )
+                .unwrap();
+            let recipes_file = write_recipes(
+                root.path(),
+                json!([{
+                    "name": "scans",
+                    "source_folder": scans,
+                    "destination_folder": sorted,
+                    "operation": "Move",
+                    "allowed_extensions": ["jpg"],
+                    "date_sources": ["AccessedDate", "ModificationDate", "ExifDate"],
+                    "date_strategy": date_strategy,
+                    "subfolders": ["%Y-%m-%d"]
+                }]),
+            );
+            let (recipe, context) = dry_run_context(recipes_file);
+            match_file(&photo, &recipe, &context)
+                .unwrap()
+                .unwrap()
+                .dest_folder
+        };
+
+        assert_eq!(dest_folder(json!("Earliest")), sorted.join("1998-12-24"));
+        assert_eq!(dest_folder(json!("Latest")), sorted.join("2023-06-15"));
+        assert_eq!(
+            dest_folder(json!({"ClosestTo": "2020-12-31"})),
+            sorted.join("2021-03-10")
+        );
+        assert_eq!(
+            dest_folder(json!({"ClosestTo": "2000-01-01"})),
+            sorted.join("1998-12-24")
+        );
+    }
+
+    #[test]
+    fn recipe_threads_cap_the_thread_pool_of_each_recipe() {
+        let root = TempDir::new().unwrap();
+        let (network_share, local, backup) = (
+            root.path().join("network_share"),
+            root.path().join("local"),
+            root.path().join("backup"),
+        );
+        fs::create_dir(&backup).unwrap();
+        for index in 0..20 {
+            create_dated_file(
+                &network_share.join(format!("remote{}.dat", index)),
+                "r",
+                "2024-01-01",
+            );
+            create_dated_file(
+                &local.join(format!("local{}.dat", index)),
+                "l",
+                "2024-01-01",
+            );
+        }
+        let recipes_file = write_recipes(
+            root.path(),
+            json!([
+                {
+                    "name": "slow_share",
+                    "source_folder": network_share,
+                    "destination_folder": backup.join("share"),
+                    "operation": "Copy",
+                    "allowed_extensions": ["dat"],
+                    "recipe_threads": 1
+                },
+                {
+                    "name": "fast_local",
+                    "source_folder": local,
+                    "destination_folder": backup.join("local"),
+                    "operation": "Copy",
+                    "allowed_extensions": ["dat"],
+                    "recipe_threads": 3
+                }
+            ]),
+        );
+        fs::create_dir(backup.join("share")).unwrap();
+        fs::create_dir(backup.join("local")).unwrap();
+        let options = || RunOptions {
+            threads: Some(8),
+            ..Default::default()
+        };
+        let file_organizer = FileOrganizer::new(recipes_file.clone(), options()).unwrap();
+        for (recipe, threads) in file_organizer.settings.recipes.iter().zip([1, 3]) {
+            let recipe = file_organizer.prepare_recipe(recipe).unwrap();
+            let entries = collect_entries(&recipe).unwrap();
+            let context = file_organizer
+                .build_context(&recipe, &entries, false, true)
+                .unwrap();
+            let thread_pool = context.thread_pool.as_ref().unwrap();
+            assert_eq!(thread_pool.current_num_threads(), threads);
+        }
+
+        let stats = FileOrganizer::new(recipes_file, options())
+            .unwrap()
+            .run()
+            .unwrap();
+
+        assert_eq!(stats.files_processed, 40);
+        assert_eq!(stats.files_errored, 0);
+        assert_eq!(fs::read_dir(backup.join("share")).unwrap().count(), 20);
+        assert_eq!(fs::read_dir(backup.join("local")).unwrap().count(), 20);
+    }
+
+    #[test]
+    fn zero_recipe_threads_are_rejected() {
+        let recipe = Recipe {
+            name: "no_threads".to_string(),
+            recipe_threads: Some(0),
+            ..Default::default()
+        };
+
+        let error = build_thread_pool(&recipe, None).err().unwrap();
+
+        assert_eq!(
+            error.to_string(),
+            "no_threads - recipe_threads must be at least 1"
+        );
+        assert!(
+            build_thread_pool(&Recipe::default(), None)
+                .unwrap()
+                .is_none()
+        );
+    }
+
+    #[test]
+    fn named_recipe_runs_alone_and_unknown_names_are_listed() {
+        let root = TempDir::new().unwrap();
+        let (inbox, docs, pics) = (
+            root.path().join("inbox"),
+            root.path().join("docs"),
+            root.path().join("pics"),
+        );
+        fs::create_dir(&docs).unwrap();
+        fs::create_dir(&pics).unwrap();
+        create_dated_file(&inbox.join("cv.pdf"), "cv", "2024-02-02");
+        create_dated_file(&inbox.join("me.png"), "me", "2024-02-02");
+        let recipes_file = write_recipes(
+            root.path(),
+            json!([
+                {
+                    "name": "Documents",
+                    "source_folder": inbox,
+                    "destination_folder": docs,
+                    "operation": "Move",
+                    "allowed_extensions": ["pdf"]
+                },
+                {
+                    "name": "Pictures",
+                    "source_folder": inbox,
+                    "destination_folder": pics,
+                    "operation": "Move",
+                    "allowed_extensions": ["png"]
+                }
+            ]),
+        );
+        let run_named = |recipe_name: &str| {
+            let options = RunOptions {
+                recipe_name: Some(recipe_name.to_string()),
+                ..Default::default()
+            };
+            FileOrganizer::new(recipes_file.clone(), options)
+                .unwrap()
+                .run()
+        };
+
+        let error = run_named("documents").err().unwrap();
+        assert_eq!(
+            error.to_string(),
+            "No recipe named documents, available recipes: Documents, Pictures"
+        );
+        assert!(run_named("Unknown").is_err());
+
+        let stats = run_named("Documents").unwrap();
+        assert_eq!(stats.files_matched, 1);
+        assert!(docs.join("cv.pdf").is_file());
+        assert!(inbox.join("me.png").is_file());
+        let settings = Settings::load_from_file(&recipes_file).unwrap();
+        assert!(settings.recipes[0].last_run.is_some());
+        assert!(settings.recipes[1].last_run.is_none());
+    }
+
+    #[test]
+    fn validation_reports_every_problem_of_every_recipe() {
+        let root = TempDir::new().unwrap();
+        let (inbox, archive) = (root.path().join("inbox"), root.path().join("archive"));
+        fs::create_dir(&inbox).unwrap();
+        fs::create_dir(&archive).unwrap();
+        let missing = root.path().join("missing");
+        let recipes_file = write_recipes(
+            root.path(),
+            json!([
+                {
+                    "name": "valid",
+                    "source_folder": inbox,
+                    "destination_folder": archive,
+                    "operation": "Move",
+                    "allowed_extensions": ["txt"],
+                    "subfolders": ["%Y", "%m"]
+                },
+                {
+                    "name": "broken",
+                    "source_folder": missing,
+                    "destination_folder": archive,
+                    "operation": "Move",
+                    "allowed_extensions": ["txt"],
+                    "subfolders": ["%Y", "%K"]
+                }
+            ]),
+        );
+        assert!(FileOrganizer::new(recipes_file.clone(), RunOptions::default()).is_err());
+        let file_organizer = FileOrganizer::new_for_validation(recipes_file).unwrap();
+
+        let problems: Vec<String> = file_organizer
+            .validate()
+            .iter()
+            .map(|v| v.to_string())
+            .collect();
+
+        assert_eq!(
+            problems,
+            [
+                format!(
+                    "broken - Source folder not a directory: {}",
+                    missing.display()
+                ),
+                "broken - Invalid subfolder format: %K".to_string()
+            ]
+        );
+        assert!(
+            file_organizer
+                .validate_recipe(&file_organizer.settings.recipes[0])
+                .is_empty()
+        );
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn source_folder_times_are_restored_after_moving_files_out() {
+        let root = TempDir::new().unwrap();
+        let (inbox, sorted) = (root.path().join("inbox"), root.path().join("sorted"));
+        let nested = inbox.join("trip");
+        fs::create_dir(&sorted).unwrap();
+        create_dated_file(&nested.join("map.gpx"), "map", "2024-05-05");
+        create_dated_file(&nested.join("keep.txt"), "keep", "2024-05-05");
+        create_dated_file(&inbox.join("track.gpx"), "track", "2024-05-06");
+        let folder_time = parse_day("2020-01-01").unwrap();
+        for folder in [&inbox, &nested] {
+            fs::File::open(folder)
+                .unwrap()
+                .set_modified(folder_time.into())
+                .unwrap();
+        }
+        let recipes_file = write_recipes(
+            root.path(),
+            json!([{
+                "name": "gps",
+                "source_folder": inbox,
+                "destination_folder": sorted,
+                "operation": "Move",
+                "allowed_extensions": ["gpx"],
+                "recursive": true,
+                "preserve_source_dir_times": true
+            }]),
+        );
+
+        let stats = FileOrganizer::new(recipes_file, RunOptions::default())
+            .unwrap()
+            .run()
+            .unwrap();
+
+        assert_eq!(stats.files_matched, 2);
+        assert!(nested.join("keep.txt").is_file());
+        for folder in [&inbox, &nested] {
+            assert_eq!(get_last_modification_date(folder).unwrap(), folder_time);
+        }
+    }
+
+    #[test]
+    fn destination_levels_beyond_the_maximum_depth_are_flattened() {
+        let root = TempDir::new().unwrap();
+        let source = root.path().join("camera");
+        create_dated_file(&source.join("dsc.jpg"), "dsc", "2024-03-07");
+        let recipe = |name: &str, max_dest_depth: usize| {
+            let destination = root.path().join(name);
+            fs::create_dir(&destination).unwrap();
+            json!({
+                "name": name,
+                "source_folder": source,
+                "destination_folder": destination,
+                "operation": "Copy",
+                "allowed_extensions": ["jpg"],
+                "subfolders": ["%Y", "%m", "%d"],
+                "max_dest_depth": max_dest_depth
+            })
+        };
+        let recipes_file = write_recipes(
+            root.path(),
+            json!([recipe("shallow", 2), recipe("deep", 3)]),
+        );
+
+        let stats = FileOrganizer::new(recipes_file, RunOptions::default())
+            .unwrap()
+            .run()
+            .unwrap();
+
+        assert_eq!(stats.files_matched, 2);
+        assert!(root.path().join("shallow/2024/03_07/dsc.jpg").is_file());
+        assert!(!root.path().join("shallow/2024/03").exists());
+        assert!(root.path().join("deep/2024/03/07/dsc.jpg").is_file());
+    }
+
+    #[test]
+    fn flattening_keeps_folders_outside_of_the_root() {
+        let root = Path::new("/srv/sorted");
+
+        assert_eq!(
+            flatten_dest_folder(&root.join("2024/03/07/Photos"), root, 1),
+            root.join("2024_03_07_Photos")
+        );
+        assert_eq!(
+            flatten_dest_folder(&root.join("2024/03"), root, 2),
+            root.join("2024/03")
+        );
+        assert_eq!(
+            flatten_dest_folder(Path::new("/elsewhere/2024/03/07"), root, 1),
+            Path::new("/elsewhere/2024/03/07")
+        );
+    }
+
+    #[test]
+    fn disabled_recipes_are_skipped_and_keep_their_last_run() {
+        let root = TempDir::new().unwrap();
+        let (downloads, archive) = (root.path().join("downloads"), root.path().join("archive"));
+        let (music, songs) = (root.path().join("music"), root.path().join("songs"));
+        fs::create_dir(&archive).unwrap();
+        fs::create_dir(&songs).unwrap();
+        create_dated_file(&downloads.join("setup.zip"), "zip", "2023-11-02");
+        create_dated_file(&music.join("track.mp3"), "mp3", "2023-11-03");
+        let recipes_file = write_recipes(
+            root.path(),
+            json!([
+                {
+                    "name": "archives",
+                    "source_folder": downloads,
+                    "destination_folder": archive,
+                    "operation": "Move",
+                    "allowed_extensions": ["zip"],
+                    "enabled": false
+                },
+                {
+                    "name": "music",
+                    "source_folder": music,
+                    "destination_folder": songs,
+                    "operation": "Move",
+                    "allowed_extensions": ["mp3"]
+                }
+            ]),
+        );
+
+        let stats = FileOrganizer::new(recipes_file.clone(), RunOptions::default())
+            .unwrap()
+            .run()
+            .unwrap();
+
+        assert_eq!(stats.files_matched, 1);
+        assert!(downloads.join("setup.zip").is_file());
+        assert!(fs::read_dir(&archive).unwrap().next().is_none());
+        assert!(songs.join("track.mp3").is_file());
+        let settings = Settings::load_from_file(&recipes_file).unwrap();
+        assert!(settings.recipes[0].last_run.is_none());
+        assert!(settings.recipes[1].last_run.is_some());
+
+        let options = RunOptions {
+            recipe_name: Some("archives".to_string()),
+            ..Default::default()
+        };
+        let stats = FileOrganizer::new(recipes_file, options)
+            .unwrap()
+            .run()
+            .unwrap();
+        assert_eq!(stats.files_matched, 0);
+        assert!(downloads.join("setup.zip").is_file());
+    }
+
+    #[test]
+    fn json_stats_list_the_operations_of_each_recipe() {
+        let root = TempDir::new().unwrap();
+        let (scans, papers) = (root.path().join("scans"), root.path().join("papers"));
+        fs::create_dir(&papers).unwrap();
+        create_dated_file(&scans.join("lease.pdf"), "lease", "2022-02-02");
+        create_dated_file(&scans.join("notes.txt"), "notes", "2022-02-02");
+        let recipes_file = write_recipes(
+            root.path(),
+            json!([{
+                "name": "papers",
+                "source_folder": scans,
+                "destination_folder": papers,
+                "operation": "Copy",
+                "allowed_extensions": ["pdf"]
+            }]),
+        );
+        let options = RunOptions {
+            json_stats: true,
+            ..Default::default()
+        };
+        let file_organizer = FileOrganizer::new(recipes_file, options).unwrap();
+        let stats = file_organizer
+            .run_recipe(&file_organizer.settings.recipes[0], &[], None)
+            .unwrap();
+
+        let json: serde_json::Value =
+            serde_json::from_str(&format_json_stats(&[("papers".to_string(), stats)]).unwrap())
+                .unwrap();
+
+        let recipe = &json.as_array().unwrap()[0];
+        assert_eq!(recipe["name"], "papers");
+        assert_eq!(recipe["files_matched"], 1);
+        assert_eq!(recipe["files_processed"], 2);
+        assert!(recipe["elapsed_time"].is_i64());
+        let operations = recipe["operations"].as_array().unwrap();
+        assert_eq!(operations.len(), 1);
+        assert_eq!(operations[0]["operation"], "Copy");
+        assert_eq!(operations[0]["source"], json!(scans.join("lease.pdf")));
+        assert_eq!(
+            operations[0]["destination"],
+            json!(papers.join("lease.pdf"))
+        );
+    }
+
+    #[test]
+    fn failed_files_are_reported_and_retried_by_the_next_run() {
+        let root = TempDir::new().unwrap();
+        let (exports, years) = (root.path().join("exports"), root.path().join("years"));
+        fs::create_dir(&years).unwrap();
+        fs::write(years.join("2024"), "not a folder").unwrap();
+        create_dated_file(&exports.join("q4.csv"), "q4", "2023-12-31");
+        create_dated_file(&exports.join("q1.csv"), "q1", "2024-03-31");
+        let recipes_file = write_recipes(
+            root.path(),
+            json!([{
+                "name": "exports",
+                "source_folder": exports,
+                "destination_folder": years,
+                "operation": "Move",
+                "allowed_extensions": ["csv"],
+                "subfolders": ["%Y"]
+            }]),
+        );
+        let file_organizer =
+            FileOrganizer::new(recipes_file.clone(), RunOptions::default()).unwrap();
+
+        let stats = file_organizer
+            .run_recipe(&file_organizer.settings.recipes[0], &[], None)
+            .unwrap();
+
+        assert_eq!(stats.files_errored, 1);
+        assert_eq!(stats.errors.len(), 1);
+        assert!(
+            stats.errors[0].starts_with(&exports.join("q1.csv").display().to_string()),
+            "{}",
+            stats.errors[0]
+        );
+        assert!(years.join("2023").join("q4.csv").is_file());
+        assert!(exports.join("q1.csv").is_file());
+
+        let stats = FileOrganizer::new(recipes_file.clone(), RunOptions::default())
+            .unwrap()
+            .run()
+            .unwrap();
+        assert_eq!(stats.files_errored, 1);
+        let settings = Settings::load_from_file(&recipes_file).unwrap();
+        assert!(settings.recipes[0].last_run.is_none());
+    }
+
+    #[test]
+    fn copies_keep_the_source_dates_only_when_asked() {
+        let root = TempDir::new().unwrap();
+        let drafts = root.path().join("drafts");
+        create_dated_file(&drafts.join("thesis.odt"), "thesis", "2019-06-15");
+        let recipe = |name: &str, preserve_timestamps: bool| {
+            let destination = root.path().join(name);
+            fs::create_dir(&destination).unwrap();
+            json!({
+                "name": name,
+                "source_folder": drafts,
+                "destination_folder": destination,
+                "operation": "Copy",
+                "allowed_extensions": ["odt"],
+                "preserve_timestamps": preserve_timestamps
+            })
+        };
+        let recipes_file = write_recipes(
+            root.path(),
+            json!([recipe("preserved", true), recipe("touched", false)]),
+        );
+
+        FileOrganizer::new(recipes_file, RunOptions::default())
+            .unwrap()
+            .run()
+            .unwrap();
+
+        let source_date = parse_day("2019-06-15").unwrap();
+        assert_eq!(
+            get_last_modification_date(&root.path().join("preserved/thesis.odt")).unwrap(),
+            source_date
+        );
+        assert_ne!(
+            get_last_modification_date(&root.path().join("touched/thesis.odt")).unwrap(),
+            source_date
+        );
+    }
+
+    #[test]
+    fn any_logic_matches_files_satisfying_a_single_filter() {
+        let root = TempDir::new().unwrap();
+        let uploads = root.path().join("uploads");
+        create_dated_file(&uploads.join("big.mp4"), &"v".repeat(2048), "2024-02-02");
+        create_dated_file(&uploads.join("tiny.txt"), "t", "2024-02-02");
+        create_dated_file(&uploads.join("huge.iso"), &"i".repeat(2048), "2024-02-02");
+        let recipe = |name: &str, filter_logic: &str| {
+            let destination = root.path().join(name);
+            fs::create_dir(&destination).unwrap();
+            json!({
+                "name": name,
+                "source_folder": uploads,
+                "destination_folder": destination,
+                "operation": "Copy",
+                "allowed_extensions": ["mp4"],
+                "max_size_bytes": 1024,
+                "filter_logic": filter_logic
+            })
+        };
+        let recipes_file = write_recipes(
+            root.path(),
+            json!([recipe("all", "All"), recipe("any", "Any")]),
+        );
+        let file_organizer = FileOrganizer::new(recipes_file, RunOptions::default()).unwrap();
+
+        for recipe in &file_organizer.settings.recipes {
+            file_organizer.run_recipe(recipe, &[], None).unwrap();
+        }
+
+        assert!(
+            fs::read_dir(root.path().join("all"))
+                .unwrap()
+                .next()
+                .is_none()
+        );
+        let any = root.path().join("any");
+        assert!(any.join("big.mp4").is_file());
+        assert!(any.join("tiny.txt").is_file());
+        assert!(!any.join("huge.iso").exists());
+    }
+
+    #[test]
+    fn single_thread_run_organizes_every_file() {
+        let root = TempDir::new().unwrap();
+        let (camera, albums) = (root.path().join("camera"), root.path().join("albums"));
+        fs::create_dir(&albums).unwrap();
+        for day in 1..=28 {
+            let date = format!("2024-{:02}-{:02}", day % 12 + 1, day);
+            create_dated_file(&camera.join(format!("img_{day}.jpg")), &date, &date);
+        }
+        let recipes_file = write_recipes(
+            root.path(),
+            json!([{
+                "name": "albums",
+                "source_folder": camera,
+                "destination_folder": albums,
+                "operation": "Move",
+                "allowed_extensions": ["jpg"],
+                "subfolders": ["%m"]
+            }]),
+        );
+        let options = RunOptions {
+            threads: Some(1),
+            ..Default::default()
+        };
+
+        let stats = FileOrganizer::new(recipes_file, options)
+            .unwrap()
+            .run()
+            .unwrap();
+
+        assert_eq!(stats.files_matched, 28);
+        assert!(fs::read_dir(&camera).unwrap().next().is_none());
+        for day in 1..=28 {
+            let month = format!("{:02}", day % 12 + 1);
+            assert!(albums.join(month).join(format!("img_{day}.jpg")).is_file());
+        }
+        let recipe = Recipe::default();
+        let thread_pool = build_thread_pool(&recipe, Some(1)).unwrap().unwrap();
+        assert_eq!(thread_pool.current_num_threads(), 1);
+        assert!(build_thread_pool(&recipe, Some(0)).unwrap().is_none());
+        assert!(build_thread_pool(&recipe, None).unwrap().is_none());
+    }
+
+    #[test]
+    fn date_range_bounds_are_inclusive_days() {
+        let root = TempDir::new().unwrap();
+        let (archive, sorted) = (root.path().join("archive"), root.path().join("sorted"));
+        fs::create_dir(&sorted).unwrap();
+        for day in ["2021-12-31", "2022-01-01", "2022-07-01"] {
+            create_dated_file(&archive.join(format!("{day}.log")), day, day);
+        }
+        let late_evening = archive.join("2022-06-30.log");
+        create_dated_file(&late_evening, "late", "2022-06-30");
+        fs::File::options()
+            .write(true)
+            .open(&late_evening)
+            .unwrap()
+            .set_modified((parse_day("2022-07-01").unwrap() - TimeDelta::seconds(1)).into())
+            .unwrap();
+        let plan_range = |date_from: Option<&str>, date_to: Option<&str>| {
+            planned_names(write_recipes(
+                root.path(),
+                json!([{
+                    "name": "window",
+                    "source_folder": archive,
+                    "destination_folder": sorted,
+                    "operation": "Move",
+                    "allowed_extensions": ["log"],
+                    "date_from": date_from,
+                    "date_to": date_to
+                }]),
+            ))
+        };
+
+        assert_eq!(
+            plan_range(Some("2022-01-01"), None),
+            ["2022-01-01.log", "2022-06-30.log", "2022-07-01.log"]
+        );
+        assert_eq!(
+            plan_range(None, Some("2022-06-30")),
+            ["2021-12-31.log", "2022-01-01.log", "2022-06-30.log"]
+        );
+        assert_eq!(
+            plan_range(Some("2022-01-01"), Some("2022-06-30")),
+            ["2022-01-01.log", "2022-06-30.log"]
+        );
+        assert_eq!(
+            plan_range(Some("2022-06-30"), Some("2022-06-30")),
+            ["2022-06-30.log"]
+        );
+    }
+
+    #[test]
+    fn reversed_date_range_is_rejected() {
+        let root = TempDir::new().unwrap();
+        let recipes_file = write_recipes(
+            root.path(),
+            json!([{
+                "name": "reversed",
+                "source_folder": root.path(),
+                "destination_folder": root.path(),
+                "operation": "Move",
+                "allowed_extensions": [],
+                "date_from": "2022-07-01",
+                "date_to": "2022-06-30"
+            }]),
+        );
+        let file_organizer = FileOrganizer::new(recipes_file, RunOptions::default()).unwrap();
+
+        let error = file_organizer
+            .prepare_recipe(&file_organizer.settings.recipes[0])
+            .err()
+            .unwrap();
+
+        assert_eq!(
+            error.to_string(),
+            "reversed - date_from must not be after date_to"
+        );
+    }
+
+    #[test]
+    fn folders_emptied_by_a_move_are_removed() {
+        let root = TempDir::new().unwrap();
+        let (phone, gallery) = (root.path().join("phone"), root.path().join("gallery"));
+        fs::create_dir(&gallery).unwrap();
+        create_dated_file(&phone.join("dcim/2023/a.jpg"), "a", "2023-04-04");
+        create_dated_file(&phone.join("dcim/2023/b.jpg"), "b", "2023-04-05");
+        create_dated_file(&phone.join("screens/s.jpg"), "s", "2023-04-06");
+        create_dated_file(&phone.join("screens/notes.txt"), "n", "2023-04-06");
+        fs::create_dir_all(phone.join("untouched/empty")).unwrap();
+        let recipes_file = write_recipes(
+            root.path(),
+            json!([{
+                "name": "gallery",
+                "source_folder": phone,
+                "destination_folder": gallery,
+                "operation": "Move",
+                "allowed_extensions": ["jpg"],
+                "recursive": true,
+                "remove_empty_dirs": true
+            }]),
+        );
+        let run = |is_dry_run: bool| {
+            let options = RunOptions {
+                is_dry_run,
+                ..Default::default()
+            };
+            FileOrganizer::new(recipes_file.clone(), options)
+                .unwrap()
+                .run()
+                .unwrap()
+        };
+
+        assert_eq!(run(true).files_matched, 3);
+        assert!(phone.join("dcim/2023/a.jpg").is_file());
+
+        assert_eq!(run(false).files_matched, 3);
+        assert!(gallery.join("a.jpg").is_file());
+        assert!(!phone.join("dcim").exists());
+        assert!(phone.join("screens/notes.txt").is_file());
+        assert!(phone.join("untouched/empty").is_dir());
+        assert!(phone.is_dir());
+    }
+
+    #[test]
+    fn forced_runs_match_files_older_than_the_last_run() {
+        let root = TempDir::new().unwrap();
+        let (mail, attachments) = (root.path().join("mail"), root.path().join("attachments"));
+        fs::create_dir(&attachments).unwrap();
+        create_dated_file(&mail.join("invoice.pdf"), "invoice", "2020-05-05");
+        let recipes_file = write_recipes(
+            root.path(),
+            json!([{
+                "name": "attachments",
+                "source_folder": mail,
+                "destination_folder": attachments,
+                "operation": "Move",
+                "allowed_extensions": ["pdf"],
+                "last_run": "2021-01-01"
+            }]),
+        );
+        let run = |is_forced: bool, is_dry_run: bool| {
+            let options = RunOptions {
+                is_forced,
+                is_dry_run,
+                ..Default::default()
+            };
+            FileOrganizer::new(recipes_file.clone(), options)
+                .unwrap()
+                .run()
+                .unwrap()
+                .files_matched
+        };
+
+        assert_eq!(run(false, true), 0);
+        assert_eq!(run(true, true), 1);
+        assert!(mail.join("invoice.pdf").is_file());
+        assert_eq!(run(false, false), 0);
+        assert!(mail.join("invoice.pdf").is_file());
+        assert_eq!(run(true, false), 1);
+        assert!(attachments.join("invoice.pdf").is_file());
+    }
+
+    #[test]
+    fn reset_clears_the_state_of_the_selected_recipes() {
+        let root = TempDir::new().unwrap();
+        let recipe = |name: &str| {
+            json!({
+                "name": name,
+                "source_folder": root.path().join(name),
+                "destination_folder": root.path(),
+                "operation": "Move",
+                "allowed_extensions": [],
+                "last_run": "2024-04-04",
+                "last_seen": "2024-04-04T10:00:00Z"
+            })
+        };
+        let recipes_file = write_recipes(
+            root.path(),
+            json!([recipe("videos"), recipe("music"), recipe("books")]),
+        );
+        let reset = |recipe_name: Option<&str>| {
+            let options = RunOptions {
+                recipe_name: recipe_name.map(String::from),
+                ..Default::default()
+            };
+            let names = FileOrganizer::new(recipes_file.clone(), options)
+                .unwrap()
+                .reset()
+                .unwrap();
+            (
+                names,
+                Settings::load_from_file(&recipes_file).unwrap().recipes,
+            )
+        };
+
+        let (names, recipes) = reset(Some("music"));
+        assert_eq!(names, ["music"]);
+        assert!(recipes[1].last_run.is_none() && recipes[1].last_seen.is_none());
+        for recipe in [&recipes[0], &recipes[2]] {
+            assert_eq!(recipe.last_run.as_deref(), Some("2024-04-04"));
+            assert!(recipe.last_seen.is_some());
+        }
+
+        let (names, recipes) = reset(None);
+        assert_eq!(names, ["videos", "music", "books"]);
+        assert!(
+            recipes
+                .iter()
+                .all(|v| v.last_run.is_none() && v.last_seen.is_none())
+        );
+    }
+
+    #[test]
+    fn watched_runs_organize_only_the_changed_files() {
+        let root = TempDir::new().unwrap();
+        let (drop_box, inbox) = (root.path().join("drop_box"), root.path().join("inbox"));
+        fs::create_dir(&inbox).unwrap();
+        create_dated_file(&drop_box.join("old.docx"), "old", "2024-01-01");
+        create_dated_file(&drop_box.join("new.docx"), "new", "2024-01-02");
+        let recipes_file = write_recipes(
+            root.path(),
+            json!([{
+                "name": "drop_box",
+                "source_folder": drop_box,
+                "destination_folder": inbox,
+                "operation": "Move",
+                "allowed_extensions": ["docx"]
+            }]),
+        );
+        let file_organizer = FileOrganizer::new(recipes_file, RunOptions::default()).unwrap();
+        let files = HashSet::from([drop_box.join("new.docx")]);
+
+        let stats = file_organizer
+            .run_recipe(&file_organizer.settings.recipes[0], &[], Some(&files))
+            .unwrap();
+
+        assert_eq!(stats.files_matched, 1);
+        assert!(inbox.join("new.docx").is_file());
+        assert!(drop_box.join("old.docx").is_file());
+    }
+
+    #[test]
+    fn source_folders_feed_one_destination_through_the_conflict_policy() {
+        let root = TempDir::new().unwrap();
+        let (work, home) = (root.path().join("work"), root.path().join("home"));
+        let scans = root.path().join("scans");
+        fs::create_dir(&scans).unwrap();
+        create_dated_file(&work.join("scan.pdf"), "work", "2024-09-01");
+        create_dated_file(&home.join("scan.pdf"), "home", "2024-09-02");
+        create_dated_file(&home.join("receipt.pdf"), "receipt", "2024-09-03");
+        let recipes_file = write_recipes(
+            root.path(),
+            json!([{
+                "name": "scans",
+                "source_folder": work,
+                "source_folders": [home, work.join(".")],
+                "destination_folder": scans,
+                "operation": "Copy",
+                "allowed_extensions": ["pdf"],
+                "on_conflict": "Rename"
+            }]),
+        );
+        let file_organizer = FileOrganizer::new(recipes_file, RunOptions::default()).unwrap();
+
+        let stats = file_organizer
+            .run_recipe(&file_organizer.settings.recipes[0], &[], None)
+            .unwrap();
+
+        assert_eq!(stats.files_matched, 3);
+        assert_eq!(stats.files_conflicted, 1);
+        let mut names: Vec<String> = fs::read_dir(&scans)
+            .unwrap()
+            .map(|v| v.unwrap().file_name().to_string_lossy().to_string())
+            .collect();
+        names.sort();
+        assert_eq!(names, ["receipt.pdf", "scan (1).pdf", "scan.pdf"]);
+        let mut contents = [
+            fs::read_to_string(scans.join("scan.pdf")).unwrap(),
+            fs::read_to_string(scans.join("scan (1).pdf")).unwrap(),
+        ];
+        contents.sort();
+        assert_eq!(contents, ["home", "work"]);
+    }
+
+    #[test]
+    fn nested_source_folders_of_a_recursive_recipe_are_rejected() {
+        let root = TempDir::new().unwrap();
+        let (inbox, nested) = (root.path().join("inbox"), root.path().join("inbox/nested"));
+        fs::create_dir_all(&nested).unwrap();
+        let mut recipe = Recipe {
+            name: "nested".to_string(),
+            source_folder: inbox.clone(),
+            source_folders: Some(vec![nested.clone(), inbox.clone()]),
+            ..Default::default()
+        };
+
+        dedupe_source_folders(&mut recipe).unwrap();
+        assert_eq!(recipe.source_folders, Some(vec![nested.clone()]));
+
+        recipe.recursive = Some(true);
+        let error = dedupe_source_folders(&mut recipe).unwrap_err();
+        assert!(
+            error.to_string().starts_with("nested - Source folders"),
+            "{error}"
+        );
+    }
+
+    #[test]
+    fn plan_report_groups_the_operations_of_each_recipe() {
+        let root = TempDir::new().unwrap();
+        let (desk, docs, backup) = (
+            root.path().join("desk"),
+            root.path().join("docs"),
+            root.path().join("backup"),
+        );
+        for name in ["a.pdf", "b.pdf", "c.txt", "d.txt"] {
+            create_dated_file(&desk.join(name), name, "2024-10-10");
+        }
+        create_dated_file(&docs.join("b.pdf"), "old b", "2024-01-01");
+        create_dated_file(&backup.join("d.txt"), "old d", "2024-01-01");
+        let recipes_file = write_recipes(
+            root.path(),
+            json!([
+                {
+                    "name": "docs",
+                    "source_folder": desk,
+                    "destination_folder": docs,
+                    "operation": "Move",
+                    "allowed_extensions": ["pdf"],
+                    "on_conflict": "Rename"
+                },
+                {
+                    "name": "backup",
+                    "source_folder": desk,
+                    "destination_folder": backup,
+                    "operation": "Copy",
+                    "allowed_extensions": ["txt"],
+                    "on_conflict": "Skip"
+                }
+            ]),
+        );
+        let options = RunOptions {
+            plan_report: true,
+            ..Default::default()
+        };
+        let file_organizer = FileOrganizer::new(recipes_file, options).unwrap();
+
+        let (result, output) =
+            logging::tests::capture_output(|| file_organizer.print_plan_report());
+
+        result.unwrap();
+        let path = |folder: &Path, name: &str| folder.join(name).display().to_string();
+        let expected = [
+            format!("ℹ️ docs Move - {} -> {}", path(&desk, "a.pdf"), path(&docs, "a.pdf")),
+            format!(
+                "ℹ️ docs Move - {} -> {} (on_conflict=Rename)",
+                path(&desk, "b.pdf"),
+                path(&docs, "b (1).pdf")
+            ),
+            "✅ docs Total - 2 move(s), 0 copy(ies), 0 symlink(s), 0 hardlink(s), 1 conflicting, 0 skipped".to_string(),
+            "----------------------------------------".to_string(),
+            format!("ℹ️ backup Copy - {} -> {}", path(&desk, "c.txt"), path(&backup, "c.txt")),
+            format!(
+                "⚠️ backup Skip - {} -> {} (on_conflict=Skip)",
+                path(&desk, "d.txt"),
+                path(&backup, "d.txt")
+            ),
+            "✅ backup Total - 0 move(s), 1 copy(ies), 0 symlink(s), 0 hardlink(s), 0 conflicting, 1 skipped".to_string(),
+            "ℹ️ file_organizer Plan report - 3 operation(s) in 2 recipe(s)".to_string(),
+        ];
+        assert_eq!(output.lines().collect::<Vec<_>>(), expected);
+        assert_eq!(fs::read_dir(&desk).unwrap().count(), 4);
+        assert!(!backup.join("c.txt").exists());
+    }
+
+    #[test]
+    fn concurrent_recipes_sum_up_their_statistics() {
+        let root = TempDir::new().unwrap();
+        let recipes: Vec<serde_json::Value> = ["mp3", "pdf", "png"]
+            .iter()
+            .enumerate()
+            .map(|(i, extension)| {
+                let (source, destination) = (
+                    root.path().join(format!("in_{extension}")),
+                    root.path().join(format!("out_{extension}")),
+                );
+                fs::create_dir(&destination).unwrap();
+                for n in 0..=i {
+                    let name = format!("{n}.{extension}");
+                    create_dated_file(&source.join(&name), &name, "2024-12-12");
+                }
+                create_dated_file(&source.join("skip.tmp"), "tmp", "2024-12-12");
+                json!({
+                    "name": extension,
+                    "source_folder": source,
+                    "destination_folder": destination,
+                    "operation": "Move",
+                    "allowed_extensions": [extension]
+                })
+            })
+            .collect();
+        let recipes_file = write_recipes(root.path(), json!(recipes));
+        let options = RunOptions {
+            parallel_recipes: true,
+            ..Default::default()
+        };
+
+        let stats = FileOrganizer::new(recipes_file.clone(), options)
+            .unwrap()
+            .run()
+            .unwrap();
+
+        assert_eq!(stats.files_matched, 6);
+        assert_eq!(stats.files_processed, 9);
+        assert_eq!(stats.files_errored, 0);
+        for (count, extension) in [(1, "mp3"), (2, "pdf"), (3, "png")] {
+            let out = root.path().join(format!("out_{extension}"));
+            assert_eq!(fs::read_dir(out).unwrap().count(), count);
+        }
+        let settings = Settings::load_from_file(&recipes_file).unwrap();
+        assert!(settings.recipes.iter().all(|v| v.last_run.is_some()));
+    }
+
+    #[test]
+    fn accessed_date_comparator_sorts_by_last_access() {
+        let root = TempDir::new().unwrap();
+        let (shared, archive) = (root.path().join("shared"), root.path().join("archive"));
+        fs::create_dir(&archive).unwrap();
+        let stale = shared.join("stale.xlsx");
+        create_dated_file(&stale, "stale", "2024-06-01");
+        fs::File::options()
+            .write(true)
+            .open(&stale)
+            .unwrap()
+            .set_times(fs::FileTimes::new().set_accessed(parse_day("2018-03-03").unwrap().into()))
+            .unwrap();
+        let recipes_file = write_recipes(
+            root.path(),
+            json!([{
+                "name": "stale",
+                "source_folder": shared,
+                "destination_folder": archive,
+                "operation": "Move",
+                "allowed_extensions": ["xlsx"],
+                "date_comparator": "AccessedDate",
+                "subfolders": ["%Y"]
+            }]),
+        );
+
+        FileOrganizer::new(recipes_file, RunOptions::default())
+            .unwrap()
+            .run()
+            .unwrap();
+
+        assert!(archive.join("2018").join("stale.xlsx").is_file());
+    }
+
+    #[test]
+    fn names_colliding_once_slugified_go_through_the_conflict_policy() {
+        let root = TempDir::new().unwrap();
+        let (scanner, scans) = (root.path().join("scanner"), root.path().join("scans"));
+        fs::create_dir(&scans).unwrap();
+        create_dated_file(&scanner.join("Tax Form.PDF"), "spaces", "2024-03-03");
+        create_dated_file(&scanner.join("tax_form.pdf"), "underscore", "2024-03-04");
+        let recipes_file = write_recipes(
+            root.path(),
+            json!([{
+                "name": "scans",
+                "source_folder": scanner,
+                "destination_folder": scans,
+                "operation": "Move",
+                "allowed_extensions": ["pdf"],
+                "rename_strategy": "Slugify",
+                "on_conflict": "Rename"
+            }]),
+        );
+        let options = RunOptions {
+            is_iterative: true,
+            ..Default::default()
+        };
+
+        let stats = FileOrganizer::new(recipes_file, options)
+            .unwrap()
+            .run()
+            .unwrap();
+
+        assert_eq!(stats.files_matched, 2);
+        let mut contents: Vec<String> = ["tax_form.pdf", "tax_form (1).pdf"]
+            .iter()
+            .map(|v| fs::read_to_string(scans.join(v)).unwrap())
+            .collect();
+        contents.sort();
+        assert_eq!(contents, ["spaces", "underscore"]);
+    }
+
+    #[test]
+    fn counter_template_numbers_the_files_of_a_day_in_path_order() {
+        let root = TempDir::new().unwrap();
+        let (card, photos) = (root.path().join("card"), root.path().join("photos"));
+        create_dated_file(
+            &photos.join("2023-05-01_1.jpg"),
+            "earlier run",
+            "2023-05-01",
+        );
+        for (name, day) in [
+            ("DSC_0003.jpg", "2023-05-01"),
+            ("DSC_0001.jpg", "2023-05-01"),
+            ("DSC_0002.jpg", "2023-05-02"),
+            ("IMG_0100.jpg", "2023-05-01"),
+        ] {
+            create_dated_file(&card.join(name), name, day);
+        }
+        let recipes_file = write_recipes(
+            root.path(),
+            json!([{
+                "name": "card",
+                "source_folder": card,
+                "destination_folder": photos,
+                "operation": "Copy",
+                "allowed_extensions": ["jpg"],
+                "file_name_template": "%Y-%m-%d_{counter}.{ext}"
+            }]),
+        );
+
+        FileOrganizer::new(recipes_file, RunOptions::default())
+            .unwrap()
+            .run()
+            .unwrap();
+
+        for (name, content) in [
+            ("2023-05-01_1.jpg", "earlier run"),
+            ("2023-05-01_2.jpg", "DSC_0001.jpg"),
+            ("2023-05-01_3.jpg", "DSC_0003.jpg"),
+            ("2023-05-01_4.jpg", "IMG_0100.jpg"),
+            ("2023-05-02_1.jpg", "DSC_0002.jpg"),
+        ] {
+            assert_eq!(fs::read_to_string(photos.join(name)).unwrap(), content);
+        }
+        assert_eq!(fs::read_dir(&photos).unwrap().count(), 5);
+    }
+
+    #[test]
+    fn original_token_keeps_the_stem_of_the_file() {
+        let root = TempDir::new().unwrap();
+        let (exports, reports) = (root.path().join("exports"), root.path().join("reports"));
+        fs::create_dir(&reports).unwrap();
+        create_dated_file(&exports.join("sales.q3.csv"), "sales", "2023-10-02");
+        let recipes_file = write_recipes(
+            root.path(),
+            json!([{
+                "name": "reports",
+                "source_folder": exports,
+                "destination_folder": reports,
+                "operation": "Move",
+                "allowed_extensions": ["csv"],
+                "file_name_template": "%Y%m%d-{original}.{ext}"
+            }]),
+        );
+
+        FileOrganizer::new(recipes_file, RunOptions::default())
+            .unwrap()
+            .run()
+            .unwrap();
+
+        assert!(reports.join("20231002-sales.q3.csv").is_file());
+    }
+
+    #[test]
+    fn quiet_level_prints_the_errors_only() {
+        let run_at = |max_level: Level| {
+            let root = TempDir::new().unwrap();
+            let (inbox, years) = (root.path().join("inbox"), root.path().join("years"));
+            fs::create_dir(&years).unwrap();
+            fs::write(years.join("2022"), "not a folder").unwrap();
+            for (name, day) in [("ok.txt", "2021-01-01"), ("ko.txt", "2022-01-01")] {
+                create_dated_file(&inbox.join(name), name, day);
+            }
+            create_dated_file(&inbox.join("other.bin"), "bin", "2021-01-01");
+            let recipes_file = write_recipes(
+                root.path(),
+                json!([{
+                    "name": "years",
+                    "source_folder": inbox,
+                    "destination_folder": years,
+                    "operation": "Copy",
+                    "allowed_extensions": ["txt"],
+                    "subfolders": ["%Y"]
+                }]),
+            );
+            let options = RunOptions {
+                is_iterative: true,
+                ..Default::default()
+            };
+            logging::tests::capture_output_at(max_level, || {
+                FileOrganizer::new(recipes_file, options).unwrap().run()
+            })
+            .1
+        };
+
+        let output = run_at(Level::ERROR);
+        let lines: Vec<&str> = output.lines().collect();
+        assert_eq!(lines.len(), 2, "{output}");
+        assert!(lines[0].starts_with("❌ years File failed - "));
+        assert!(lines[0].contains("ko.txt"));
+        assert_eq!(lines[1], "❌ years Files errored - 1");
+
+        let output = run_at(Level::INFO);
+        assert!(output.contains("✅ years File copied - "));
+        assert!(!output.contains("File not matched"));
+
+        let output = run_at(Level::DEBUG);
+        assert!(output.contains("➖ years File not matched - "));
+        assert!(output.contains("other.bin"));
+    }
+
+    #[test]
+    fn log_file_records_every_handled_file_of_each_run() {
+        let root = TempDir::new().unwrap();
+        let (outbox, sent) = (root.path().join("outbox"), root.path().join("sent"));
+        let log_file = root.path().join("audit.jsonl");
+        fs::create_dir(&sent).unwrap();
+        fs::write(sent.join("2020"), "not a folder").unwrap();
+        for n in 0..8 {
+            create_dated_file(&outbox.join(format!("mail_{n}.eml")), "mail", "2021-07-07");
+        }
+        create_dated_file(&outbox.join("bounced.eml"), "bounced", "2020-07-07");
+        let recipes_file = write_recipes(
+            root.path(),
+            json!([{
+                "name": "sent",
+                "source_folder": outbox,
+                "destination_folder": sent,
+                "operation": "Move",
+                "allowed_extensions": ["eml"],
+                "subfolders": ["%Y"]
+            }]),
+        );
+        let run = || {
+            let options = RunOptions {
+                is_forced: true,
+                log_file: Some(log_file.clone()),
+                ..Default::default()
+            };
+            FileOrganizer::new(recipes_file.clone(), options)
+                .unwrap()
+                .run()
+                .unwrap();
+        };
+
+        run();
+        create_dated_file(&outbox.join("late.eml"), "late", "2021-07-08");
+        run();
+
+        let entries: Vec<serde_json::Value> = fs::read_to_string(&log_file)
+            .unwrap()
+            .lines()
+            .map(|v| serde_json::from_str(v).unwrap())
+            .collect();
+        assert_eq!(entries.len(), 11);
+        let runs: HashSet<&str> = entries.iter().map(|v| v["run"].as_str().unwrap()).collect();
+        assert_eq!(runs.len(), 2);
+        let ok: Vec<&serde_json::Value> = entries.iter().filter(|v| v["result"] == "ok").collect();
+        assert_eq!(ok.len(), 9);
+        for entry in &ok {
+            let source = PathBuf::from(entry["source"].as_str().unwrap());
+            let destination = sent.join("2021").join(source.file_name().unwrap());
+            assert_eq!(entry["recipe"], "sent");
+            assert_eq!(entry["operation"], "Move");
+            assert_eq!(entry["destination"], json!(destination));
+            assert!(destination.is_file());
+            assert!(entry["timestamp"].is_string());
+        }
+        let failed: Vec<&serde_json::Value> = entries
+            .iter()
+            .filter(|v| v["result"].as_str().unwrap().starts_with("error: "))
+            .collect();
+        assert_eq!(failed.len(), 2);
+        assert!(
+            failed
+                .iter()
+                .all(|v| v["source"] == json!(outbox.join("bounced.eml")))
+        );
+    }
+
+    #[test]
+    fn files_already_in_the_destination_are_skipped_by_content() {
+        let root = TempDir::new().unwrap();
+        let (phone, backup) = (root.path().join("phone"), root.path().join("backup"));
+        create_dated_file(&phone.join("beach.jpg"), "waves", "2024-07-01");
+        create_dated_file(&phone.join("IMG_2.jpg"), "sunset", "2024-07-02");
+        create_dated_file(&phone.join("dinner.jpg"), "pasta", "2024-07-03");
+        create_dated_file(&backup.join("beach.jpg"), "waves", "2024-07-01");
+        create_dated_file(&backup.join("renamed_sunset.jpg"), "sunset", "2024-07-02");
+        create_dated_file(&backup.join("dinner.jpg"), "pizza", "2024-06-30");
+        let recipes_file = write_recipes(
+            root.path(),
+            json!([{
+                "name": "backup",
+                "source_folder": phone,
+                "destination_folder": backup,
+                "operation": "Copy",
+                "allowed_extensions": ["jpg"],
+                "skip_duplicates": true,
+                "on_conflict": "Rename"
+            }]),
+        );
+        let file_organizer = FileOrganizer::new(recipes_file, RunOptions::default()).unwrap();
+
+        let stats = file_organizer
+            .run_recipe(&file_organizer.settings.recipes[0], &[], None)
+            .unwrap();
+
+        assert_eq!(stats.files_duplicate_skipped, 2);
+        assert_eq!(stats.files_conflicted, 1);
+        assert_eq!(
+            fs::read_to_string(backup.join("dinner (1).jpg")).unwrap(),
+            "pasta"
+        );
+        assert_eq!(
+            fs::read_to_string(backup.join("dinner.jpg")).unwrap(),
+            "pizza"
+        );
+        assert_eq!(fs::read_dir(&backup).unwrap().count(), 4);
+    }
+
+    #[test]
+    fn hidden_files_are_organized_only_when_included() {
+        let root = TempDir::new().unwrap();
+        let (project, backups) = (root.path().join("project"), root.path().join("backups"));
+        fs::create_dir(&backups).unwrap();
+        for name in [".env", "config.yml", PIN_FILE_NAME, ".git/HEAD"] {
+            create_dated_file(&project.join(name), name, "2024-05-20");
+        }
+        let plan_hidden = |include_hidden: Option<bool>| {
+            planned_names(write_recipes(
+                root.path(),
+                json!([{
+                    "name": "dotfiles",
+                    "source_folder": project,
+                    "destination_folder": backups,
+                    "operation": "Copy",
+                    "allowed_extensions": [],
+                    "recursive": true,
+                    "include_hidden": include_hidden
+                }]),
+            ))
+        };
+
+        assert_eq!(plan_hidden(None), ["config.yml"]);
+        assert_eq!(plan_hidden(Some(false)), ["config.yml"]);
+        assert_eq!(plan_hidden(Some(true)), [".env", "config.yml"]);
+    }
+
+    #[test]
+    fn mime_types_match_the_files_by_their_content() {
+        let root = TempDir::new().unwrap();
+        let (recovered, images) = (root.path().join("recovered"), root.path().join("images"));
+        fs::create_dir(&images).unwrap();
+        fs::create_dir(&recovered).unwrap();
+        fs::write(
+            recovered.join("file001.dat"),
+            b"\x89PNG\r\n\x1a\n\0\0\0\rIHDR",
+        )
+        .unwrap();
+        fs::write(
+            recovered.join("file002.dat"),
+            b"\xff\xd8\xff\xe0\0\x10JFIF\0",
+        )
+        .unwrap();
+        fs::write(recovered.join("file003.dat"), "plain text, not an image").unwrap();
+        fs::write(recovered.join("photo.png"), "text named like an image").unwrap();
+        let plan_mime = |mime_types: serde_json::Value, allowed_extensions: serde_json::Value| {
+            planned_names(write_recipes(
+                root.path(),
+                json!([{
+                    "name": "images",
+                    "source_folder": recovered,
+                    "destination_folder": images,
+                    "operation": "Move",
+                    "allowed_extensions": allowed_extensions,
+                    "mime_types": mime_types
+                }]),
+            ))
+        };
+
+        assert_eq!(
+            plan_mime(json!(["image/*"]), json!(null)),
+            ["file001.dat", "file002.dat"]
+        );
+        assert_eq!(
+            plan_mime(json!(["IMAGE/JPEG"]), json!(null)),
+            ["file002.dat"]
+        );
+        assert!(plan_mime(json!(["image/*"]), json!(["png"])).is_empty());
+    }
+
+    #[test]
+    fn total_statistics_add_up_the_recipes() {
+        let recipes_stats = vec![
+            (
+                "photos".to_string(),
+                FileOrganizerStats {
+                    files_matched: 12,
+                    files_processed: 20,
+                    files_duplicate_skipped: 2,
+                    files_locked_skipped: 1,
+                    elapsed_time: 61_500,
+                    ..Default::default()
+                },
+            ),
+            (
+                "documents".to_string(),
+                FileOrganizerStats {
+                    files_matched: 3,
+                    files_processed: 4,
+                    files_conflicted: 2,
+                    files_conflict_skipped: 1,
+                    files_duplicate_skipped: 1,
+                    files_errored: 1,
+                    elapsed_time: 3_600_000,
+                    errors: vec!["report.pdf: locked".to_string()],
+                    ..Default::default()
+                },
+            ),
+            (
+                "broken".to_string(),
+                FileOrganizerStats {
+                    recipes_failed: 1,
+                    ..Default::default()
+                },
+            ),
+        ];
+
+        let ((), output) = logging::tests::capture_output(|| print_total_stats(&recipes_stats));
+        let ((), single_output) =
+            logging::tests::capture_output(|| print_total_stats(&recipes_stats[..1]));
+
+        assert_eq!(
+            output.lines().collect::<Vec<_>>(),
+            [
+                "----------------------------------------",
+                "✅ file_organizer Total files matched - 15",
+                "✅ file_organizer Total files processed - 24",
+                "✅ file_organizer Total duplicates skipped - 3",
+                "⚠️ file_organizer Total locked files skipped - 1",
+                "❌ file_organizer Total files errored - 1",
+                "❌ file_organizer Total recipes failed - 1",
+                &format!(
+                    "✅ file_organizer Total elapsed time - {}",
+                    seconds_to_string(3661)
+                ),
+            ]
+        );
+        assert!(single_output.is_empty());
+        let total_stats = sum_stats(&recipes_stats);
+        assert_eq!(
+            (
+                total_stats.files_conflicted,
+                total_stats.files_conflict_skipped,
+                total_stats.errors
+            ),
+            (2, 1, vec!["report.pdf: locked".to_string()])
+        );
+    }
+
+    #[test]
+    fn files_younger_than_the_minimum_age_stay_in_place() {
+        let root = TempDir::new().unwrap();
+        let (downloads, finished) = (root.path().join("downloads"), root.path().join("finished"));
+        fs::create_dir(&finished).unwrap();
+        create_dated_file(&downloads.join("done.iso"), "done", "2024-01-15");
+        create_dated_file(&downloads.join("growing.iso"), "growing", "2024-01-15");
+        fs::File::options()
+            .write(true)
+            .open(downloads.join("growing.iso"))
+            .unwrap()
+            .set_modified(SystemTime::now() - Duration::from_secs(600))
+            .unwrap();
+        let recipes_file = write_recipes(
+            root.path(),
+            json!([{
+                "name": "finished",
+                "source_folder": downloads,
+                "destination_folder": finished,
+                "operation": "Move",
+                "allowed_extensions": ["iso"],
+                "min_age_seconds": 3600
+            }]),
+        );
+
+        let stats = FileOrganizer::new(recipes_file, RunOptions::default())
+            .unwrap()
+            .run()
+            .unwrap();
+
+        assert_eq!(stats.files_matched, 1);
+        assert!(finished.join("done.iso").is_file());
+        assert!(downloads.join("growing.iso").is_file());
+    }
+
+    #[test]
+    fn invalid_subfolder_format_fails_before_any_recipe_runs() {
+        let root = TempDir::new().unwrap();
+        let (inbox, sorted) = (root.path().join("inbox"), root.path().join("sorted"));
+        fs::create_dir(&sorted).unwrap();
+        create_dated_file(&inbox.join("a.txt"), "a", "2024-01-01");
+        let recipe = |name: &str, subfolders: &[&str]| {
+            json!({
+                "name": name,
+                "source_folder": inbox,
+                "destination_folder": sorted,
+                "operation": "Move",
+                "allowed_extensions": ["txt"],
+                "subfolders": subfolders
+            })
+        };
+        let recipes_file = write_recipes(
+            root.path(),
+            json!([recipe("first", &["%Y"]), recipe("second", &["%Q"])]),
+        );
+
+        let error = FileOrganizer::new(recipes_file, RunOptions::default())
+            .err()
+            .unwrap();
+
+        assert_eq!(error.to_string(), "second - Invalid subfolder format: %Q");
+        assert!(inbox.join("a.txt").is_file());
+    }
+
+    #[test]
+    fn large_files_are_streamed_with_their_progress() {
+        let root = TempDir::new().unwrap();
+        let (from_file, to_file) = (root.path().join("disk.img"), root.path().join("copy.img"));
+        let content: Vec<u8> = (0..10_000u32).map(|v| (v % 251) as u8).collect();
+        fs::write(&from_file, &content).unwrap();
+        let recipe = Recipe {
+            stream_copy_bytes: Some(4096),
+            copy_buffer_bytes: Some(1000),
+            ..Default::default()
+        };
+        let progress = Mutex::new(Vec::new());
+
+        copy_file(&from_file, &to_file, &recipe, |copied, total| {
+            progress.lock().unwrap().push((copied, total));
+        })
+        .unwrap();
+
+        assert_eq!(fs::read(&to_file).unwrap(), content);
+        let progress = progress.into_inner().unwrap();
+        assert_eq!(progress.len(), 10);
+        assert_eq!(progress[0], (1000, 10_000));
+        assert_eq!(progress.last(), Some(&(10_000, 10_000)));
+        assert!(progress.windows(2).all(|v| v[0].0 < v[1].0));
+    }
+
+    #[test]
+    fn small_files_are_copied_at_once() {
+        let root = TempDir::new().unwrap();
+        let (from_file, to_file) = (root.path().join("notes.txt"), root.path().join("copy.txt"));
+        fs::write(&from_file, "short").unwrap();
+        let recipe = Recipe {
+            stream_copy_bytes: Some(4096),
+            ..Default::default()
+        };
+        let progress_count = Mutex::new(0);
+
+        copy_file(&from_file, &to_file, &recipe, |_, _| {
+            *progress_count.lock().unwrap() += 1;
+        })
+        .unwrap();
+
+        assert_eq!(fs::read_to_string(&to_file).unwrap(), "short");
+        assert_eq!(progress_count.into_inner().unwrap(), 0);
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn hooks_run_around_the_recipe_with_its_statistics() {
+        let root = TempDir::new().unwrap();
+        let (inbox, sorted) = (root.path().join("inbox"), root.path().join("sorted"));
+        let (pre_output, post_output) = (root.path().join("pre.txt"), root.path().join("post.txt"));
+        fs::create_dir(&sorted).unwrap();
+        create_dated_file(&inbox.join("report.pdf"), "report", "2024-01-05");
+        create_dated_file(&inbox.join("notes.txt"), "notes", "2024-01-05");
+        let recipes_file = write_recipes(
+            root.path(),
+            json!([{
+                "name": "reports",
+                "source_folder": inbox,
+                "destination_folder": sorted,
+                "operation": "Move",
+                "allowed_extensions": ["pdf"],
+                "pre_hook": format!("echo \"$FILE_ORGANIZER_RECIPE\" > '{}'", pre_output.display()),
+                "post_hook": format!(
+                    "echo \"$FILE_ORGANIZER_RECIPE $FILE_ORGANIZER_RESULT $FILE_ORGANIZER_FILES_MATCHED $FILE_ORGANIZER_FILES_PROCESSED $FILE_ORGANIZER_FILES_ERRORED\" > '{}'",
+                    post_output.display()
+                )
+            }]),
+        );
+
+        FileOrganizer::new(recipes_file, RunOptions::default())
+            .unwrap()
+            .run()
+            .unwrap();
+
+        assert_eq!(fs::read_to_string(&pre_output).unwrap(), "reports\n");
+        assert_eq!(
+            fs::read_to_string(&post_output).unwrap(),
+            "reports success 1 2 0\n"
+        );
+        assert!(sorted.join("report.pdf").is_file());
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn failing_pre_hook_fails_its_recipe_only() {
+        let root = TempDir::new().unwrap();
+        let (scans, papers) = (root.path().join("scans"), root.path().join("papers"));
+        let (music, songs) = (root.path().join("music"), root.path().join("songs"));
+        let post_output = root.path().join("post.txt");
+        fs::create_dir(&papers).unwrap();
+        fs::create_dir(&songs).unwrap();
+        create_dated_file(&scans.join("lease.pdf"), "lease", "2022-02-02");
+        create_dated_file(&music.join("track.mp3"), "mp3", "2022-02-03");
+        let recipes_file = write_recipes(
+            root.path(),
+            json!([
+                {
+                    "name": "papers",
+                    "source_folder": scans,
+                    "destination_folder": papers,
+                    "operation": "Move",
+                    "allowed_extensions": ["pdf"],
+                    "pre_hook": "exit 3",
+                    "post_hook": format!("touch '{}'", post_output.display())
+                },
+                {
+                    "name": "music",
+                    "source_folder": music,
+                    "destination_folder": songs,
+                    "operation": "Move",
+                    "allowed_extensions": ["mp3"]
+                }
+            ]),
+        );
+
+        let stats = FileOrganizer::new(recipes_file, RunOptions::default())
+            .unwrap()
+            .run()
+            .unwrap();
+
+        assert_eq!(stats.recipes_failed, 1);
+        assert_eq!(stats.files_matched, 1);
+        assert!(scans.join("lease.pdf").is_file());
+        assert!(!post_output.exists());
+        assert!(songs.join("track.mp3").is_file());
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn hooks_of_remote_recipe_files_run_only_when_allowed() {
+        let root = TempDir::new().unwrap();
+        let (inbox, sorted) = (root.path().join("inbox"), root.path().join("sorted"));
+        let pre_output = root.path().join("pre.txt");
+        fs::create_dir(&sorted).unwrap();
+        create_dated_file(&inbox.join("report.pdf"), "report", "2024-01-05");
+        let recipes_file = write_recipes(
+            root.path(),
+            json!([{
+                "name": "reports",
+                "source_folder": inbox,
+                "destination_folder": sorted,
+                "operation": "Move",
+                "allowed_extensions": ["pdf"],
+                "pre_hook": format!("touch '{}'", pre_output.display())
+            }]),
+        );
+        let remote_path = PathBuf::from("https://example.com/recipes.json");
+
+        let mut organizer =
+            FileOrganizer::new(recipes_file.clone(), RunOptions::default()).unwrap();
+        organizer.settings.path = remote_path.clone();
+        let error = organizer.run().err().unwrap();
+        assert!(
+            error
+                .to_string()
+                .contains("reports - The hooks of a recipe file fetched from a URL are not run")
+        );
+        assert!(!pre_output.exists());
+        assert!(inbox.join("report.pdf").is_file());
+
+        let options = RunOptions {
+            allow_remote_hooks: true,
+            ..Default::default()
+        };
+        let mut organizer = FileOrganizer::new(recipes_file, options).unwrap();
+        organizer.settings.path = remote_path;
+        organizer.run().unwrap();
+        assert!(pre_output.is_file());
+        assert!(sorted.join("report.pdf").is_file());
+    }
+
+    #[test]
+    fn runs_with_errored_files_fail() {
+        let root = TempDir::new().unwrap();
+        let (exports, years) = (root.path().join("exports"), root.path().join("years"));
+        fs::create_dir(&years).unwrap();
+        fs::write(years.join("2024"), "not a folder").unwrap();
+        create_dated_file(&exports.join("q1.csv"), "q1", "2024-03-31");
+        let recipes_file = write_recipes(
+            root.path(),
+            json!([{
+                "name": "exports",
+                "source_folder": exports,
+                "destination_folder": years,
+                "operation": "Move",
+                "allowed_extensions": ["csv"],
+                "subfolders": ["%Y"]
+            }]),
+        );
+
+        let stats = FileOrganizer::new(recipes_file.clone(), RunOptions::default())
+            .unwrap()
+            .run()
+            .unwrap();
+        assert_eq!(
+            stats.get_failure(false).as_deref(),
+            Some("1 file(s) failed, see the errors above")
+        );
+
+        fs::remove_file(years.join("2024")).unwrap();
+        let stats = FileOrganizer::new(recipes_file, RunOptions::default())
+            .unwrap()
+            .run()
+            .unwrap();
+        assert_eq!(stats.get_failure(false), None);
+        assert!(years.join("2024").join("q1.csv").is_file());
+    }
+
+    #[test]
+    fn failures_of_dry_runs_are_the_failed_recipes_only() {
+        let files_errored = FileOrganizerStats {
+            files_errored: 2,
+            ..Default::default()
+        };
+        let recipes_failed = FileOrganizerStats {
+            recipes_failed: 1,
+            ..Default::default()
+        };
+
+        assert_eq!(files_errored.get_failure(true), None);
+        assert_eq!(
+            recipes_failed.get_failure(true).as_deref(),
+            Some("1 recipe(s) failed, see the errors above")
+        );
+        assert_eq!(FileOrganizerStats::default().get_failure(false), None);
+    }
+
+    #[test]
+    fn size_buckets_sort_the_files_by_the_recipe_thresholds() {
+        let root = TempDir::new().unwrap();
+        let (uploads, by_size) = (root.path().join("uploads"), root.path().join("by_size"));
+        fs::create_dir_all(&uploads).unwrap();
+        fs::create_dir(&by_size).unwrap();
+        fs::write(uploads.join("icon.bin"), vec![0; 99]).unwrap();
+        fs::write(uploads.join("thumb.bin"), vec![0; 100]).unwrap();
+        fs::write(uploads.join("photo.bin"), vec![0; 999]).unwrap();
+        fs::write(uploads.join("video.bin"), vec![0; 1000]).unwrap();
+        let recipes_file = write_recipes(
+            root.path(),
+            json!([{
+                "name": "uploads",
+                "source_folder": uploads,
+                "destination_folder": by_size,
+                "operation": "Move",
+                "allowed_extensions": ["bin"],
+                "subfolders": ["{size_bucket}"],
+                "small_size_bytes": 100,
+                "medium_size_bytes": 1000
+            }]),
+        );
+
+        FileOrganizer::new(recipes_file, RunOptions::default())
+            .unwrap()
+            .run()
+            .unwrap();
+
+        assert!(by_size.join("small").join("icon.bin").is_file());
+        assert!(by_size.join("medium").join("thumb.bin").is_file());
+        assert!(by_size.join("medium").join("photo.bin").is_file());
+        assert!(by_size.join("large").join("video.bin").is_file());
+    }
+
+    #[test]
+    fn reversed_size_bucket_thresholds_are_rejected() {
+        let root = TempDir::new().unwrap();
+        let recipes_file = write_recipes(
+            root.path(),
+            json!([{
+                "name": "reversed",
+                "source_folder": root.path(),
+                "destination_folder": root.path(),
+                "operation": "Move",
+                "allowed_extensions": [],
+                "subfolders": ["{size_bucket}"],
+                "medium_size_bytes": 1024
+            }]),
+        );
+        let file_organizer = FileOrganizer::new(recipes_file, RunOptions::default()).unwrap();
+
+        let error = file_organizer
+            .prepare_recipe(&file_organizer.settings.recipes[0])
+            .err()
+            .unwrap();
+
+        assert_eq!(
+            error.to_string(),
+            format!(
+                "reversed - small_size_bytes {} is greater than medium_size_bytes 1024",
+                DEFAULT_SMALL_SIZE_BYTES
+            )
+        );
     }
-    return format!(
-        "{}d {}h {}m {}s",
-        seconds / 86400,
-        (seconds % 86400) / 3600,
-        (seconds % 3600) / 60,
-        seconds % 60
-    );
 }
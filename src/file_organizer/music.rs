@@ -0,0 +1,192 @@
+use crate::file_organizer::file_names::sanitize_folder_name;
+use crate::file_organizer::settings::Recipe;
+use lofty::prelude::*;
+use std::path::Path;
+
+/// The subfolder token replaced by the artist of a music file.
+pub const ARTIST_TOKEN: &str = "{artist}";
+
+/// The subfolder token replaced by the album of a music file.
+pub const ALBUM_TOKEN: &str = "{album}";
+
+/// The default folder of the music files without tags.
+const DEFAULT_FALLBACK_FOLDER: &str = "Unknown";
+
+/// MusicTags is a struct that contains the tags of a music file used to build its destination folder.
+#[derive(Default)]
+pub struct MusicTags {
+    artist: Option<String>,
+    album: Option<String>,
+}
+
+impl MusicTags {
+    /// Reads the tags of a music file.
+    /// Files that cannot be read as music files, or without tags, have no tags.
+    ///
+    /// ### Parameters
+    /// - `file`: The music file.
+    ///
+    /// ### Returns
+    /// - `MusicTags`: The tags of the file.
+    pub fn read(file: &Path) -> MusicTags {
+        let Ok(tagged_file) = lofty::read_from_path(file) else {
+            return MusicTags::default();
+        };
+        let Some(tag) = tagged_file
+            .primary_tag()
+            .or_else(|| tagged_file.first_tag())
+        else {
+            return MusicTags::default();
+        };
+        let non_empty = |value: Option<std::borrow::Cow<'_, str>>| {
+            value
+                .map(|v| v.trim().to_string())
+                .filter(|v| !v.is_empty())
+        };
+        MusicTags {
+            artist: non_empty(tag.artist()),
+            album: non_empty(tag.album()),
+        }
+    }
+
+    /// Checks if the file has none of the tags.
+    ///
+    /// ### Returns
+    /// - `bool`: True if the file has neither an artist nor an album, false otherwise.
+    pub fn is_empty(&self) -> bool {
+        self.artist.is_none() && self.album.is_none()
+    }
+
+    /// Replaces the `{artist}` and `{album}` tokens of a subfolder name.
+    /// The tags are sanitized, path separators included, and a missing tag is replaced by the fallback folder name.
+    ///
+    /// ### Parameters
+    /// - `subfolder_name`: The subfolder name.
+    /// - `recipe`: The recipe defining the fallback folder.
+    ///
+    /// ### Returns
+    /// - `String`: The subfolder name with its tokens replaced.
+    pub fn replace_tokens(&self, subfolder_name: &str, recipe: &Recipe) -> String {
+        let to_folder_name = |tag: &Option<String>| match tag {
+            Some(tag) => sanitize_folder_name(&tag.replace(['/', '\\'], "_")),
+            None => get_fallback_folder(recipe).to_string(),
+        };
+        subfolder_name
+            .replace(ARTIST_TOKEN, &to_folder_name(&self.artist))
+            .replace(ALBUM_TOKEN, &to_folder_name(&self.album))
+    }
+}
+
+/// Checks if the subfolders of a recipe use music tags tokens.
+///
+/// ### Parameters
+/// - `recipe`: The recipe to check.
+///
+/// ### Returns
+/// - `bool`: True if a subfolder contains `{artist}` or `{album}`, false otherwise.
+pub fn uses_music_tokens(recipe: &Recipe) -> bool {
+    recipe
+        .subfolders
+        .iter()
+        .flatten()
+        .any(|subfolder| subfolder.contains(ARTIST_TOKEN) || subfolder.contains(ALBUM_TOKEN))
+}
+
+/// Gets the folder of the music files without tags.
+///
+/// ### Parameters
+/// - `recipe`: The recipe defining the fallback folder.
+///
+/// ### Returns
+/// - `&str`: The fallback folder.
+pub fn get_fallback_folder(recipe: &Recipe) -> &str {
+    recipe
+        .music_fallback_folder
+        .as_deref()
+        .unwrap_or(DEFAULT_FALLBACK_FOLDER)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::fs;
+    use tempfile::TempDir;
+
+    /// Builds an MP3 of a few silent frames, preceded by an ID3v2.3 tag when tags are given.
+    ///
+    /// ### Parameters
+    /// - `tags`: The ID3 frame identifiers and text values, e.g. `("TPE1", "Artist")`.
+    ///
+    /// ### Returns
+    /// - `Vec<u8>`: The bytes of the MP3.
+    fn build_mp3(tags: &[(&str, &str)]) -> Vec<u8> {
+        let mut frames = Vec::new();
+        for (id, value) in tags {
+            frames.extend(id.as_bytes());
+            frames.extend(((value.len() + 1) as u32).to_be_bytes());
+            frames.extend([0, 0, 0]);
+            frames.extend(value.as_bytes());
+        }
+        let mut mp3 = Vec::new();
+        if !tags.is_empty() {
+            mp3.extend(b"ID3\x03\x00\x00");
+            let size = frames.len() as u32;
+            mp3.extend([
+                (size >> 21 & 0x7F) as u8,
+                (size >> 14 & 0x7F) as u8,
+                (size >> 7 & 0x7F) as u8,
+                (size & 0x7F) as u8,
+            ]);
+            mp3.extend(frames);
+        }
+        // MPEG-1 Layer III frames at 128 kbit/s and 44.1 kHz, 417 bytes each.
+        for _ in 0..4 {
+            mp3.extend([0xFF, 0xFB, 0x90, 0x00]);
+            mp3.extend([0; 413]);
+        }
+        mp3
+    }
+
+    #[test]
+    fn tagged_file_is_routed_by_artist_and_album() {
+        let folder = TempDir::new().unwrap();
+        let song = folder.path().join("track01.mp3");
+        fs::write(
+            &song,
+            build_mp3(&[("TPE1", "AC/DC"), ("TALB", "Back in Black")]),
+        )
+        .unwrap();
+        let recipe = Recipe::default();
+
+        let tags = MusicTags::read(&song);
+
+        assert!(!tags.is_empty());
+        assert_eq!(
+            tags.replace_tokens("{artist}/{album}", &recipe),
+            "AC_DC/Back in Black"
+        );
+    }
+
+    #[test]
+    fn untagged_file_is_routed_to_the_fallback_folder() {
+        let folder = TempDir::new().unwrap();
+        let (song, text) = (
+            folder.path().join("untagged.mp3"),
+            folder.path().join("a.txt"),
+        );
+        fs::write(&song, build_mp3(&[])).unwrap();
+        fs::write(&text, "not music").unwrap();
+        let recipe = Recipe {
+            music_fallback_folder: Some("Untagged".to_string()),
+            ..Default::default()
+        };
+
+        assert!(MusicTags::read(&song).is_empty());
+        assert!(MusicTags::read(&text).is_empty());
+        assert_eq!(
+            MusicTags::read(&song).replace_tokens("{artist}", &recipe),
+            "Untagged"
+        );
+        assert_eq!(get_fallback_folder(&Recipe::default()), "Unknown");
+    }
+}
@@ -0,0 +1,130 @@
+use image_hasher::{Hasher, HasherConfig, ImageHash};
+use std::path::{Path, PathBuf};
+
+/// The default maximum number of differing bits between the perceptual hashes of two near-duplicate images, out of 64.
+pub const DEFAULT_NEAR_DUPE_THRESHOLD: u32 = 8;
+
+/// The extensions of the images compared by their perceptual hash.
+const IMAGE_EXTENSIONS: [&str; 8] = ["jpg", "jpeg", "png", "gif", "webp", "bmp", "tif", "tiff"];
+
+/// Checks if a file is an image that can be compared by its perceptual hash, from its extension.
+///
+/// ### Parameters
+/// - `file`: The file to check.
+///
+/// ### Returns
+/// - `bool`: True if the file has an image extension, case insensitive.
+pub fn is_image(file: &Path) -> bool {
+    file.extension()
+        .map(|v| v.to_string_lossy().to_lowercase())
+        .is_some_and(|v| IMAGE_EXTENSIONS.contains(&v.as_str()))
+}
+
+/// Builds the hasher computing the perceptual hashes of the images.
+///
+/// ### Returns
+/// - `Hasher`: The hasher, computing 64 bits gradient hashes.
+pub fn build_hasher() -> Hasher {
+    HasherConfig::new().to_hasher()
+}
+
+/// Computes the perceptual hash of an image, which stays close for the re-encoded, resized or slightly edited copies of the image.
+///
+/// ### Parameters
+/// - `file`: The image.
+/// - `hasher`: The hasher.
+///
+/// ### Returns
+/// - `Result<ImageHash, anyhow::Error>`: The perceptual hash, an error if the image cannot be decoded.
+pub fn get_image_hash(file: &Path, hasher: &Hasher) -> anyhow::Result<ImageHash> {
+    let image = image::open(file)?;
+    Ok(hasher.hash_image(&image))
+}
+
+/// Groups the images whose perceptual hashes are within a threshold of each other.
+/// Each image joins the first group whose first image is close enough to it, the images being taken in path order so that the groups do not depend on the scan order.
+///
+/// ### Parameters
+/// - `images`: The images with their perceptual hash.
+/// - `threshold`: The maximum number of differing bits between two near-duplicates.
+///
+/// ### Returns
+/// - `Vec<Vec<PathBuf>>`: The groups of near-duplicate images, single images forming their own group.
+pub fn group_near_duplicates(
+    mut images: Vec<(PathBuf, ImageHash)>,
+    threshold: u32,
+) -> Vec<Vec<PathBuf>> {
+    images.sort_by(|a, b| a.0.cmp(&b.0));
+    let mut groups: Vec<(ImageHash, Vec<PathBuf>)> = Vec::new();
+    for (image, hash) in images {
+        match groups.iter_mut().find(|(v, _)| v.dist(&hash) <= threshold) {
+            Some((_, group)) => group.push(image),
+            None => groups.push((hash, vec![image])),
+        }
+    }
+    groups.into_iter().map(|(_, group)| group).collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use image::{Rgb, RgbImage};
+    use std::fs;
+    use tempfile::TempDir;
+
+    /// Draws an image of vertical bands whose brightness follows a pattern.
+    ///
+    /// ### Parameters
+    /// - `size`: The width and height of the image.
+    /// - `brightness`: The brightness of a column, from its position between 0 and 1.
+    ///
+    /// ### Returns
+    /// - `RgbImage`: The image.
+    fn draw_bands(size: u32, brightness: impl Fn(f32) -> f32) -> RgbImage {
+        RgbImage::from_fn(size, size, |x, y| {
+            let value = (brightness(x as f32 / size as f32) * 255.0) as u8;
+            Rgb([value, value, (y * 255 / size) as u8])
+        })
+    }
+
+    #[test]
+    fn resized_copies_are_grouped_with_their_original() {
+        let folder = TempDir::new().unwrap();
+        let (original, resized, other) = (
+            folder.path().join("sunset.png"),
+            folder.path().join("sunset_small.jpg"),
+            folder.path().join("stripes.png"),
+        );
+        draw_bands(128, |v| v).save(&original).unwrap();
+        draw_bands(96, |v| v).save(&resized).unwrap();
+        draw_bands(128, |v| {
+            if ((v * 8.0) as u32).is_multiple_of(2) {
+                0.9
+            } else {
+                0.1
+            }
+        })
+        .save(&other)
+        .unwrap();
+        let hasher = build_hasher();
+        let images: Vec<(PathBuf, ImageHash)> = [&other, &resized, &original]
+            .into_iter()
+            .map(|v| (v.clone(), get_image_hash(v, &hasher).unwrap()))
+            .collect();
+
+        let groups = group_near_duplicates(images, DEFAULT_NEAR_DUPE_THRESHOLD);
+
+        assert_eq!(groups, [vec![other], vec![original, resized]]);
+    }
+
+    #[test]
+    fn files_that_are_not_images_cannot_be_hashed() {
+        let folder = TempDir::new().unwrap();
+        let fake = folder.path().join("fake.PNG");
+        fs::write(&fake, "not an image").unwrap();
+
+        assert!(is_image(&fake));
+        assert!(!is_image(Path::new("notes.txt")));
+        assert!(get_image_hash(&fake, &build_hasher()).is_err());
+    }
+}
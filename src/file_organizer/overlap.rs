@@ -0,0 +1,251 @@
+use crate::file_organizer::settings::Recipe;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+/// OverlapPolicy is an enum that contains what a run does when the destination of a recipe overlaps the source of another one.
+#[derive(Default, Debug, Clone, PartialEq)]
+pub enum OverlapPolicy {
+    #[default]
+    Warn,
+    Reorder,
+    Error,
+}
+
+impl OverlapPolicy {
+    /// Parses an overlap policy.
+    ///
+    /// ### Parameters
+    /// - `policy`: The policy to parse: warn, reorder or error.
+    ///
+    /// ### Returns
+    /// - `Result<OverlapPolicy, anyhow::Error>`: The overlap policy.
+    pub fn parse(policy: &str) -> anyhow::Result<OverlapPolicy> {
+        match policy.trim().to_lowercase().as_str() {
+            "warn" => Ok(OverlapPolicy::Warn),
+            "reorder" => Ok(OverlapPolicy::Reorder),
+            "error" => Ok(OverlapPolicy::Error),
+            _ => Err(anyhow::Error::msg(format!(
+                "Invalid overlap policy: {}, expected warn, reorder or error",
+                policy
+            ))),
+        }
+    }
+}
+
+/// Overlap is a struct that contains a recipe whose destination folder overlaps a source folder of another recipe, which then picks up its files.
+pub struct Overlap {
+    pub from: usize,
+    pub to: usize,
+}
+
+/// Finds the recipes whose destination folder overlaps a source folder of another recipe.
+/// Two folders overlap if they are the same folder or if the source folder is inside the destination folder, after symlinks resolution.
+/// A destination folder inside a source folder only overlaps it when the recipe of the source scans its subfolders (recursive or in place), a recipe sorting `Downloads` into `Downloads/Images` never picking up the files of another one doing the same.
+///
+/// ### Parameters
+/// - `recipes`: The recipes to analyze, with their folders resolved.
+///
+/// ### Returns
+/// - `Vec<Overlap>`: The overlaps, ordered by recipe.
+pub fn find_overlaps(recipes: &[Recipe]) -> Vec<Overlap> {
+    let folders = get_canonical_folders(recipes);
+    let mut overlaps = Vec::new();
+    for (from, (_, destination_folder)) in folders.iter().enumerate() {
+        for (to, (source_folders, _)) in folders.iter().enumerate() {
+            let is_scanning_subfolders =
+                recipes[to].recursive.unwrap_or(false) || recipes[to].in_place.unwrap_or(false);
+            if from != to
+                && source_folders.iter().any(|source_folder| {
+                    source_folder.starts_with(destination_folder)
+                        || (is_scanning_subfolders && destination_folder.starts_with(source_folder))
+                })
+            {
+                overlaps.push(Overlap { from, to });
+            }
+        }
+    }
+    overlaps
+}
+
+/// Sorts the recipes topologically, each recipe running before the recipes picking up its files.
+/// Independent recipes keep their order in the recipe file.
+///
+/// ### Parameters
+/// - `recipes_count`: The number of recipes.
+/// - `overlaps`: The overlaps between the recipes.
+///
+/// ### Returns
+/// - `Result<Vec<usize>, Vec<usize>>`: The indexes of the recipes in their running order, or the indexes of the recipes in or after a cycle.
+pub fn sort_recipes(recipes_count: usize, overlaps: &[Overlap]) -> Result<Vec<usize>, Vec<usize>> {
+    let mut incoming_counts = vec![0; recipes_count];
+    for overlap in overlaps {
+        incoming_counts[overlap.to] += 1;
+    }
+    let mut order = Vec::new();
+    while order.len() < recipes_count {
+        let Some(next) =
+            (0..recipes_count).find(|v| incoming_counts[*v] == 0 && !order.contains(v))
+        else {
+            return Err((0..recipes_count).filter(|v| !order.contains(v)).collect());
+        };
+        for overlap in overlaps.iter().filter(|v| v.from == next) {
+            incoming_counts[overlap.to] -= 1;
+        }
+        order.push(next);
+    }
+    Ok(order)
+}
+
+/// Groups the recipes into batches of recipes that can run concurrently, the batches running one after another.
+/// A recipe starts a new batch when it shares a folder with a recipe of the current batch, its destination folder overlapping their destination or source folders or the other way around, so that such recipes keep running in order.
+///
+/// ### Parameters
+/// - `recipes`: The recipes to run, in order, with their folders resolved.
+///
+/// ### Returns
+/// - `Vec<Vec<usize>>`: The indexes of the recipes of each batch, in order.
+pub fn batch_recipes(recipes: &[Recipe]) -> Vec<Vec<usize>> {
+    let folders = get_canonical_folders(recipes);
+    let is_overlapping = |a: &PathBuf, b: &PathBuf| a.starts_with(b) || b.starts_with(a);
+    let is_conflicting = |a: usize, b: usize| {
+        is_overlapping(&folders[a].1, &folders[b].1)
+            || folders[a]
+                .0
+                .iter()
+                .any(|v| is_overlapping(v, &folders[b].1))
+            || folders[b]
+                .0
+                .iter()
+                .any(|v| is_overlapping(v, &folders[a].1))
+    };
+    let mut batches: Vec<Vec<usize>> = Vec::new();
+    for index in 0..recipes.len() {
+        match batches.last_mut() {
+            Some(batch) if !batch.iter().any(|v| is_conflicting(*v, index)) => batch.push(index),
+            _ => batches.push(vec![index]),
+        }
+    }
+    batches
+}
+
+/// Gets the canonical folders of the recipes.
+///
+/// ### Parameters
+/// - `recipes`: The recipes, with their folders resolved.
+///
+/// ### Returns
+/// - `Vec<(Vec<PathBuf>, PathBuf)>`: The source folders and the destination folder of each recipe.
+fn get_canonical_folders(recipes: &[Recipe]) -> Vec<(Vec<PathBuf>, PathBuf)> {
+    recipes
+        .iter()
+        .map(|v| {
+            (
+                v.get_source_folders()
+                    .into_iter()
+                    .map(|v| canonicalize(v))
+                    .collect(),
+                canonicalize(&v.destination_folder),
+            )
+        })
+        .collect()
+}
+
+/// Canonicalizes a folder, keeping it as is if it does not exist.
+///
+/// ### Parameters
+/// - `folder`: The folder to canonicalize.
+///
+/// ### Returns
+/// - `PathBuf`: The canonical folder.
+fn canonicalize(folder: &Path) -> PathBuf {
+    fs::canonicalize(folder).unwrap_or(folder.to_path_buf())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Builds a recipe moving files from a source folder into a destination folder.
+    ///
+    /// ### Parameters
+    /// - `name`: The name of the recipe.
+    /// - `source_folder`: The source folder.
+    /// - `destination_folder`: The destination folder.
+    ///
+    /// ### Returns
+    /// - `Recipe`: The recipe.
+    fn moving_recipe(name: &str, source_folder: &str, destination_folder: &str) -> Recipe {
+        Recipe {
+            name: name.to_string(),
+            source_folder: PathBuf::from(source_folder),
+            destination_folder: PathBuf::from(destination_folder),
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn two_recipe_cycle_is_detected() {
+        let recipes = [
+            moving_recipe("inbox_to_archive", "/srv/inbox", "/srv/archive"),
+            moving_recipe("archive_to_inbox", "/srv/archive", "/srv/inbox"),
+        ];
+
+        let overlaps = find_overlaps(&recipes);
+
+        let pairs: Vec<(usize, usize)> = overlaps.iter().map(|v| (v.from, v.to)).collect();
+        assert_eq!(pairs, [(0, 1), (1, 0)]);
+        assert_eq!(sort_recipes(recipes.len(), &overlaps), Err(vec![0, 1]));
+    }
+
+    #[test]
+    fn chain_is_ordered_with_producers_first() {
+        let recipes = [
+            moving_recipe("sorted_to_archive", "/srv/sorted", "/srv/archive"),
+            moving_recipe("unrelated", "/srv/music", "/srv/library"),
+            moving_recipe("inbox_to_sorted", "/srv/inbox", "/srv/sorted"),
+        ];
+
+        let overlaps = find_overlaps(&recipes);
+
+        assert_eq!(sort_recipes(recipes.len(), &overlaps), Ok(vec![1, 2, 0]));
+    }
+
+    #[test]
+    fn nested_destination_only_overlaps_recursive_sources() {
+        let mut recipes = [
+            moving_recipe("images", "/srv/downloads", "/srv/downloads/Images"),
+            moving_recipe("documents", "/srv/downloads", "/srv/downloads/Documents"),
+        ];
+        assert!(find_overlaps(&recipes).is_empty());
+
+        recipes[1].recursive = Some(true);
+
+        let pairs: Vec<(usize, usize)> = find_overlaps(&recipes)
+            .iter()
+            .map(|v| (v.from, v.to))
+            .collect();
+        assert_eq!(pairs, [(0, 1)]);
+    }
+
+    #[test]
+    fn invalid_overlap_policy_is_rejected() {
+        assert_eq!(
+            OverlapPolicy::parse(" Reorder ").unwrap(),
+            OverlapPolicy::Reorder
+        );
+        assert!(OverlapPolicy::parse("ignore").is_err());
+    }
+
+    #[test]
+    fn recipes_sharing_folders_are_batched_apart() {
+        let recipes = [
+            moving_recipe("photos", "/srv/phone", "/srv/photos"),
+            moving_recipe("music", "/srv/downloads", "/srv/music"),
+            moving_recipe("screenshots", "/srv/desktop", "/srv/photos/screens"),
+            moving_recipe("books", "/srv/mail", "/srv/books"),
+            moving_recipe("podcasts", "/srv/music", "/srv/podcasts"),
+        ];
+
+        assert_eq!(batch_recipes(&recipes), [vec![0, 1], vec![2, 3, 4]]);
+    }
+}
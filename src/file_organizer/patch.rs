@@ -0,0 +1,442 @@
+use crate::file_organizer::compact::extract_member;
+use crate::file_organizer::journal::get_fingerprint;
+use crate::file_organizer::plan::PlanOperationKind;
+use crate::file_organizer::settings::Settings;
+use crate::file_organizer::{move_file, remove_empty_folders};
+use colored::*;
+use serde::{Deserialize, Serialize};
+use std::collections::HashSet;
+use std::ffi::OsString;
+use std::fs;
+use std::fs::File;
+use std::io::Write;
+use std::path::{Path, PathBuf};
+use std::sync::Mutex;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use tracing::{error, info, warn};
+
+/// The extension appended to the name of a patch file to name the folder of its backups.
+const BACKUP_FOLDER_EXTENSION: &str = "backup";
+
+/// PatchOperation is a struct that contains an operation executed by a run, with what is needed to undo it.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct PatchOperation {
+    pub recipe: String,
+    pub operation: PlanOperationKind,
+    pub source: PathBuf,
+    pub destination: PathBuf,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub backup: Option<PathBuf>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub created_folder: Option<PathBuf>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub archive_member: Option<PathBuf>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub size: Option<u64>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub modified: Option<String>,
+}
+
+/// Patch is a struct that contains everything a run changed, so that the run can be reverted.
+/// The patch file holds the patch without its operations on its first line, then one operation per line, appended as the run goes.
+#[derive(Debug, Default, Serialize, Deserialize)]
+pub struct Patch {
+    pub recipes_file: PathBuf,
+    pub last_runs: Vec<(String, Option<String>)>,
+    #[serde(default)]
+    pub last_seen_dates: Vec<(String, Option<String>)>,
+    pub operations: Vec<PatchOperation>,
+}
+
+/// PatchRecorder is a struct that records the operations of a run into a patch file as they are executed, backing up the files they overwrite, so that an interrupted run can be reverted as well.
+pub struct PatchRecorder {
+    path: PathBuf,
+    backup_folder: PathBuf,
+    backups_count: AtomicUsize,
+    operations_count: AtomicUsize,
+    file: Mutex<File>,
+}
+
+impl PatchRecorder {
+    /// Creates a patch recorder, writing the first line of the patch file.
+    ///
+    /// ### Parameters
+    /// - `path`: The path to the patch file.
+    /// - `settings`: The settings of the run, before it updates them.
+    ///
+    /// ### Returns
+    /// - `Result<PatchRecorder, anyhow::Error>`: The patch recorder.
+    pub fn new(path: &Path, settings: &Settings) -> anyhow::Result<PatchRecorder> {
+        let patch = Patch {
+            recipes_file: fs::canonicalize(&settings.path).unwrap_or(settings.path.clone()),
+            last_runs: settings
+                .recipes
+                .iter()
+                .map(|v| (v.name.clone(), v.last_run.clone()))
+                .collect(),
+            last_seen_dates: settings
+                .recipes
+                .iter()
+                .map(|v| (v.name.clone(), v.last_seen.clone()))
+                .collect(),
+            operations: Vec::new(),
+        };
+        let write = || -> anyhow::Result<File> {
+            let mut file = File::create(path)?;
+            writeln!(file, "{}", serde_json::to_string(&patch)?)?;
+            Ok(file)
+        };
+        let file = write().map_err(|e| {
+            anyhow::Error::msg(format!(
+                "Error writing the patch file {}: {}",
+                path.display(),
+                e
+            ))
+        })?;
+        Ok(PatchRecorder {
+            path: path.to_path_buf(),
+            backup_folder: get_backup_folder(
+                &std::path::absolute(path).unwrap_or(path.to_path_buf()),
+            ),
+            backups_count: AtomicUsize::new(0),
+            operations_count: AtomicUsize::new(0),
+            file: Mutex::new(file),
+        })
+    }
+
+    /// Moves a file about to be overwritten into the backup folder of the patch.
+    ///
+    /// ### Parameters
+    /// - `file`: The file about to be overwritten.
+    ///
+    /// ### Returns
+    /// - `Result<PathBuf, anyhow::Error>`: The backup of the file.
+    pub fn backup(&self, file: &Path) -> anyhow::Result<PathBuf> {
+        let backup = self.get_backup(file)?;
+        move_file(file, &backup)?;
+        Ok(backup)
+    }
+
+    /// Copies a file about to be rewritten into the backup folder of the patch, the file being kept in place.
+    ///
+    /// ### Parameters
+    /// - `file`: The file about to be rewritten, e.g. an archive appended to.
+    ///
+    /// ### Returns
+    /// - `Result<PathBuf, anyhow::Error>`: The backup of the file.
+    pub fn backup_copy(&self, file: &Path) -> anyhow::Result<PathBuf> {
+        let backup = self.get_backup(file)?;
+        fs::copy(file, &backup)?;
+        Ok(backup)
+    }
+
+    /// Gets a free path in the backup folder of the patch for a file, creating the folder.
+    ///
+    /// ### Parameters
+    /// - `file`: The file to back up.
+    ///
+    /// ### Returns
+    /// - `Result<PathBuf, anyhow::Error>`: The backup of the file, named after it.
+    fn get_backup(&self, file: &Path) -> anyhow::Result<PathBuf> {
+        fs::create_dir_all(&self.backup_folder)?;
+        let mut backup_name = OsString::from(format!(
+            "{}_",
+            self.backups_count.fetch_add(1, Ordering::SeqCst)
+        ));
+        backup_name.push(file.file_name().unwrap_or_default());
+        Ok(self.backup_folder.join(backup_name))
+    }
+
+    /// Records an executed operation, appending it to the patch file at once along with the size and modification date of its destination, which --revert compares to tell if it changed since.
+    /// A failed write is reported as a warning, the run going on.
+    ///
+    /// ### Parameters
+    /// - `operation`: The executed operation.
+    pub fn record(&self, mut operation: PatchOperation) {
+        (operation.size, operation.modified) = get_fingerprint(&operation.destination);
+        let write = || -> anyhow::Result<()> {
+            let mut line = serde_json::to_string(&operation)?;
+            line.push('\n');
+            let mut file = self.file.lock().unwrap();
+            file.write_all(line.as_bytes())?;
+            file.flush()?;
+            Ok(())
+        };
+        match write() {
+            Ok(()) => {
+                self.operations_count.fetch_add(1, Ordering::SeqCst);
+            }
+            Err(e) => warn!(
+                "{} {} {} - {}: {}",
+                "⚠️".yellow(),
+                operation.recipe.blue(),
+                "Patch file not written".yellow(),
+                operation.source.display(),
+                e
+            ),
+        }
+    }
+
+    /// Syncs the patch file to the disk.
+    ///
+    /// ### Returns
+    /// - `Result<usize, anyhow::Error>`: The number of recorded operations.
+    pub fn save(&self) -> anyhow::Result<usize> {
+        self.file.lock().unwrap().sync_all().map_err(|e| {
+            anyhow::Error::msg(format!(
+                "Error writing the patch file {}: {}",
+                self.path.display(),
+                e
+            ))
+        })?;
+        Ok(self.operations_count.load(Ordering::SeqCst))
+    }
+}
+
+/// Loads a patch file, its first line holding the patch and the next ones its operations.
+///
+/// ### Parameters
+/// - `path`: The path to the patch file.
+///
+/// ### Returns
+/// - `Result<Patch, anyhow::Error>`: The patch, with its operations in execution order.
+fn load_patch(path: &Path) -> anyhow::Result<Patch> {
+    let content = fs::read_to_string(path).map_err(|e| {
+        anyhow::Error::msg(format!(
+            "Error loading the patch file {}: {}",
+            path.display(),
+            e
+        ))
+    })?;
+    let mut lines = content
+        .lines()
+        .enumerate()
+        .filter(|(_, v)| !v.trim().is_empty());
+    let parse_error = |index: usize, e: serde_json::Error| {
+        anyhow::Error::msg(format!(
+            "Error parsing line {} of the patch file {}: {}",
+            index + 1,
+            path.display(),
+            e
+        ))
+    };
+    let Some((index, line)) = lines.next() else {
+        return Err(anyhow::Error::msg(format!(
+            "Empty patch file {}",
+            path.display()
+        )));
+    };
+    let mut patch: Patch = serde_json::from_str(line).map_err(|e| parse_error(index, e))?;
+    for (index, line) in lines {
+        patch
+            .operations
+            .push(serde_json::from_str(line).map_err(|e| parse_error(index, e))?);
+    }
+    Ok(patch)
+}
+
+/// Gets the folder of the backups of a patch file.
+///
+/// ### Parameters
+/// - `path`: The path to the patch file.
+///
+/// ### Returns
+/// - `PathBuf`: The backup folder, named after the patch file.
+fn get_backup_folder(path: &Path) -> PathBuf {
+    let mut backup_folder = path.as_os_str().to_os_string();
+    backup_folder.push(".");
+    backup_folder.push(BACKUP_FOLDER_EXTENSION);
+    PathBuf::from(backup_folder)
+}
+
+/// Gets the topmost folder that creating a folder would create.
+///
+/// ### Parameters
+/// - `folder`: The folder about to be created.
+///
+/// ### Returns
+/// - `Option<PathBuf>`: The topmost missing folder, None if the folder already exists.
+pub fn get_created_folder(folder: &Path) -> Option<PathBuf> {
+    folder
+        .ancestors()
+        .take_while(|v| !v.as_os_str().is_empty() && !v.exists())
+        .last()
+        .map(|v| v.to_path_buf())
+}
+
+/// Reverts the run recorded in a patch file.
+/// The operations are undone in reverse order: moved files are moved back, copies are deleted and overwritten files are restored from their backup.
+/// The files moved into an archive by a compact recipe are extracted back, and the archives are deleted, or restored from their backup if the run appended to them.
+/// An operation whose destination has been modified since the run is skipped rather than clobbering the file.
+/// The folders created by the run are removed if empty, and the last run and last seen dates of the recipes are restored.
+///
+/// ### Parameters
+/// - `path`: The path to the patch file.
+///
+/// ### Returns
+/// - `Result<(u32, u32), anyhow::Error>`: The number of reverted and of failed operations.
+pub fn revert_patch(path: &Path) -> anyhow::Result<(u32, u32)> {
+    let patch = load_patch(path)?;
+    let mut reverted = 0;
+    let mut failed = 0;
+    let mut failed_archives = HashSet::new();
+    for operation in patch.operations.iter().rev() {
+        match revert_operation(operation) {
+            Ok(()) => {
+                reverted += 1;
+                info!(
+                    "{} {} {} - {}",
+                    "✅".green(),
+                    operation.recipe.blue(),
+                    "Operation reverted".green(),
+                    operation.source.display()
+                );
+            }
+            Err(e) => {
+                failed += 1;
+                if operation.archive_member.is_some() {
+                    failed_archives.insert(&operation.destination);
+                }
+                error!(
+                    "{} {} {} - {}: {}",
+                    "❌".red(),
+                    operation.recipe.blue(),
+                    "Revert skipped".red(),
+                    operation.destination.display(),
+                    e
+                );
+            }
+        }
+    }
+    let mut reverted_archives = HashSet::new();
+    for operation in patch.operations.iter().rev() {
+        if operation.archive_member.is_none()
+            || failed_archives.contains(&operation.destination)
+            || !reverted_archives.insert(&operation.destination)
+        {
+            continue;
+        }
+        if let Err(e) = revert_archive(&patch, &operation.destination) {
+            failed += 1;
+            error!(
+                "{} {} {} - {}: {}",
+                "❌".red(),
+                operation.recipe.blue(),
+                "Revert skipped".red(),
+                operation.destination.display(),
+                e
+            );
+        }
+    }
+    let mut created_folders: Vec<&PathBuf> = patch
+        .operations
+        .iter()
+        .filter_map(|v| v.created_folder.as_ref())
+        .collect();
+    created_folders.sort_by_key(|v| std::cmp::Reverse(v.components().count()));
+    for folder in created_folders {
+        remove_empty_folders(folder);
+    }
+    remove_empty_folders(&get_backup_folder(path));
+    restore_last_runs(&patch)?;
+    Ok((reverted, failed))
+}
+
+/// Reverts an operation of a patch.
+///
+/// ### Parameters
+/// - `operation`: The operation to revert.
+///
+/// ### Returns
+/// - `Result<(), anyhow::Error>`: The result of the revert.
+fn revert_operation(operation: &PatchOperation) -> anyhow::Result<()> {
+    if !operation.destination.is_file() {
+        return Err(anyhow::Error::msg("Destination no longer exists"));
+    }
+    if operation.size.is_some()
+        && get_fingerprint(&operation.destination) != (operation.size, operation.modified.clone())
+    {
+        return Err(anyhow::Error::msg("Destination modified since the run"));
+    }
+    if let Some(archive_member) = &operation.archive_member {
+        if operation.operation == PlanOperationKind::Move {
+            if operation.source.exists() {
+                return Err(anyhow::Error::msg(format!(
+                    "Source reappeared since the run: {}",
+                    operation.source.display()
+                )));
+            }
+            extract_member(&operation.destination, archive_member, &operation.source)?;
+        }
+        return Ok(());
+    }
+    if let Some(backup) = &operation.backup
+        && !backup.is_file()
+    {
+        return Err(anyhow::Error::msg(format!(
+            "Backup of the overwritten file no longer exists: {}",
+            backup.display()
+        )));
+    }
+    match operation.operation {
+        PlanOperationKind::Move => {
+            if operation.source.exists() {
+                return Err(anyhow::Error::msg(format!(
+                    "Source reappeared since the run: {}",
+                    operation.source.display()
+                )));
+            }
+            if let Some(source_folder) = operation.source.parent() {
+                fs::create_dir_all(source_folder)?;
+            }
+            move_file(&operation.destination, &operation.source)?;
+        }
+        PlanOperationKind::Copy | PlanOperationKind::Symlink | PlanOperationKind::Hardlink => {
+            fs::remove_file(&operation.destination)?
+        }
+    }
+    if let Some(backup) = &operation.backup {
+        move_file(backup, &operation.destination)?;
+    }
+    Ok(())
+}
+
+/// Reverts an archive written by a compact recipe once its files have been extracted back: the archive is restored from its backup if the run appended to it, deleted otherwise.
+///
+/// ### Parameters
+/// - `patch`: The patch.
+/// - `archive`: The archive.
+///
+/// ### Returns
+/// - `Result<(), anyhow::Error>`: The result of the revert.
+fn revert_archive(patch: &Patch, archive: &Path) -> anyhow::Result<()> {
+    let backup = patch
+        .operations
+        .iter()
+        .filter(|v| v.destination == archive)
+        .find_map(|v| v.backup.as_ref());
+    match backup {
+        Some(backup) => move_file(backup, archive),
+        None => Ok(fs::remove_file(archive)?),
+    }
+}
+
+/// Restores the last run and last seen dates of the recipes recorded in a patch.
+///
+/// ### Parameters
+/// - `patch`: The patch.
+///
+/// ### Returns
+/// - `Result<(), anyhow::Error>`: The result of the restore.
+fn restore_last_runs(patch: &Patch) -> anyhow::Result<()> {
+    let mut settings = Settings::load_from_file(&patch.recipes_file)?;
+    for recipe in &mut settings.recipes {
+        if let Some((_, last_run)) = patch.last_runs.iter().find(|v| v.0 == recipe.name) {
+            recipe.last_run = last_run.clone();
+        }
+        if let Some((_, last_seen)) = patch.last_seen_dates.iter().find(|v| v.0 == recipe.name) {
+            recipe.last_seen = last_seen.clone();
+        }
+    }
+    settings.save()
+}
@@ -0,0 +1,104 @@
+use crate::file_organizer::file_names::sanitize_folder_name;
+use crate::file_organizer::remove_empty_folders;
+use crate::file_organizer::settings::Recipe;
+use colored::*;
+use std::fs;
+use std::path::{Path, PathBuf};
+use tracing::warn;
+
+/// Chains the recipes of a pipeline, the destination folder of each recipe being the only source folder of the next one.
+/// The recipes after the first one scan their source folder recursively, so that they pick the files the previous recipe organized into subfolders.
+/// With a staging folder, each recipe but the last one organizes its files into its own folder of the staging folder instead of its destination folder.
+///
+/// ### Parameters
+/// - `recipes`: The recipes of the pipeline, in order.
+/// - `stage_dir`: The staging folder shared by the recipes, if any.
+///
+/// ### Returns
+/// - `Vec<Recipe>`: The chained recipes.
+pub fn chain_recipes(recipes: &[Recipe], stage_dir: Option<&Path>) -> Vec<Recipe> {
+    let mut chained_recipes: Vec<Recipe> = Vec::new();
+    for (index, recipe) in recipes.iter().enumerate() {
+        let mut recipe = recipe.clone();
+        if let Some(previous_recipe) = chained_recipes.last() {
+            recipe.source_folder = previous_recipe.destination_folder.clone();
+            recipe.source_folders = None;
+            recipe.recursive = Some(true);
+        }
+        if let Some(stage_dir) = stage_dir
+            && index < recipes.len() - 1
+        {
+            recipe.destination_folder = get_stage_folder(stage_dir, &recipe);
+        }
+        chained_recipes.push(recipe);
+    }
+    chained_recipes
+}
+
+/// Creates the staging folders of the chained recipes of a pipeline.
+///
+/// ### Parameters
+/// - `recipes`: The chained recipes of the pipeline.
+/// - `stage_dir`: The staging folder shared by the recipes.
+///
+/// ### Returns
+/// - `Result<(), anyhow::Error>`: The result of the creation.
+pub fn create_stage_folders(recipes: &[Recipe], stage_dir: &Path) -> anyhow::Result<()> {
+    for recipe in recipes.iter().filter(|v| is_stage_folder(v, stage_dir)) {
+        fs::create_dir_all(&recipe.destination_folder).map_err(|e| {
+            anyhow::Error::msg(format!(
+                "{} - Error creating the staging folder {}: {}",
+                recipe.name,
+                recipe.destination_folder.display(),
+                e
+            ))
+        })?;
+    }
+    Ok(())
+}
+
+/// Removes the staging folders of the chained recipes of a pipeline, once emptied by the next recipes.
+/// The files left in a staging folder, which the next recipe did not match, are never deleted: the folder is kept and a warning is printed.
+///
+/// ### Parameters
+/// - `recipes`: The chained recipes of the pipeline.
+/// - `stage_dir`: The staging folder shared by the recipes.
+pub fn clean_stage_folders(recipes: &[Recipe], stage_dir: &Path) {
+    for recipe in recipes.iter().filter(|v| is_stage_folder(v, stage_dir)) {
+        if !remove_empty_folders(&recipe.destination_folder) {
+            warn!(
+                "{} {} - Staging folder kept, some files were not picked by the next stage: {}",
+                "⚠️".yellow(),
+                recipe.name.blue(),
+                recipe.destination_folder.display()
+            );
+        }
+    }
+    if fs::read_dir(stage_dir).is_ok_and(|mut v| v.next().is_none()) {
+        let _ = fs::remove_dir(stage_dir);
+    }
+}
+
+/// Gets the staging folder of a recipe.
+///
+/// ### Parameters
+/// - `stage_dir`: The staging folder shared by the recipes.
+/// - `recipe`: The recipe to get the staging folder for.
+///
+/// ### Returns
+/// - `PathBuf`: The staging folder of the recipe, named after it.
+fn get_stage_folder(stage_dir: &Path, recipe: &Recipe) -> PathBuf {
+    stage_dir.join(sanitize_folder_name(&recipe.name.replace(['/', '\\'], "_")))
+}
+
+/// Checks if the destination folder of a chained recipe is a staging folder.
+///
+/// ### Parameters
+/// - `recipe`: The chained recipe to check.
+/// - `stage_dir`: The staging folder shared by the recipes.
+///
+/// ### Returns
+/// - `bool`: True if the recipe organizes its files into the staging folder, false otherwise.
+pub fn is_stage_folder(recipe: &Recipe, stage_dir: &Path) -> bool {
+    recipe.destination_folder == get_stage_folder(stage_dir, recipe)
+}
@@ -0,0 +1,289 @@
+use crate::file_organizer::settings::{ConflictPolicy, FileOperation};
+use crate::file_organizer::{link_file, move_file};
+use colored::*;
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use std::fs;
+use std::path::{Path, PathBuf};
+use tracing::{error, info, warn};
+
+/// The number of hexadecimal characters of a plan hash.
+const PLAN_HASH_LENGTH: usize = 12;
+
+/// PlanOperationKind is an enum that contains what a planned operation does with its file.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub enum PlanOperationKind {
+    Move,
+    Copy,
+    Symlink,
+    Hardlink,
+}
+
+impl From<&FileOperation> for PlanOperationKind {
+    /// Gets the kind of the operations of a recipe.
+    ///
+    /// ### Parameters
+    /// - `operation`: The operation of the recipe.
+    ///
+    /// ### Returns
+    /// - `PlanOperationKind`: The kind of its operations.
+    fn from(operation: &FileOperation) -> PlanOperationKind {
+        match operation {
+            FileOperation::Move => PlanOperationKind::Move,
+            FileOperation::Copy => PlanOperationKind::Copy,
+            FileOperation::Symlink => PlanOperationKind::Symlink,
+            FileOperation::Hardlink => PlanOperationKind::Hardlink,
+        }
+    }
+}
+
+/// PlanOperation is a struct that contains an operation of a saved plan.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct PlanOperation {
+    pub recipe: String,
+    pub operation: PlanOperationKind,
+    pub source: PathBuf,
+    pub destination: PathBuf,
+    pub destination_exists: bool,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub conflict: Option<ConflictPolicy>,
+}
+
+/// Saves a plan to a file.
+/// The plan is saved as a pretty printed JSON array so that it can be reviewed and edited before being applied.
+///
+/// ### Parameters
+/// - `path`: The path to the plan file.
+/// - `operations`: The planned operations.
+///
+/// ### Returns
+/// - `Result<(), anyhow::Error>`: The result of the save.
+pub fn save_plan(path: &Path, operations: &[PlanOperation]) -> anyhow::Result<()> {
+    fs::write(path, serde_json::to_string_pretty(operations)?)?;
+    Ok(())
+}
+
+/// Loads a plan from a file.
+///
+/// ### Parameters
+/// - `path`: The path to the plan file.
+///
+/// ### Returns
+/// - `Result<Vec<PlanOperation>, anyhow::Error>`: The planned operations.
+pub fn load_plan(path: &Path) -> anyhow::Result<Vec<PlanOperation>> {
+    let plan = fs::read_to_string(path).map_err(|e| {
+        anyhow::Error::msg(format!(
+            "Error loading the plan file {}: {}",
+            path.display(),
+            e
+        ))
+    })?;
+    Ok(serde_json::from_str(&plan)?)
+}
+
+/// Prints the report of the operations planned for a recipe, followed by their totals.
+///
+/// ### Parameters
+/// - `recipe_name`: The name of the recipe.
+/// - `operations`: The planned operations.
+/// - `skipped_operations`: The operations skipped as their destination already exists.
+pub fn print_report(
+    recipe_name: &str,
+    operations: &[PlanOperation],
+    skipped_operations: &[PlanOperation],
+) {
+    let mut lines: Vec<&PlanOperation> = operations.iter().chain(skipped_operations).collect();
+    lines.sort_by(|a, b| a.source.cmp(&b.source));
+    for operation in lines {
+        let annotation = match &operation.conflict {
+            Some(conflict) => format!(" (on_conflict={:?})", conflict),
+            None => "".to_string(),
+        };
+        if operation.conflict == Some(ConflictPolicy::Skip) {
+            warn!(
+                "{} {} {} - {} -> {}{}",
+                "⚠️".yellow(),
+                recipe_name.blue(),
+                "Skip".yellow(),
+                operation.source.display(),
+                operation.destination.display(),
+                annotation.dimmed()
+            );
+            continue;
+        }
+        info!(
+            "{} {} {} - {} -> {}{}",
+            "ℹ️".green(),
+            recipe_name.blue(),
+            format!("{:?}", operation.operation).purple(),
+            operation.source.display(),
+            operation.destination.display(),
+            annotation.dimmed()
+        );
+    }
+    let count_kind =
+        |kind: PlanOperationKind| operations.iter().filter(|v| v.operation == kind).count();
+    info!(
+        "{} {} {} - {} move(s), {} copy(ies), {} symlink(s), {} hardlink(s), {} conflicting, {} skipped",
+        "✅".green(),
+        recipe_name.blue(),
+        "Total".purple(),
+        count_kind(PlanOperationKind::Move),
+        count_kind(PlanOperationKind::Copy),
+        count_kind(PlanOperationKind::Symlink),
+        count_kind(PlanOperationKind::Hardlink),
+        operations.iter().filter(|v| v.conflict.is_some()).count(),
+        skipped_operations.len()
+    );
+}
+
+/// Hashes a plan, so that a previewed plan can be compared to the plan of a later run.
+///
+/// ### Parameters
+/// - `operations`: The planned operations.
+///
+/// ### Returns
+/// - `String`: The short hexadecimal hash of the plan.
+pub fn hash_plan(operations: &[PlanOperation]) -> String {
+    let mut hasher = Sha256::new();
+    for operation in operations {
+        hasher.update(serde_json::to_string(operation).unwrap_or_default());
+        hasher.update("\n");
+    }
+    let mut plan_hash = format!("{:x}", hasher.finalize());
+    plan_hash.truncate(PLAN_HASH_LENGTH);
+    plan_hash
+}
+
+/// Applies a saved plan.
+/// Only the operations of the plan are executed, each one being re-validated first: its source must still exist and its destination must not have appeared or disappeared since the plan was made.
+///
+/// ### Parameters
+/// - `path`: The path to the plan file.
+///
+/// ### Returns
+/// - `Result<(u32, u32), anyhow::Error>`: The number of applied and of failed operations.
+pub fn apply_plan(path: &Path) -> anyhow::Result<(u32, u32)> {
+    let operations = load_plan(path)?;
+    let mut applied = 0;
+    let mut failed = 0;
+    for operation in &operations {
+        match apply_operation(operation) {
+            Ok(()) => {
+                applied += 1;
+                info!(
+                    "{} {} {} - {}",
+                    "✅".green(),
+                    operation.recipe.blue(),
+                    match operation.operation {
+                        PlanOperationKind::Move => "File moved".green(),
+                        PlanOperationKind::Copy => "File copied".green(),
+                        PlanOperationKind::Symlink => "File symlinked".green(),
+                        PlanOperationKind::Hardlink => "File hardlinked".green(),
+                    },
+                    operation.destination.display()
+                );
+            }
+            Err(e) => {
+                failed += 1;
+                error!(
+                    "{} {} {} - {}: {}",
+                    "❌".red(),
+                    operation.recipe.blue(),
+                    "Operation skipped".red(),
+                    operation.source.display(),
+                    e
+                );
+            }
+        }
+    }
+    Ok((applied, failed))
+}
+
+/// Applies an operation of a saved plan.
+///
+/// ### Parameters
+/// - `operation`: The operation to apply.
+///
+/// ### Returns
+/// - `Result<(), anyhow::Error>`: The result of the operation.
+fn apply_operation(operation: &PlanOperation) -> anyhow::Result<()> {
+    if !operation.source.is_file() {
+        return Err(anyhow::Error::msg("Source no longer exists"));
+    }
+    if operation.destination.exists() != operation.destination_exists {
+        return Err(anyhow::Error::msg(format!(
+            "Destination changed since the plan was made: {}",
+            operation.destination.display()
+        )));
+    }
+    if let Some(dest_folder) = operation.destination.parent() {
+        fs::create_dir_all(dest_folder)?;
+    }
+    match operation.operation {
+        PlanOperationKind::Move => move_file(&operation.source, &operation.destination)?,
+        PlanOperationKind::Copy => {
+            fs::copy(&operation.source, &operation.destination)?;
+        }
+        PlanOperationKind::Symlink | PlanOperationKind::Hardlink => {
+            link_file(
+                &operation.source,
+                &operation.destination,
+                &operation.operation,
+            )?;
+        }
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::TempDir;
+
+    /// Builds a copy operation, its destination not existing when planned.
+    ///
+    /// ### Parameters
+    /// - `source`: The planned source.
+    /// - `destination`: The planned destination.
+    ///
+    /// ### Returns
+    /// - `PlanOperation`: The operation.
+    fn copy_operation(source: PathBuf, destination: PathBuf) -> PlanOperation {
+        PlanOperation {
+            recipe: "plan".to_string(),
+            operation: PlanOperationKind::Copy,
+            source,
+            destination,
+            destination_exists: false,
+            conflict: None,
+        }
+    }
+
+    #[test]
+    fn operations_whose_destination_appeared_are_skipped() {
+        let folder = TempDir::new().unwrap();
+        let (source, destination) = (folder.path().join("a.txt"), folder.path().join("b.txt"));
+        fs::write(&source, "planned").unwrap();
+        let plan_file = folder.path().join("plan.json");
+        save_plan(&plan_file, &[copy_operation(source, destination.clone())]).unwrap();
+        fs::write(&destination, "appeared since").unwrap();
+
+        assert_eq!(apply_plan(&plan_file).unwrap(), (0, 1));
+        assert_eq!(fs::read_to_string(&destination).unwrap(), "appeared since");
+    }
+
+    #[test]
+    fn saved_plan_is_loaded_back() {
+        let folder = TempDir::new().unwrap();
+        let plan_file = folder.path().join("plan.json");
+        let operation = copy_operation(PathBuf::from("/a"), PathBuf::from("/b/a"));
+
+        save_plan(&plan_file, std::slice::from_ref(&operation)).unwrap();
+        let plan = load_plan(&plan_file).unwrap();
+
+        assert_eq!(plan.len(), 1);
+        assert_eq!(plan[0].destination, operation.destination);
+        assert_eq!(hash_plan(&plan), hash_plan(&[operation]));
+    }
+}
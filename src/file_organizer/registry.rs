@@ -0,0 +1,530 @@
+use crate::file_organizer::music::{ALBUM_TOKEN, ARTIST_TOKEN};
+use crate::file_organizer::{
+    COUNTER_TOKEN, EXT_TOKEN, HASH2_TOKEN, HASH8_TOKEN, NAME_TOKEN, ORIGINAL_TOKEN,
+    SIZE_BUCKET_TOKEN,
+};
+use colored::*;
+
+/// TokenDefinition is a struct that contains the description of a subfolder token.
+pub struct TokenDefinition {
+    pub token: &'static str,
+    pub description: &'static str,
+    pub example: &'static str,
+}
+
+/// FieldDefinition is a struct that contains the description of a recipe field.
+pub struct FieldDefinition {
+    pub name: &'static str,
+    pub default: &'static str,
+    pub description: &'static str,
+}
+
+/// The tokens supported in the subfolders and the file name template of a recipe.
+pub const TOKENS: &[TokenDefinition] = &[
+    TokenDefinition {
+        token: "%Y",
+        description: "Year of the file date",
+        example: "2024",
+    },
+    TokenDefinition {
+        token: "%m",
+        description: "Month of the file date, zero padded",
+        example: "03",
+    },
+    TokenDefinition {
+        token: "%d",
+        description: "Day of the month of the file date, zero padded",
+        example: "07",
+    },
+    TokenDefinition {
+        token: "%B",
+        description: "Full month name of the file date",
+        example: "March",
+    },
+    TokenDefinition {
+        token: "%b",
+        description: "Abbreviated month name of the file date",
+        example: "Mar",
+    },
+    TokenDefinition {
+        token: "%A",
+        description: "Full weekday name of the file date, any other strftime specifier being supported as well",
+        example: "Thursday",
+    },
+    TokenDefinition {
+        token: ARTIST_TOKEN,
+        description: "Artist tag of a music file, music_fallback_folder if missing",
+        example: "Daft Punk",
+    },
+    TokenDefinition {
+        token: ALBUM_TOKEN,
+        description: "Album tag of a music file, music_fallback_folder if missing",
+        example: "Discovery",
+    },
+    TokenDefinition {
+        token: HASH8_TOKEN,
+        description: "First 8 hexadecimal characters of the SHA-256 hash of the file content",
+        example: "9f86d081",
+    },
+    TokenDefinition {
+        token: HASH2_TOKEN,
+        description: "First 2 hexadecimal characters of the SHA-256 hash of the file content",
+        example: "9f",
+    },
+    TokenDefinition {
+        token: NAME_TOKEN,
+        description: "Original name of the file, in file_name_template only",
+        example: "IMG_0042.jpg",
+    },
+    TokenDefinition {
+        token: ORIGINAL_TOKEN,
+        description: "Original name of the file without its extension, in file_name_template only",
+        example: "IMG_0042",
+    },
+    TokenDefinition {
+        token: COUNTER_TOKEN,
+        description: "First number, from 1, giving a name free in the destination folder, in file_name_template only",
+        example: "3",
+    },
+    TokenDefinition {
+        token: EXT_TOKEN,
+        description: "Extension of the file, lowercased in the subfolders, no_extension if missing",
+        example: "jpg",
+    },
+    TokenDefinition {
+        token: SIZE_BUCKET_TOKEN,
+        description: "Size bucket of the file, small, medium or large, by small_size_bytes and medium_size_bytes, in subfolders only",
+        example: "medium",
+    },
+];
+
+/// The fields of a recipe.
+pub const FIELDS: &[FieldDefinition] = &[
+    FieldDefinition {
+        name: "name",
+        default: "required",
+        description: "Unique identifier of the recipe",
+    },
+    FieldDefinition {
+        name: "enabled",
+        default: "true",
+        description: "Whether the recipe is run, a disabled recipe being skipped without updating its last_run",
+    },
+    FieldDefinition {
+        name: "source_folder",
+        default: "required",
+        description: "Folder containing the files to organize",
+    },
+    FieldDefinition {
+        name: "source_folders",
+        default: "none",
+        description: "Additional folders containing files to organize, merged with source_folder",
+    },
+    FieldDefinition {
+        name: "destination_folder",
+        default: "required",
+        description: "Folder the organized files are placed into",
+    },
+    FieldDefinition {
+        name: "year_roots",
+        default: "none",
+        description: "Destination roots by year of the file date, the other years going into destination_folder",
+    },
+    FieldDefinition {
+        name: "allow_symlink_dest",
+        default: "true",
+        description: "Whether destination_folder may be a symlink, a warning being printed if so",
+    },
+    FieldDefinition {
+        name: "date_comparator",
+        default: "ModificationDate",
+        description: "Date of the files: CreationDate, ModificationDate, ExifDate, the capture date of the images, or AccessedDate",
+    },
+    FieldDefinition {
+        name: "subfolders",
+        default: "none",
+        description: "Format of each level of subfolders, see --list_tokens",
+    },
+    FieldDefinition {
+        name: "create_folders_only_for",
+        default: "none",
+        description: "Extensions of the files getting the subfolders, the others going into destination_folder",
+    },
+    FieldDefinition {
+        name: "file_name_template",
+        default: "{name}",
+        description: "Template of the destination file names, see --list_tokens",
+    },
+    FieldDefinition {
+        name: "require_dest_extension",
+        default: "false",
+        description: "Whether the files whose destination name loses their extension are rejected",
+    },
+    FieldDefinition {
+        name: "on_conflict",
+        default: "Skip",
+        description: "Files whose destination exists: Overwrite, Skip or Rename with a (1), (2)... suffix",
+    },
+    FieldDefinition {
+        name: "allowed_extensions",
+        default: "none",
+        description: "Extensions of the files to organize, an empty list allowing all of them",
+    },
+    FieldDefinition {
+        name: "infer_extensions_from_dest",
+        default: "false",
+        description: "Whether allowed_extensions is replaced by the extensions of the files already in destination_folder",
+    },
+    FieldDefinition {
+        name: "name_patterns",
+        default: "none",
+        description: "Glob patterns the file names must match one of, in addition to allowed_extensions",
+    },
+    FieldDefinition {
+        name: "mime_types",
+        default: "none",
+        description: "MIME types (image/jpeg) or groups (image/*) the file content must match one of, replacing allowed_extensions if not set",
+    },
+    FieldDefinition {
+        name: "report_unknown_extensions",
+        default: "false",
+        description: "Whether the extensions of the files skipped by allowed_extensions are summarized",
+    },
+    FieldDefinition {
+        name: "min_size_bytes",
+        default: "none",
+        description: "Minimum size of the files to organize, in bytes, inclusive",
+    },
+    FieldDefinition {
+        name: "max_size_bytes",
+        default: "none",
+        description: "Maximum size of the files to organize, in bytes, inclusive",
+    },
+    FieldDefinition {
+        name: "small_size_bytes",
+        default: "1048576",
+        description: "Size in bytes from which a file is medium rather than small, for the {size_bucket} subfolder token",
+    },
+    FieldDefinition {
+        name: "medium_size_bytes",
+        default: "104857600",
+        description: "Size in bytes from which a file is large rather than medium, for the {size_bucket} subfolder token",
+    },
+    FieldDefinition {
+        name: "filter_logic",
+        default: "All",
+        description: "How the size, name_patterns, allowed_extensions, weekday_filter and reference_file filters combine: All or Any",
+    },
+    FieldDefinition {
+        name: "operation",
+        default: "required",
+        description: "What is done with the files: Move, Copy, Symlink or Hardlink, a move_files boolean being accepted as Move or Copy",
+    },
+    FieldDefinition {
+        name: "verify_copy",
+        default: "false",
+        description: "Whether the copies are checked against the SHA-256 hash of their source, bad copies being removed",
+    },
+    FieldDefinition {
+        name: "stream_copy_bytes",
+        default: "none",
+        description: "Size from which the files are copied through a buffer, reporting the progress of the copy",
+    },
+    FieldDefinition {
+        name: "copy_buffer_bytes",
+        default: "8388608",
+        description: "Size of the buffer of the streamed copies",
+    },
+    FieldDefinition {
+        name: "preserve_timestamps",
+        default: "false",
+        description: "Whether the copies keep the modification date of their source, and its creation date on Windows",
+    },
+    FieldDefinition {
+        name: "preserve_source_dir_times",
+        default: "false",
+        description: "Whether the modification dates of the source folders are restored after moving files out of them",
+    },
+    FieldDefinition {
+        name: "remove_empty_dirs",
+        default: "false",
+        description: "Whether the subfolders of the source folder left empty by a Move recipe are removed",
+    },
+    FieldDefinition {
+        name: "pre_hook",
+        default: "none",
+        description: "Shell command run before the recipe, a failure aborting the recipe",
+    },
+    FieldDefinition {
+        name: "post_hook",
+        default: "none",
+        description: "Shell command run after the recipe, with its statistics in FILE_ORGANIZER_* environment variables",
+    },
+    FieldDefinition {
+        name: "last_run",
+        default: "none",
+        description: "Date of the last run, only the files dated from it being organized",
+    },
+    FieldDefinition {
+        name: "track_last_seen",
+        default: "false",
+        description: "Whether the date each run lists the source folder is tracked in last_seen",
+    },
+    FieldDefinition {
+        name: "last_seen",
+        default: "none",
+        description: "Date the previous run listed the source folder, only the files arrived since then being organized",
+    },
+    FieldDefinition {
+        name: "date_from",
+        default: "none",
+        description: "First day of the files organized, inclusive",
+    },
+    FieldDefinition {
+        name: "date_to",
+        default: "none",
+        description: "Last day of the files organized, inclusive",
+    },
+    FieldDefinition {
+        name: "reference_file",
+        default: "none",
+        description: "File whose modification date filters the files, see reference_filter",
+    },
+    FieldDefinition {
+        name: "reference_filter",
+        default: "NewerThan",
+        description: "Files kept by reference_file: NewerThan (dated at or after it) or OlderThan (dated strictly before it)",
+    },
+    FieldDefinition {
+        name: "weekday_filter",
+        default: "none",
+        description: "Weekdays the files must be dated on",
+    },
+    FieldDefinition {
+        name: "in_place",
+        default: "false",
+        description: "Whether the source folder is organized within itself",
+    },
+    FieldDefinition {
+        name: "recursive",
+        default: "false",
+        description: "Whether the subfolders of the source folder are scanned, hidden ones excepted",
+    },
+    FieldDefinition {
+        name: "max_depth",
+        default: "none",
+        description: "Number of subfolder levels scanned by recursive and in place recipes",
+    },
+    FieldDefinition {
+        name: "preserve_subpath",
+        default: "false",
+        description: "Whether recursive recipes keep the subfolder of each file under its destination folder",
+    },
+    FieldDefinition {
+        name: "max_dest_depth",
+        default: "none",
+        description: "Number of folder levels under the destination folder, the deeper levels being joined with _ into the last one",
+    },
+    FieldDefinition {
+        name: "honor_pins",
+        default: "true",
+        description: "Whether the pinned files are left in the source",
+    },
+    FieldDefinition {
+        name: "keep_recent",
+        default: "none",
+        description: "Number of most recently modified files left in the source",
+    },
+    FieldDefinition {
+        name: "min_age_seconds",
+        default: "none",
+        description: "Number of seconds since their last modification before the files are organized, leaving the files being written in place",
+    },
+    FieldDefinition {
+        name: "magic_routes",
+        default: "none",
+        description: "Routes of the files by their leading bytes into subfolders",
+    },
+    FieldDefinition {
+        name: "tag_rules",
+        default: "none",
+        description: "File of name patterns routing the files into folders",
+    },
+    FieldDefinition {
+        name: "detect_language",
+        default: "false",
+        description: "Whether the text files are routed into a folder named after their language",
+    },
+    FieldDefinition {
+        name: "sanitize_folder_names",
+        default: "false",
+        description: "Whether the characters forbidden on Windows are replaced in the subfolder names",
+    },
+    FieldDefinition {
+        name: "sanitize_filenames",
+        default: "false",
+        description: "Whether the trailing whitespaces and dots stripped by Windows are removed from the file names",
+    },
+    FieldDefinition {
+        name: "rename_strategy",
+        default: "None",
+        description: "How the file names are normalized: None, Lowercase or Slugify",
+    },
+    FieldDefinition {
+        name: "ascii_fold_folders",
+        default: "false",
+        description: "Whether the subfolder names are transliterated to ASCII",
+    },
+    FieldDefinition {
+        name: "skip_if_name_exists_anywhere",
+        default: "false",
+        description: "Whether the files whose name exists anywhere under destination_folder are skipped",
+    },
+    FieldDefinition {
+        name: "skip_duplicates",
+        default: "false",
+        description: "Whether the files whose content already exists in their destination folder are skipped",
+    },
+    FieldDefinition {
+        name: "date_sources",
+        default: "none",
+        description: "Ordered date comparators, the first one succeeding being used",
+    },
+    FieldDefinition {
+        name: "date_strategy",
+        default: "none",
+        description: "Date picked among the date sources: Earliest, Latest or {\"ClosestTo\": \"YYYY-MM-DD\"}",
+    },
+    FieldDefinition {
+        name: "mode",
+        default: "Organize",
+        description: "What the recipe does: Organize, Dedupe or Compact",
+    },
+    FieldDefinition {
+        name: "dedupe_keeper",
+        default: "Oldest",
+        description: "File kept in place by a Dedupe recipe: Oldest, Newest or FirstName",
+    },
+    FieldDefinition {
+        name: "image_near_dupe",
+        default: "false",
+        description: "Whether a Dedupe recipe treats the visually similar images as duplicates, by their perceptual hash",
+    },
+    FieldDefinition {
+        name: "near_dupe_threshold",
+        default: "8",
+        description: "Maximum number of differing bits, out of 64, between the perceptual hashes of two near-duplicate images",
+    },
+    FieldDefinition {
+        name: "compact_format",
+        default: "%Y-%m-%d",
+        description: "Date format of the buckets of a Compact recipe, naming their archives",
+    },
+    FieldDefinition {
+        name: "compact_append",
+        default: "false",
+        description: "Whether a Compact recipe appends to the existing archive of a bucket instead of numbering a new one",
+    },
+    FieldDefinition {
+        name: "music_fallback_folder",
+        default: "Unknown",
+        description: "Folder of the music files without tags",
+    },
+    FieldDefinition {
+        name: "skip_readonly",
+        default: "false",
+        description: "Whether the read-only files are skipped",
+    },
+    FieldDefinition {
+        name: "skip_system",
+        default: "false",
+        description: "Whether the Windows system files are skipped",
+    },
+    FieldDefinition {
+        name: "include_hidden",
+        default: "false",
+        description: "Whether the hidden files (starting with .) are organized as well, the hidden folders being still skipped",
+    },
+    FieldDefinition {
+        name: "recipe_threads",
+        default: "none",
+        description: "Maximum number of threads processing the files of the recipe, all the cores being used if not set",
+    },
+    FieldDefinition {
+        name: "on_locked",
+        default: "none",
+        description: "Files locked by another process (Windows): Skip, Retry or Wait, failing if not set",
+    },
+    FieldDefinition {
+        name: "locked_timeout",
+        default: "30",
+        description: "Seconds the Wait policy waits for a locked file to be unlocked",
+    },
+    FieldDefinition {
+        name: "only_own_files",
+        default: "false",
+        description: "Whether the files not owned by the effective user are skipped (Unix)",
+    },
+    FieldDefinition {
+        name: "write_manifest",
+        default: "false",
+        description: "Whether the hashes of the organized files are appended to manifest.sha256",
+    },
+];
+
+/// Prints the tokens supported in the subfolders and the file name template of a recipe.
+pub fn print_tokens() {
+    for token in TOKENS {
+        println!(
+            "{} - {} (e.g. {})",
+            token.token.blue(),
+            token.description,
+            token.example.dimmed()
+        );
+    }
+}
+
+/// Prints the fields of a recipe.
+pub fn print_fields() {
+    for field in FIELDS {
+        println!(
+            "{} - {} (default: {})",
+            field.name.blue(),
+            field.description,
+            field.default.dimmed()
+        );
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::file_organizer::settings::Settings;
+    use std::path::Path;
+
+    #[test]
+    fn registry_lists_known_tokens_and_fields() {
+        let size_bucket = TOKENS
+            .iter()
+            .find(|v| v.token == SIZE_BUCKET_TOKEN)
+            .unwrap();
+        assert!(!size_bucket.description.is_empty());
+        assert!(!size_bucket.example.is_empty());
+        let keep_recent = FIELDS.iter().find(|v| v.name == "keep_recent").unwrap();
+        assert!(!keep_recent.description.is_empty());
+    }
+
+    #[test]
+    fn registry_lists_every_recipe_field() {
+        let example = Settings::example(Path::new("recipes.json"));
+        let recipe = serde_json::to_value(&example.recipes[0]).unwrap();
+
+        for name in recipe.as_object().unwrap().keys() {
+            assert!(
+                FIELDS.iter().any(|v| v.name == name),
+                "{} is missing from the fields registry",
+                name
+            );
+        }
+    }
+}
@@ -0,0 +1,287 @@
+use colored::*;
+use sha2::{Digest, Sha256};
+use std::env;
+use std::fs;
+use std::path::{Path, PathBuf};
+use tracing::warn;
+
+/// The number of hexadecimal characters of the hash naming the cached copy of a recipe file.
+const CACHE_KEY_LENGTH: usize = 16;
+
+/// The extension of the file keeping the ETag of a cached recipe file.
+const ETAG_EXTENSION: &str = "etag";
+
+/// Checks if a recipe file is a remote URL.
+///
+/// ### Parameters
+/// - `file_path`: The path to the recipe file.
+///
+/// ### Returns
+/// - `bool`: True if the path is an `http://` or `https://` URL.
+pub fn is_url(file_path: &Path) -> bool {
+    let file_path = file_path.to_string_lossy();
+    file_path.starts_with("http://") || file_path.starts_with("https://")
+}
+
+/// Fetches a remote recipe file.
+/// The fetched file is cached locally with its ETag, so that an unchanged file is not downloaded again and that the cached copy is used if the server cannot be reached.
+/// An error answered by the server is never hidden by the cached copy.
+///
+/// ### Parameters
+/// - `url`: The URL of the recipe file.
+///
+/// ### Returns
+/// - `Result<String, anyhow::Error>`: The content of the recipe file.
+pub fn fetch(url: &str) -> anyhow::Result<String> {
+    fetch_with_cache(url, &get_cache_file(url))
+}
+
+/// Fetches a remote recipe file, cached with its ETag in a given file.
+///
+/// ### Parameters
+/// - `url`: The URL of the recipe file.
+/// - `cache_file`: The file caching the recipe file, its ETag being kept next to it.
+///
+/// ### Returns
+/// - `Result<String, anyhow::Error>`: The content of the recipe file, the cached one if it is unchanged or the server cannot be reached.
+fn fetch_with_cache(url: &str, cache_file: &Path) -> anyhow::Result<String> {
+    let etag_file = cache_file.with_extension(ETAG_EXTENSION);
+    let cached = fs::read_to_string(cache_file).ok();
+    let mut request = ureq::get(url);
+    if cached.is_some()
+        && let Ok(etag) = fs::read_to_string(&etag_file)
+    {
+        request = request.header("If-None-Match", etag.trim());
+    }
+    let mut response = match request.call() {
+        Ok(response) => response,
+        Err(e) => {
+            let error =
+                anyhow::Error::msg(format!("Error fetching the recipe file {}: {}", url, e));
+            let Some(cached) = cached.filter(|_| is_network_error(&e)) else {
+                return Err(error);
+            };
+            warn!(
+                "{} {} {} - {}",
+                "⚠️".yellow(),
+                "file_organizer".blue(),
+                "Cached recipe file used".yellow(),
+                error
+            );
+            return Ok(cached);
+        }
+    };
+    if response.status() == 304
+        && let Some(cached) = cached
+    {
+        return Ok(cached);
+    }
+    let etag = response
+        .headers()
+        .get("ETag")
+        .and_then(|v| v.to_str().ok())
+        .map(String::from);
+    let content = response
+        .body_mut()
+        .read_to_string()
+        .map_err(|e| anyhow::Error::msg(format!("Error reading the recipe file {}: {}", url, e)))?;
+    if let Some(cache_folder) = cache_file.parent()
+        && fs::create_dir_all(cache_folder).is_ok()
+        && fs::write(cache_file, &content).is_ok()
+    {
+        let _ = match etag {
+            Some(etag) => fs::write(&etag_file, etag),
+            None => fs::remove_file(&etag_file),
+        };
+    }
+    Ok(content)
+}
+
+/// Checks if a request failed to reach the server, rather than being answered with an error.
+///
+/// ### Parameters
+/// - `error`: The error of the request.
+///
+/// ### Returns
+/// - `bool`: True if the server could not be reached, false if it answered.
+fn is_network_error(error: &ureq::Error) -> bool {
+    matches!(
+        error,
+        ureq::Error::Io(_)
+            | ureq::Error::Timeout(_)
+            | ureq::Error::HostNotFound
+            | ureq::Error::ConnectionFailed
+    )
+}
+
+/// Gets the file caching a remote recipe file, named after the hash of its URL.
+///
+/// ### Parameters
+/// - `url`: The URL of the recipe file.
+///
+/// ### Returns
+/// - `PathBuf`: The cache file, in the user cache folder, the temporary folder if it cannot be found.
+fn get_cache_file(url: &str) -> PathBuf {
+    let cache_folder = env::var_os("XDG_CACHE_HOME")
+        .map(PathBuf::from)
+        .or_else(|| env::var_os("LOCALAPPDATA").map(PathBuf::from))
+        .or_else(|| env::var_os("HOME").map(|v| PathBuf::from(v).join(".cache")))
+        .unwrap_or_else(env::temp_dir)
+        .join("file_organizer");
+    let hash = format!("{:x}", Sha256::digest(url.as_bytes()));
+    let extension = Path::new(url)
+        .extension()
+        .map(|v| v.to_string_lossy().to_lowercase())
+        .unwrap_or("json".to_string());
+    cache_folder.join(format!("{}.{}", &hash[..CACHE_KEY_LENGTH], extension))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::file_organizer::{FileOrganizer, RunOptions};
+    use std::io::{Read, Write};
+    use std::net::TcpListener;
+    use std::thread::{self, JoinHandle};
+    use tempfile::TempDir;
+
+    /// Serves canned HTTP responses, one connection each, on a local port.
+    ///
+    /// ### Parameters
+    /// - `responses`: The raw responses, in the order of the requests.
+    ///
+    /// ### Returns
+    /// - `(String, JoinHandle<Vec<String>>)`: The URL of a recipe file on the server, and the thread returning the heads of the requests it received.
+    fn serve(responses: Vec<String>) -> (String, JoinHandle<Vec<String>>) {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let url = format!("http://{}/recipes.json", listener.local_addr().unwrap());
+        let server = thread::spawn(move || {
+            let mut requests = Vec::new();
+            for response in responses {
+                let (mut stream, _) = listener.accept().unwrap();
+                let mut request = Vec::new();
+                let mut buffer = [0; 1024];
+                while !request.windows(4).any(|v| v == b"\r\n\r\n") {
+                    let read = stream.read(&mut buffer).unwrap();
+                    request.extend_from_slice(&buffer[..read]);
+                }
+                requests.push(String::from_utf8(request).unwrap().to_lowercase());
+                stream.write_all(response.as_bytes()).unwrap();
+            }
+            requests
+        });
+        (url, server)
+    }
+
+    #[test]
+    fn unchanged_recipe_file_is_read_from_the_cache() {
+        let cache = TempDir::new().unwrap();
+        let cache_file = cache.path().join("recipes.json");
+        let (url, server) = serve(vec![
+            "HTTP/1.1 200 OK\r\nETag: \"v1\"\r\nContent-Length: 15\r\nConnection: close\r\n\r\n{\"recipes\": []}".to_string(),
+            "HTTP/1.1 304 Not Modified\r\nConnection: close\r\n\r\n".to_string(),
+        ]);
+
+        assert_eq!(
+            fetch_with_cache(&url, &cache_file).unwrap(),
+            "{\"recipes\": []}"
+        );
+        assert_eq!(
+            fs::read_to_string(cache_file.with_extension(ETAG_EXTENSION)).unwrap(),
+            "\"v1\""
+        );
+        assert_eq!(
+            fetch_with_cache(&url, &cache_file).unwrap(),
+            "{\"recipes\": []}"
+        );
+
+        let requests = server.join().unwrap();
+        assert!(!requests[0].contains("if-none-match"));
+        assert!(requests[1].contains("if-none-match: \"v1\""));
+    }
+
+    #[test]
+    fn unreachable_server_falls_back_to_the_cached_copy() {
+        let cache = TempDir::new().unwrap();
+        let cache_file = cache.path().join("recipes.json");
+        let url = {
+            let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+            format!("http://{}/recipes.json", listener.local_addr().unwrap())
+        };
+
+        assert!(fetch_with_cache(&url, &cache_file).is_err());
+
+        fs::write(&cache_file, "[]").unwrap();
+        assert_eq!(fetch_with_cache(&url, &cache_file).unwrap(), "[]");
+    }
+
+    #[test]
+    fn server_errors_do_not_fall_back_to_the_cached_copy() {
+        let cache = TempDir::new().unwrap();
+        let cache_file = cache.path().join("recipes.json");
+        fs::write(&cache_file, "[]").unwrap();
+        let (url, server) = serve(vec![
+            "HTTP/1.1 404 Not Found\r\nContent-Length: 0\r\nConnection: close\r\n\r\n".to_string(),
+            "HTTP/1.1 503 Service Unavailable\r\nContent-Length: 0\r\nConnection: close\r\n\r\n"
+                .to_string(),
+        ]);
+
+        assert!(fetch_with_cache(&url, &cache_file).is_err());
+        assert!(fetch_with_cache(&url, &cache_file).is_err());
+
+        server.join().unwrap();
+        assert_eq!(fs::read_to_string(&cache_file).unwrap(), "[]");
+    }
+
+    #[test]
+    fn remote_recipe_file_is_run_without_being_written_back() {
+        let root = TempDir::new().unwrap();
+        let (source, destination) = (root.path().join("inbox"), root.path().join("notes"));
+        fs::create_dir_all(&source).unwrap();
+        fs::create_dir_all(&destination).unwrap();
+        fs::write(source.join("todo.txt"), "todo").unwrap();
+        let recipes = serde_json::json!([{
+            "name": "notes",
+            "source_folder": source,
+            "destination_folder": destination,
+            "operation": "Move",
+            "allowed_extensions": ["txt"]
+        }])
+        .to_string();
+        let (url, server) = serve(vec![format!(
+            "HTTP/1.1 200 OK\r\nContent-Length: {}\r\nConnection: close\r\n\r\n{}",
+            recipes.len(),
+            recipes
+        )]);
+        let cache_file = get_cache_file(&url);
+
+        let stats = FileOrganizer::new(PathBuf::from(&url), RunOptions::default())
+            .unwrap()
+            .run()
+            .unwrap();
+
+        // The only request is the GET of the recipe file, whose cached copy is left as served, without last_run.
+        let requests = server.join().unwrap();
+        let cached = fs::read_to_string(&cache_file).unwrap();
+        let _ = fs::remove_file(&cache_file);
+        assert_eq!(stats.files_processed, 1);
+        assert!(destination.join("todo.txt").is_file());
+        assert_eq!(requests.len(), 1);
+        assert!(requests[0].starts_with("get /recipes.json"));
+        assert_eq!(cached, recipes);
+    }
+
+    #[test]
+    fn cache_files_are_named_after_the_url() {
+        let (json, yaml) = (
+            get_cache_file("https://example.com/recipes.json"),
+            get_cache_file("https://example.com/team/recipes.yaml"),
+        );
+
+        assert_ne!(json.file_stem(), yaml.file_stem());
+        assert_eq!(json.extension().unwrap(), "json");
+        assert_eq!(yaml.extension().unwrap(), "yaml");
+        assert!(is_url(Path::new("https://example.com/recipes.json")));
+        assert!(!is_url(Path::new("recipes/https.json")));
+    }
+}
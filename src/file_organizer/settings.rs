@@ -1,44 +1,552 @@
-use std::path::PathBuf;
+use crate::file_organizer::remote;
+use std::path::{Component, Path, PathBuf};
 
-use serde::{Deserialize, Serialize};
+use serde::de::{self, Visitor};
+use serde::{Deserialize, Deserializer, Serialize};
+use std::collections::{BTreeMap, HashMap};
+use std::fmt;
 use std::fs;
 use std::io::Write;
 
-#[derive(Debug, Serialize, Deserialize)]
+/// DateComparator is an enum that contains which date of the files a recipe uses.
+#[allow(clippy::enum_variant_names)]
+#[derive(Default, Debug, Clone, Serialize, Deserialize)]
 pub enum DateComparator {
     CreationDate,
+    #[default]
     ModificationDate,
+    ExifDate,
+    AccessedDate,
 }
 
-impl Default for DateComparator {
-    fn default() -> Self {
-        DateComparator::ModificationDate
+/// DateStrategy is an enum that contains how the date of a file is picked among the dates of its date sources.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub enum DateStrategy {
+    Earliest,
+    Latest,
+    ClosestTo(String),
+}
+
+/// RecipeMode is an enum that contains what a recipe does with the files.
+#[derive(Default, Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub enum RecipeMode {
+    #[default]
+    Organize,
+    Dedupe,
+    Compact,
+}
+
+/// DedupeKeeper is an enum that contains which file of a group of identical files is kept in place by a dedupe recipe.
+#[derive(Default, Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub enum DedupeKeeper {
+    #[default]
+    Oldest,
+    Newest,
+    FirstName,
+}
+
+/// ReferenceFilter is an enum that contains which files are kept when comparing their date to a reference file.
+#[derive(Default, Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub enum ReferenceFilter {
+    #[default]
+    NewerThan,
+    OlderThan,
+}
+
+/// LockedPolicy is an enum that contains what a recipe does with the files locked by another process (Windows).
+#[derive(Default, Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub enum LockedPolicy {
+    #[default]
+    Skip,
+    Retry,
+    Wait,
+}
+
+/// RenameStrategy is an enum that contains how a recipe normalizes the names of the organized files.
+#[derive(Default, Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub enum RenameStrategy {
+    #[default]
+    None,
+    Lowercase,
+    Slugify,
+}
+
+/// FilterLogic is an enum that contains how the filters of a recipe combine: All of them must match, or Any of them.
+#[derive(Default, Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub enum FilterLogic {
+    #[default]
+    All,
+    Any,
+}
+
+/// ConflictPolicy is an enum that contains what a recipe does with the files whose destination already exists.
+#[derive(Default, Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub enum ConflictPolicy {
+    Overwrite,
+    #[default]
+    Skip,
+    Rename,
+}
+
+/// FileOperation is an enum that contains what a recipe does with the files.
+/// It is deserialized from its name, or from the `move_files` boolean of the older recipe files.
+#[derive(Default, Debug, Clone, PartialEq, Serialize)]
+pub enum FileOperation {
+    Move,
+    #[default]
+    Copy,
+    Symlink,
+    Hardlink,
+}
+
+impl<'de> Deserialize<'de> for FileOperation {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<FileOperation, D::Error> {
+        deserializer.deserialize_any(FileOperationVisitor)
+    }
+}
+
+/// FileOperationVisitor is a struct that deserializes a file operation from its name or from a move_files boolean.
+struct FileOperationVisitor;
+
+impl Visitor<'_> for FileOperationVisitor {
+    type Value = FileOperation;
+
+    fn expecting(&self, formatter: &mut fmt::Formatter) -> fmt::Result {
+        formatter.write_str("Move, Copy, Symlink, Hardlink, or a move_files boolean")
     }
+
+    fn visit_bool<E: de::Error>(self, move_files: bool) -> Result<FileOperation, E> {
+        Ok(if move_files {
+            FileOperation::Move
+        } else {
+            FileOperation::Copy
+        })
+    }
+
+    fn visit_str<E: de::Error>(self, operation: &str) -> Result<FileOperation, E> {
+        match operation {
+            "Move" => Ok(FileOperation::Move),
+            "Copy" => Ok(FileOperation::Copy),
+            "Symlink" => Ok(FileOperation::Symlink),
+            "Hardlink" => Ok(FileOperation::Hardlink),
+            _ => Err(E::unknown_variant(
+                operation,
+                &["Move", "Copy", "Symlink", "Hardlink"],
+            )),
+        }
+    }
+}
+
+/// MagicRoute is a struct that routes the files starting with a magic number into a subfolder.
+#[derive(Default, Debug, Clone, Serialize, Deserialize)]
+pub struct MagicRoute {
+    pub magic: String,
+    pub subfolder: String,
 }
 
 /// Recipe is a struct that contains the settings for a recipe.
-#[derive(Default, Debug, Serialize, Deserialize)]
+#[derive(Default, Debug, Clone, Serialize, Deserialize)]
 pub struct Recipe {
     pub name: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub enabled: Option<bool>,
     pub source_folder: PathBuf,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub source_folders: Option<Vec<PathBuf>>,
     pub destination_folder: PathBuf,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub year_roots: Option<HashMap<String, PathBuf>>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub allow_symlink_dest: Option<bool>,
+    #[serde(skip_serializing_if = "Option::is_none")]
     pub date_comparator: Option<DateComparator>,
+    #[serde(skip_serializing_if = "Option::is_none")]
     pub subfolders: Option<Vec<String>>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub create_folders_only_for: Option<Vec<String>>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub file_name_template: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub require_dest_extension: Option<bool>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub on_conflict: Option<ConflictPolicy>,
+    #[serde(skip_serializing_if = "Option::is_none")]
     pub allowed_extensions: Option<Vec<String>>,
-    pub move_files: bool,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub infer_extensions_from_dest: Option<bool>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub name_patterns: Option<Vec<String>>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub mime_types: Option<Vec<String>>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub report_unknown_extensions: Option<bool>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub min_size_bytes: Option<u64>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub max_size_bytes: Option<u64>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub small_size_bytes: Option<u64>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub medium_size_bytes: Option<u64>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub filter_logic: Option<FilterLogic>,
+    #[serde(alias = "move_files")]
+    pub operation: FileOperation,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub verify_copy: Option<bool>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub stream_copy_bytes: Option<u64>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub copy_buffer_bytes: Option<usize>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub preserve_timestamps: Option<bool>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub preserve_source_dir_times: Option<bool>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub remove_empty_dirs: Option<bool>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub pre_hook: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub post_hook: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub last_run: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub track_last_seen: Option<bool>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub last_seen: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub date_from: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub date_to: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub reference_file: Option<PathBuf>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub reference_filter: Option<ReferenceFilter>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub weekday_filter: Option<Vec<String>>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub in_place: Option<bool>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub recursive: Option<bool>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub max_depth: Option<u32>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub preserve_subpath: Option<bool>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub max_dest_depth: Option<usize>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub honor_pins: Option<bool>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub keep_recent: Option<u32>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub min_age_seconds: Option<u64>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub magic_routes: Option<Vec<MagicRoute>>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub tag_rules: Option<PathBuf>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub detect_language: Option<bool>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub sanitize_folder_names: Option<bool>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub sanitize_filenames: Option<bool>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub rename_strategy: Option<RenameStrategy>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub ascii_fold_folders: Option<bool>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub skip_if_name_exists_anywhere: Option<bool>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub skip_duplicates: Option<bool>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub date_sources: Option<Vec<DateComparator>>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub date_strategy: Option<DateStrategy>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub mode: Option<RecipeMode>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub dedupe_keeper: Option<DedupeKeeper>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub image_near_dupe: Option<bool>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub near_dupe_threshold: Option<u32>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub compact_format: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub compact_append: Option<bool>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub music_fallback_folder: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub skip_readonly: Option<bool>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub skip_system: Option<bool>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub include_hidden: Option<bool>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub on_locked: Option<LockedPolicy>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub recipe_threads: Option<usize>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub locked_timeout: Option<u64>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub only_own_files: Option<bool>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub write_manifest: Option<bool>,
+}
+
+/// RecipeState is a struct that contains the state of a recipe updated by the runs, as kept in a state file.
+#[derive(Default, Debug, Serialize, Deserialize)]
+pub struct RecipeState {
+    #[serde(skip_serializing_if = "Option::is_none")]
     pub last_run: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub last_seen: Option<String>,
 }
 
 /// Settings is a struct that contains the settings for the file organizer.
 #[derive(Default, Debug, Serialize, Deserialize)]
 pub struct Settings {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub root_prefix: Option<PathBuf>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub pipeline: Option<bool>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub stage_dir: Option<PathBuf>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub state_file: Option<PathBuf>,
     pub recipes: Vec<Recipe>,
     #[serde(skip)]
     pub path: PathBuf,
+    #[serde(skip)]
+    pub is_recipe_list: bool,
+    #[serde(skip)]
+    default_state_file: Option<PathBuf>,
+}
+
+impl Recipe {
+    /// Gets the source folders of the recipe, its source folder first, then its additional source folders.
+    ///
+    /// ### Returns
+    /// - `Vec<&PathBuf>`: The source folders, in order.
+    pub fn get_source_folders(&self) -> Vec<&PathBuf> {
+        [&self.source_folder]
+            .into_iter()
+            .chain(self.source_folders.iter().flatten())
+            .collect()
+    }
+
+    /// Resolves the source and destination folders of the recipe under a root prefix.
+    /// Relative folders are resolved from the prefix, and any folder escaping the prefix, after `..` and symlinks resolution, is rejected.
+    ///
+    /// ### Parameters
+    /// - `root_prefix`: The root prefix all the folders must be under.
+    ///
+    /// ### Returns
+    /// - `Result<Recipe, anyhow::Error>`: A copy of the recipe with its folders resolved.
+    pub fn resolve_under(&self, root_prefix: &Path) -> anyhow::Result<Recipe> {
+        let root_prefix = fs::canonicalize(root_prefix).map_err(|e| {
+            anyhow::Error::msg(format!(
+                "Invalid root prefix {}: {}",
+                root_prefix.display(),
+                e
+            ))
+        })?;
+        let mut recipe = self.clone();
+        recipe.source_folder =
+            resolve_path_under(&self.source_folder, &root_prefix).ok_or_else(|| {
+                anyhow::Error::msg(format!(
+                    "{} - Source folder escapes the root prefix: {}",
+                    self.name,
+                    self.source_folder.display()
+                ))
+            })?;
+        if let Some(source_folders) = &mut recipe.source_folders {
+            for source_folder in source_folders.iter_mut() {
+                *source_folder =
+                    resolve_path_under(source_folder, &root_prefix).ok_or_else(|| {
+                        anyhow::Error::msg(format!(
+                            "{} - Source folder escapes the root prefix: {}",
+                            self.name,
+                            source_folder.display()
+                        ))
+                    })?;
+            }
+        }
+        recipe.destination_folder = resolve_path_under(&self.destination_folder, &root_prefix)
+            .ok_or_else(|| {
+                anyhow::Error::msg(format!(
+                    "{} - Target folder escapes the root prefix: {}",
+                    self.name,
+                    self.destination_folder.display()
+                ))
+            })?;
+        if let Some(year_roots) = &mut recipe.year_roots {
+            for (year, year_root) in year_roots.iter_mut() {
+                *year_root = resolve_path_under(year_root, &root_prefix).ok_or_else(|| {
+                    anyhow::Error::msg(format!(
+                        "{} - Year root {} escapes the root prefix: {}",
+                        self.name,
+                        year,
+                        year_root.display()
+                    ))
+                })?;
+            }
+        }
+        Ok(recipe)
+    }
+}
+
+/// Resolves a path under a root prefix.
+/// The `.` and `..` components are resolved lexically first, then the symlinks are resolved if the path exists.
+///
+/// ### Parameters
+/// - `path`: The path to resolve, relative paths being relative to the prefix.
+/// - `root_prefix`: The canonical root prefix.
+///
+/// ### Returns
+/// - `Option<PathBuf>`: The resolved path, None if it escapes the prefix.
+fn resolve_path_under(path: &Path, root_prefix: &Path) -> Option<PathBuf> {
+    let mut resolved = PathBuf::new();
+    for component in root_prefix.join(path).components() {
+        match component {
+            Component::CurDir => {}
+            Component::ParentDir => {
+                if !resolved.pop() {
+                    return None;
+                }
+            }
+            component => resolved.push(component),
+        }
+    }
+    let resolved = fs::canonicalize(&resolved).unwrap_or(resolved);
+    if resolved.starts_with(root_prefix) {
+        Some(resolved)
+    } else {
+        None
+    }
+}
+
+/// Checks if a settings file is a YAML file, from its extension.
+///
+/// ### Parameters
+/// - `file_path`: The path to the settings file.
+///
+/// ### Returns
+/// - `bool`: True if the extension of the file is `.yaml` or `.yml`, case insensitive.
+fn is_yaml_file(file_path: &Path) -> bool {
+    file_path
+        .extension()
+        .map(|v| v.to_string_lossy().to_lowercase())
+        .is_some_and(|v| v == "yaml" || v == "yml")
 }
 
 impl Settings {
+    /// Builds example settings to start a recipe file from, with a recipe setting every field, to its default value or to an example value.
+    /// The recipe is built without defaults, so that a new field cannot be left out of the example.
+    ///
+    /// ### Parameters
+    /// - `file_path`: The path to the settings file to write.
+    ///
+    /// ### Returns
+    /// - `Settings`: The example settings.
+    pub fn example(file_path: &Path) -> Self {
+        let to_strings = |values: &[&str]| values.iter().map(|v| v.to_string()).collect();
+        Self {
+            recipes: vec![Recipe {
+                name: "Photo_Organization".to_string(),
+                enabled: Some(true),
+                source_folder: PathBuf::from("/path/to/Downloads"),
+                source_folders: Some(vec![PathBuf::from("/path/to/Camera")]),
+                destination_folder: PathBuf::from("/path/to/Pictures"),
+                year_roots: Some(HashMap::from([(
+                    "2020".to_string(),
+                    PathBuf::from("/path/to/Archive/Pictures"),
+                )])),
+                allow_symlink_dest: Some(true),
+                date_comparator: Some(DateComparator::ModificationDate),
+                subfolders: Some(to_strings(&["%Y", "%m"])),
+                create_folders_only_for: Some(to_strings(&["jpg", "jpeg", "png", "heic"])),
+                file_name_template: Some("{name}".to_string()),
+                require_dest_extension: Some(false),
+                on_conflict: Some(ConflictPolicy::Rename),
+                allowed_extensions: Some(to_strings(&["jpg", "jpeg", "png", "heic"])),
+                infer_extensions_from_dest: Some(false),
+                name_patterns: Some(to_strings(&["*"])),
+                mime_types: Some(to_strings(&["image/*"])),
+                report_unknown_extensions: Some(false),
+                min_size_bytes: Some(1),
+                max_size_bytes: Some(10_737_418_240),
+                small_size_bytes: Some(1_048_576),
+                medium_size_bytes: Some(104_857_600),
+                filter_logic: Some(FilterLogic::All),
+                operation: FileOperation::Move,
+                verify_copy: Some(false),
+                stream_copy_bytes: Some(1_073_741_824),
+                copy_buffer_bytes: Some(8_388_608),
+                preserve_timestamps: Some(false),
+                preserve_source_dir_times: Some(false),
+                remove_empty_dirs: Some(false),
+                pre_hook: Some("echo Organizing the photos".to_string()),
+                post_hook: Some("echo Photos organized".to_string()),
+                last_run: Some("1970-01-01".to_string()),
+                track_last_seen: Some(false),
+                last_seen: Some("1970-01-01T00:00:00Z".to_string()),
+                date_from: Some("2000-01-01".to_string()),
+                date_to: Some("2100-01-01".to_string()),
+                reference_file: Some(PathBuf::from("/path/to/last_backup.marker")),
+                reference_filter: Some(ReferenceFilter::OlderThan),
+                weekday_filter: Some(to_strings(&[
+                    "Mon", "Tue", "Wed", "Thu", "Fri", "Sat", "Sun",
+                ])),
+                in_place: Some(false),
+                recursive: Some(false),
+                max_depth: Some(3),
+                preserve_subpath: Some(false),
+                max_dest_depth: Some(2),
+                honor_pins: Some(true),
+                keep_recent: Some(0),
+                min_age_seconds: Some(60),
+                magic_routes: Some(vec![MagicRoute {
+                    magic: "89 50 4E 47".to_string(),
+                    subfolder: "Screenshots".to_string(),
+                }]),
+                tag_rules: Some(PathBuf::from("/path/to/tag_rules.txt")),
+                detect_language: Some(false),
+                sanitize_folder_names: Some(false),
+                sanitize_filenames: Some(false),
+                rename_strategy: Some(RenameStrategy::None),
+                ascii_fold_folders: Some(false),
+                skip_if_name_exists_anywhere: Some(false),
+                skip_duplicates: Some(false),
+                date_sources: Some(vec![
+                    DateComparator::ExifDate,
+                    DateComparator::ModificationDate,
+                ]),
+                date_strategy: Some(DateStrategy::Earliest),
+                mode: Some(RecipeMode::Organize),
+                dedupe_keeper: Some(DedupeKeeper::Oldest),
+                image_near_dupe: Some(false),
+                near_dupe_threshold: Some(8),
+                compact_format: Some("%Y-%m-%d".to_string()),
+                compact_append: Some(false),
+                music_fallback_folder: Some("Unknown".to_string()),
+                skip_readonly: Some(false),
+                skip_system: Some(false),
+                include_hidden: Some(false),
+                on_locked: Some(LockedPolicy::Skip),
+                recipe_threads: Some(4),
+                locked_timeout: Some(30),
+                only_own_files: Some(false),
+                write_manifest: Some(false),
+            }],
+            path: file_path.to_path_buf(),
+            ..Default::default()
+        }
+    }
+
     /// Loads the settings from a file.
+    /// The file is read as YAML if its extension is `.yaml` or `.yml`, as JSON otherwise, and is fetched if it is an `http(s)://` URL.
+    /// The state of the recipes is read from the state file instead, if any, a local YAML file keeping it in a `.state.json` file next to it by default.
     ///
     /// ### Parameters
     /// - `file_path`: The path to the settings file.
@@ -46,20 +554,41 @@ impl Settings {
     /// ### Returns
     /// - `Result<Settings, anyhow::Error>`: The settings.
     pub fn load_from_file(file_path: &PathBuf) -> anyhow::Result<Settings> {
-        let settings_result = fs::read_to_string(file_path);
+        let settings_result = if remote::is_url(file_path) {
+            Ok(remote::fetch(&file_path.to_string_lossy())?)
+        } else {
+            fs::read_to_string(file_path)
+        };
         if let Ok(settings_string) = settings_result {
-            let recipes: Vec<Recipe> = serde_json::from_str(&settings_string.as_str())?;
-            let to_return = Settings {
-                recipes,
-                path: file_path.clone(),
+            let value: serde_json::Value = if is_yaml_file(file_path) {
+                serde_yaml::from_str(settings_string.as_str())?
+            } else {
+                serde_json::from_str(settings_string.as_str())?
             };
-            return Ok(to_return);
+            let mut to_return = if value.is_array() {
+                Settings {
+                    recipes: serde_json::from_value(value)?,
+                    is_recipe_list: true,
+                    ..Default::default()
+                }
+            } else {
+                serde_json::from_value(value)?
+            };
+            to_return.path = file_path.clone();
+            // The state of a local YAML file is kept next to it, so that saving it never rewrites the YAML file and loses its comments.
+            if is_yaml_file(file_path) && !remote::is_url(file_path) {
+                to_return.default_state_file = Some(file_path.with_extension("state.json"));
+            }
+            to_return.load_state()?;
+            Ok(to_return)
         } else {
-            return Err(anyhow::Error::msg("Error while loading the settings file"));
+            Err(anyhow::Error::msg("Error while loading the settings file"))
         }
     }
 
     /// Saves the settings to a file.
+    /// The settings are saved as a bare list of recipes if they have been loaded from one, in the format of the file they have been loaded from.
+    /// With a state file, set or the default one of a local YAML file, only the state of the recipes is saved, into it. Settings fetched from a URL are not saved otherwise.
     ///
     /// ### Parameters
     /// - `self`: The settings to save.
@@ -67,9 +596,293 @@ impl Settings {
     /// ### Returns
     /// - `Result<(), anyhow::Error>`: The result of the save.
     pub fn save(&self) -> anyhow::Result<()> {
-        let to_write = serde_json::to_string(&self.recipes)?;
+        if self.get_state_file().is_some() {
+            return self.save_state();
+        }
+        if remote::is_url(&self.path) {
+            return Ok(());
+        }
+        // YAML is written from the JSON value, so that the enums are maps rather than YAML tags, as in the JSON files.
+        let to_write = match (is_yaml_file(&self.path), self.is_recipe_list) {
+            (true, true) => serde_yaml::to_string(&serde_json::to_value(&self.recipes)?)?,
+            (true, false) => serde_yaml::to_string(&serde_json::to_value(self)?)?,
+            (false, true) => serde_json::to_string(&self.recipes)?,
+            (false, false) => serde_json::to_string(self)?,
+        };
         let mut file = fs::File::create(self.path.clone())?;
-        let _ = write!(file, "{}", to_write)?;
+        write!(file, "{}", to_write)?;
+        Ok(())
+    }
+
+    /// Gets the file keeping the state of the recipes: the state file set, or else the default one of a local YAML file.
+    ///
+    /// ### Returns
+    /// - `Option<&PathBuf>`: The state file, None if the state is kept in the recipe file.
+    pub fn get_state_file(&self) -> Option<&PathBuf> {
+        self.state_file
+            .as_ref()
+            .or(self.default_state_file.as_ref())
+    }
+
+    /// Reads the state of the recipes from the state file, if any.
+    /// The recipes missing from the state file keep the state of the recipe file.
+    ///
+    /// ### Returns
+    /// - `Result<(), anyhow::Error>`: The result of the read, a missing state file being read as empty.
+    fn load_state(&mut self) -> anyhow::Result<()> {
+        let Some(state_file) = self.get_state_file() else {
+            return Ok(());
+        };
+        if !state_file.exists() {
+            return Ok(());
+        }
+        let states: HashMap<String, RecipeState> =
+            serde_json::from_str(&fs::read_to_string(state_file)?).map_err(|e| {
+                anyhow::Error::msg(format!(
+                    "Invalid state file {}: {}",
+                    state_file.display(),
+                    e
+                ))
+            })?;
+        for recipe in &mut self.recipes {
+            if let Some(state) = states.get(&recipe.name) {
+                recipe.last_run = state.last_run.clone();
+                recipe.last_seen = state.last_seen.clone();
+            }
+        }
         Ok(())
     }
+
+    /// Saves the state of the recipes to the state file.
+    ///
+    /// ### Returns
+    /// - `Result<(), anyhow::Error>`: The result of the save.
+    fn save_state(&self) -> anyhow::Result<()> {
+        let Some(state_file) = self.get_state_file() else {
+            return Ok(());
+        };
+        let states: BTreeMap<&str, RecipeState> = self
+            .recipes
+            .iter()
+            .map(|recipe| {
+                (
+                    recipe.name.as_str(),
+                    RecipeState {
+                        last_run: recipe.last_run.clone(),
+                        last_seen: recipe.last_seen.clone(),
+                    },
+                )
+            })
+            .collect();
+        fs::write(state_file, serde_json::to_string_pretty(&states)?)?;
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::file_organizer::registry::FIELDS;
+    use tempfile::TempDir;
+
+    #[test]
+    fn folders_within_the_root_prefix_are_resolved() {
+        let root = TempDir::new().unwrap();
+        fs::create_dir_all(root.path().join("inbox")).unwrap();
+        let recipe = Recipe {
+            name: "sandboxed".to_string(),
+            source_folder: PathBuf::from("inbox"),
+            destination_folder: PathBuf::from("sorted/../archive"),
+            ..Default::default()
+        };
+
+        let resolved = recipe.resolve_under(root.path()).unwrap();
+
+        let root_prefix = fs::canonicalize(root.path()).unwrap();
+        assert_eq!(resolved.source_folder, root_prefix.join("inbox"));
+        assert_eq!(resolved.destination_folder, root_prefix.join("archive"));
+    }
+
+    #[test]
+    fn folders_escaping_the_root_prefix_are_rejected() {
+        let root = TempDir::new().unwrap();
+        let recipe = Recipe {
+            name: "escaping".to_string(),
+            source_folder: PathBuf::from("inbox"),
+            destination_folder: PathBuf::from("../../etc"),
+            ..Default::default()
+        };
+
+        let error = recipe.resolve_under(root.path()).unwrap_err();
+
+        assert!(
+            error
+                .to_string()
+                .contains("Target folder escapes the root prefix")
+        );
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn symlinks_escaping_the_root_prefix_are_rejected() {
+        let (root, outside) = (TempDir::new().unwrap(), TempDir::new().unwrap());
+        std::os::unix::fs::symlink(outside.path(), root.path().join("link")).unwrap();
+
+        assert_eq!(
+            resolve_path_under(Path::new("link"), &fs::canonicalize(root.path()).unwrap()),
+            None
+        );
+    }
+
+    #[test]
+    fn operation_is_read_from_its_name_or_the_move_files_boolean() {
+        let read_operation = |field: &str| {
+            let recipe: Recipe = serde_json::from_str(&format!(
+                r#"{{"name": "a", "source_folder": "/a", "destination_folder": "/b", {}}}"#,
+                field
+            ))
+            .unwrap();
+            recipe.operation
+        };
+
+        assert_eq!(read_operation(r#""move_files": true"#), FileOperation::Move);
+        assert_eq!(
+            read_operation(r#""move_files": false"#),
+            FileOperation::Copy
+        );
+        assert_eq!(
+            read_operation(r#""operation": "Symlink""#),
+            FileOperation::Symlink
+        );
+        assert_eq!(
+            read_operation(r#""operation": "Hardlink""#),
+            FileOperation::Hardlink
+        );
+        assert!(
+            serde_json::from_str::<Recipe>(
+                r#"{"name": "a", "source_folder": "/a", "destination_folder": "/b", "operation": "Link"}"#
+            )
+            .is_err()
+        );
+    }
+
+    #[test]
+    fn yaml_recipes_survive_a_save_and_reload() {
+        let folder = TempDir::new().unwrap();
+        let recipes_file = folder.path().join("recipes.yaml");
+        let yaml = "\
+# Photos from the phone, sorted by year.
+recipes:
+  - name: phone
+    source_folder: /home/me/Phone
+    destination_folder: /home/me/Pictures
+    operation: Move
+    allowed_extensions: [jpg, heic]
+    subfolders: ['%Y']
+    date_strategy:
+      ClosestTo: '2020-01-01'
+";
+        fs::write(&recipes_file, yaml).unwrap();
+        let mut settings = Settings::load_from_file(&recipes_file).unwrap();
+        let recipe = &settings.recipes[0];
+        assert_eq!(recipe.operation, FileOperation::Move);
+        assert_eq!(
+            recipe.date_strategy,
+            Some(DateStrategy::ClosestTo("2020-01-01".to_string()))
+        );
+        settings.recipes[0].last_run = Some("2024-08-08".to_string());
+
+        settings.save().unwrap();
+
+        assert_eq!(fs::read_to_string(&recipes_file).unwrap(), yaml);
+        let reloaded = Settings::load_from_file(&recipes_file).unwrap();
+        assert_eq!(
+            serde_json::to_value(&reloaded.recipes).unwrap(),
+            serde_json::to_value(&settings.recipes).unwrap()
+        );
+    }
+
+    #[test]
+    fn yaml_recipe_list_is_read_like_a_json_one() {
+        let folder = TempDir::new().unwrap();
+        let (yaml_file, json_file) = (
+            folder.path().join("recipes.yml"),
+            folder.path().join("recipes.json"),
+        );
+        fs::write(
+            &yaml_file,
+            "- name: logs\n  source_folder: /var/log/app\n  destination_folder: /srv/logs\n  move_files: false\n",
+        )
+        .unwrap();
+        fs::write(
+            &json_file,
+            r#"[{"name": "logs", "source_folder": "/var/log/app", "destination_folder": "/srv/logs", "move_files": false}]"#,
+        )
+        .unwrap();
+
+        let (from_yaml, from_json) = (
+            Settings::load_from_file(&yaml_file).unwrap(),
+            Settings::load_from_file(&json_file).unwrap(),
+        );
+
+        assert_eq!(
+            serde_json::to_value(&from_yaml.recipes).unwrap(),
+            serde_json::to_value(&from_json.recipes).unwrap()
+        );
+        assert_eq!(from_yaml.recipes[0].operation, FileOperation::Copy);
+    }
+
+    #[test]
+    fn remote_recipe_files_are_never_written_back() {
+        let folder = TempDir::new().unwrap();
+        let state_file = folder.path().join("state.json");
+        let mut settings: Settings = serde_json::from_value(serde_json::json!({
+            "recipes": [{
+                "name": "shared",
+                "source_folder": "/in",
+                "destination_folder": "/out",
+                "operation": "Copy"
+            }]
+        }))
+        .unwrap();
+        settings.path = PathBuf::from("https://example.com/recipes.json");
+        settings.recipes[0].last_run = Some("2025-01-01".to_string());
+
+        settings.save().unwrap();
+        assert!(fs::read_dir(folder.path()).unwrap().next().is_none());
+
+        settings.state_file = Some(state_file.clone());
+        settings.save().unwrap();
+        let states: serde_json::Value =
+            serde_json::from_str(&fs::read_to_string(&state_file).unwrap()).unwrap();
+        assert_eq!(states["shared"]["last_run"], "2025-01-01");
+    }
+
+    #[test]
+    fn example_recipe_file_loads_back_with_every_field() {
+        let folder = TempDir::new().unwrap();
+        for name in ["recipes.json", "recipes.yaml"] {
+            let file_path = folder.path().join(name);
+            let example = Settings::example(&file_path);
+
+            example.save().unwrap();
+            let loaded = Settings::load_from_file(&file_path).unwrap();
+
+            assert_eq!(
+                serde_json::to_value(&loaded.recipes).unwrap(),
+                serde_json::to_value(&example.recipes).unwrap(),
+                "{name}"
+            );
+        }
+        let written: serde_json::Value =
+            serde_json::from_str(&fs::read_to_string(folder.path().join("recipes.json")).unwrap())
+                .unwrap();
+        let recipe = written["recipes"][0].as_object().unwrap();
+        let missing: Vec<&str> = FIELDS
+            .iter()
+            .map(|v| v.name)
+            .filter(|v| !recipe.contains_key(*v))
+            .collect();
+        assert!(missing.is_empty(), "{:?}", missing);
+    }
 }
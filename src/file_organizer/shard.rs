@@ -0,0 +1,99 @@
+use std::ffi::OsStr;
+
+/// The offset basis of the 64 bits FNV-1a hash.
+const FNV_OFFSET_BASIS: u64 = 0xcbf29ce484222325;
+
+/// The prime of the 64 bits FNV-1a hash.
+const FNV_PRIME: u64 = 0x100000001b3;
+
+/// Shard is a struct that contains the part of the files a run processes when a run is split across several invocations.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Shard {
+    index: u64,
+    total: u64,
+}
+
+impl Shard {
+    /// Parses a shard.
+    ///
+    /// ### Parameters
+    /// - `shard`: The shard to parse, as `INDEX/TOTAL` with `INDEX` lower than `TOTAL`.
+    ///
+    /// ### Returns
+    /// - `Result<Shard, anyhow::Error>`: The shard.
+    pub fn parse(shard: &str) -> anyhow::Result<Shard> {
+        let invalid = || {
+            anyhow::Error::msg(format!(
+                "Invalid shard: {}, expected INDEX/TOTAL with INDEX lower than TOTAL",
+                shard
+            ))
+        };
+        let (index, total) = shard.split_once('/').ok_or_else(invalid)?;
+        let index: u64 = index.trim().parse().map_err(|_| invalid())?;
+        let total: u64 = total.trim().parse().map_err(|_| invalid())?;
+        if index >= total {
+            return Err(invalid());
+        }
+        Ok(Shard { index, total })
+    }
+
+    /// Checks if a file belongs to the shard.
+    /// The files are assigned by the 64 bits FNV-1a hash of their name modulo the number of shards, which is stable across runs, machines and platforms.
+    ///
+    /// ### Parameters
+    /// - `file_name`: The name of the file.
+    ///
+    /// ### Returns
+    /// - `bool`: True if the file belongs to the shard, false otherwise.
+    pub fn contains(&self, file_name: &OsStr) -> bool {
+        let hash = file_name
+            .as_encoded_bytes()
+            .iter()
+            .fold(FNV_OFFSET_BASIS, |hash, byte| {
+                (hash ^ *byte as u64).wrapping_mul(FNV_PRIME)
+            });
+        hash % self.total == self.index
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::collections::HashSet;
+    use std::ffi::OsString;
+
+    #[test]
+    fn shards_are_disjoint_and_cover_all_files() {
+        let file_names: Vec<OsString> = (0..100)
+            .map(|i| OsString::from(format!("IMG_{:04}.jpg", i)))
+            .collect();
+        let shards: Vec<Shard> = (0..3)
+            .map(|i| Shard::parse(&format!("{}/3", i)).unwrap())
+            .collect();
+
+        let mut union = HashSet::new();
+        for shard in &shards {
+            let shard_files: Vec<&OsString> =
+                file_names.iter().filter(|v| shard.contains(v)).collect();
+            assert!(!shard_files.is_empty());
+            for file_name in shard_files {
+                assert!(union.insert(file_name), "{:?} is in two shards", file_name);
+            }
+        }
+        assert_eq!(union.len(), file_names.len());
+    }
+
+    #[test]
+    fn shard_hash_is_stable() {
+        // FNV-1a of "a" is 0xaf63dc4c8601ec8c, which is even.
+        assert!(Shard::parse("0/2").unwrap().contains(OsStr::new("a")));
+        assert!(!Shard::parse("1/2").unwrap().contains(OsStr::new("a")));
+    }
+
+    #[test]
+    fn invalid_shards_are_rejected() {
+        for shard in ["3/3", "1", "a/3", "1/0"] {
+            assert!(Shard::parse(shard).is_err(), "{} was accepted", shard);
+        }
+    }
+}
@@ -0,0 +1,211 @@
+use crate::file_organizer::inodes::{get_device, get_existing_folder};
+use crate::file_organizer::plan::{PlanOperation, PlanOperationKind};
+use std::collections::HashMap;
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+
+/// The units of the sizes printed, each 1024 times the previous one.
+const SIZE_UNITS: [&str; 5] = ["B", "KiB", "MiB", "GiB", "TiB"];
+
+/// FilesystemUsage is a struct that contains the bytes a recipe writes on a filesystem.
+struct FilesystemUsage {
+    folder: PathBuf,
+    bytes_count: u64,
+}
+
+/// Checks that the destination filesystems have enough free space for the files planned by a recipe, so that the recipe does not fill a drive halfway through.
+/// Copies write the size of their file, as do moves across filesystems, while links and moves within a filesystem write nothing. The files the recipe overwrites are not deducted, so that the estimate errs on the safe side.
+///
+/// ### Parameters
+/// - `recipe_name`: The name of the recipe.
+/// - `operations`: The operations planned by the recipe, the files filtered out by its dates being already left out.
+///
+/// ### Returns
+/// - `Result<(), anyhow::Error>`: An error naming the filesystems short of space, if any.
+pub fn check_space(recipe_name: &str, operations: &[PlanOperation]) -> anyhow::Result<()> {
+    check_space_with(recipe_name, operations, get_available_bytes)
+}
+
+/// Checks that the destination filesystems have enough free space for the files planned by a recipe, with the given way of getting the free space of a filesystem.
+///
+/// ### Parameters
+/// - `recipe_name`: The name of the recipe.
+/// - `operations`: The operations planned by the recipe.
+/// - `get_available_bytes`: Gets the number of free bytes of the filesystem holding a folder.
+///
+/// ### Returns
+/// - `Result<(), anyhow::Error>`: An error naming the filesystems short of space, if any.
+fn check_space_with(
+    recipe_name: &str,
+    operations: &[PlanOperation],
+    get_available_bytes: impl Fn(&Path) -> io::Result<Option<u64>>,
+) -> anyhow::Result<()> {
+    let mut devices: HashMap<PathBuf, Option<u64>> = HashMap::new();
+    let mut usages: HashMap<u64, FilesystemUsage> = HashMap::new();
+    let mut get_cached_device = |folder: &Path| -> Option<u64> {
+        *devices
+            .entry(folder.to_path_buf())
+            .or_insert_with(|| get_device(folder))
+    };
+    for operation in operations {
+        let Some(dest_folder) = operation.destination.parent() else {
+            continue;
+        };
+        let Some(dest_device) = get_cached_device(dest_folder) else {
+            continue;
+        };
+        let writes_file = match operation.operation {
+            PlanOperationKind::Copy => true,
+            PlanOperationKind::Symlink | PlanOperationKind::Hardlink => false,
+            PlanOperationKind::Move => operation
+                .source
+                .parent()
+                .and_then(&mut get_cached_device)
+                .is_none_or(|v| v != dest_device),
+        };
+        if !writes_file {
+            continue;
+        }
+        let Ok(metadata) = fs::metadata(&operation.source) else {
+            continue;
+        };
+        usages
+            .entry(dest_device)
+            .or_insert_with(|| FilesystemUsage {
+                folder: get_existing_folder(dest_folder).to_path_buf(),
+                bytes_count: 0,
+            })
+            .bytes_count += metadata.len();
+    }
+    let mut problems = Vec::new();
+    for usage in usages.values() {
+        let Ok(Some(available_bytes)) = get_available_bytes(&usage.folder) else {
+            continue;
+        };
+        if usage.bytes_count > available_bytes {
+            problems.push(format!(
+                "{}: {} to write, {} available",
+                usage.folder.display(),
+                format_size(usage.bytes_count),
+                format_size(available_bytes)
+            ));
+        }
+    }
+    if !problems.is_empty() {
+        problems.sort();
+        return Err(anyhow::Error::msg(format!(
+            "{} - Not enough disk space, recipe aborted before touching any file - {}",
+            recipe_name,
+            problems.join(", ")
+        )));
+    }
+    Ok(())
+}
+
+/// Formats a number of bytes with the largest unit keeping it at least 1.
+///
+/// ### Parameters
+/// - `bytes`: The number of bytes.
+///
+/// ### Returns
+/// - `String`: The formatted size, e.g. `1.5 GiB`.
+pub fn format_size(bytes: u64) -> String {
+    let mut size = bytes as f64;
+    let mut unit = 0;
+    while size >= 1024.0 && unit < SIZE_UNITS.len() - 1 {
+        size /= 1024.0;
+        unit += 1;
+    }
+    if unit == 0 {
+        format!("{} {}", bytes, SIZE_UNITS[unit])
+    } else {
+        format!("{:.1} {}", size, SIZE_UNITS[unit])
+    }
+}
+
+/// Gets the free space of the filesystem holding a folder, for an unprivileged user.
+///
+/// ### Parameters
+/// - `folder`: The existing folder.
+///
+/// ### Returns
+/// - `io::Result<Option<u64>>`: The number of free bytes.
+#[cfg(unix)]
+fn get_available_bytes(folder: &Path) -> io::Result<Option<u64>> {
+    use std::ffi::CString;
+    use std::os::unix::ffi::OsStrExt;
+    let path = CString::new(folder.as_os_str().as_bytes())?;
+    let mut stat: libc::statvfs = unsafe { std::mem::zeroed() };
+    if unsafe { libc::statvfs(path.as_ptr(), &mut stat) } != 0 {
+        return Err(io::Error::last_os_error());
+    }
+    Ok(Some(
+        (stat.f_bavail as u64).saturating_mul(stat.f_frsize as u64),
+    ))
+}
+
+/// Gets the free space of the filesystem holding a folder, for an unprivileged user.
+///
+/// ### Parameters
+/// - `folder`: The existing folder.
+///
+/// ### Returns
+/// - `io::Result<Option<u64>>`: The number of free bytes, always None outside of Unix.
+#[cfg(not(unix))]
+fn get_available_bytes(_folder: &Path) -> io::Result<Option<u64>> {
+    Ok(None)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::TempDir;
+
+    #[test]
+    fn sizes_are_formatted_with_the_largest_unit() {
+        assert_eq!(format_size(0), "0 B");
+        assert_eq!(format_size(1023), "1023 B");
+        assert_eq!(format_size(1536), "1.5 KiB");
+        assert_eq!(format_size(5 * 1024 * 1024 * 1024), "5.0 GiB");
+        assert_eq!(format_size(u64::MAX), "16777216.0 TiB");
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn recipe_is_aborted_when_its_copies_do_not_fit() {
+        let root = TempDir::new().unwrap();
+        let (videos, drive) = (root.path().join("videos"), root.path().join("drive"));
+        fs::create_dir(&videos).unwrap();
+        let operation = |kind: PlanOperationKind, name: &str, size: usize| {
+            let source = videos.join(name);
+            fs::write(&source, vec![0; size]).unwrap();
+            PlanOperation {
+                recipe: "videos".to_string(),
+                operation: kind,
+                source,
+                destination: drive.join("2024").join(name),
+                destination_exists: false,
+                conflict: None,
+            }
+        };
+        let operations = [
+            operation(PlanOperationKind::Copy, "a.mp4", 1024),
+            operation(PlanOperationKind::Copy, "b.mp4", 1024),
+            operation(PlanOperationKind::Hardlink, "c.mp4", 4096),
+            operation(PlanOperationKind::Move, "d.mp4", 4096),
+        ];
+
+        let error = check_space_with("videos", &operations, |_| Ok(Some(1000))).unwrap_err();
+
+        assert_eq!(
+            error.to_string(),
+            format!(
+                "videos - Not enough disk space, recipe aborted before touching any file - {}: 2.0 KiB to write, 1000 B available",
+                root.path().display()
+            )
+        );
+        assert!(check_space_with("videos", &operations, |_| Ok(Some(2048))).is_ok());
+        assert!(check_space_with("videos", &operations, |_| Ok(None)).is_ok());
+    }
+}
@@ -0,0 +1,110 @@
+use regex::Regex;
+use serde::Deserialize;
+use std::fs;
+use std::path::Path;
+
+/// The default folder of the files matching no tag rule.
+const DEFAULT_TAG_FOLDER: &str = "Other";
+
+/// TagRuleDefinition is a struct that contains a rule of a tag rules file, as written in the file.
+#[derive(Deserialize)]
+struct TagRuleDefinition {
+    pattern: String,
+    folder: String,
+}
+
+/// TagRulesDefinition is a struct that contains a tag rules file, as written in the file.
+#[derive(Deserialize)]
+struct TagRulesDefinition {
+    rules: Vec<TagRuleDefinition>,
+    default_folder: Option<String>,
+}
+
+/// TagRules is a struct that contains the compiled rules routing files into folders by their name.
+pub struct TagRules {
+    rules: Vec<(Regex, String)>,
+    default_folder: String,
+}
+
+impl TagRules {
+    /// Loads the tag rules from a file.
+    ///
+    /// ### Parameters
+    /// - `path`: The path to the tag rules file.
+    ///
+    /// ### Returns
+    /// - `Result<TagRules, anyhow::Error>`: The compiled tag rules.
+    pub fn load(path: &Path) -> anyhow::Result<TagRules> {
+        let definition: TagRulesDefinition = serde_json::from_str(&fs::read_to_string(path)?)?;
+        let mut rules = Vec::new();
+        for rule in definition.rules {
+            let pattern = Regex::new(&rule.pattern).map_err(|e| {
+                anyhow::Error::msg(format!("Invalid pattern {}: {}", rule.pattern, e))
+            })?;
+            rules.push((pattern, rule.folder));
+        }
+        Ok(TagRules {
+            rules,
+            default_folder: definition
+                .default_folder
+                .unwrap_or(DEFAULT_TAG_FOLDER.to_string()),
+        })
+    }
+
+    /// Gets the folder of a file, from the first rule matching its name.
+    ///
+    /// ### Parameters
+    /// - `file_name`: The name of the file.
+    ///
+    /// ### Returns
+    /// - `&str`: The folder of the first matching rule, the default folder if no rule matches.
+    pub fn get_folder(&self, file_name: &str) -> &str {
+        self.rules
+            .iter()
+            .find(|(pattern, _)| pattern.is_match(file_name))
+            .map(|(_, folder)| folder.as_str())
+            .unwrap_or(&self.default_folder)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::NamedTempFile;
+
+    #[test]
+    fn files_go_to_the_first_matching_rule_or_the_default_folder() {
+        let rules_file = NamedTempFile::new().unwrap();
+        fs::write(
+            rules_file.path(),
+            r#"{
+                "rules": [
+                    {"pattern": "^invoice_", "folder": "Invoices"},
+                    {"pattern": "(?i)scan", "folder": "Scans"},
+                    {"pattern": "scan_invoice", "folder": "Unreachable"}
+                ]
+            }"#,
+        )
+        .unwrap();
+
+        let tag_rules = TagRules::load(rules_file.path()).unwrap();
+
+        assert_eq!(tag_rules.get_folder("invoice_2024.pdf"), "Invoices");
+        assert_eq!(tag_rules.get_folder("SCAN_invoice_2024.pdf"), "Scans");
+        assert_eq!(tag_rules.get_folder("holidays.jpg"), DEFAULT_TAG_FOLDER);
+    }
+
+    #[test]
+    fn invalid_pattern_is_rejected() {
+        let rules_file = NamedTempFile::new().unwrap();
+        fs::write(
+            rules_file.path(),
+            r#"{"rules": [{"pattern": "([a-z", "folder": "Broken"}], "default_folder": "Misc"}"#,
+        )
+        .unwrap();
+
+        let error = TagRules::load(rules_file.path()).err().unwrap();
+
+        assert!(error.to_string().starts_with("Invalid pattern ([a-z"));
+    }
+}
@@ -0,0 +1,312 @@
+use std::env;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+/// Schedule is an enum that contains the schedules a timer can run file_organizer on.
+#[derive(Debug, PartialEq)]
+pub enum Schedule {
+    Hourly,
+    Daily,
+    Weekly,
+    DailyAt(u32, u32),
+}
+
+impl Schedule {
+    /// Parses a schedule.
+    ///
+    /// ### Parameters
+    /// - `schedule`: The schedule to parse: `hourly`, `daily`, `weekly` or a `HH:MM` time of the day.
+    ///
+    /// ### Returns
+    /// - `Result<Schedule, anyhow::Error>`: The schedule.
+    pub fn parse(schedule: &str) -> anyhow::Result<Schedule> {
+        match schedule.to_lowercase().as_str() {
+            "hourly" => Ok(Schedule::Hourly),
+            "daily" => Ok(Schedule::Daily),
+            "weekly" => Ok(Schedule::Weekly),
+            time => {
+                let invalid = || {
+                    anyhow::Error::msg(format!(
+                        "Invalid schedule: {}, expected hourly, daily, weekly or HH:MM",
+                        schedule
+                    ))
+                };
+                let (hour, minute) = time.split_once(':').ok_or_else(invalid)?;
+                let hour: u32 = hour.parse().map_err(|_| invalid())?;
+                let minute: u32 = minute.parse().map_err(|_| invalid())?;
+                if hour > 23 || minute > 59 {
+                    return Err(invalid());
+                }
+                Ok(Schedule::DailyAt(hour, minute))
+            }
+        }
+    }
+
+    /// Converts the schedule to a systemd calendar event.
+    ///
+    /// ### Returns
+    /// - `String`: The `OnCalendar` value.
+    fn to_on_calendar(&self) -> String {
+        match self {
+            Schedule::Hourly => "hourly".to_string(),
+            Schedule::Daily => "daily".to_string(),
+            Schedule::Weekly => "weekly".to_string(),
+            Schedule::DailyAt(hour, minute) => format!("*-*-* {:02}:{:02}:00", hour, minute),
+        }
+    }
+
+    /// Converts the schedule to a launchd calendar interval.
+    ///
+    /// ### Returns
+    /// - `Vec<(&str, u32)>`: The keys and values of the `StartCalendarInterval` dictionary.
+    fn to_calendar_interval(&self) -> Vec<(&'static str, u32)> {
+        match self {
+            Schedule::Hourly => vec![("Minute", 0)],
+            Schedule::Daily => vec![("Hour", 0), ("Minute", 0)],
+            Schedule::Weekly => vec![("Weekday", 1), ("Hour", 0), ("Minute", 0)],
+            Schedule::DailyAt(hour, minute) => vec![("Hour", *hour), ("Minute", *minute)],
+        }
+    }
+}
+
+/// Generates the systemd service and timer units running file_organizer on a schedule.
+///
+/// ### Parameters
+/// - `binary`: The path to the file_organizer binary.
+/// - `recipes`: The path to the recipes file.
+/// - `schedule`: The schedule of the timer.
+///
+/// ### Returns
+/// - `(String, String)`: The content of the service unit and of the timer unit.
+pub fn generate_systemd_units(
+    binary: &Path,
+    recipes: &Path,
+    schedule: &Schedule,
+) -> (String, String) {
+    let service = format!(
+        "[Unit]\n\
+         Description=Organizes files with file_organizer ({recipes_name})\n\
+         \n\
+         [Service]\n\
+         Type=oneshot\n\
+         ExecStart={binary} {recipes}\n",
+        recipes_name = recipes.display(),
+        binary = quote_systemd_argument(binary),
+        recipes = quote_systemd_argument(recipes),
+    );
+    let timer = format!(
+        "[Unit]\n\
+         Description=Runs file_organizer ({recipes_name}) on {on_calendar}\n\
+         \n\
+         [Timer]\n\
+         OnCalendar={on_calendar}\n\
+         Persistent=true\n\
+         \n\
+         [Install]\n\
+         WantedBy=timers.target\n",
+        recipes_name = recipes.display(),
+        on_calendar = schedule.to_on_calendar(),
+    );
+    (service, timer)
+}
+
+/// Generates the launchd agent running file_organizer on a schedule.
+///
+/// ### Parameters
+/// - `label`: The label of the agent.
+/// - `binary`: The path to the file_organizer binary.
+/// - `recipes`: The path to the recipes file.
+/// - `schedule`: The schedule of the agent.
+///
+/// ### Returns
+/// - `String`: The content of the property list.
+pub fn generate_launchd_plist(
+    label: &str,
+    binary: &Path,
+    recipes: &Path,
+    schedule: &Schedule,
+) -> String {
+    let calendar_interval: String = schedule
+        .to_calendar_interval()
+        .iter()
+        .map(|(key, value)| format!("\t\t<key>{}</key>\n\t\t<integer>{}</integer>\n", key, value))
+        .collect();
+    format!(
+        "<?xml version=\"1.0\" encoding=\"UTF-8\"?>\n\
+         <!DOCTYPE plist PUBLIC \"-//Apple//DTD PLIST 1.0//EN\" \"http://www.apple.com/DTDs/PropertyList-1.0.dtd\">\n\
+         <plist version=\"1.0\">\n\
+         <dict>\n\
+         \t<key>Label</key>\n\
+         \t<string>{label}</string>\n\
+         \t<key>ProgramArguments</key>\n\
+         \t<array>\n\
+         \t\t<string>{binary}</string>\n\
+         \t\t<string>{recipes}</string>\n\
+         \t</array>\n\
+         \t<key>StartCalendarInterval</key>\n\
+         \t<dict>\n\
+         {calendar_interval}\
+         \t</dict>\n\
+         </dict>\n\
+         </plist>\n",
+        label = escape_xml(label),
+        binary = escape_xml(&binary.to_string_lossy()),
+        recipes = escape_xml(&recipes.to_string_lossy()),
+    )
+}
+
+/// Installs a timer running file_organizer on a schedule, as systemd user units on Linux or a launchd agent on macOS.
+/// The files are only written, enabling the timer is left to the user. Existing files are never overwritten.
+///
+/// ### Parameters
+/// - `recipes`: The path to the recipes file.
+/// - `schedule`: The schedule of the timer.
+///
+/// ### Returns
+/// - `Result<(Vec<PathBuf>, String), anyhow::Error>`: The written files and the command enabling the timer.
+pub fn install_timer(
+    recipes: &Path,
+    schedule: &Schedule,
+) -> anyhow::Result<(Vec<PathBuf>, String)> {
+    let binary = env::current_exe()?;
+    let recipes = fs::canonicalize(recipes).map_err(|e| {
+        anyhow::Error::msg(format!(
+            "Error resolving the recipes file {}: {}",
+            recipes.display(),
+            e
+        ))
+    })?;
+    let name = format!(
+        "file_organizer-{}",
+        recipes
+            .file_stem()
+            .map(|v| v.to_string_lossy().to_string())
+            .unwrap_or_default()
+    );
+    let home = env::var_os("HOME")
+        .map(PathBuf::from)
+        .ok_or_else(|| anyhow::Error::msg("HOME is not set"))?;
+    let (files, enable_command) = if cfg!(target_os = "macos") {
+        let label = format!("com.{}", name);
+        let plist = generate_launchd_plist(&label, &binary, &recipes, schedule);
+        let path = home
+            .join("Library")
+            .join("LaunchAgents")
+            .join(format!("{}.plist", label));
+        let enable_command = format!("launchctl load {}", path.display());
+        (vec![(path, plist)], enable_command)
+    } else if cfg!(target_os = "linux") {
+        let (service, timer) = generate_systemd_units(&binary, &recipes, schedule);
+        let units_folder = env::var_os("XDG_CONFIG_HOME")
+            .filter(|v| !v.is_empty())
+            .map(PathBuf::from)
+            .unwrap_or_else(|| home.join(".config"))
+            .join("systemd")
+            .join("user");
+        let enable_command = format!(
+            "systemctl --user daemon-reload && systemctl --user enable --now {}.timer",
+            name
+        );
+        (
+            vec![
+                (units_folder.join(format!("{}.service", name)), service),
+                (units_folder.join(format!("{}.timer", name)), timer),
+            ],
+            enable_command,
+        )
+    } else {
+        return Err(anyhow::Error::msg(
+            "Timers can only be installed on Linux (systemd) and macOS (launchd)",
+        ));
+    };
+    for (path, _) in &files {
+        if path.exists() {
+            return Err(anyhow::Error::msg(format!(
+                "Timer file already exists: {}",
+                path.display()
+            )));
+        }
+    }
+    let mut written = Vec::new();
+    for (path, content) in files {
+        if let Some(folder) = path.parent() {
+            fs::create_dir_all(folder)?;
+        }
+        fs::write(&path, content)?;
+        written.push(path);
+    }
+    Ok((written, enable_command))
+}
+
+/// Quotes an argument of a systemd command line.
+///
+/// ### Parameters
+/// - `argument`: The argument to quote.
+///
+/// ### Returns
+/// - `String`: The quoted argument, with its specifiers escaped.
+fn quote_systemd_argument(argument: &Path) -> String {
+    let escaped = argument
+        .to_string_lossy()
+        .replace('\\', "\\\\")
+        .replace('"', "\\\"")
+        .replace('%', "%%");
+    format!("\"{}\"", escaped)
+}
+
+/// Escapes a string for an XML document.
+///
+/// ### Parameters
+/// - `value`: The string to escape.
+///
+/// ### Returns
+/// - `String`: The escaped string.
+fn escape_xml(value: &str) -> String {
+    value
+        .replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('"', "&quot;")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn systemd_units_run_the_binary_on_the_recipes() {
+        let (service, timer) = generate_systemd_units(
+            Path::new("/usr/local/bin/file_organizer"),
+            Path::new("/home/user/100% recipes.json"),
+            &Schedule::parse("07:30").unwrap(),
+        );
+
+        assert!(service.contains(
+            "ExecStart=\"/usr/local/bin/file_organizer\" \"/home/user/100%% recipes.json\"\n"
+        ));
+        assert!(timer.contains("OnCalendar=*-*-* 07:30:00\n"));
+        assert!(timer.contains("WantedBy=timers.target"));
+    }
+
+    #[test]
+    fn launchd_plist_runs_the_binary_on_the_recipes() {
+        let plist = generate_launchd_plist(
+            "com.file_organizer.recipes",
+            Path::new("/opt/file_organizer"),
+            Path::new("/Users/user/R&D.json"),
+            &Schedule::Weekly,
+        );
+
+        assert!(plist.contains("<string>com.file_organizer.recipes</string>"));
+        assert!(plist.contains("\t\t<string>/opt/file_organizer</string>\n\t\t<string>/Users/user/R&amp;D.json</string>\n"));
+        assert!(plist.contains("<key>Weekday</key>\n\t\t<integer>1</integer>"));
+    }
+
+    #[test]
+    fn schedules_are_parsed() {
+        assert_eq!(Schedule::parse("Hourly").unwrap(), Schedule::Hourly);
+        assert_eq!(Schedule::parse("23:59").unwrap(), Schedule::DailyAt(23, 59));
+        assert!(Schedule::parse("24:00").is_err());
+        assert!(Schedule::parse("noon").is_err());
+    }
+}
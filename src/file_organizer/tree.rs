@@ -0,0 +1,86 @@
+use std::collections::{BTreeMap, HashMap};
+use std::path::{Path, PathBuf};
+
+/// TreeNode is a struct that contains a folder of the destination tree and the number of files organized under it.
+#[derive(Default)]
+struct TreeNode {
+    files_count: u32,
+    children: BTreeMap<String, TreeNode>,
+}
+
+/// Renders the destination folders files have been organized into as an ASCII tree.
+/// Each folder is followed by the number of files organized into it or into its subfolders.
+///
+/// ### Parameters
+/// - `root`: The destination folder.
+/// - `folders_counts`: The number of files organized into each folder.
+///
+/// ### Returns
+/// - `String`: The rendered tree.
+pub fn render_tree(root: &Path, folders_counts: &HashMap<PathBuf, u32>) -> String {
+    let mut root_node = TreeNode::default();
+    for (folder, count) in folders_counts {
+        root_node.files_count += count;
+        let mut node = &mut root_node;
+        for component in folder.strip_prefix(root).unwrap_or(folder).components() {
+            node = node
+                .children
+                .entry(component.as_os_str().to_string_lossy().to_string())
+                .or_default();
+            node.files_count += count;
+        }
+    }
+    let mut tree = format!("{} ({})\n", root.display(), root_node.files_count);
+    render_children(&root_node, "", &mut tree);
+    tree
+}
+
+/// Renders the children of a node of the tree.
+///
+/// ### Parameters
+/// - `node`: The node to render the children of.
+/// - `prefix`: The prefix of the lines of the children.
+/// - `tree`: The rendered tree to append the children to.
+fn render_children(node: &TreeNode, prefix: &str, tree: &mut String) {
+    let children_count = node.children.len();
+    for (index, (name, child)) in node.children.iter().enumerate() {
+        let is_last = index == children_count - 1;
+        tree.push_str(&format!(
+            "{}{}{} ({})\n",
+            prefix,
+            if is_last { "└── " } else { "├── " },
+            name,
+            child.files_count
+        ));
+        let child_prefix = format!("{}{}", prefix, if is_last { "    " } else { "│   " });
+        render_children(child, &child_prefix, tree);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn tree_counts_the_files_under_each_folder() {
+        let root = Path::new("/photos");
+        let folders_counts = HashMap::from([
+            (root.join("2023").join("01"), 2),
+            (root.join("2023").join("02"), 1),
+            (root.join("2024").join("01"), 4),
+            (root.to_path_buf(), 1),
+        ]);
+
+        let tree = render_tree(root, &folders_counts);
+
+        let expected = [
+            "/photos (8)",
+            "├── 2023 (3)",
+            "│   ├── 01 (2)",
+            "│   └── 02 (1)",
+            "└── 2024 (4)",
+            "    └── 01 (4)",
+        ];
+        assert_eq!(tree.lines().collect::<Vec<_>>(), expected);
+    }
+}
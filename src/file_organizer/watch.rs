@@ -0,0 +1,202 @@
+use crate::file_organizer::settings::Recipe;
+use notify::event::{EventKind, ModifyKind};
+use notify::{Event, RecursiveMode};
+use std::collections::HashMap;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::time::{Duration, Instant, SystemTime};
+
+/// The time a file must stay unchanged before being organized, so that the files being written are not grabbed mid-write.
+const WATCH_DEBOUNCE: Duration = Duration::from_secs(2);
+
+/// The interval at which the files waiting to be organized are checked.
+pub const WATCH_POLL_INTERVAL: Duration = Duration::from_millis(500);
+
+/// Debouncer is a struct that contains the files created or changed in the watched folders, with the time they are ready to be organized.
+#[derive(Default)]
+pub struct Debouncer {
+    pending: HashMap<PathBuf, Instant>,
+}
+
+impl Debouncer {
+    /// Adds the files of a watch event, if created, written or moved in, the time they are ready being reset.
+    ///
+    /// ### Parameters
+    /// - `event`: The watch event.
+    pub fn add_event(&mut self, event: &Event) {
+        if !matches!(
+            event.kind,
+            EventKind::Create(_)
+                | EventKind::Modify(ModifyKind::Data(_) | ModifyKind::Name(_) | ModifyKind::Any)
+        ) {
+            return;
+        }
+        let ready_time = Instant::now() + WATCH_DEBOUNCE;
+        for path in &event.paths {
+            self.pending.insert(path.clone(), ready_time);
+        }
+    }
+
+    /// Adds a file again if it is too recent for the min_age_seconds of a recipe, which skipped it, so that it is organized once old enough rather than left until it changes again.
+    ///
+    /// ### Parameters
+    /// - `file`: The file.
+    /// - `min_age_seconds`: The minimum age of the files organized by the recipe.
+    pub fn requeue_recent(&mut self, file: &Path, min_age_seconds: u64) {
+        let Ok(modified) = fs::metadata(file).and_then(|v| v.modified()) else {
+            return;
+        };
+        let age = SystemTime::now()
+            .duration_since(modified)
+            .unwrap_or_default();
+        let min_age = Duration::from_secs(min_age_seconds);
+        if age >= min_age {
+            return;
+        }
+        let ready_time = Instant::now() + (min_age - age);
+        self.pending
+            .entry(file.to_path_buf())
+            .and_modify(|v| *v = (*v).max(ready_time))
+            .or_insert(ready_time);
+    }
+
+    /// Takes the files unchanged for long enough to be organized, or old enough for the recipes which skipped them as too recent.
+    ///
+    /// ### Returns
+    /// - `Vec<PathBuf>`: The files ready, in path order, the ones no longer existing being left out.
+    pub fn take_ready(&mut self) -> Vec<PathBuf> {
+        self.take_ready_at(Instant::now())
+    }
+
+    /// Takes the files ready to be organized at a given time.
+    ///
+    /// ### Parameters
+    /// - `now`: The time the files are taken at.
+    ///
+    /// ### Returns
+    /// - `Vec<PathBuf>`: The files ready, in path order, the ones no longer existing being left out.
+    fn take_ready_at(&mut self, now: Instant) -> Vec<PathBuf> {
+        let ready: Vec<PathBuf> = self
+            .pending
+            .iter()
+            .filter(|(_, ready_time)| **ready_time <= now)
+            .map(|(path, _)| path.clone())
+            .collect();
+        for path in &ready {
+            self.pending.remove(path);
+        }
+        let mut ready: Vec<PathBuf> = ready.into_iter().filter(|v| v.is_file()).collect();
+        ready.sort();
+        ready
+    }
+}
+
+/// Gets how the source folder of a recipe is watched.
+///
+/// ### Parameters
+/// - `recipe`: The recipe.
+///
+/// ### Returns
+/// - `RecursiveMode`: Recursive if the recipe scans the subfolders of its source folder, non recursive otherwise.
+pub fn get_recursive_mode(recipe: &Recipe) -> RecursiveMode {
+    if recipe.recursive.unwrap_or(false) || recipe.in_place.unwrap_or(false) {
+        RecursiveMode::Recursive
+    } else {
+        RecursiveMode::NonRecursive
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use notify::event::{AccessKind, CreateKind, DataChange};
+    use tempfile::TempDir;
+
+    #[test]
+    fn files_are_ready_once_unchanged_for_the_debounce_delay() {
+        let folder = TempDir::new().unwrap();
+        let (download, removed) = (
+            folder.path().join("movie.mkv"),
+            folder.path().join("tmp.part"),
+        );
+        fs::write(&download, "partial").unwrap();
+        let mut debouncer = Debouncer::default();
+        let start = Instant::now();
+
+        debouncer.add_event(
+            &Event::new(EventKind::Create(CreateKind::File))
+                .add_path(download.clone())
+                .add_path(removed),
+        );
+        debouncer.add_event(
+            &Event::new(EventKind::Access(AccessKind::Any))
+                .add_path(folder.path().join("read.txt")),
+        );
+        assert!(debouncer.take_ready_at(start).is_empty());
+
+        debouncer.add_event(
+            &Event::new(EventKind::Modify(ModifyKind::Data(DataChange::Content)))
+                .add_path(download.clone()),
+        );
+        assert!(debouncer.take_ready_at(start + WATCH_DEBOUNCE).is_empty());
+
+        let ready = debouncer.take_ready_at(Instant::now() + WATCH_DEBOUNCE);
+        assert_eq!(ready, [download]);
+        assert!(debouncer.pending.is_empty());
+    }
+
+    #[test]
+    fn recent_files_are_requeued_until_old_enough() {
+        let folder = TempDir::new().unwrap();
+        let (fresh, old) = (
+            folder.path().join("fresh.txt"),
+            folder.path().join("old.txt"),
+        );
+        fs::write(&fresh, "fresh").unwrap();
+        fs::write(&old, "old").unwrap();
+        fs::File::options()
+            .write(true)
+            .open(&old)
+            .unwrap()
+            .set_modified(SystemTime::now() - Duration::from_secs(120))
+            .unwrap();
+        let mut debouncer = Debouncer::default();
+
+        debouncer.requeue_recent(&fresh, 60);
+        debouncer.requeue_recent(&old, 60);
+
+        assert_eq!(debouncer.pending.len(), 1);
+        let now = Instant::now();
+        assert!(
+            debouncer
+                .take_ready_at(now + Duration::from_secs(30))
+                .is_empty()
+        );
+        assert_eq!(
+            debouncer.take_ready_at(now + Duration::from_secs(61)),
+            [fresh]
+        );
+    }
+
+    #[test]
+    fn subfolders_are_watched_for_recursive_recipes_only() {
+        let recipe = |recursive: Option<bool>, in_place: Option<bool>| Recipe {
+            recursive,
+            in_place,
+            ..Default::default()
+        };
+
+        assert_eq!(
+            get_recursive_mode(&recipe(None, None)),
+            RecursiveMode::NonRecursive
+        );
+        assert_eq!(
+            get_recursive_mode(&recipe(Some(true), None)),
+            RecursiveMode::Recursive
+        );
+        assert_eq!(
+            get_recursive_mode(&recipe(None, Some(true))),
+            RecursiveMode::Recursive
+        );
+    }
+}
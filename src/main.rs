@@ -1,10 +1,20 @@
 use clap::{ArgAction, ArgMatches, Command, arg, command, value_parser};
-use log::LevelFilter;
-use simple_logger::SimpleLogger;
-use std::path::PathBuf;
+use clap_complete::Shell;
+use std::path::{Path, PathBuf};
 mod file_organizer;
 use colored::*;
-use file_organizer::FileOrganizer;
+use file_organizer::config_diff::{self, ConfigChange};
+use file_organizer::journal;
+use file_organizer::logging::{self, LogFormat};
+use file_organizer::overlap::OverlapPolicy;
+use file_organizer::patch;
+use file_organizer::plan;
+use file_organizer::registry;
+use file_organizer::settings::Settings;
+use file_organizer::shard::Shard;
+use file_organizer::timer::{self, Schedule};
+use file_organizer::{FileOrganizer, RunOptions};
+use tracing::{Level, error, info, warn};
 
 /// Makes the arguments.
 ///
@@ -15,9 +25,18 @@ fn make_args() -> Command {
         .about("Organize files into folders based on their extension")
         .arg(
             arg!(
-                <RECIPES> "Path to the JSON file containing recipes"
+                [RECIPES] "Path to the JSON file containing recipes"
             )
-            .required(true)
+            .required_unless_present_any([
+                "apply",
+                "revert",
+                "undo",
+                "diff_config",
+                "list_tokens",
+                "list_fields",
+                "init",
+                "completions",
+            ])
             .value_parser(value_parser!(PathBuf)),
         )
         .arg(
@@ -34,6 +53,268 @@ fn make_args() -> Command {
             .required(false)
             .action(ArgAction::SetTrue),
         )
+        .arg(
+            arg!(
+                --force "Processes all the matching files, ignoring the last_run and last_seen dates of the recipes, or overwrites the file written by --init"
+            )
+            .required(false)
+            .action(ArgAction::SetTrue),
+        )
+        .arg(
+            arg!(
+                --watch "Keeps watching the source folders after the run, organizing the files as they appear"
+            )
+            .required(false)
+            .conflicts_with_all(["plan", "confirm_plan", "apply", "revert", "install_timer"])
+            .action(ArgAction::SetTrue),
+        )
+        .arg(
+            arg!(
+                --allow_remote_hooks "Runs the pre and post hooks of a recipe file fetched from a URL, which are refused otherwise"
+            )
+            .required(false)
+            .action(ArgAction::SetTrue),
+        )
+        .arg(
+            arg!(
+                --parallel_recipes "Runs the recipes not sharing folders concurrently, processing the files of each recipe iteratively"
+            )
+            .required(false)
+            .conflicts_with_all(["iterative", "chunk_size"])
+            .action(ArgAction::SetTrue),
+        )
+        .arg(
+            arg!(
+                --threads <N> "Number of threads processing the files of each recipe in parallel, 0 for all the cores (the default), ignored with --iterative"
+            )
+            .required(false)
+            .value_parser(value_parser!(usize)),
+        )
+        .arg(
+            arg!(
+                --metrics_file <PATH> "Writes Prometheus metrics of the run to a file, for the node_exporter textfile collector"
+            )
+            .required(false)
+            .value_parser(value_parser!(PathBuf)),
+        )
+        .arg(
+            arg!(
+                --chunk_size <SIZE> "Processes the files of each recipe in chunks of SIZE files, reporting the progress and flushing the metrics after each chunk"
+            )
+            .required(false)
+            .value_parser(value_parser!(u64).range(1..)),
+        )
+        .arg(
+            arg!(
+                --plan <FILE> "Writes the operations of the recipes to a plan file instead of executing them"
+            )
+            .required(false)
+            .value_parser(value_parser!(PathBuf)),
+        )
+        .arg(
+            arg!(
+                --apply <FILE> "Executes exactly the operations of a saved plan file, without running the recipes"
+            )
+            .required(false)
+            .conflicts_with_all(["dry_run", "plan", "install_timer"])
+            .value_parser(value_parser!(PathBuf)),
+        )
+        .arg(
+            arg!(
+                --patch <FILE> "Records the operations of the run to a patch file, backing up the files they overwrite, so that --revert can undo the run"
+            )
+            .required(false)
+            .conflicts_with_all(["dry_run", "plan", "apply", "install_timer"])
+            .value_parser(value_parser!(PathBuf)),
+        )
+        .arg(
+            arg!(
+                --log_file <PATH> "Appends a JSON line for each file handled by the run to a log file, with its recipe, operation, source, destination and result, for auditing"
+            )
+            .required(false)
+            .value_parser(value_parser!(PathBuf)),
+        )
+        .arg(
+            arg!(
+                --revert <PATCH> "Undoes exactly the run recorded in a patch file, without running the recipes"
+            )
+            .required(false)
+            .conflicts_with_all(["dry_run", "plan", "apply", "patch", "install_timer"])
+            .value_parser(value_parser!(PathBuf)),
+        )
+        .arg(
+            arg!(
+                --undo <LOG_FILE> "Undoes the last run recorded in a log file written by --log_file, without running the recipes"
+            )
+            .required(false)
+            .conflicts_with_all(["dry_run", "plan", "apply", "patch", "revert", "install_timer"])
+            .value_parser(value_parser!(PathBuf)),
+        )
+        .arg(
+            arg!(
+                --plan_report "Prints the operations the recipes would execute, grouped by recipe with their totals, without executing them"
+            )
+            .required(false)
+            .conflicts_with_all(["plan", "confirm_plan", "apply", "revert", "install_timer", "watch"])
+            .action(ArgAction::SetTrue),
+        )
+        .arg(
+            arg!(
+                --confirm_plan <HASH> "Runs the recipes only if their plan still has the hash printed by a dry run"
+            )
+            .required(false)
+            .conflicts_with_all(["dry_run", "plan", "apply"]),
+        )
+        .arg(
+            arg!(
+                --assert_idempotent "Plans the recipes again after running them, failing if any file would be processed again"
+            )
+            .required(false)
+            .conflicts_with_all(["dry_run", "plan", "apply"])
+            .action(ArgAction::SetTrue),
+        )
+        .arg(
+            arg!(
+                --json "Prints the statistics and the operations of each recipe as a JSON array on the standard output, only the warnings and errors being printed, to the standard error"
+            )
+            .required(false)
+            .action(ArgAction::SetTrue),
+        )
+        .arg(
+            arg!(
+                --check_space "Checks that the destination drives have enough free space for the files of each recipe before running it, aborting the recipe otherwise"
+            )
+            .required(false)
+            .action(ArgAction::SetTrue),
+        )
+        .arg(
+            arg!(
+                --show_tree "Prints the tree of the destination folders files have been organized into after each recipe"
+            )
+            .required(false)
+            .action(ArgAction::SetTrue),
+        )
+        .arg(
+            arg!(
+                --diff_config <FILES> "Compares two recipe files, reporting the recipes added or removed and the fields changed, without running them"
+            )
+            .required(false)
+            .num_args(2)
+            .value_names(["OLD", "NEW"])
+            .conflicts_with_all(["RECIPES", "dry_run", "plan", "apply", "revert", "install_timer"])
+            .value_parser(value_parser!(PathBuf)),
+        )
+        .arg(
+            arg!(
+                --validate "Checks the recipes without running them, reporting all their problems"
+            )
+            .required(false)
+            .conflicts_with_all(["dry_run", "plan", "apply", "revert", "install_timer"])
+            .action(ArgAction::SetTrue),
+        )
+        .arg(
+            arg!(
+                --reset "Clears the last_run and last_seen dates of the recipes without running them, so that their next run processes all the files"
+            )
+            .required(false)
+            .conflicts_with_all(["dry_run", "plan", "apply", "revert", "install_timer", "validate"])
+            .action(ArgAction::SetTrue),
+        )
+        .arg(
+            arg!(
+                --recipe <NAME> "Runs only the recipe with this name, case sensitively"
+            )
+            .required(false),
+        )
+        .arg(
+            arg!(
+                --shard <SHARD> "Processes only the files of a shard, as INDEX/TOTAL, to split a run across several invocations"
+            )
+            .required(false)
+            .value_parser(Shard::parse),
+        )
+        .arg(
+            arg!(
+                --on_overlap <POLICY> "What to do when the destination of a recipe overlaps the source of another one: warn, reorder (the recipes feeding others running first) or error"
+            )
+            .required(false)
+            .default_value("warn")
+            .value_parser(OverlapPolicy::parse),
+        )
+        .arg(
+            arg!(
+                --log_format <FORMAT> "Format of the output: text, or json for one structured event per line with its recipe and file spans"
+            )
+            .required(false)
+            .default_value("text")
+            .value_parser(LogFormat::parse),
+        )
+        .arg(
+            arg!(
+                --no_color "Prints the output without colors, as does setting the NO_COLOR environment variable"
+            )
+            .required(false)
+            .action(ArgAction::SetTrue),
+        )
+        .arg(
+            arg!(
+                --quiet "Prints the errors only"
+            )
+            .required(false)
+            .action(ArgAction::SetTrue)
+            .conflicts_with("verbose"),
+        )
+        .arg(
+            arg!(
+                -v --verbose "Prints the files not matched by the recipes as well, -vv printing all the events"
+            )
+            .required(false)
+            .action(ArgAction::Count),
+        )
+        .arg(
+            arg!(
+                --list_tokens "Lists the tokens supported in the subfolders and the file name template of a recipe"
+            )
+            .required(false)
+            .action(ArgAction::SetTrue),
+        )
+        .arg(
+            arg!(
+                --init <PATH> "Writes an example recipe file to start from, refusing to overwrite an existing file without --force"
+            )
+            .required(false)
+            .conflicts_with_all(["RECIPES", "dry_run", "plan", "apply", "revert", "install_timer"])
+            .value_parser(value_parser!(PathBuf)),
+        )
+        .arg(
+            arg!(
+                --list_fields "Lists the fields of a recipe with their default"
+            )
+            .required(false)
+            .action(ArgAction::SetTrue),
+        )
+        .arg(
+            arg!(
+                --completions <SHELL> "Prints the completion script of the command for a shell: bash, zsh, fish, powershell or elvish"
+            )
+            .required(false)
+            .value_parser(value_parser!(Shell)),
+        )
+        .arg(
+            arg!(
+                --install_timer "Installs a systemd timer (Linux) or a launchd agent (macOS) running the recipes on a schedule, without enabling it"
+            )
+            .required(false)
+            .action(ArgAction::SetTrue),
+        )
+        .arg(
+            arg!(
+                --schedule <SCHEDULE> "Schedule of the installed timer: hourly, daily, weekly or HH:MM"
+            )
+            .required(false)
+            .requires("install_timer")
+            .default_value("daily"),
+        )
 }
 
 /// Gets the recipes path.
@@ -60,45 +341,807 @@ fn get_iterative_flag(matches: &ArgMatches) -> bool {
     matches.get_flag("iterative")
 }
 
-fn main() {
-    let level = if cfg!(debug_assertions) {
-        LevelFilter::Debug
-    } else {
-        LevelFilter::Off
+/// Gets the force flag.
+///
+/// ### Return
+/// A boolean with the force flag.
+fn get_force_flag(matches: &ArgMatches) -> bool {
+    matches.get_flag("force")
+}
+
+/// Gets the allow remote hooks flag.
+///
+/// ### Return
+/// A boolean with the allow remote hooks flag.
+fn get_allow_remote_hooks_flag(matches: &ArgMatches) -> bool {
+    matches.get_flag("allow_remote_hooks")
+}
+
+/// Gets the parallel recipes flag.
+///
+/// ### Return
+/// A boolean with the parallel recipes flag.
+fn get_parallel_recipes_flag(matches: &ArgMatches) -> bool {
+    matches.get_flag("parallel_recipes")
+}
+
+/// Gets the plan report flag.
+///
+/// ### Return
+/// A boolean with the plan report flag.
+fn get_plan_report_flag(matches: &ArgMatches) -> bool {
+    matches.get_flag("plan_report")
+}
+
+/// Gets the watch flag.
+///
+/// ### Return
+/// A boolean with the watch flag.
+fn get_watch_flag(matches: &ArgMatches) -> bool {
+    matches.get_flag("watch")
+}
+
+/// Gets the number of threads.
+///
+/// ### Return
+/// An Option<usize> with the number of threads.
+fn get_threads(matches: &ArgMatches) -> Option<usize> {
+    matches.get_one::<usize>("threads").copied()
+}
+
+/// Gets the metrics file path.
+///
+/// ### Return
+/// An Option<PathBuf> with the metrics file path.
+fn get_metrics_file(matches: &ArgMatches) -> Option<PathBuf> {
+    matches.get_one::<PathBuf>("metrics_file").cloned()
+}
+
+/// Gets the chunk size.
+///
+/// ### Return
+/// An Option<usize> with the chunk size.
+fn get_chunk_size(matches: &ArgMatches) -> Option<usize> {
+    matches.get_one::<u64>("chunk_size").map(|v| *v as usize)
+}
+
+/// Gets the plan file path.
+///
+/// ### Return
+/// An Option<PathBuf> with the plan file path.
+fn get_plan_file(matches: &ArgMatches) -> Option<PathBuf> {
+    matches.get_one::<PathBuf>("plan").cloned()
+}
+
+/// Gets the path of the plan file to apply.
+///
+/// ### Return
+/// An Option<PathBuf> with the path of the plan file to apply.
+fn get_apply_file(matches: &ArgMatches) -> Option<PathBuf> {
+    matches.get_one::<PathBuf>("apply").cloned()
+}
+
+/// Gets the hash of the plan to confirm.
+///
+/// ### Return
+/// An Option<String> with the hash of the plan to confirm.
+fn get_confirm_plan(matches: &ArgMatches) -> Option<String> {
+    matches.get_one::<String>("confirm_plan").cloned()
+}
+
+/// Gets the assert idempotent flag.
+///
+/// ### Return
+/// A boolean with the assert idempotent flag.
+fn get_assert_idempotent_flag(matches: &ArgMatches) -> bool {
+    matches.get_flag("assert_idempotent")
+}
+
+/// Gets the JSON flag.
+///
+/// ### Return
+/// A boolean with the JSON flag.
+fn get_json_flag(matches: &ArgMatches) -> bool {
+    matches.get_flag("json")
+}
+
+/// Gets the check space flag.
+///
+/// ### Return
+/// A boolean with the check space flag.
+fn get_check_space_flag(matches: &ArgMatches) -> bool {
+    matches.get_flag("check_space")
+}
+
+/// Gets the show tree flag.
+///
+/// ### Return
+/// A boolean with the show tree flag.
+fn get_show_tree_flag(matches: &ArgMatches) -> bool {
+    matches.get_flag("show_tree")
+}
+
+/// Gets the recipe files to compare.
+///
+/// ### Return
+/// An Option<(PathBuf, PathBuf)> with the old and new recipe files.
+fn get_diff_config_files(matches: &ArgMatches) -> Option<(PathBuf, PathBuf)> {
+    let mut files = matches.get_many::<PathBuf>("diff_config")?;
+    Some((files.next()?.clone(), files.next()?.clone()))
+}
+
+/// Gets the validate flag.
+///
+/// ### Return
+/// A boolean with the validate flag.
+fn get_validate_flag(matches: &ArgMatches) -> bool {
+    matches.get_flag("validate")
+}
+
+/// Gets the reset flag.
+///
+/// ### Return
+/// A boolean with the reset flag.
+fn get_reset_flag(matches: &ArgMatches) -> bool {
+    matches.get_flag("reset")
+}
+
+/// Gets the name of the recipe to run.
+///
+/// ### Return
+/// An Option<String> with the name of the recipe to run.
+fn get_recipe_name(matches: &ArgMatches) -> Option<String> {
+    matches.get_one::<String>("recipe").cloned()
+}
+
+/// Gets the shard.
+///
+/// ### Return
+/// An Option<Shard> with the shard.
+fn get_shard(matches: &ArgMatches) -> Option<Shard> {
+    matches.get_one::<Shard>("shard").cloned()
+}
+
+/// Gets the overlap policy.
+///
+/// ### Return
+/// An OverlapPolicy with the overlap policy.
+fn get_overlap_policy(matches: &ArgMatches) -> OverlapPolicy {
+    matches
+        .get_one::<OverlapPolicy>("on_overlap")
+        .cloned()
+        .unwrap_or_default()
+}
+
+/// Gets the log format.
+///
+/// ### Return
+/// A LogFormat with the log format.
+fn get_log_format(matches: &ArgMatches) -> LogFormat {
+    matches
+        .get_one::<LogFormat>("log_format")
+        .cloned()
+        .unwrap_or_default()
+}
+
+/// Gets the no color flag, set as well by a non-empty NO_COLOR environment variable.
+///
+/// ### Return
+/// A boolean with the no color flag.
+fn get_no_color_flag(matches: &ArgMatches) -> bool {
+    is_color_disabled(
+        matches.get_flag("no_color"),
+        std::env::var_os("NO_COLOR").as_deref(),
+    )
+}
+
+/// Checks if the colors are disabled, by the no color flag or by the NO_COLOR environment variable.
+///
+/// ### Parameters
+/// - `no_color_flag`: The no color flag.
+/// - `no_color_env`: The value of the NO_COLOR environment variable, if set.
+///
+/// ### Return
+/// A boolean, true if the flag is set or the variable is set and not empty.
+fn is_color_disabled(no_color_flag: bool, no_color_env: Option<&std::ffi::OsStr>) -> bool {
+    no_color_flag || no_color_env.is_some_and(|v| !v.is_empty())
+}
+
+/// Gets the level of the events printed, from the quiet and verbose flags.
+///
+/// ### Return
+/// A Level with the most verbose level printed: ERROR if quiet, DEBUG with -v, TRACE with -vv, INFO otherwise.
+fn get_log_level(matches: &ArgMatches) -> Level {
+    if matches.get_flag("quiet") {
+        return Level::ERROR;
+    }
+    match matches.get_count("verbose") {
+        0 => Level::INFO,
+        1 => Level::DEBUG,
+        _ => Level::TRACE,
+    }
+}
+
+/// Gets the list tokens flag.
+///
+/// ### Return
+/// A boolean with the list tokens flag.
+fn get_list_tokens_flag(matches: &ArgMatches) -> bool {
+    matches.get_flag("list_tokens")
+}
+
+/// Gets the path of the recipe file to initialize.
+///
+/// ### Return
+/// An Option<PathBuf> with the path of the recipe file to initialize.
+fn get_init_file(matches: &ArgMatches) -> Option<PathBuf> {
+    matches.get_one::<PathBuf>("init").cloned()
+}
+
+/// Gets the list fields flag.
+///
+/// ### Return
+/// A boolean with the list fields flag.
+fn get_list_fields_flag(matches: &ArgMatches) -> bool {
+    matches.get_flag("list_fields")
+}
+
+/// Gets the shell to print the completion script for.
+///
+/// ### Return
+/// An Option<Shell> with the shell.
+fn get_completions_shell(matches: &ArgMatches) -> Option<Shell> {
+    matches.get_one::<Shell>("completions").copied()
+}
+
+/// Prints the completion script of the command for a shell.
+///
+/// ### Parameters
+/// - `shell`: The shell to print the completion script for.
+/// - `output`: The output to print the script to.
+fn print_completions(shell: Shell, output: &mut impl std::io::Write) {
+    let mut command = make_args();
+    let name = command.get_name().to_string();
+    clap_complete::generate(shell, &mut command, name, output);
+}
+
+/// Gets the install timer flag.
+///
+/// ### Return
+/// A boolean with the install timer flag.
+fn get_install_timer_flag(matches: &ArgMatches) -> bool {
+    matches.get_flag("install_timer")
+}
+
+/// Gets the schedule of the timer.
+///
+/// ### Return
+/// A String with the schedule.
+fn get_schedule(matches: &ArgMatches) -> String {
+    matches.get_one::<String>("schedule").unwrap().to_string()
+}
+
+/// Installs the timer running the recipes on a schedule.
+///
+/// ### Parameters
+/// - `recipes`: The path to the recipes file.
+/// - `schedule`: The schedule of the timer.
+fn install_timer(recipes: &Path, schedule: &str) {
+    let result =
+        Schedule::parse(schedule).and_then(|schedule| timer::install_timer(recipes, &schedule));
+    match result {
+        Ok((files, enable_command)) => {
+            for file in files {
+                info!(
+                    "{} {} - {}",
+                    "✅".green(),
+                    "Timer file written".purple(),
+                    file.display()
+                );
+            }
+            info!(
+                "{} {} - {}",
+                "ℹ️".blue(),
+                "The timer is not enabled, enable it with".purple(),
+                enable_command
+            );
+        }
+        Err(e) => error!("{} {}", "❌Error:".red().bold(), e),
+    }
+}
+
+/// Gets the path of the patch file to record.
+///
+/// ### Return
+/// An Option<PathBuf> with the path of the patch file to record.
+fn get_patch_file(matches: &ArgMatches) -> Option<PathBuf> {
+    matches.get_one::<PathBuf>("patch").cloned()
+}
+
+/// Gets the path of the log file to append to.
+///
+/// ### Return
+/// An Option<PathBuf> with the path of the log file to append to.
+fn get_log_file(matches: &ArgMatches) -> Option<PathBuf> {
+    matches.get_one::<PathBuf>("log_file").cloned()
+}
+
+/// Gets the path of the log file whose last run to undo.
+///
+/// ### Return
+/// An Option<PathBuf> with the path of the log file whose last run to undo.
+fn get_undo_file(matches: &ArgMatches) -> Option<PathBuf> {
+    matches.get_one::<PathBuf>("undo").cloned()
+}
+
+/// Gets the path of the patch file to revert.
+///
+/// ### Return
+/// An Option<PathBuf> with the path of the patch file to revert.
+fn get_revert_file(matches: &ArgMatches) -> Option<PathBuf> {
+    matches.get_one::<PathBuf>("revert").cloned()
+}
+
+/// Reverts the run recorded in a patch file.
+///
+/// ### Parameters
+/// - `patch_file`: The path to the patch file.
+fn revert_patch(patch_file: &Path) {
+    match patch::revert_patch(patch_file) {
+        Ok((reverted, 0)) => info!(
+            "{} {} - {} reverted",
+            "✅".green(),
+            "Patch reverted".purple(),
+            reverted
+        ),
+        Ok((reverted, failed)) => {
+            warn!(
+                "{} {} - {} reverted, {} skipped",
+                "⚠️".yellow(),
+                "Patch partially reverted".yellow(),
+                reverted,
+                failed
+            );
+            std::process::exit(1);
+        }
+        Err(e) => {
+            error!("{} {}", "❌Error:".red().bold(), e);
+            std::process::exit(1);
+        }
+    }
+}
+
+/// Undoes the last run recorded in a log file.
+///
+/// ### Parameters
+/// - `log_file`: The path to the log file.
+fn undo_run(log_file: &Path) {
+    match journal::undo_last_run(log_file) {
+        Ok((undone, 0)) => info!(
+            "{} {} - {} undone",
+            "✅".green(),
+            "Run undone".purple(),
+            undone
+        ),
+        Ok((undone, refused)) => {
+            warn!(
+                "{} {} - {} undone, {} refused",
+                "⚠️".yellow(),
+                "Run partially undone".yellow(),
+                undone,
+                refused
+            );
+            std::process::exit(1);
+        }
+        Err(e) => {
+            error!("{} {}", "❌Error:".red().bold(), e);
+            std::process::exit(1);
+        }
+    }
+}
+
+/// Compares two recipe files and prints their differences.
+///
+/// ### Parameters
+/// - `old_file`: The path to the old recipe file.
+/// - `new_file`: The path to the new recipe file.
+fn diff_configs(old_file: &PathBuf, new_file: &PathBuf) {
+    let changes = Settings::load_from_file(old_file)
+        .and_then(|old| config_diff::diff_settings(&old, &Settings::load_from_file(new_file)?));
+    let changes = match changes {
+        Ok(changes) => changes,
+        Err(e) => {
+            error!("{} {}", "❌Error:".red().bold(), e);
+            std::process::exit(1);
+        }
+    };
+    if changes.is_empty() {
+        info!(
+            "{} {} {}",
+            "✅".green(),
+            "file_organizer".blue(),
+            "No changes".purple()
+        );
+        return;
+    }
+    let to_string = |value: &Option<serde_json::Value>| match value {
+        Some(value) => value.to_string(),
+        None => "none".to_string(),
+    };
+    for change in changes {
+        match change {
+            ConfigChange::RecipeAdded(name) => {
+                info!(
+                    "{} {} {}",
+                    "➕".green(),
+                    name.blue(),
+                    "Recipe added".green()
+                )
+            }
+            ConfigChange::RecipeRemoved(name) => {
+                info!("{} {} {}", "➖".red(), name.blue(), "Recipe removed".red())
+            }
+            ConfigChange::RecipesReordered(names) => info!(
+                "{} {} {} - {}",
+                "✏️".yellow(),
+                "file_organizer".blue(),
+                "Recipes reordered".yellow(),
+                names.join(", ")
+            ),
+            ConfigChange::SettingChanged { field, old, new } => info!(
+                "{} {} {} - {} -> {}",
+                "✏️".yellow(),
+                "file_organizer".blue(),
+                field.yellow(),
+                to_string(&old),
+                to_string(&new)
+            ),
+            ConfigChange::FieldChanged {
+                recipe,
+                field,
+                old,
+                new,
+            } => info!(
+                "{} {} {} - {} -> {}",
+                "✏️".yellow(),
+                recipe.blue(),
+                field.yellow(),
+                to_string(&old),
+                to_string(&new)
+            ),
+        }
+    }
+}
+
+/// Validates the recipes of a recipe file without running them, exiting with a non-zero code if any recipe is invalid.
+///
+/// ### Parameters
+/// - `recipes`: The path to the recipe file.
+fn validate_recipes(recipes: PathBuf) {
+    let problems = match FileOrganizer::new_for_validation(recipes) {
+        Ok(file_organizer) => file_organizer.validate(),
+        Err(e) => vec![e],
+    };
+    if problems.is_empty() {
+        info!(
+            "{} {} {}",
+            "✅".green(),
+            "file_organizer".blue(),
+            "All the recipes are valid".purple()
+        );
+        return;
+    }
+    for problem in &problems {
+        error!("{} {}", "❌".red(), problem);
+    }
+    error!(
+        "{} {} problem(s) found",
+        "❌Error:".red().bold(),
+        problems.len()
+    );
+    std::process::exit(1);
+}
+
+/// Resets the state of the recipes.
+///
+/// ### Parameters
+/// - `recipes`: The path to the recipes file.
+/// - `recipe_name`: The name of the only recipe to reset, if any.
+fn reset_recipes(recipes: PathBuf, recipe_name: Option<String>) {
+    let options = RunOptions {
+        recipe_name,
+        ..Default::default()
     };
-    SimpleLogger::new()
-        .with_colors(true)
-        .with_level(level)
-        .init()
-        .unwrap();
+    let reset = FileOrganizer::new(recipes, options).and_then(|mut v| v.reset());
+    match reset {
+        Ok(names) => {
+            for name in names {
+                info!(
+                    "{} {} {}",
+                    "✅".green(),
+                    name.blue(),
+                    "Recipe reset".purple()
+                );
+            }
+        }
+        Err(e) => {
+            error!("{} {}", "❌Error:".red().bold(), e);
+            std::process::exit(1);
+        }
+    }
+}
+
+/// Writes an example recipe file.
+///
+/// ### Parameters
+/// - `file_path`: The path to the recipe file.
+/// - `is_forced`: If true, an existing file is overwritten.
+fn init_recipes(file_path: &Path, is_forced: bool) {
+    if file_path.exists() && !is_forced {
+        error!(
+            "{} {} already exists, run with --force to overwrite it",
+            "❌Error:".red().bold(),
+            file_path.display()
+        );
+        std::process::exit(1);
+    }
+    if let Err(e) = Settings::example(file_path).save() {
+        error!("{} {}", "❌Error:".red().bold(), e);
+        std::process::exit(1);
+    }
+    info!(
+        "{} {} - {}",
+        "✅".green(),
+        "Recipe file written".purple(),
+        file_path.display()
+    );
+    info!(
+        "ℹ️ {} - Set the folders and files of the recipe and remove the fields not needed before running it, --list_fields describes all the fields with their default",
+        "file_organizer".blue()
+    );
+}
+
+/// Applies a saved plan, exiting with a non-zero code if the plan cannot be loaded or any of its operations is skipped.
+///
+/// ### Parameters
+/// - `plan_file`: The path to the plan file.
+fn apply_plan(plan_file: &Path) {
+    match plan::apply_plan(plan_file) {
+        Ok((applied, 0)) => info!(
+            "{} {} - {} applied",
+            "✅".green(),
+            "Plan applied".purple(),
+            applied
+        ),
+        Ok((applied, failed)) => {
+            warn!(
+                "{} {} - {} applied, {} skipped",
+                "⚠️".yellow(),
+                "Plan partially applied".yellow(),
+                applied,
+                failed
+            );
+            std::process::exit(1);
+        }
+        Err(e) => {
+            error!("{} {}", "❌Error:".red().bold(), e);
+            std::process::exit(1);
+        }
+    }
+}
+
+fn main() {
     let matches = make_args().get_matches();
+    if let Some(shell) = get_completions_shell(&matches) {
+        print_completions(shell, &mut std::io::stdout());
+        return;
+    }
+    if get_no_color_flag(&matches) {
+        colored::control::set_override(false);
+    }
+    logging::init(
+        &get_log_format(&matches),
+        get_json_flag(&matches),
+        get_log_level(&matches),
+    );
+    if get_list_tokens_flag(&matches) || get_list_fields_flag(&matches) {
+        if get_list_tokens_flag(&matches) {
+            registry::print_tokens();
+        }
+        if get_list_fields_flag(&matches) {
+            registry::print_fields();
+        }
+        return;
+    }
+    if let Some(init_file) = get_init_file(&matches) {
+        init_recipes(&init_file, get_force_flag(&matches));
+        return;
+    }
+    if let Some(plan_file) = get_apply_file(&matches) {
+        apply_plan(&plan_file);
+        return;
+    }
+    if let Some(patch_file) = get_revert_file(&matches) {
+        revert_patch(&patch_file);
+        return;
+    }
+    if let Some(log_file) = get_undo_file(&matches) {
+        undo_run(&log_file);
+        return;
+    }
+    if let Some((old_file, new_file)) = get_diff_config_files(&matches) {
+        diff_configs(&old_file, &new_file);
+        return;
+    }
     let recipes = get_recipes(&matches);
-    let is_dry_run = get_dry_run_flag(&matches);
+    let plan_file = get_plan_file(&matches);
+    let is_dry_run =
+        get_dry_run_flag(&matches) || plan_file.is_some() || get_plan_report_flag(&matches);
     let is_iterative = get_iterative_flag(&matches);
-    println!("{}", "----------------------------------------".blue());
-    println!("{}", "- file_organizer                       -".blue());
-    println!("{}", "----------------------------------------".blue());
+    let is_forced = get_force_flag(&matches);
+    if get_install_timer_flag(&matches) {
+        install_timer(&recipes, &get_schedule(&matches));
+        return;
+    }
+    if get_validate_flag(&matches) {
+        validate_recipes(recipes);
+        return;
+    }
+    if get_reset_flag(&matches) {
+        reset_recipes(recipes, get_recipe_name(&matches));
+        return;
+    }
+    info!("{}", "----------------------------------------".blue());
+    info!("{}", "- file_organizer                       -".blue());
+    info!("{}", "----------------------------------------".blue());
     if is_dry_run {
-        println!(
+        info!(
             "{} - No files will be moved or copied",
             "ℹ️ Dry run mode enabled".blue()
         );
     }
+    if let Some(plan_file) = &plan_file {
+        info!(
+            "{} - The operations will be written to {}",
+            "ℹ️ Plan mode enabled".blue(),
+            plan_file.display()
+        );
+    }
     if is_iterative {
-        println!(
+        info!(
             "{} - Running iteratively instead of in parallel",
             "ℹ️ Iterative mode enabled".blue()
         );
     }
+    if is_forced {
+        info!(
+            "{} - The last_run and last_seen dates are ignored",
+            "ℹ️ Force mode enabled".blue()
+        );
+    }
 
-    let mut file_organizer = match FileOrganizer::new(recipes, is_dry_run, is_iterative) {
+    let options = RunOptions {
+        is_dry_run,
+        is_iterative,
+        is_forced,
+        metrics_file: get_metrics_file(&matches),
+        chunk_size: get_chunk_size(&matches),
+        plan_file,
+        confirm_plan: get_confirm_plan(&matches),
+        assert_idempotent: get_assert_idempotent_flag(&matches),
+        show_tree: get_show_tree_flag(&matches),
+        recipe_name: get_recipe_name(&matches),
+        shard: get_shard(&matches),
+        on_overlap: get_overlap_policy(&matches),
+        patch_file: get_patch_file(&matches),
+        log_file: get_log_file(&matches),
+        check_space: get_check_space_flag(&matches),
+        json_stats: get_json_flag(&matches),
+        threads: get_threads(&matches),
+        plan_report: get_plan_report_flag(&matches),
+        parallel_recipes: get_parallel_recipes_flag(&matches),
+        allow_remote_hooks: get_allow_remote_hooks_flag(&matches),
+    };
+    let mut file_organizer = match FileOrganizer::new(recipes, options) {
         Ok(file_organizer) => file_organizer,
         Err(e) => {
-            println!("{} {}", "❌Error:".red().bold(), e);
-            return;
+            error!("{} {}", "❌Error:".red().bold(), e);
+            std::process::exit(1);
+        }
+    };
+    if get_plan_report_flag(&matches) {
+        if let Err(e) = file_organizer.print_plan_report() {
+            error!("{} {}", "❌Error:".red().bold(), e);
+            std::process::exit(1);
+        }
+        return;
+    }
+    // With --watch, a failed initial run does not prevent the watch from starting, its exit code being kept for when the watch ends.
+    let is_failed = match file_organizer.run() {
+        Ok(stats) => match stats.get_failure(is_dry_run) {
+            Some(failure) => {
+                error!("{} {}", "❌Error:".red().bold(), failure);
+                true
+            }
+            None => false,
+        },
+        Err(e) => {
+            error!("{} {}", "❌Error:".red().bold(), e);
+            std::process::exit(1);
         }
     };
-    if let Err(e) = file_organizer.run() {
-        println!("{} {}", "❌Error:".red().bold(), e);
+    if get_watch_flag(&matches)
+        && let Err(e) = file_organizer.watch()
+    {
+        error!("{} {}", "❌Error:".red().bold(), e);
+        std::process::exit(1);
+    }
+    if is_failed {
+        std::process::exit(1);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::ffi::OsStr;
+
+    #[test]
+    fn completions_are_printed_without_a_recipe_file() {
+        let matches = make_args()
+            .try_get_matches_from(["file_organizer", "--completions", "bash"])
+            .unwrap();
+        let shell = get_completions_shell(&matches).unwrap();
+        let mut script = Vec::new();
+
+        print_completions(shell, &mut script);
+
+        let script = String::from_utf8(script).unwrap();
+        assert!(script.contains("_file_organizer()"));
+        for option in ["--dry_run", "--recipe", "--completions", "--no_color"] {
+            assert!(script.contains(option), "{option} is not completed");
+        }
+    }
+
+    #[test]
+    fn unknown_shells_are_rejected() {
+        assert!(
+            make_args()
+                .try_get_matches_from(["file_organizer", "--completions", "tcsh"])
+                .is_err()
+        );
+    }
+
+    #[test]
+    fn quiet_and_verbose_flags_set_the_log_level() {
+        let log_level = |args: &[&str]| {
+            let matches = make_args()
+                .try_get_matches_from([&["file_organizer", "recipes.json"], args].concat())
+                .unwrap();
+            get_log_level(&matches)
+        };
+
+        assert_eq!(log_level(&[]), Level::INFO);
+        assert_eq!(log_level(&["--quiet"]), Level::ERROR);
+        assert_eq!(log_level(&["-v"]), Level::DEBUG);
+        assert_eq!(log_level(&["-vv"]), Level::TRACE);
+        assert!(
+            make_args()
+                .try_get_matches_from(["file_organizer", "recipes.json", "--quiet", "-v"])
+                .is_err()
+        );
+    }
+
+    #[test]
+    fn colors_are_disabled_by_the_flag_or_a_non_empty_no_color() {
+        let matches = make_args()
+            .try_get_matches_from(["file_organizer", "recipes.json", "--no_color"])
+            .unwrap();
+
+        assert!(matches.get_flag("no_color"));
+        assert!(is_color_disabled(true, None));
+        assert!(is_color_disabled(false, Some(OsStr::new("1"))));
+        assert!(!is_color_disabled(false, Some(OsStr::new(""))));
+        assert!(!is_color_disabled(false, None));
     }
 }
@@ -0,0 +1,133 @@
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::process::Command;
+use std::time::{Duration, SystemTime};
+use tempfile::TempDir;
+
+/// Writes a recipe moving the CSV exports into a folder per year, along with an export of 2024.
+///
+/// ### Parameters
+/// - `root`: The folder holding the exports, the years and the recipe file.
+///
+/// ### Returns
+/// - `PathBuf`: The recipe file.
+fn write_exports_recipe(root: &Path) -> PathBuf {
+    let (exports, years) = (root.join("exports"), root.join("years"));
+    fs::create_dir_all(&exports).unwrap();
+    fs::create_dir_all(&years).unwrap();
+    let export = exports.join("q1.csv");
+    fs::write(&export, "q1").unwrap();
+    // 2024-03-31 at noon UTC.
+    let modified = SystemTime::UNIX_EPOCH + Duration::from_secs(1_711_886_400);
+    fs::File::options()
+        .write(true)
+        .open(&export)
+        .unwrap()
+        .set_modified(modified)
+        .unwrap();
+    let recipes_file = root.join("recipes.json");
+    let recipes = serde_json::json!([{
+        "name": "exports",
+        "source_folder": exports,
+        "destination_folder": years,
+        "operation": "Move",
+        "allowed_extensions": ["csv"],
+        "subfolders": ["%Y"]
+    }]);
+    fs::write(&recipes_file, recipes.to_string()).unwrap();
+    recipes_file
+}
+
+/// Runs the file organizer binary.
+///
+/// ### Parameters
+/// - `args`: The arguments of the run.
+///
+/// ### Returns
+/// - `Option<i32>`: The exit code of the run.
+fn run_file_organizer(args: &[&Path]) -> Option<i32> {
+    Command::new(env!("CARGO_BIN_EXE_file_organizer"))
+        .args(args)
+        .output()
+        .unwrap()
+        .status
+        .code()
+}
+
+#[test]
+fn run_with_a_failed_file_exits_with_an_error() {
+    let root = TempDir::new().unwrap();
+    let recipes_file = write_exports_recipe(root.path());
+    let years = root.path().join("years");
+    fs::write(years.join("2024"), "not a folder").unwrap();
+
+    assert_eq!(run_file_organizer(&[&recipes_file]), Some(1));
+    assert!(root.path().join("exports").join("q1.csv").is_file());
+
+    fs::remove_file(years.join("2024")).unwrap();
+    assert_eq!(run_file_organizer(&[&recipes_file]), Some(0));
+    assert!(years.join("2024").join("q1.csv").is_file());
+}
+
+#[test]
+fn dry_run_with_a_failed_file_exits_successfully() {
+    let root = TempDir::new().unwrap();
+    let recipes_file = write_exports_recipe(root.path());
+    fs::write(root.path().join("years").join("2024"), "not a folder").unwrap();
+
+    assert_eq!(
+        run_file_organizer(&[&recipes_file, Path::new("--dry_run")]),
+        Some(0)
+    );
+}
+
+#[test]
+fn missing_recipe_file_exits_with_an_error() {
+    let root = TempDir::new().unwrap();
+
+    assert_eq!(
+        run_file_organizer(&[&root.path().join("missing.json")]),
+        Some(1)
+    );
+}
+
+#[test]
+fn applied_plan_with_a_skipped_operation_exits_with_an_error() {
+    let root = TempDir::new().unwrap();
+    let (source, destination) = (root.path().join("q1.csv"), root.path().join("q1-2024.csv"));
+    fs::write(&source, "q1").unwrap();
+    let plan_file = root.path().join("plan.json");
+    let write_plan = |source: &Path| {
+        let operations = serde_json::json!([{
+            "recipe": "exports",
+            "operation": "Move",
+            "source": source,
+            "destination": destination,
+            "destination_exists": false
+        }]);
+        fs::write(&plan_file, operations.to_string()).unwrap();
+    };
+
+    write_plan(&root.path().join("missing.csv"));
+    assert_eq!(
+        run_file_organizer(&[Path::new("--apply"), &plan_file]),
+        Some(1)
+    );
+
+    write_plan(&source);
+    assert_eq!(
+        run_file_organizer(&[Path::new("--apply"), &plan_file]),
+        Some(0)
+    );
+    assert!(destination.is_file());
+}
+
+#[test]
+fn applied_plan_which_cannot_be_loaded_exits_with_an_error() {
+    let root = TempDir::new().unwrap();
+
+    assert_eq!(
+        run_file_organizer(&[Path::new("--apply"), &root.path().join("missing.json")]),
+        Some(1)
+    );
+}